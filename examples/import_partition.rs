@@ -0,0 +1,19 @@
+//! Turns METIS or KaHIP `.part` files into a level directory, so that a
+//! partition computed elsewhere can be customized, renumbered and queried like
+//! one `chipper` assembled.
+//! Usage: import_partition <directory.out> <level0.part> [<level1.part> ...]
+use toolbox_rs::{io, partition_file::read_hierarchy};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let output = args.next().expect("output file");
+    let levels = args.collect::<Vec<_>>();
+    assert!(!levels.is_empty(), "at least one .part file");
+
+    let directory = read_hierarchy(&levels).unwrap_or_else(|error| panic!("{error}"));
+    for level in 0..directory.levels() {
+        println!("level {level}: {} cells", directory.cells_on_level(level));
+    }
+    io::write_to_file(&output, &directory);
+    println!("wrote {} nodes to {output}", directory.number_of_nodes());
+}
@@ -1,14 +1,14 @@
 //! Reports statistics of a partition file produced by chipper.
 //! Usage: partition_stats <partition.bin> <graph.toolbox>
 use rustc_hash::FxHashMap;
-use toolbox_rs::{io, partition_id::PartitionID};
+use toolbox_rs::io;
 
 fn main() {
     let mut args = std::env::args().skip(1);
     let partition_file = args.next().expect("partition file");
     let graph_file = args.next().expect("graph file");
 
-    let ids = io::read_vec_from_file::<PartitionID>(&partition_file);
+    let ids = io::read_partition_ids(&partition_file);
     let edges = io::read_graph_into_trivial_edges(&graph_file);
 
     let mut level_histogram = FxHashMap::default();
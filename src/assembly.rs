@@ -94,6 +94,9 @@ impl CellGraph {
 /// merged, so a cell of the result is a union of cells joined along arcs and
 /// stays in one piece as long as the cells it was built from were.
 ///
+/// Sizes and arcs are read as the graph holds them: node and arc counts from
+/// [`cell_graph`], weights and capacities from [`weighted_cell_graph`].
+///
 /// # Why the smallest first
 ///
 /// Taking the heaviest pair first, once, is the coarsening a multilevel
@@ -520,14 +523,46 @@ pub fn fragments(nodes: usize, arcs: &[TrivialEdge], cell_of_node: &[CellId]) ->
 /// ways weighs twice what a pair joined by a one way street does.
 #[must_use]
 pub fn cell_graph(arcs: &[TrivialEdge], cell_of_node: &[CellId]) -> CellGraph {
+    weighted_cell_graph(arcs, cell_of_node, None, None)
+}
+
+/// Builds the graph on the cells, where a cell is as large as its nodes weigh
+/// and two cells are joined as strongly as the arcs between them carry.
+///
+/// Without weights a node weighs one and an arc carries one, which is what
+/// [`cell_graph`] builds. With them, [`agglomerate`] and [`assemble_connected`]
+/// read the sizes of the levels as weights, so a level of 1000 holds cells of
+/// up to that much weight however many nodes that takes, and the merges that
+/// are taken first are the ones that hide the most capacity inside a cell.
+///
+/// `node_weights` is indexed by node and `capacities` runs along `arcs`.
+///
+/// # Panics
+///
+/// Panics if `node_weights` does not hold a weight for every node or
+/// `capacities` a capacity for every arc.
+#[must_use]
+pub fn weighted_cell_graph(
+    arcs: &[TrivialEdge],
+    cell_of_node: &[CellId],
+    node_weights: Option<&[u32]>,
+    capacities: Option<&[u32]>,
+) -> CellGraph {
+    if let Some(weights) = node_weights {
+        assert_eq!(weights.len(), cell_of_node.len(), "a node without a weight");
+    }
+    if let Some(capacities) = capacities {
+        assert_eq!(capacities.len(), arcs.len(), "an arc without a capacity");
+    }
+
     let cells = cell_of_node
         .iter()
         .copied()
         .max()
         .map_or(0, |cell| cell as usize + 1);
     let mut sizes = vec![0; cells];
-    for &cell in cell_of_node {
-        sizes[cell as usize] += 1;
+    for (node, &cell) in cell_of_node.iter().enumerate() {
+        sizes[cell as usize] += node_weights.map_or(1, |weights| weights[node] as usize);
     }
 
     // Collect the pairs and count the runs rather than hashing them, as a road
@@ -547,30 +582,23 @@ pub fn cell_graph(arcs: &[TrivialEdge], cell_of_node: &[CellId]) -> CellGraph {
     // that can be driven in both directions are more strongly joined than two
     // held together by a single one way street, and the weight says so.
     let mut pairs = Vec::new();
-    for arc in arcs {
+    for (index, arc) in arcs.iter().enumerate() {
         let (left, right) = (cell_of_node[arc.source], cell_of_node[arc.target]);
         if left != right {
-            pairs.push((left.min(right), left.max(right)));
+            let capacity = capacities.map_or(1, |capacities| capacities[index] as usize);
+            pairs.push((left.min(right), left.max(right), capacity));
         }
     }
     pairs.sort_unstable();
 
-    let mut between = Vec::new();
-    let mut run = pairs.first().copied();
-    let mut count = 0;
-    for pair in &pairs {
-        if Some(*pair) == run {
-            count += 1;
-        } else {
-            let (left, right) = run.expect("a run has a pair");
-            between.push((left as usize, right as usize, count));
-            run = Some(*pair);
-            count = 1;
+    let mut between: Vec<(usize, usize, usize)> = Vec::new();
+    for (left, right, capacity) in pairs {
+        let (left, right) = (left as usize, right as usize);
+        match between.last_mut() {
+            Some(last) if last.0 == left && last.1 == right => last.2 += capacity,
+            _ => between.push((left, right, capacity)),
         }
     }
-    if let Some((left, right)) = run {
-        between.push((left as usize, right as usize, count));
-    }
 
     CellGraph::new(sizes, &between)
 }
@@ -610,7 +638,7 @@ mod tests {
                     }
                 }
                 // a second walk into the same cell means it fell apart
-                if !pieces.insert(cell, start).is_none() {
+                if pieces.insert(cell, start).is_some() {
                     return false;
                 }
             }
@@ -1176,4 +1204,37 @@ mod tests {
         let pieces = fragments(5, &arcs, &[0, 0, 1, 1, 1]);
         assert_eq!(pieces, vec![0, 0, 1, 2, 2]);
     }
+
+    #[test]
+    fn a_weighted_cell_graph_sums_weights_and_capacities() {
+        let arcs = [edge(0, 1), edge(1, 0), edge(1, 2), edge(2, 3)];
+        let cell_of_node = [0, 0, 1, 1];
+        let graph = weighted_cell_graph(
+            &arcs,
+            &cell_of_node,
+            Some(&[1, 2, 3, 4]),
+            Some(&[9, 9, 5, 7]),
+        );
+        assert_eq!(graph.size_of(0), 3);
+        assert_eq!(graph.size_of(1), 7);
+        // the arcs inside a cell carry nothing between cells
+        assert_eq!(graph.arcs(), vec![(0, 1, 5)]);
+
+        let unweighted = weighted_cell_graph(&arcs, &cell_of_node, None, None);
+        assert_eq!(unweighted.arcs(), cell_graph(&arcs, &cell_of_node).arcs());
+        assert_eq!(unweighted.size_of(1), 2);
+    }
+
+    #[test]
+    fn merging_by_weight_keeps_heavy_cells_apart() {
+        // a line of four nodes, each its own cell, where the middle two are
+        // heavy enough that they do not fit together
+        let arcs = [edge(0, 1), edge(1, 2), edge(2, 3)];
+        let cell_of_node = [0, 1, 2, 3];
+        let graph = weighted_cell_graph(&arcs, &cell_of_node, Some(&[1, 3, 3, 1]), None);
+        let merged = agglomerate(&graph, 4);
+        assert_eq!(merged[0], merged[1]);
+        assert_eq!(merged[2], merged[3]);
+        assert_ne!(merged[1], merged[2]);
+    }
 }
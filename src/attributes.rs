@@ -0,0 +1,262 @@
+//! Side tables that hold one value per node or per arc of a graph.
+//!
+//! A [`StaticGraph`] keeps one datum per arc, the one its searches read, and
+//! nothing per node. Everything else a graph comes with, the coordinates of
+//! its nodes or the names and road classes of its arcs, is kept beside it,
+//! indexed by node or arc id. Those ids change whenever a graph is built from
+//! another: the arcs are sorted on the way in, a renumbering moves the nodes,
+//! and a subgraph keeps only some of either. The tables here move along with
+//! them, given the numbering or the origin of each arc that
+//! [`StaticGraph::new_with_origin`] and the `_with_origin` transforms of
+//! [`crate::graph_transform`] report.
+//!
+//! [`StaticGraph`]: crate::static_graph::StaticGraph
+//! [`StaticGraph::new_with_origin`]: crate::static_graph::StaticGraph::new_with_origin
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{
+//!     attributes::EdgeAttributes,
+//!     edge::InputEdge,
+//!     graph::Graph,
+//!     static_graph::StaticGraph,
+//! };
+//!
+//! let input = vec![InputEdge::new(1, 0, 5), InputEdge::new(0, 1, 7)];
+//! let names = vec!["Hauptstraße", "Bahnhofstraße"];
+//!
+//! // sorting puts the arc out of 0 first, and its name with it
+//! let (graph, origin) = StaticGraph::new_with_origin(2, input);
+//! let names = EdgeAttributes::new(names).permuted(&origin);
+//! let edge = graph.find_edge(0, 1).unwrap();
+//! assert_eq!(*names.get(edge), "Bahnhofstraße");
+//! ```
+use crate::{
+    graph::{EdgeID, NodeID},
+    node_ordering::NodeOrdering,
+};
+
+/// One value per node, indexed by node id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeAttributes<A> {
+    values: Vec<A>,
+}
+
+impl<A: Clone> NodeAttributes<A> {
+    /// The values of the nodes in the order of their ids.
+    #[must_use]
+    pub fn new(values: Vec<A>) -> Self {
+        Self { values }
+    }
+
+    /// # Panics
+    ///
+    /// Panics for a node the table does not hold.
+    #[must_use]
+    pub fn get(&self, node: NodeID) -> &A {
+        &self.values[node]
+    }
+
+    /// # Panics
+    ///
+    /// Panics for a node the table does not hold.
+    pub fn get_mut(&mut self, node: NodeID) -> &mut A {
+        &mut self.values[node]
+    }
+
+    #[must_use]
+    pub fn values(&self) -> &[A] {
+        &self.values
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The same values, at the numbers `ordering` gives the nodes, to go with
+    /// [`crate::graph_transform::renumber`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ordering is not over as many nodes as the table.
+    #[must_use]
+    pub fn renumbered(&self, ordering: &NodeOrdering) -> Self {
+        assert_eq!(
+            ordering.len(),
+            self.len(),
+            "the ordering is over another number of nodes"
+        );
+        Self::new(
+            (0..self.len())
+                .map(|new| self.values[ordering.old_of(new)].clone())
+                .collect(),
+        )
+    }
+
+    /// The values of `nodes`, the i-th node of the list at i, to go with
+    /// [`crate::graph_transform::induced_subgraph`].
+    ///
+    /// # Panics
+    ///
+    /// Panics for a node the table does not hold.
+    #[must_use]
+    pub fn induced(&self, nodes: &[NodeID]) -> Self {
+        Self::new(
+            nodes
+                .iter()
+                .map(|&node| self.values[node].clone())
+                .collect(),
+        )
+    }
+}
+
+/// One value per arc, indexed by arc id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EdgeAttributes<A> {
+    values: Vec<A>,
+}
+
+impl<A: Clone> EdgeAttributes<A> {
+    /// The values of the arcs in the order of their ids, or in the order of
+    /// the input a graph is about to be built from.
+    #[must_use]
+    pub fn new(values: Vec<A>) -> Self {
+        Self { values }
+    }
+
+    /// # Panics
+    ///
+    /// Panics for an arc the table does not hold.
+    #[must_use]
+    pub fn get(&self, edge: EdgeID) -> &A {
+        &self.values[edge]
+    }
+
+    /// # Panics
+    ///
+    /// Panics for an arc the table does not hold.
+    pub fn get_mut(&mut self, edge: EdgeID) -> &mut A {
+        &mut self.values[edge]
+    }
+
+    #[must_use]
+    pub fn values(&self) -> &[A] {
+        &self.values
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The values of the arcs of a graph built from the one this table is
+    /// for, where `origin` holds the arc each new arc came from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an origin is an arc the table does not hold.
+    #[must_use]
+    pub fn permuted(&self, origin: &[EdgeID]) -> Self {
+        Self::new(
+            origin
+                .iter()
+                .map(|&edge| self.values[edge].clone())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        edge::InputEdge,
+        graph::Graph,
+        graph_transform::{induced_subgraph_with_origin, renumber_with_origin},
+        static_graph::StaticGraph,
+    };
+
+    /// Arcs out of order, each named after its ends.
+    fn input() -> (Vec<InputEdge<u32>>, Vec<String>) {
+        let pairs = [(3, 0), (0, 1), (2, 3), (1, 2), (0, 2), (2, 0), (1, 0)];
+        let edges = pairs
+            .iter()
+            .map(|&(source, target)| InputEdge::new(source, target, 1))
+            .collect();
+        let names = pairs
+            .iter()
+            .map(|(source, target)| format!("{source}-{target}"))
+            .collect();
+        (edges, names)
+    }
+
+    fn names_match<G: Graph<u32>>(graph: &G, names: &EdgeAttributes<String>, id: &[NodeID]) {
+        assert_eq!(graph.number_of_edges(), names.len());
+        for node in graph.node_range() {
+            for edge in graph.edge_range(node) {
+                let expected = format!("{}-{}", id[node], id[graph.target(edge)]);
+                assert_eq!(*names.get(edge), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn edge_attributes_follow_the_sort() {
+        let (edges, names) = input();
+        let (graph, origin) = StaticGraph::new_with_origin(4, edges);
+        let names = EdgeAttributes::new(names).permuted(&origin);
+        names_match(&graph, &names, &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn attributes_follow_a_renumbering() {
+        let (edges, names) = input();
+        let (graph, origin) = StaticGraph::new_with_origin(4, edges);
+        let names = EdgeAttributes::new(names).permuted(&origin);
+        let labels = NodeAttributes::new(vec!['a', 'b', 'c', 'd']);
+
+        let ordering = NodeOrdering::from_places(vec![2, 0, 3, 1], 0);
+        let (renumbered, origin) = renumber_with_origin(&graph, &ordering);
+        let names = names.permuted(&origin);
+        let labels = labels.renumbered(&ordering);
+
+        let old: Vec<NodeID> = (0..4).map(|new| ordering.old_of(new)).collect();
+        names_match(&renumbered, &names, &old);
+        assert_eq!(labels.values(), &['b', 'd', 'a', 'c']);
+    }
+
+    #[test]
+    fn attributes_follow_a_subgraph() {
+        let (edges, names) = input();
+        let (graph, origin) = StaticGraph::new_with_origin(4, edges);
+        let names = EdgeAttributes::new(names).permuted(&origin);
+        let mut labels = NodeAttributes::new(vec!['a', 'b', 'c', 'd']);
+        *labels.get_mut(2) = 'C';
+
+        let nodes = [2, 0, 3];
+        let (subgraph, origin) = induced_subgraph_with_origin(&graph, &nodes);
+        let names = names.permuted(&origin);
+        names_match(&subgraph, &names, &nodes);
+        assert_eq!(subgraph.number_of_edges(), 4);
+        assert_eq!(labels.induced(&nodes).values(), &['C', 'a', 'd']);
+    }
+
+    #[test]
+    fn equal_arcs_keep_their_order() {
+        let edges = vec![InputEdge::new(0, 1, 1), InputEdge::new(0, 1, 1)];
+        let (_, origin) = StaticGraph::new_with_origin(2, edges);
+        assert_eq!(origin, vec![0, 1]);
+    }
+}
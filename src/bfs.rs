@@ -65,11 +65,10 @@ impl BFS {
         while let Some(node) = self.queue.pop_front() {
             let node_is_source = self.parents[node] == node;
             // sources have themselves as parents
-            for edge in graph.edge_range(node) {
+            for (edge, target) in graph.out_arcs(node) {
                 if filter(graph, edge) {
                     continue;
                 }
-                let target = graph.target(edge);
                 if self.parents[target] != INVALID_NODE_ID
                     || (node_is_source && self.parents[target] == target)
                 {
@@ -134,9 +134,9 @@ impl<S: HeapStats<NodeID>> BidirectionalMldSearch<S> {
     /// # Panics
     ///
     /// Panics if a level of the partition has no cells worked out for it.
-    pub fn run<G: Graph<u32>>(
+    pub fn run<F: Graph<u32>, G: Graph<u32>>(
         &mut self,
-        customization: &Customization,
+        customization: &Customization<F>,
         reverse: &G,
         reverse_borders: &BorderLevels,
         source: NodeID,
@@ -237,9 +237,9 @@ impl<S: HeapStats<NodeID>> BidirectionalMldSearch<S> {
     /// here to each of the others. The backward side reads its column, what it
     /// costs to get to here from each of the others.
     #[inline(never)]
-    fn relax_across_cell(
+    fn relax_across_cell<G: Graph<u32>>(
         &mut self,
-        customization: &Customization,
+        customization: &Customization<G>,
         partition: &PackedPartition,
         side: Side,
         node: NodeID,
@@ -1,11 +1,12 @@
 use std::{fmt::Display, ops::RangeInclusive};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use toolbox_rs::partition_id::WidePartitionID;
 
-static RECURSION_RANGE: RangeInclusive<u8> = 1..=31;
+static RECURSION_RANGE: RangeInclusive<u8> = 1..=WidePartitionID::MAX_LEVEL;
 static BALANCE_RANGE: RangeInclusive<f64> = 0. ..=0.5;
 
-/// Checks whether the recursion range is within the expected range of (1, 31].
+/// Checks whether the recursion range is within the expected range of [1, 127].
 pub fn recursion_depth_in_range(s: &str) -> Result<u8, String> {
     let recursion_depth: u8 = s.parse().map_err(|_| format!("`{s}` isn't a number"))?;
     if RECURSION_RANGE.contains(&recursion_depth) {
@@ -33,6 +34,23 @@ pub fn balance_factor_in_range(s: &str) -> Result<f64, String> {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Solver {
+    /// Dinic's algorithm, augmenting along shortest paths
+    Dinic,
+    /// highest-label push-relabel, for large and dense cuts
+    PushRelabel,
+}
+
+impl Display for Solver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Solver::Dinic => write!(f, "dinic"),
+            Solver::PushRelabel => write!(f, "push-relabel"),
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Arguments {
@@ -60,19 +78,34 @@ pub struct Arguments {
     #[clap(short, long, value_parser = balance_factor_in_range, default_value_t = 0.25)]
     pub b_factor: f64,
 
+    /// max-flow solver that computes the cuts
+    #[clap(short, long, value_enum, default_value_t = Solver::Dinic)]
+    pub solver: Solver,
+
     /// depth of recursive partitioning; off by one from the level of a node
     /// since the root node has level 1, e.g. depths of 1 gives cells on level 2
     #[clap(short, long, value_parser=recursion_depth_in_range, default_value_t = 1)]
     pub recursion_depth: u8,
 
-    /// path to the output file with partition ids
+    /// path to the output file with partition ids, 32 bits each up to a
+    /// recursion depth of 31 and a .toolbox container of 128 bit ids beyond
     #[clap(short, long, default_value_t = String::new(), action)]
     pub partition_file: String,
 
-    /// Minimum size of a cell
+    /// Minimum size of a cell, counted in node weight if weights are given
     #[clap(short, long, default_value_t = 50, action)]
     pub minimum_cell_size: usize,
 
+    /// path to the node weights, one u32 per coordinate. Balance and cell
+    /// sizes are measured in weight rather than in nodes if given
+    #[clap(short = 'w', long, default_value_t = String::new(), action)]
+    pub node_weights: String,
+
+    /// path to the edge capacities, one u32 per edge of the graph file. A cut
+    /// costs what its edges carry rather than how many there are if given
+    #[clap(short = 'e', long, default_value_t = String::new(), action)]
+    pub edge_capacities: String,
+
     /// Sizes of the levels to assemble, from the finest up, e.g.
     /// 50,250,1000,10000,100000,1000000. Giving them cuts the graph down to
     /// cells of a quarter of the finest size instead of to the minimum cell
@@ -84,6 +117,22 @@ pub struct Arguments {
     /// path to write the level directory to
     #[clap(short = 'd', long, default_value_t = String::new(), action)]
     pub level_directory: String,
+
+    /// path to write a JSON report on the quality of the partition to: cut,
+    /// boundaries, imbalance and connectivity of the cells on every level
+    #[clap(short = 'R', long, default_value_t = String::new(), action)]
+    pub report: String,
+
+    /// path to the level directory of an earlier run to repair instead of
+    /// cutting the graph anew. Only the cells the changed arcs touch and their
+    /// ancestors are assembled again, and all other cells keep their ids
+    #[clap(short = 'I', long, default_value_t = String::new(), action)]
+    pub incremental: String,
+
+    /// path to the arcs that were added or removed since the earlier run, in
+    /// the format of the graph file
+    #[clap(short = 'C', long, default_value_t = String::new(), action)]
+    pub changed_arcs: String,
 }
 
 impl Display for Arguments {
@@ -105,12 +154,26 @@ impl Display for Arguments {
         writeln!(f, "coordinates: {}", self.coordinates)?;
         writeln!(f, "recursion depth: {}", self.recursion_depth)?;
         writeln!(f, "balance factor: {}", self.b_factor)?;
+        writeln!(f, "solver: {}", self.solver)?;
         if !self.level_sizes.is_empty() {
             writeln!(f, "level sizes: {:?}", self.level_sizes)?;
         }
         if !self.level_directory.is_empty() {
             writeln!(f, "level directory: {}", self.level_directory)?;
         }
+        if !self.report.is_empty() {
+            writeln!(f, "report: {}", self.report)?;
+        }
+        if !self.incremental.is_empty() {
+            writeln!(f, "repairing level directory: {}", self.incremental)?;
+            writeln!(f, "changed arcs: {}", self.changed_arcs)?;
+        }
+        if !self.node_weights.is_empty() {
+            writeln!(f, "node weights: {}", self.node_weights)?;
+        }
+        if !self.edge_capacities.is_empty() {
+            writeln!(f, "edge capacities: {}", self.edge_capacities)?;
+        }
         writeln!(f, "minimum_cell_size: {}", self.minimum_cell_size)
     }
 }
@@ -14,19 +14,23 @@ use rayon::prelude::*;
 use rustc_hash::FxHashSet;
 use std::sync::{
     Arc,
-    atomic::{AtomicI32, AtomicU8, AtomicU32, Ordering},
+    atomic::{AtomicI64, AtomicU64, Ordering},
 };
-use toolbox_rs::geometry::FPCoordinate;
 use toolbox_rs::io;
 use toolbox_rs::{
     assembly,
-    inertial_flow::{self, Flow, flow_cmp},
-    level_directory::CellId,
-    partition_id::PartitionID,
+    dinic::Dinic,
+    inertial_flow::{self, Flow, SubStepOptions, flow_cmp},
+    level_directory::{CellId, LevelDirectory},
+    partition_id::WidePartitionID,
+    partition_quality,
+    push_relabel::PushRelabel,
+    repartition,
 };
+use toolbox_rs::{edge::TrivialEdge, geometry::FPCoordinate};
 use {
-    command_line::Arguments,
-    serialize::{write_level_directory, write_results},
+    command_line::{Arguments, Solver},
+    serialize::{write_level_directory, write_report, write_results},
 };
 
 /// Numbers whatever the bisection left behind from zero without a gap.
@@ -41,6 +45,56 @@ fn compact(of_node: &[usize]) -> Vec<CellId> {
         .collect()
 }
 
+/// Repairs the levels of an earlier run after some arcs changed, rather than
+/// cutting the whole graph again. Cells no changed arc touches keep their ids.
+fn repair(
+    args: &Arguments,
+    edges: &[TrivialEdge],
+    node_weights: Option<&[u32]>,
+    capacities: Option<&[u32]>,
+) {
+    assert!(
+        !args.level_sizes.is_empty(),
+        "repairing the levels needs the sizes they were assembled with"
+    );
+    assert!(
+        !args.changed_arcs.is_empty(),
+        "repairing the levels needs the arcs that changed"
+    );
+    let directory: LevelDirectory = io::read_from_file(&args.incremental);
+    let changed = io::read_graph_into_trivial_edges(&args.changed_arcs);
+    info!(
+        "repairing {} levels after {} changed arcs",
+        directory.levels(),
+        changed.len()
+    );
+
+    let directory = repartition::repartition(
+        &directory,
+        edges,
+        &changed,
+        node_weights,
+        capacities,
+        &args.level_sizes,
+    );
+    for level in 0..directory.levels() {
+        info!(
+            "level {level} of {} nodes: {} cells",
+            args.level_sizes[level],
+            directory.cells_on_level(level)
+        );
+    }
+    if args.level_directory.is_empty() {
+        warn!("no level directory was asked for, so the levels are dropped");
+    } else {
+        write_level_directory(&args.level_directory, &directory);
+    }
+    if !args.report.is_empty() {
+        let quality = partition_quality::evaluate(edges, &directory, node_weights);
+        write_report(&args.report, &quality);
+    }
+}
+
 fn main() {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
@@ -74,18 +128,61 @@ fn main() {
         coordinates.len()
     );
 
+    // Nodes weigh one and arcs carry one unless told otherwise. Capacities run
+    // along the arcs of the graph file and are split along with them.
+    let node_weights =
+        (!args.node_weights.is_empty()).then(|| io::read_vec_from_file::<u32>(&args.node_weights));
+    let capacities = (!args.edge_capacities.is_empty())
+        .then(|| io::read_vec_from_file::<u32>(&args.edge_capacities));
+    if let Some(node_weights) = &node_weights {
+        assert_eq!(
+            node_weights.len(),
+            coordinates.len(),
+            "one node weight per coordinate expected"
+        );
+    }
+    if let Some(capacities) = &capacities {
+        assert_eq!(
+            capacities.len(),
+            edges.len(),
+            "one capacity per edge expected"
+        );
+    }
+    if !args.incremental.is_empty() {
+        repair(
+            &args,
+            &edges,
+            node_weights.as_deref(),
+            capacities.as_deref(),
+        );
+        info!("done.");
+        return;
+    }
+
+    let weight_of = |ids: &[usize]| -> usize {
+        node_weights.as_ref().map_or(ids.len(), |weights| {
+            ids.iter().map(|&id| weights[id] as usize).sum()
+        })
+    };
+
     // enqueue initial job for partitioning of the root node into job queue. The
     // root job takes ownership of the edge set, which is only needed again if
     // the cut is to be written out.
     let id_vector = (0..coordinates.len()).collect_vec();
     // The assembly walks the arcs to find which cells hold together and which
     // of them are neighbours, so they are kept for it as well as for the cut
-    // csv. Nothing else needs them and a copy of the arcs of a continent is
-    // not worth keeping for nobody.
-    let input_edges = if args.cut_csv.is_empty() && args.level_sizes.is_empty() {
-        Vec::new()
+    // csv and the report. Nothing else needs them and a copy of the arcs of a
+    // continent is not worth keeping for nobody.
+    let input_edges =
+        if args.cut_csv.is_empty() && args.level_sizes.is_empty() && args.report.is_empty() {
+            Vec::new()
+        } else {
+            edges.clone()
+        };
+    let input_capacities = if args.level_sizes.is_empty() {
+        None
     } else {
-        edges.clone()
+        capacities.clone()
     };
     let node_count = coordinates.len();
     // The size a cell has to reach before the cutting stops. Assembling a level
@@ -98,7 +195,7 @@ fn main() {
         .map_or(args.minimum_cell_size, |smallest| (smallest / 4).max(1));
     if !args.level_sizes.is_empty() {
         info!(
-            "cutting down to cells of {stop_at} nodes, then assembling {:?}",
+            "cutting down to cells of size {stop_at}, then assembling {:?}",
             args.level_sizes
         );
     }
@@ -109,7 +206,7 @@ fn main() {
     let mut leaf_of_node = vec![0_usize; coordinates.len()];
     let mut cells_created = 1;
 
-    let job = (edges, id_vector, 0_usize);
+    let job = (edges, id_vector, 0_usize, capacities.unwrap_or_default());
     let mut current_job_queue = vec![job];
 
     let sty = ProgressStyle::default_spinner()
@@ -120,25 +217,36 @@ fn main() {
     let mut current_level = 0;
     // Cells are disjoint, hence each entry is written by at most one thread per
     // level. Relaxed atomics express that without an aliasing hazard and
-    // compile to plain loads and stores.
+    // compile to plain loads and stores. There is no stable atomic of 128 bits,
+    // so an id is kept as its upper and lower half, and as no two threads touch
+    // the same node within a level the halves cannot tear.
     let partition_ids = (0..coordinates.len())
-        .map(|_| AtomicU32::new(PartitionID::root().0))
-        .collect_vec();
-    let load = |index: usize| PartitionID(partition_ids[index].load(Ordering::Relaxed));
-    let store = |index: usize, id: PartitionID| partition_ids[index].store(id.0, Ordering::Relaxed);
-
-    // The side of the latest cut that a node ended up on, which is all that
-    // splitting the edge set needs. Reading it off the partition id instead
-    // only works while the id has room: it shifts left by one per level and
-    // packs the whole path into a u32, so it cannot record a bisection deeper
-    // than 31 and the lowest bit stops meaning the latest cut.
-    const LEFT: u8 = 0;
-    const RIGHT: u8 = 1;
-    let sides = (0..coordinates.len())
-        .map(|_| AtomicU8::new(LEFT))
+        .map(|_| {
+            let root = WidePartitionID::root().0;
+            [
+                AtomicU64::new((root >> 64) as u64),
+                AtomicU64::new(root as u64),
+            ]
+        })
         .collect_vec();
-    let side_of = |index: usize| sides[index].load(Ordering::Relaxed);
-    let set_side = |index: usize, side: u8| sides[index].store(side, Ordering::Relaxed);
+    let load = |index: usize| {
+        let [upper, lower] = &partition_ids[index];
+        WidePartitionID(
+            (u128::from(upper.load(Ordering::Relaxed)) << 64)
+                | u128::from(lower.load(Ordering::Relaxed)),
+        )
+    };
+    let store = |index: usize, id: WidePartitionID| {
+        let [upper, lower] = &partition_ids[index];
+        upper.store((id.0 >> 64) as u64, Ordering::Relaxed);
+        lower.store(id.0 as u64, Ordering::Relaxed);
+    };
+
+    // the cuts of all cells are computed by the solver of choice
+    let sub_step = match args.solver {
+        Solver::Dinic => inertial_flow::weighted_sub_step::<Dinic<i64>>,
+        Solver::PushRelabel => inertial_flow::weighted_sub_step::<PushRelabel<i64>>,
+    };
 
     while !current_job_queue.is_empty() && current_level < args.recursion_depth {
         let pb = ProgressBar::new(current_job_queue.len() as u64);
@@ -151,20 +259,24 @@ fn main() {
                 pb.set_message(format!("cell #{id}"));
                 pb.inc(1);
 
-                // we use the count of coordinates as an upper bound to the cut size
-                let upper_bound = Arc::new(AtomicI32::new(job.1.len().try_into().unwrap()));
+                // we use the count of coordinates as an upper bound to the cut
+                // size, or what all arcs of the cell carry if they have capacities
+                let bound = if job.3.is_empty() {
+                    job.1.len()
+                } else {
+                    job.3.iter().map(|&capacity| capacity as usize).sum()
+                };
+                let upper_bound = Arc::new(AtomicI64::new(bound.try_into().unwrap_or(i64::MAX)));
+                let options = SubStepOptions {
+                    node_weights: node_weights.as_deref(),
+                    capacities: (!job.3.is_empty()).then_some(&job.3),
+                    ..SubStepOptions::new(args.b_factor, upper_bound)
+                };
                 // run inertial flow on all four axes
                 let best_max_flow = (0..4)
                     .into_par_iter()
-                    .map(|axis| -> Result<Flow, inertial_flow::FlowError> {
-                        inertial_flow::sub_step(
-                            &job.0,
-                            &job.1,
-                            &coordinates,
-                            axis,
-                            args.b_factor,
-                            upper_bound.clone(),
-                        )
+                    .map(|axis| -> Result<Flow<i64>, inertial_flow::FlowError> {
+                        sub_step(&job.0, &job.1, &coordinates, axis, &options)
                     })
                     .filter_map(Result::ok)
                     .min_by(flow_cmp);
@@ -193,13 +305,11 @@ fn main() {
                     let mut id = load(*i);
                     id.make_left_child();
                     store(*i, id);
-                    set_side(*i, LEFT);
                 });
                 (result.right_ids).iter().for_each(|i| {
                     let mut id = load(*i);
                     id.make_right_child();
                     store(*i, id);
-                    set_side(*i, RIGHT);
                 });
 
                 // Partition edge and node id sets for the next iteration. The
@@ -207,32 +317,40 @@ fn main() {
                 // right away instead of at the end of the level. Edges of the
                 // cut are dropped: their head is outside of the cell they would
                 // end up in, where they only inflate the flow graph by a node
-                // that no flow can pass through.
+                // that no flow can pass through. The lowest bit of a partition
+                // id tells the side of the latest cut.
                 debug!("generating next level edges");
                 let mut left_edges = Vec::new();
                 let mut right_edges = Vec::new();
-                for edge in std::mem::take(&mut job.0) {
-                    let tail_side = side_of(edge.source);
-                    if tail_side != side_of(edge.target) {
+                let mut left_capacities = Vec::new();
+                let mut right_capacities = Vec::new();
+                let capacities = std::mem::take(&mut job.3);
+                for (index, edge) in std::mem::take(&mut job.0).into_iter().enumerate() {
+                    let tail = load(edge.source);
+                    if tail != load(edge.target) {
                         continue;
                     }
-                    if tail_side == LEFT {
-                        left_edges.push(edge);
+                    let (edges, capacities_of_side) = if tail.is_left_child() {
+                        (&mut left_edges, &mut left_capacities)
                     } else {
-                        right_edges.push(edge);
+                        (&mut right_edges, &mut right_capacities)
+                    };
+                    edges.push(edge);
+                    if let Some(capacity) = capacities.get(index) {
+                        capacities_of_side.push(*capacity);
                     }
                 }
                 debug!("generating next level ids");
 
                 let level_difference = (args.recursion_depth - current_level - 1) as usize;
-                if result.left_ids.len() <= stop_at {
+                if weight_of(&result.left_ids) <= stop_at {
                     for i in &result.left_ids {
                         let mut id = load(*i);
                         id.make_leftmost_descendant(level_difference);
                         store(*i, id);
                     }
                 }
-                if result.right_ids.len() <= stop_at {
+                if weight_of(&result.right_ids) <= stop_at {
                     for i in &result.right_ids {
                         let mut id = load(*i);
                         id.make_rightmost_descendant(level_difference);
@@ -241,7 +359,10 @@ fn main() {
                 }
                 (
                     job.2,
-                    Some((left_edges, result.left_ids, right_edges, result.right_ids)),
+                    Some((
+                        (left_edges, left_capacities, result.left_ids),
+                        (right_edges, right_capacities, result.right_ids),
+                    )),
                     Vec::new(),
                 )
             })
@@ -251,7 +372,7 @@ fn main() {
         // that a cell gets the same number whichever order the jobs finish in.
         let mut next_job_queue = Vec::new();
         for (parent, cut, uncut_ids) in outcomes {
-            let Some((left_edges, left_ids, right_edges, right_ids)) = cut else {
+            let Some((left, right)) = cut else {
                 // a cell that could not be cut stays as it is and its nodes
                 // stay in it
                 for &node in &uncut_ids {
@@ -260,14 +381,14 @@ fn main() {
                 continue;
             };
 
-            for (ids, edges) in [(left_ids, left_edges), (right_ids, right_edges)] {
+            for (edges, capacities, ids) in [left, right] {
                 let cell = cells_created;
                 cells_created += 1;
                 for &node in &ids {
                     leaf_of_node[node] = cell;
                 }
-                if ids.len() > stop_at {
-                    next_job_queue.push((edges, ids, cell));
+                if weight_of(&ids) > stop_at {
+                    next_job_queue.push((edges, ids, cell, capacities));
                 }
             }
         }
@@ -282,7 +403,7 @@ fn main() {
     let leaves = leaf_of_node.iter().copied().collect::<FxHashSet<_>>().len();
     info!("the cutting left {leaves} cells over {cells_created} it made on the way");
 
-    if !args.level_sizes.is_empty() {
+    let directory = (!args.level_sizes.is_empty()).then(|| {
         // The cells of the bisection need not hold together, as a minimum cut
         // puts everything the source cannot reach on the far side whether it
         // hangs together with the rest or not. Merging such a cell into a
@@ -292,7 +413,12 @@ fn main() {
         let piece_count = pieces.iter().copied().max().map_or(0, |p| p as usize + 1);
         info!("those cells hold together in {piece_count} pieces");
 
-        let cells = assembly::cell_graph(&input_edges, &pieces);
+        let cells = assembly::weighted_cell_graph(
+            &input_edges,
+            &pieces,
+            node_weights.as_deref(),
+            input_capacities.as_deref(),
+        );
         let directory = assembly::assemble_connected(&cells, &pieces, &args.level_sizes);
         for level in 0..directory.levels() {
             info!(
@@ -306,12 +432,20 @@ fn main() {
         } else {
             write_level_directory(&args.level_directory, &directory);
         }
+        directory
+    });
+
+    if !args.report.is_empty() {
+        // Without levels to assemble, the cells the cutting left are the one
+        // level there is to report on.
+        let directory =
+            directory.unwrap_or_else(|| LevelDirectory::new(compact(&leaf_of_node), Vec::new()));
+        let quality =
+            partition_quality::evaluate(&input_edges, &directory, node_weights.as_deref());
+        write_report(&args.report, &quality);
     }
 
-    let partition_ids_vec = partition_ids
-        .iter()
-        .map(|id| PartitionID(id.load(Ordering::Relaxed)))
-        .collect_vec();
+    let partition_ids_vec = (0..partition_ids.len()).map(load).collect_vec();
     for id in &partition_ids_vec {
         debug_assert_eq!(id.level(), args.recursion_depth);
     }
@@ -5,8 +5,8 @@ use std::{
     io::{BufWriter, Write},
 };
 use toolbox_rs::{
-    edge::TrivialEdge, geometry::FPCoordinate, level_directory::LevelDirectory,
-    partition_id::PartitionID,
+    edge::TrivialEdge, geometry::FPCoordinate, io, level_directory::LevelDirectory,
+    partition_id::WidePartitionID, partition_quality::PartitionQuality,
 };
 
 use crate::command_line::Arguments;
@@ -14,7 +14,7 @@ use crate::command_line::Arguments;
 pub fn cut_csv(
     file_path: &str,
     edges: &[TrivialEdge],
-    partition_ids: &[PartitionID],
+    partition_ids: &[WidePartitionID],
     coordinates: &[FPCoordinate],
 ) {
     let mut file = BufWriter::new(File::create(file_path).expect("output file cannot be opened"));
@@ -57,7 +57,11 @@ pub fn cut_csv(
     file.flush().expect("error writing file");
 }
 
-pub fn assignment_csv(filename: &str, partition_ids: &[PartitionID], coordinates: &[FPCoordinate]) {
+pub fn assignment_csv(
+    filename: &str,
+    partition_ids: &[WidePartitionID],
+    coordinates: &[FPCoordinate],
+) {
     let mut file = BufWriter::new(File::create(filename).expect("output file cannot be opened"));
     file.write_all("partition_id, latitude, longitude\n".as_bytes())
         .expect("error writing file");
@@ -83,15 +87,9 @@ pub fn assignment_csv(filename: &str, partition_ids: &[PartitionID], coordinates
     }
 }
 
-pub fn binary_partition_file(partition_file: &str, partition_ids: &[PartitionID]) {
-    let mut f = BufWriter::new(File::create(partition_file).unwrap());
-    let bytes = rkyv::to_bytes::<rancor::Error>(&partition_ids.to_vec()).unwrap();
-    f.write_all(&bytes).unwrap();
-}
-
 pub fn write_results(
     args: &Arguments,
-    partition_ids: &[PartitionID],
+    partition_ids: &[WidePartitionID],
     coordinates: &[FPCoordinate],
     edges: &[TrivialEdge],
 ) {
@@ -105,7 +103,7 @@ pub fn write_results(
     }
     if !args.partition_file.is_empty() {
         info!("writing partition ids to {}", args.partition_file);
-        binary_partition_file(&args.partition_file, partition_ids);
+        io::write_partition_ids(&args.partition_file, partition_ids);
     }
 }
 
@@ -117,3 +115,12 @@ pub fn write_level_directory(path: &str, directory: &LevelDirectory) {
     let bytes = rkyv::to_bytes::<rancor::Error>(directory).unwrap();
     file.write_all(&bytes).unwrap();
 }
+
+/// Writes the quality of the partition, level by level, as JSON.
+pub fn write_report(path: &str, quality: &PartitionQuality) {
+    info!("writing report to {path}");
+    let mut file = BufWriter::new(File::create(path).expect("output file cannot be opened"));
+    file.write_all(quality.to_json().as_bytes())
+        .expect("error writing file");
+    file.flush().expect("error writing file");
+}
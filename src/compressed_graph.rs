@@ -0,0 +1,438 @@
+//! A read-only graph that keeps its adjacency lists gap encoded.
+//!
+//! An adjacency array spends four bytes on the target of every arc, whatever
+//! the target is. The neighbours of a node in a road network, or in any graph
+//! that was numbered with some locality, lie close to each other and close to
+//! the node itself, so the differences between consecutive targets of a
+//! sorted list are small numbers. Written as variable length integers, seven
+//! bits to the byte, most of them take a single byte.
+//!
+//! # Layout
+//!
+//! All targets form one stream in the order of the arc ids. Each is written as
+//! the difference to the target before it, zigzag encoded, as the difference
+//! may be negative where one list ends and the next begins. Every
+//! [`BLOCK_SIZE`]-th target is written as it is instead, and the byte offset
+//! of each such block start is kept. The target of an arbitrary arc is thus
+//! found by decoding from the start of its block, which is what block-wise
+//! random access amounts to and what [`Graph::target`] does. Walking the
+//! neighbours of a node in order, as [`CompressedGraph::neighbors`] and
+//! [`Graph::out_arcs`] do, decodes each target once, and so do the BFS and
+//! the SCC searches.
+//!
+//! The data of the arcs is kept as it is. A graph for connectivity only has
+//! none, and `()` takes no space.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{compressed_graph::CompressedGraph, edge::InputEdge, graph::Graph};
+//!
+//! let graph: CompressedGraph = CompressedGraph::new(
+//!     4,
+//!     vec![
+//!         InputEdge::new(0, 2, ()),
+//!         InputEdge::new(0, 1, ()),
+//!         InputEdge::new(2, 3, ()),
+//!     ],
+//! );
+//! assert_eq!(graph.neighbors(0).collect::<Vec<_>>(), vec![1, 2]);
+//! assert_eq!(graph.target(2), 3);
+//! assert_eq!(graph.find_edge(2, 3), Some(2));
+//! ```
+use core::ops::Range;
+
+use crate::{
+    edge::InputEdge,
+    graph::{EdgeID, Graph, NodeID},
+};
+
+/// How many targets share one stored byte offset. A lookup decodes half as
+/// many on average, while the offsets cost eight bytes per block.
+pub const BLOCK_SIZE: usize = 16;
+
+/// Appends `value` seven bits at a time, lowest first, with the high bit of
+/// each byte set when another follows.
+fn push_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads a value written by [`push_varint`] and moves `position` past it.
+fn read_varint(bytes: &[u8], position: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// Maps small differences of either sign to small unsigned numbers.
+fn zigzag(delta: i64) -> u64 {
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// A graph of gap encoded, sorted adjacency lists, see the module
+/// documentation.
+pub struct CompressedGraph<T = ()> {
+    /// the first arc of each node, and the number of arcs at the end
+    first_edge: Vec<EdgeID>,
+    /// the byte offset of every [`BLOCK_SIZE`]-th target
+    block_offset: Vec<usize>,
+    targets: Vec<u8>,
+    data: Vec<T>,
+}
+
+impl<T: Copy + Eq> CompressedGraph<T> {
+    /// Builds the graph over `nodes` nodes, or as many as the arcs reach if
+    /// that is more. The arcs are sorted by source and target.
+    #[must_use]
+    pub fn new(nodes: usize, mut input: Vec<InputEdge<T>>) -> Self {
+        input.sort_by_key(|edge| (edge.source, edge.target));
+        let nodes = input
+            .iter()
+            .map(|edge| 1 + edge.source.max(edge.target))
+            .max()
+            .unwrap_or(0)
+            .max(nodes);
+
+        let mut first_edge = vec![0; nodes + 1];
+        for edge in &input {
+            first_edge[edge.source + 1] += 1;
+        }
+        for node in 0..nodes {
+            first_edge[node + 1] += first_edge[node];
+        }
+
+        let mut graph = Self {
+            first_edge,
+            block_offset: Vec::with_capacity(input.len().div_ceil(BLOCK_SIZE)),
+            targets: Vec::with_capacity(input.len()),
+            data: Vec::with_capacity(input.len()),
+        };
+        let mut previous = 0_i64;
+        for (edge, arc) in input.iter().enumerate() {
+            let target = arc.target as i64;
+            if edge.is_multiple_of(BLOCK_SIZE) {
+                graph.block_offset.push(graph.targets.len());
+                push_varint(&mut graph.targets, target as u64);
+            } else {
+                push_varint(&mut graph.targets, zigzag(target - previous));
+            }
+            previous = target;
+            graph.data.push(arc.data);
+        }
+        graph.targets.shrink_to_fit();
+        graph
+    }
+
+    /// Compresses another graph, with the same node ids. Its adjacency lists
+    /// need not be sorted, and the arc ids change where they are not.
+    #[must_use]
+    pub fn from_graph(graph: &impl Graph<T>) -> Self {
+        let mut input = Vec::with_capacity(graph.number_of_edges());
+        for source in graph.node_range() {
+            for edge in graph.edge_range(source) {
+                input.push(InputEdge::new(
+                    source,
+                    graph.target(edge),
+                    *graph.data(edge),
+                ));
+            }
+        }
+        Self::new(graph.number_of_nodes(), input)
+    }
+}
+
+impl<T> CompressedGraph<T> {
+    /// The targets of the arcs of `node`, ascending, each decoded once.
+    pub fn neighbors(&self, node: NodeID) -> Neighbors<'_, T> {
+        let edges = self.first_edge[node]..self.first_edge[node + 1];
+        let (position, previous) = match edges.start {
+            start if start < edges.end => self.seek(start),
+            _ => (0, 0),
+        };
+        Neighbors {
+            graph: self,
+            edges,
+            position,
+            previous,
+        }
+    }
+
+    /// The bytes that the adjacency lists take, offsets included, which is
+    /// what the encoding saves on.
+    #[must_use]
+    pub fn size_in_bytes(&self) -> usize {
+        self.targets.len()
+            + std::mem::size_of_val(self.block_offset.as_slice())
+            + std::mem::size_of_val(self.first_edge.as_slice())
+            + std::mem::size_of_val(self.data.as_slice())
+    }
+
+    /// The byte position of the target of `edge`, and the target before it,
+    /// found by decoding from the start of its block.
+    fn seek(&self, edge: EdgeID) -> (usize, i64) {
+        let block = edge / BLOCK_SIZE;
+        let mut position = self.block_offset[block];
+        let mut previous = 0;
+        for current in block * BLOCK_SIZE..edge {
+            previous = self.decode(current, &mut position, previous);
+        }
+        (position, previous)
+    }
+
+    /// Decodes the target of `edge` at `position`, given the target before.
+    fn decode(&self, edge: EdgeID, position: &mut usize, previous: i64) -> i64 {
+        let value = read_varint(&self.targets, position);
+        if edge.is_multiple_of(BLOCK_SIZE) {
+            value as i64
+        } else {
+            previous + unzigzag(value)
+        }
+    }
+}
+
+/// The targets of the arcs of one node, see [`CompressedGraph::neighbors`].
+pub struct Neighbors<'a, T> {
+    graph: &'a CompressedGraph<T>,
+    edges: Range<EdgeID>,
+    position: usize,
+    previous: i64,
+}
+
+impl<T> Iterator for Neighbors<'_, T> {
+    type Item = NodeID;
+
+    fn next(&mut self) -> Option<NodeID> {
+        let edge = self.edges.next()?;
+        self.previous = self.graph.decode(edge, &mut self.position, self.previous);
+        Some(self.previous as NodeID)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.edges.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Neighbors<'_, T> {}
+
+impl<T> Graph<T> for CompressedGraph<T> {
+    fn node_range(&self) -> Range<NodeID> {
+        0..self.number_of_nodes()
+    }
+
+    fn edge_range(&self, n: NodeID) -> Range<EdgeID> {
+        self.begin_edges(n)..self.end_edges(n)
+    }
+
+    fn number_of_nodes(&self) -> usize {
+        self.first_edge.len() - 1
+    }
+
+    fn number_of_edges(&self) -> usize {
+        self.data.len()
+    }
+
+    fn begin_edges(&self, n: NodeID) -> EdgeID {
+        self.first_edge[n]
+    }
+
+    fn end_edges(&self, n: NodeID) -> EdgeID {
+        self.first_edge[n + 1]
+    }
+
+    fn out_degree(&self, n: NodeID) -> usize {
+        self.end_edges(n) - self.begin_edges(n)
+    }
+
+    fn target(&self, e: EdgeID) -> NodeID {
+        let (mut position, previous) = self.seek(e);
+        self.decode(e, &mut position, previous) as NodeID
+    }
+
+    fn data(&self, e: EdgeID) -> &T {
+        &self.data[e]
+    }
+
+    fn data_mut(&mut self, e: EdgeID) -> &mut T {
+        &mut self.data[e]
+    }
+
+    fn find_edge(&self, s: NodeID, t: NodeID) -> Option<EdgeID> {
+        if s >= self.number_of_nodes() {
+            return None;
+        }
+        // the list is sorted, so the walk ends at the first larger target
+        self.out_arcs(s)
+            .take_while(|&(_, target)| target <= t)
+            .find(|&(_, target)| target == t)
+            .map(|(edge, _)| edge)
+    }
+
+    fn find_edge_unchecked(&self, s: NodeID, t: NodeID) -> EdgeID {
+        self.find_edge(s, t).unwrap_or(EdgeID::MAX)
+    }
+
+    fn out_arcs(&self, n: NodeID) -> impl Iterator<Item = (EdgeID, NodeID)> {
+        self.edge_range(n).zip(self.neighbors(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bfs::BFS, path_based_scc::PathBasedScc, static_graph::StaticGraph, tarjan::Tarjan,
+    };
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+
+    /// A random graph whose arcs mostly stay near their source, as they do in
+    /// a road network, and now and then jump far.
+    fn random_edges(rng: &mut StdRng, nodes: usize, arcs: usize) -> Vec<InputEdge<()>> {
+        (0..arcs)
+            .map(|_| {
+                let source = rng.random_range(0..nodes);
+                let target = if rng.random_range(0..10) == 0 {
+                    rng.random_range(0..nodes)
+                } else {
+                    (source + rng.random_range(0..20)).min(nodes - 1)
+                };
+                InputEdge::new(source, target, ())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn varints_and_zigzag_round_trip() {
+        let mut bytes = Vec::new();
+        let values = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+        for value in values {
+            push_varint(&mut bytes, value);
+        }
+        let mut position = 0;
+        for value in values {
+            assert_eq!(read_varint(&bytes, &mut position), value);
+        }
+        assert_eq!(position, bytes.len());
+
+        for delta in [0, 1, -1, 63, -64, i64::from(i32::MAX), i64::from(i32::MIN)] {
+            assert_eq!(unzigzag(zigzag(delta)), delta);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn same_arcs_as_a_static_graph() {
+        let mut rng = StdRng::seed_from_u64(0xC0DE);
+        let edges = random_edges(&mut rng, 500, 4000);
+        let compressed = CompressedGraph::new(0, edges.clone());
+        let reference = StaticGraph::new(edges);
+
+        assert_eq!(compressed.number_of_nodes(), reference.number_of_nodes());
+        assert_eq!(compressed.number_of_edges(), reference.number_of_edges());
+        for node in reference.node_range() {
+            assert_eq!(compressed.edge_range(node), reference.edge_range(node));
+            let targets: Vec<_> = reference
+                .edge_range(node)
+                .map(|edge| reference.target(edge))
+                .collect();
+            assert_eq!(compressed.neighbors(node).collect::<Vec<_>>(), targets);
+            assert!(
+                compressed
+                    .out_arcs(node)
+                    .eq(reference.edge_range(node).zip(targets.iter().copied()))
+            );
+            for edge in reference.edge_range(node) {
+                assert_eq!(compressed.target(edge), reference.target(edge));
+            }
+            for target in [0, node, node + 1, 499] {
+                assert_eq!(
+                    compressed.find_edge(node, target).is_some(),
+                    reference.find_edge(node, target).is_some()
+                );
+            }
+        }
+        assert_eq!(compressed.find_edge(1000, 0), None);
+        assert_eq!(compressed.find_edge_unchecked(1000, 0), EdgeID::MAX);
+    }
+
+    #[test]
+    fn searches_run_over_it() {
+        let mut rng = StdRng::seed_from_u64(0x5CC);
+        let edges = random_edges(&mut rng, 300, 900);
+        let compressed = CompressedGraph::new(300, edges.clone());
+        let reference = StaticGraph::new_with_nodes(300, edges);
+
+        assert_eq!(
+            Tarjan::new().run(&compressed),
+            Tarjan::new().run(&reference)
+        );
+        assert_eq!(
+            PathBasedScc::new().run(&compressed),
+            PathBasedScc::new().run(&reference)
+        );
+
+        let mut on_compressed = BFS::new(&[0], &[299], 300);
+        let mut on_reference = BFS::new(&[0], &[299], 300);
+        assert_eq!(on_compressed.run(&compressed), on_reference.run(&reference));
+        assert_eq!(
+            on_compressed.fetch_node_path(),
+            on_reference.fetch_node_path()
+        );
+    }
+
+    #[test]
+    fn local_arcs_take_little_space() {
+        // a path of a hundred thousand nodes, both ways
+        let nodes = 100_000;
+        let edges: Vec<_> = (1..nodes)
+            .flat_map(|node| {
+                [
+                    InputEdge::new(node - 1, node, ()),
+                    InputEdge::new(node, node - 1, ()),
+                ]
+            })
+            .collect();
+        let arcs = edges.len();
+        let compressed = CompressedGraph::new(nodes, edges);
+        // an adjacency array takes four bytes per target alone
+        let targets = compressed.size_in_bytes() - (nodes + 1) * size_of::<EdgeID>();
+        assert!(targets < 2 * arcs, "{targets} bytes for {arcs} arcs");
+    }
+
+    #[test]
+    fn data_is_kept_per_arc() {
+        let mut graph = CompressedGraph::new(
+            3,
+            vec![
+                InputEdge::new(1, 2, 7),
+                InputEdge::new(0, 2, 5),
+                InputEdge::new(0, 1, 3),
+            ],
+        );
+        assert_eq!(graph.number_of_nodes(), 3);
+        assert_eq!(*graph.data(graph.find_edge(0, 2).unwrap()), 5);
+        *graph.data_mut(graph.find_edge_unchecked(1, 2)) = 9;
+        assert_eq!(*graph.data(2), 9);
+
+        let copy = CompressedGraph::from_graph(&graph);
+        assert_eq!(copy.neighbors(0).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(*copy.data(2), 9);
+        assert_eq!(copy.out_degree(2), 0);
+    }
+}
@@ -0,0 +1,656 @@
+//! A versioned container for the `.toolbox` files the tools hand each other.
+//!
+//! A bare rkyv archive says nothing about itself: a file of the wrong type, of
+//! an older layout or with a flipped bit either fails to validate with a
+//! message about bytes, or worse, validates. A container puts a header in
+//! front of one or more named sections, each an rkyv archive of its own:
+//!
+//! ```text
+//! magic           8 bytes  "TOOLBOX\0"
+//! version         u32
+//! section count   u32
+//! nodes           u64      number of nodes of the graph, if there is one
+//! edges           u64      number of arcs of the graph, if there is one
+//! sections        per section: name (32 bytes), type tag (32 bytes),
+//!                 offset, length and xxh3 checksum (u64 each)
+//! checksum        u64      xxh3 of everything above
+//! payloads        each at an offset that is a multiple of 16
+//! ```
+//!
+//! All numbers are little endian. The type tag names what a section holds,
+//! such that reading it as something else is an error rather than garbage,
+//! and the checksums find corruption before rkyv looks at the bytes. The
+//! `edges` and `coordinates` sections have to hold as many entries as the
+//! header counts arcs and nodes.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{
+//!     container::{Container, ContainerWriter, section},
+//!     edge::InputEdge,
+//!     geometry::FPCoordinate,
+//! };
+//!
+//! let edges = vec![InputEdge::new(0, 1, 7_usize), InputEdge::new(1, 0, 7)];
+//! let coordinates = vec![FPCoordinate::new(1, 2), FPCoordinate::new(3, 4)];
+//!
+//! let mut writer = ContainerWriter::new(2, edges.len());
+//! writer.add_section(section::EDGES, &edges).unwrap();
+//! writer.add_section(section::COORDINATES, &coordinates).unwrap();
+//! let file = tempfile::NamedTempFile::new().unwrap();
+//! let path = file.path().to_str().unwrap();
+//! writer.write(path).unwrap();
+//!
+//! let container = Container::read(path).unwrap();
+//! assert_eq!(container.number_of_nodes(), 2);
+//! let read: Vec<FPCoordinate> = container.section(section::COORDINATES).unwrap();
+//! assert_eq!(read, coordinates);
+//! ```
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+};
+
+use rkyv::{rancor, util::AlignedVec};
+use thiserror::Error;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    edge::InputEdge, geometry::FPCoordinate, level_directory::LevelDirectory,
+    node_ordering::NodeOrdering, partition_id::WidePartitionID,
+};
+
+/// The bytes every container starts with.
+pub const MAGIC: [u8; 8] = *b"TOOLBOX\0";
+
+/// The version of the layout written, and the only one read.
+pub const VERSION: u32 = 1;
+
+/// The room for the name and for the type tag of a section.
+const NAME_SIZE: usize = 32;
+
+/// The size of everything in the header before the sections.
+const FIXED_HEADER_SIZE: usize = 32;
+
+/// The size of the description of a section in the header.
+const SECTION_ENTRY_SIZE: usize = 2 * NAME_SIZE + 3 * 8;
+
+/// What rkyv wants the start of an archive aligned to.
+const PAYLOAD_ALIGNMENT: usize = 16;
+
+/// The names of the sections the tools agree on.
+pub mod section {
+    pub const EDGES: &str = "edges";
+    pub const COORDINATES: &str = "coordinates";
+    pub const PARTITION: &str = "partition";
+    pub const ORDERING: &str = "ordering";
+}
+
+/// Why a container could not be read or written.
+#[derive(Debug, Error)]
+pub enum ContainerError {
+    #[error("{file}: {source}")]
+    Io {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{file}: not a .toolbox container")]
+    NotAContainer { file: String },
+    #[error("{file}: format version {found}, but version {supported} is the one supported")]
+    Version {
+        file: String,
+        found: u32,
+        supported: u32,
+    },
+    #[error("{file}: {reason}")]
+    Malformed { file: String, reason: String },
+    #[error("{file}: the checksum of {part} does not match, the file is corrupt")]
+    Checksum { file: String, part: String },
+    #[error("{file}: there is no section {name}")]
+    MissingSection { file: String, name: String },
+    #[error("{file}: section {name} holds {found}, not {expected}")]
+    WrongType {
+        file: String,
+        name: String,
+        expected: &'static str,
+        found: String,
+    },
+    #[error("{file}: section {name}: {reason}")]
+    Payload {
+        file: String,
+        name: String,
+        reason: String,
+    },
+    #[error("section {name} cannot be laid out: {reason}")]
+    Layout { name: String, reason: String },
+}
+
+/// What a section can hold, named by the tag written along with it.
+pub trait Payload {
+    const TAG: &'static str;
+
+    /// How many entries the payload holds, if it is a list of them.
+    fn entries(&self) -> Option<usize> {
+        None
+    }
+}
+
+macro_rules! list_payload {
+    ($entry:ty, $tag:literal) => {
+        impl Payload for Vec<$entry> {
+            const TAG: &'static str = $tag;
+
+            fn entries(&self) -> Option<usize> {
+                Some(self.len())
+            }
+        }
+    };
+}
+
+list_payload!(InputEdge<usize>, "Vec<InputEdge<usize>>");
+list_payload!(InputEdge<u32>, "Vec<InputEdge<u32>>");
+list_payload!(FPCoordinate, "Vec<FPCoordinate>");
+list_payload!(WidePartitionID, "Vec<WidePartitionID>");
+list_payload!(u32, "Vec<u32>");
+
+impl Payload for NodeOrdering {
+    const TAG: &'static str = "NodeOrdering";
+}
+
+impl Payload for LevelDirectory {
+    const TAG: &'static str = "LevelDirectory";
+}
+
+/// Whether bytes start like a container.
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Where a section lies in the file and what it holds.
+#[derive(Clone, Debug)]
+struct SectionEntry {
+    name: String,
+    tag: String,
+    offset: usize,
+    length: usize,
+    checksum: u64,
+}
+
+/// Collects sections to write them as a container.
+#[derive(Debug, Default)]
+pub struct ContainerWriter {
+    number_of_nodes: usize,
+    number_of_edges: usize,
+    sections: Vec<(String, &'static str, AlignedVec)>,
+}
+
+impl ContainerWriter {
+    /// A container for a graph of the given size, or for no graph with both
+    /// counts zero.
+    #[must_use]
+    pub fn new(number_of_nodes: usize, number_of_edges: usize) -> Self {
+        Self {
+            number_of_nodes,
+            number_of_edges,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Adds a section of the given name.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value cannot be laid out by rkyv.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is longer than 32 bytes or already taken.
+    pub fn add_section<T>(&mut self, name: &str, value: &T) -> Result<(), ContainerError>
+    where
+        T: Payload
+            + for<'a> rkyv::Serialize<
+                rkyv::api::high::HighSerializer<
+                    AlignedVec,
+                    rkyv::ser::allocator::ArenaHandle<'a>,
+                    rancor::Error,
+                >,
+            >,
+    {
+        assert!(
+            name.len() <= NAME_SIZE,
+            "section name {name} is longer than {NAME_SIZE} bytes"
+        );
+        assert!(
+            self.sections.iter().all(|(taken, ..)| taken != name),
+            "there is a section {name} already"
+        );
+        let bytes =
+            rkyv::to_bytes::<rancor::Error>(value).map_err(|error| ContainerError::Layout {
+                name: name.to_string(),
+                reason: error.to_string(),
+            })?;
+        self.sections.push((name.to_string(), T::TAG, bytes));
+        Ok(())
+    }
+
+    /// The container as it goes into a file.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_size =
+            FIXED_HEADER_SIZE + self.sections.len() * SECTION_ENTRY_SIZE + size_of::<u64>();
+        let mut offset = header_size.next_multiple_of(PAYLOAD_ALIGNMENT);
+
+        let mut header = Vec::with_capacity(header_size);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.sections.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(self.number_of_nodes as u64).to_le_bytes());
+        header.extend_from_slice(&(self.number_of_edges as u64).to_le_bytes());
+        for (name, tag, bytes) in &self.sections {
+            header.extend_from_slice(&padded(name));
+            header.extend_from_slice(&padded(tag));
+            header.extend_from_slice(&(offset as u64).to_le_bytes());
+            header.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            header.extend_from_slice(&xxh3_64(bytes).to_le_bytes());
+            offset = (offset + bytes.len()).next_multiple_of(PAYLOAD_ALIGNMENT);
+        }
+        let checksum = xxh3_64(&header);
+        header.extend_from_slice(&checksum.to_le_bytes());
+
+        let mut out = header;
+        for (_, _, bytes) in &self.sections {
+            out.resize(out.len().next_multiple_of(PAYLOAD_ALIGNMENT), 0);
+            out.extend_from_slice(bytes);
+        }
+        out
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the file cannot be written.
+    pub fn write(&self, filename: &str) -> Result<(), ContainerError> {
+        let io_error = |source| ContainerError::Io {
+            file: filename.to_string(),
+            source,
+        };
+        let mut file = BufWriter::new(File::create(filename).map_err(io_error)?);
+        file.write_all(&self.to_bytes()).map_err(io_error)?;
+        file.flush().map_err(io_error)
+    }
+}
+
+/// A name or tag NUL padded to its room in the header.
+fn padded(name: &str) -> [u8; NAME_SIZE] {
+    let mut bytes = [0; NAME_SIZE];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    bytes
+}
+
+/// A container read from a file, its header and all checksums checked.
+#[derive(Debug)]
+pub struct Container {
+    file: String,
+    number_of_nodes: usize,
+    number_of_edges: usize,
+    sections: Vec<SectionEntry>,
+    bytes: Vec<u8>,
+}
+
+impl Container {
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read, is not a container, is of another
+    /// version, or is cut short or corrupt.
+    pub fn read(filename: &str) -> Result<Self, ContainerError> {
+        let mut bytes = Vec::new();
+        File::open(filename)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|source| ContainerError::Io {
+                file: filename.to_string(),
+                source,
+            })?;
+        Self::from_bytes(filename, bytes)
+    }
+
+    /// Reads a container from its bytes, `filename` saying where they came
+    /// from in errors.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the bytes are not a container, are of another version, or are
+    /// cut short or corrupt.
+    pub fn from_bytes(filename: &str, bytes: Vec<u8>) -> Result<Self, ContainerError> {
+        let file = filename.to_string();
+        if !is_container(&bytes) {
+            return Err(ContainerError::NotAContainer { file });
+        }
+        let malformed = |reason: &str| ContainerError::Malformed {
+            file: filename.to_string(),
+            reason: reason.to_string(),
+        };
+        let u32_at = |at: usize| -> Result<u32, ContainerError> {
+            let field = bytes
+                .get(at..at + 4)
+                .ok_or_else(|| malformed("the header is cut short"))?;
+            Ok(u32::from_le_bytes(field.try_into().expect("four bytes")))
+        };
+        let u64_at = |at: usize| -> Result<u64, ContainerError> {
+            let field = bytes
+                .get(at..at + 8)
+                .ok_or_else(|| malformed("the header is cut short"))?;
+            Ok(u64::from_le_bytes(field.try_into().expect("eight bytes")))
+        };
+
+        let version = u32_at(8)?;
+        if version != VERSION {
+            return Err(ContainerError::Version {
+                file,
+                found: version,
+                supported: VERSION,
+            });
+        }
+        let section_count = u32_at(12)? as usize;
+        let header_size = section_count
+            .checked_mul(SECTION_ENTRY_SIZE)
+            .and_then(|size| size.checked_add(FIXED_HEADER_SIZE))
+            .filter(|&size| size + 8 <= bytes.len())
+            .ok_or_else(|| malformed("the header is cut short"))?;
+        if xxh3_64(&bytes[..header_size]) != u64_at(header_size)? {
+            return Err(ContainerError::Checksum {
+                file,
+                part: "the header".to_string(),
+            });
+        }
+
+        let text = |at: usize| {
+            let field = &bytes[at..at + NAME_SIZE];
+            let end = field
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(NAME_SIZE);
+            std::str::from_utf8(&field[..end])
+                .map(str::to_string)
+                .map_err(|_| malformed("a section name is not UTF-8"))
+        };
+        let mut sections = Vec::with_capacity(section_count);
+        for index in 0..section_count {
+            let at = FIXED_HEADER_SIZE + index * SECTION_ENTRY_SIZE;
+            let entry = SectionEntry {
+                name: text(at)?,
+                tag: text(at + NAME_SIZE)?,
+                offset: u64_at(at + 2 * NAME_SIZE)? as usize,
+                length: u64_at(at + 2 * NAME_SIZE + 8)? as usize,
+                checksum: u64_at(at + 2 * NAME_SIZE + 16)?,
+            };
+            let payload = entry
+                .offset
+                .checked_add(entry.length)
+                .and_then(|end| bytes.get(entry.offset..end))
+                .ok_or_else(|| ContainerError::Malformed {
+                    file: filename.to_string(),
+                    reason: format!("section {} reaches past the end of the file", entry.name),
+                })?;
+            if xxh3_64(payload) != entry.checksum {
+                return Err(ContainerError::Checksum {
+                    file,
+                    part: format!("section {}", entry.name),
+                });
+            }
+            sections.push(entry);
+        }
+
+        Ok(Self {
+            number_of_nodes: u64_at(16)? as usize,
+            number_of_edges: u64_at(24)? as usize,
+            file,
+            sections,
+            bytes,
+        })
+    }
+
+    #[must_use]
+    pub fn number_of_nodes(&self) -> usize {
+        self.number_of_nodes
+    }
+
+    #[must_use]
+    pub fn number_of_edges(&self) -> usize {
+        self.number_of_edges
+    }
+
+    /// The names of the sections in the order they were written.
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|entry| entry.name.as_str())
+    }
+
+    #[must_use]
+    pub fn has_section(&self, name: &str) -> bool {
+        self.sections.iter().any(|entry| entry.name == name)
+    }
+
+    /// Reads a section as what it holds.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such section, if it holds something else, if it
+    /// does not validate as what it claims to hold, or if it is the `edges`
+    /// or `coordinates` section and holds another number of entries than the
+    /// header counts.
+    pub fn section<T>(&self, name: &str) -> Result<T, ContainerError>
+    where
+        T: Payload + rkyv::Archive,
+        <T as rkyv::Archive>::Archived: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rancor::Error>>
+            + rkyv::Deserialize<T, rkyv::api::high::HighDeserializer<rancor::Error>>,
+    {
+        let entry = self
+            .sections
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| ContainerError::MissingSection {
+                file: self.file.clone(),
+                name: name.to_string(),
+            })?;
+        if entry.tag != T::TAG {
+            return Err(ContainerError::WrongType {
+                file: self.file.clone(),
+                name: name.to_string(),
+                expected: T::TAG,
+                found: entry.tag.clone(),
+            });
+        }
+        let value =
+            rkyv::from_bytes::<T, rancor::Error>(&self.payload(entry)).map_err(|error| {
+                ContainerError::Payload {
+                    file: self.file.clone(),
+                    name: name.to_string(),
+                    reason: error.to_string(),
+                }
+            })?;
+
+        let counted = match name {
+            section::EDGES => Some(self.number_of_edges),
+            section::COORDINATES => Some(self.number_of_nodes),
+            _ => None,
+        };
+        if let (Some(counted), Some(entries)) = (counted, value.entries())
+            && counted != entries
+        {
+            return Err(ContainerError::Malformed {
+                file: self.file.clone(),
+                reason: format!(
+                    "section {name} holds {entries} entries, but the header counts {counted}"
+                ),
+            });
+        }
+        Ok(value)
+    }
+
+    /// The bytes of the one section of a container that holds just one, for
+    /// readers that do not know of sections.
+    pub(crate) fn only_payload(&self) -> Result<AlignedVec, ContainerError> {
+        match self.sections.as_slice() {
+            [entry] => Ok(self.payload(entry)),
+            sections => Err(ContainerError::Malformed {
+                file: self.file.clone(),
+                reason: format!("{} sections where one was expected", sections.len()),
+            }),
+        }
+    }
+
+    /// The bytes of a section, aligned as rkyv wants them.
+    fn payload(&self, entry: &SectionEntry) -> AlignedVec {
+        let mut payload = AlignedVec::with_capacity(entry.length);
+        payload.extend_from_slice(&self.bytes[entry.offset..entry.offset + entry.length]);
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn writer() -> ContainerWriter {
+        let edges = vec![
+            InputEdge::new(0, 1, 3_usize),
+            InputEdge::new(1, 2, 4),
+            InputEdge::new(2, 0, 5),
+        ];
+        let coordinates = vec![
+            FPCoordinate::new(1, 2),
+            FPCoordinate::new(3, 4),
+            FPCoordinate::new(5, 6),
+        ];
+        let mut writer = ContainerWriter::new(3, edges.len());
+        writer.add_section(section::EDGES, &edges).unwrap();
+        writer
+            .add_section(section::COORDINATES, &coordinates)
+            .unwrap();
+        writer
+            .add_section(
+                section::ORDERING,
+                &NodeOrdering::from_places(vec![2, 0, 1], 0),
+            )
+            .unwrap();
+        writer
+    }
+
+    #[test]
+    fn sections_read_back_as_written() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        writer().write(path).unwrap();
+
+        let container = Container::read(path).unwrap();
+        assert_eq!(container.number_of_nodes(), 3);
+        assert_eq!(container.number_of_edges(), 3);
+        assert_eq!(
+            container.section_names().collect::<Vec<_>>(),
+            vec!["edges", "coordinates", "ordering"]
+        );
+        assert!(!container.has_section(section::PARTITION));
+
+        let edges: Vec<InputEdge<usize>> = container.section(section::EDGES).unwrap();
+        assert_eq!(edges[1], InputEdge::new(1, 2, 4));
+        let coordinates: Vec<FPCoordinate> = container.section(section::COORDINATES).unwrap();
+        assert_eq!(coordinates[2], FPCoordinate::new(5, 6));
+        let ordering: NodeOrdering = container.section(section::ORDERING).unwrap();
+        assert_eq!(ordering.new_of(0), 2);
+    }
+
+    #[test]
+    fn asking_for_the_wrong_thing_is_an_error() {
+        let container = Container::from_bytes("test", writer().to_bytes()).unwrap();
+        assert!(matches!(
+            container.section::<Vec<FPCoordinate>>(section::EDGES),
+            Err(ContainerError::WrongType {
+                expected: "Vec<FPCoordinate>",
+                ..
+            })
+        ));
+        assert!(matches!(
+            container.section::<Vec<WidePartitionID>>(section::PARTITION),
+            Err(ContainerError::MissingSection { .. })
+        ));
+        assert!(container.only_payload().is_err());
+    }
+
+    #[test]
+    fn sections_have_to_match_the_counts_of_the_header() {
+        let edges = vec![InputEdge::new(0, 1, 3_usize)];
+        let mut writer = ContainerWriter::new(3, 2);
+        writer.add_section(section::EDGES, &edges).unwrap();
+        writer
+            .add_section(section::COORDINATES, &vec![FPCoordinate::new(1, 2); 3])
+            .unwrap();
+        writer
+            .add_section(section::PARTITION, &vec![7_u32])
+            .unwrap();
+        let container = Container::from_bytes("test", writer.to_bytes()).unwrap();
+
+        let error = container
+            .section::<Vec<InputEdge<usize>>>(section::EDGES)
+            .unwrap_err();
+        assert!(matches!(error, ContainerError::Malformed { .. }));
+        assert!(
+            error
+                .to_string()
+                .contains("section edges holds 1 entries, but the header counts 2")
+        );
+        assert!(
+            container
+                .section::<Vec<FPCoordinate>>(section::COORDINATES)
+                .is_ok()
+        );
+        // the header counts nothing else
+        assert!(container.section::<Vec<u32>>(section::PARTITION).is_ok());
+    }
+
+    #[test]
+    fn corruption_and_version_skew_are_reported() {
+        let bytes = writer().to_bytes();
+
+        let mut flipped = bytes.clone();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 0x10;
+        let error = Container::from_bytes("test", flipped).unwrap_err();
+        assert!(matches!(error, ContainerError::Checksum { .. }));
+        assert!(error.to_string().contains("section ordering"));
+
+        let mut flipped = bytes.clone();
+        flipped[20] ^= 1;
+        let error = Container::from_bytes("test", flipped).unwrap_err();
+        assert!(error.to_string().contains("checksum of the header"));
+
+        let mut newer = bytes.clone();
+        newer[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Container::from_bytes("test", newer),
+            Err(ContainerError::Version { found: 2, .. })
+        ));
+
+        assert!(matches!(
+            Container::from_bytes("test", bytes[..bytes.len() - 8].to_vec()),
+            Err(ContainerError::Malformed { .. })
+        ));
+        assert!(matches!(
+            Container::from_bytes("test", bytes[..40].to_vec()),
+            Err(ContainerError::Malformed { .. })
+        ));
+        assert!(matches!(
+            Container::from_bytes("test", vec![1, 2, 3]),
+            Err(ContainerError::NotAContainer { .. })
+        ));
+    }
+
+    #[test]
+    fn payloads_are_aligned() {
+        let bytes = writer().to_bytes();
+        let container = Container::from_bytes("test", bytes).unwrap();
+        for entry in &container.sections {
+            assert!(entry.offset.is_multiple_of(PAYLOAD_ALIGNMENT));
+        }
+    }
+}
@@ -0,0 +1,442 @@
+//! Graphs as plain CSV: one file of arcs and one of nodes.
+//!
+//! This is what analysts hand around when a graph leaves NetworkX, Gephi or a
+//! spreadsheet. The arcs come as `source,target,weight` and the nodes as
+//! `id,lat,lon`, with the coordinates in degrees. The first line of each file
+//! names the columns, which may come in any order and next to any others.
+//! [`CsvColumns`] says which names to look for, as the weight in particular is
+//! called `length`, `travel_time` or anything else depending on who wrote it.
+//!
+//! Nodes are numbered from zero, as in the rest of the crate. Weights may be
+//! decimals, are multiplied by the scale of the columns and rounded to whole
+//! numbers. Fields may be quoted, empty lines and lines starting with `#` are
+//! skipped.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{csv::{self, CsvColumns}, edge::InputEdge};
+//!
+//! let file = tempfile::NamedTempFile::new().unwrap();
+//! let path = file.path().to_str().unwrap();
+//! std::fs::write(path, "from,to,minutes\n0,1,1.5\n1,2,2.25\n").unwrap();
+//!
+//! // weights in tenths of a minute
+//! let columns = CsvColumns {
+//!     source: "from".into(),
+//!     target: "to".into(),
+//!     weight: Some("minutes".into()),
+//!     scale: 10.,
+//!     ..Default::default()
+//! };
+//! let edges = csv::try_read_graph::<usize>(path, &columns).unwrap();
+//! assert_eq!(edges, vec![InputEdge::new(0, 1, 15), InputEdge::new(1, 2, 23)]);
+//! ```
+use std::fmt::Display;
+
+use log::info;
+
+use crate::{
+    edge::InputEdge,
+    geometry::FPCoordinate,
+    graph::NodeID,
+    io::{GraphFileError, LineReader, NumberedLines, TextWriter, scaled_weight},
+};
+
+/// The names of the columns to read and write.
+#[derive(Clone, Debug)]
+pub struct CsvColumns {
+    pub source: String,
+    pub target: String,
+    /// the cost of an arc, or `None` for every arc to cost one
+    pub weight: Option<String>,
+    /// what a weight is multiplied by before it is rounded
+    pub scale: f64,
+    pub id: String,
+    pub lat: String,
+    pub lon: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            source: "source".to_string(),
+            target: "target".to_string(),
+            weight: Some("weight".to_string()),
+            scale: 1.,
+            id: "id".to_string(),
+            lat: "lat".to_string(),
+            lon: "lon".to_string(),
+        }
+    }
+}
+
+/// Reads the arcs of a CSV file, without loops.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, lacks a column, or a record does not
+/// have as many fields as the header or does not parse.
+pub fn try_read_graph<T: Eq + From<usize>>(
+    filename: &str,
+    columns: &CsvColumns,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut file = CsvFile::open(filename)?;
+    let source = file.column(&columns.source)?;
+    let target = file.column(&columns.target)?;
+    let weight = match &columns.weight {
+        Some(name) => Some(file.column(name)?),
+        None => None,
+    };
+
+    let mut edges = Vec::new();
+    while let Some((number, fields)) = file.next_record()? {
+        let lines = &file.lines;
+        let source = lines.parse::<NodeID>(number, &fields[source], "node id")?;
+        let target = lines.parse::<NodeID>(number, &fields[target], "node id")?;
+        let data = match weight {
+            Some(weight) => {
+                let value = lines.parse::<f64>(number, &fields[weight], "weight")?;
+                scaled_weight(value, columns.scale).map_err(|reason| lines.error(number, reason))?
+            }
+            None => 1,
+        };
+        // avoid eigenloops
+        if source != target {
+            edges.push(InputEdge::new(source, target, T::from(data)));
+        }
+    }
+    info!("read {} arcs", edges.len());
+    Ok(edges)
+}
+
+/// Reads the coordinates of a CSV file of nodes, which may list the nodes in
+/// any order but has to list each of them once.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, lacks a column, a record does not parse,
+/// or a node is listed twice or its id is not below the number of nodes.
+pub fn try_read_coordinates(
+    filename: &str,
+    columns: &CsvColumns,
+) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let mut file = CsvFile::open(filename)?;
+    let id = file.column(&columns.id)?;
+    let lat = file.column(&columns.lat)?;
+    let lon = file.column(&columns.lon)?;
+
+    let mut records = Vec::new();
+    while let Some((number, fields)) = file.next_record()? {
+        let lines = &file.lines;
+        let node = lines.parse::<NodeID>(number, &fields[id], "node id")?;
+        let lat = lines.parse::<f64>(number, &fields[lat], "latitude")?;
+        let lon = lines.parse::<f64>(number, &fields[lon], "longitude")?;
+        if !(-90. ..=90.).contains(&lat) || !(-180. ..=180.).contains(&lon) {
+            return Err(lines.error(number, format!("({lat}, {lon}) is not on the earth")));
+        }
+        records.push((number, node, degrees(lat, lon)));
+    }
+
+    // Each node is listed once, so the ids are those below the number of
+    // records. Checking that before filling the vector keeps a stray id from
+    // sizing it.
+    let mut coordinates = vec![FPCoordinate::new(0, 0); records.len()];
+    let mut listed = vec![false; records.len()];
+    for (number, node, coordinate) in records {
+        if node >= listed.len() {
+            return Err(file.lines.error(
+                number,
+                format!("node {node} is beyond the {} nodes listed", listed.len()),
+            ));
+        }
+        if listed[node] {
+            return Err(file
+                .lines
+                .error(number, format!("node {node} is listed twice")));
+        }
+        listed[node] = true;
+        coordinates[node] = coordinate;
+    }
+    info!("read {} coordinates", coordinates.len());
+    Ok(coordinates)
+}
+
+/// Writes arcs for [`try_read_graph`] to read back, with a weight column
+/// even if the columns name none.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+pub fn write_graph<T: Eq + Display>(
+    filename: &str,
+    edges: &[InputEdge<T>],
+    columns: &CsvColumns,
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    let weight = columns.weight.as_deref().unwrap_or("weight");
+    out.write_line(format_args!(
+        "{},{},{}",
+        quoted(&columns.source),
+        quoted(&columns.target),
+        quoted(weight)
+    ))?;
+    for edge in edges {
+        out.write_line(format_args!(
+            "{},{},{}",
+            edge.source, edge.target, edge.data
+        ))?;
+    }
+    out.finish()
+}
+
+/// Writes coordinates in degrees for [`try_read_coordinates`] to read back.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+pub fn write_coordinates(
+    filename: &str,
+    coordinates: &[FPCoordinate],
+    columns: &CsvColumns,
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    out.write_line(format_args!(
+        "{},{},{}",
+        quoted(&columns.id),
+        quoted(&columns.lat),
+        quoted(&columns.lon)
+    ))?;
+    for (node, coordinate) in coordinates.iter().enumerate() {
+        let (lon, lat) = coordinate.to_lon_lat_pair();
+        out.write_line(format_args!("{node},{lat},{lon}"))?;
+    }
+    out.finish()
+}
+
+/// A coordinate in degrees, rounded rather than cut to millionths, such that
+/// what was written in degrees reads back as it was.
+pub(crate) fn degrees(lat: f64, lon: f64) -> FPCoordinate {
+    FPCoordinate::new(
+        (lat * 1_000_000.).round() as i32,
+        (lon * 1_000_000.).round() as i32,
+    )
+}
+
+/// A name as a field, quoted if it has to be.
+fn quoted(name: &str) -> String {
+    if name.contains([',', '"']) {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_string()
+    }
+}
+
+/// A CSV file read record by record, past its header.
+struct CsvFile {
+    lines: NumberedLines,
+    header: Vec<String>,
+}
+
+impl CsvFile {
+    fn open(filename: &str) -> Result<Self, GraphFileError> {
+        let mut file = Self {
+            lines: NumberedLines::open(filename)?,
+            header: Vec::new(),
+        };
+        let Some((_, header)) = file.next_fields()? else {
+            return Err(file.lines.missing("header"));
+        };
+        file.header = header;
+        Ok(file)
+    }
+
+    /// Where the column of the given name is.
+    fn column(&self, name: &str) -> Result<usize, GraphFileError> {
+        self.header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| {
+                self.lines.error(
+                    1,
+                    format!(
+                        "there is no column `{name}` among {}",
+                        self.header.join(", ")
+                    ),
+                )
+            })
+    }
+
+    /// The next record and the number of its line, as many fields as the
+    /// header has.
+    fn next_record(&mut self) -> Result<Option<(usize, Vec<String>)>, GraphFileError> {
+        let Some((number, fields)) = self.next_fields()? else {
+            return Ok(None);
+        };
+        if fields.len() != self.header.len() {
+            return Err(self.lines.error(
+                number,
+                format!(
+                    "{} fields, but the header names {}",
+                    fields.len(),
+                    self.header.len()
+                ),
+            ));
+        }
+        Ok(Some((number, fields)))
+    }
+
+    fn next_fields(&mut self) -> Result<Option<(usize, Vec<String>)>, GraphFileError> {
+        while let Some((number, line)) = self.lines.next_line()? {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = split_record(line).map_err(|reason| self.lines.error(number, reason))?;
+            return Ok(Some((number, fields)));
+        }
+        Ok(None)
+    }
+}
+
+/// Splits a line into its fields, which may be quoted, with a doubled quote
+/// standing for one.
+fn split_record(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (false, ',') => fields.push(std::mem::take(&mut field).trim().to_string()),
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => {
+                quoted = false;
+                // what follows a closing quote up to the comma is padding
+                while chars.peek().is_some_and(|c| *c != ',') {
+                    if !chars.next().is_some_and(char::is_whitespace) {
+                        return Err("text after a closing quote");
+                    }
+                }
+            }
+            (_, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("a quote that is not closed");
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn file_with(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn columns_are_found_by_name() {
+        let file = file_with(
+            "# exported\nweight,\"name, long\",target,source\n\n2.4,\"a, b\",1,0\n7,\"\"\"c\"\"\",0,2\n3,x,2,2\n",
+        );
+        let edges =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), &CsvColumns::default()).unwrap();
+        assert_eq!(
+            edges,
+            vec![InputEdge::new(0, 1, 2), InputEdge::new(2, 0, 7)]
+        );
+
+        let unit = CsvColumns {
+            weight: None,
+            ..Default::default()
+        };
+        let edges = try_read_graph::<usize>(file.path().to_str().unwrap(), &unit).unwrap();
+        assert!(edges.iter().all(|edge| edge.data == 1));
+    }
+
+    #[test]
+    fn broken_files_say_where() {
+        let file = file_with("source,target\n0,1\n");
+        let error = try_read_graph::<usize>(file.path().to_str().unwrap(), &CsvColumns::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("no column `weight`"));
+
+        let file = file_with("source,target,weight\n0,1,3\n1,2\n");
+        let error = try_read_graph::<usize>(file.path().to_str().unwrap(), &CsvColumns::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("line 3"));
+
+        let file = file_with("source,target,weight\n0,1,-3\n");
+        let error = try_read_graph::<usize>(file.path().to_str().unwrap(), &CsvColumns::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("not a cost"));
+
+        let file = file_with("id,lat,lon\n0,1,2\n2,3,4\n");
+        let error = try_read_coordinates(file.path().to_str().unwrap(), &CsvColumns::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("line 3"));
+        assert!(error.to_string().contains("node 2 is beyond the 2 nodes"));
+
+        // an id far beyond the records is an error rather than an allocation
+        let file = file_with("id,lat,lon\n0,1,2\n4000000000,3,4\n");
+        let error = try_read_coordinates(file.path().to_str().unwrap(), &CsvColumns::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("node 4000000000 is beyond"));
+    }
+
+    #[test]
+    fn nodes_may_come_in_any_order() {
+        let file = file_with("lon,id,lat\n13.4,1,52.5\n-0.1275,0,51.507222\n");
+        let coordinates =
+            try_read_coordinates(file.path().to_str().unwrap(), &CsvColumns::default()).unwrap();
+        assert_eq!(
+            coordinates,
+            vec![
+                FPCoordinate::new(51_507_222, -127_500),
+                FPCoordinate::new(52_500_000, 13_400_000)
+            ]
+        );
+    }
+
+    #[test]
+    fn written_graph_and_coordinates_read_back() {
+        let edges = vec![InputEdge::new(0, 1, 3_usize), InputEdge::new(2, 0, 11)];
+        let coordinates = vec![
+            FPCoordinate::new(52_520_008, 13_404_954),
+            FPCoordinate::new(-33_868_820, 151_209_296),
+            FPCoordinate::new(1, -1),
+        ];
+        let columns = CsvColumns {
+            weight: Some("length, in metres".into()),
+            ..Default::default()
+        };
+        let graph = NamedTempFile::new().unwrap();
+        let nodes = NamedTempFile::new().unwrap();
+        write_graph(graph.path().to_str().unwrap(), &edges, &columns).unwrap();
+        write_coordinates(nodes.path().to_str().unwrap(), &coordinates, &columns).unwrap();
+
+        assert_eq!(
+            try_read_graph::<usize>(graph.path().to_str().unwrap(), &columns).unwrap(),
+            edges
+        );
+        assert_eq!(
+            try_read_coordinates(nodes.path().to_str().unwrap(), &columns).unwrap(),
+            coordinates
+        );
+    }
+}
@@ -177,8 +177,8 @@ impl CellDistances {
 ///
 /// Panics in a debug build if the node it starts from is not in the cell,
 /// which would answer about a cell the caller did not ask about.
-pub(crate) fn distances_within_cell(
-    graph: &StaticGraph<u32>,
+pub(crate) fn distances_within_cell<G: Graph<u32>>(
+    graph: &G,
     of_node: &[CellId],
     cell: CellId,
     from: NodeID,
@@ -244,8 +244,11 @@ pub struct CellReport<'a> {
 }
 
 /// The cells of a partition, worked out level by level as they are asked for.
-pub struct Customization {
-    graph: StaticGraph<u32>,
+///
+/// The graph is an adjacency array held in memory by default, and may be any
+/// other graph, such as one mapped from a file that need not be read first.
+pub struct Customization<G = StaticGraph<u32>> {
+    graph: G,
     directory: LevelDirectory,
     /// The cells of a level, and the nodes of each of them, worked out the
     /// first time that level is asked about. Walking the directory per node
@@ -289,9 +292,9 @@ pub struct Customization {
     customization_nanos: AtomicU64,
 }
 
-impl Customization {
+impl<G: Graph<u32>> Customization<G> {
     #[must_use]
-    pub fn new(graph: StaticGraph<u32>, directory: LevelDirectory) -> Self {
+    pub fn new(graph: G, directory: LevelDirectory) -> Self {
         assert_eq!(
             graph.number_of_nodes(),
             directory.number_of_nodes(),
@@ -331,7 +334,7 @@ impl Customization {
     }
 
     /// the graph the partition was built over
-    pub const fn graph(&self) -> &StaticGraph<u32> {
+    pub const fn graph(&self) -> &G {
         &self.graph
     }
 
@@ -730,7 +733,7 @@ pub struct CellCheck {
     pub has_border: bool,
 }
 
-impl Customization {
+impl<G: Graph<u32>> Customization<G> {
     /// Holds every distance of a cell against a search over the graph itself.
     ///
     /// This is the slow way round and the point of it: the cell is worked out
@@ -1,7 +1,15 @@
 use itertools::Itertools;
 use log::info;
 
-use crate::{edge::InputEdge, geometry::FPCoordinate, graph::NodeID, io::read_lines};
+use crate::{
+    edge::InputEdge,
+    geometry::FPCoordinate,
+    graph::NodeID,
+    io::{
+        Chunk, GraphFileError, LineReader, NumberedCoordinates, NumberedLines, TextFile, TextLines,
+        TextWriter, concat_chunks, read_lines,
+    },
+};
 
 pub enum WeightType {
     Unit,
@@ -30,6 +38,15 @@ impl TryFrom<i32> for Direction {
     }
 }
 
+/// Reads the arcs of a DDSG graph, each edge in the directions it is open in
+/// and without loops.
+///
+/// A file that does not start with `d` gives no arcs, and lines that do not
+/// read as an edge are skipped. [`try_read_graph`] checks the file instead.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or a number does not parse.
 pub fn read_graph<T: std::fmt::Debug + std::cmp::Eq + From<usize>>(
     filename: &str,
     weight_type: WeightType,
@@ -124,6 +141,13 @@ pub fn read_graph<T: std::fmt::Debug + std::cmp::Eq + From<usize>>(
     edges
 }
 
+/// Reads the coordinates of a DDSG graph, longitude and latitude in units of
+/// 10⁻⁵ degrees.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read, a number does not parse, or the nodes
+/// are out of order or not as many as announced.
 pub fn read_coordinates(filename: &str) -> Vec<FPCoordinate> {
     let mut lines = read_lines(filename).expect("could not load coordinates file");
     let first_line = lines.next().unwrap().unwrap();
@@ -146,12 +170,292 @@ pub fn read_coordinates(filename: &str) -> Vec<FPCoordinate> {
     coordinates
 }
 
+/// Reads the arcs of a DDSG graph like [`read_graph`], but reports what is
+/// wrong with the file rather than skipping or panicking on it.
+///
+/// The file starts with a line `d` and the header `<nodes> <edges>`, followed
+/// by one line `<source> <target> <weight> <direction>` per edge, the nodes
+/// numbered from zero. There have to be as many edges as announced, closed
+/// ones and loops included, though neither gives an arc.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, if it does not start with `d` and the
+/// header, if a line is not an edge, if a number does not parse, a node is
+/// out of range or a direction unknown, or if the number of edges is not the
+/// announced one.
+pub fn try_read_graph<T: std::fmt::Debug + std::cmp::Eq + From<usize>>(
+    filename: &str,
+    weight_type: WeightType,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut lines = NumberedLines::open(filename)?;
+    let (number_of_nodes, number_of_edges) = read_graph_header(&mut lines)?;
+    let chunks = lines.parse_rest(|file, number, line, arcs| {
+        parse_edge_line(file, number, line, number_of_nodes, &weight_type, arcs)
+    })?;
+    join_arcs(&lines, number_of_edges, chunks)
+}
+
+/// Reads the coordinates of a DDSG graph like [`read_coordinates`], but
+/// reports what is wrong with the file rather than panicking on it.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, if the count on its first line or a
+/// number of a coordinate does not parse, if the nodes do not follow each other
+/// from zero, or if there are not as many as announced.
+pub fn try_read_coordinates(filename: &str) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let mut lines = NumberedLines::open(filename)?;
+    let coordinate_count = read_coordinate_count(&mut lines)?;
+    let chunks = lines.parse_rest(parse_coordinate_line)?;
+    join_coordinates(&lines, coordinate_count, chunks)
+}
+
+/// Reads the arcs of a DDSG graph like [`try_read_graph`], but parses the
+/// edges [on all threads](crate::io#reading-in-parallel).
+///
+/// # Errors
+///
+/// Fails where [`try_read_graph`] fails.
+pub fn par_try_read_graph<T: std::fmt::Debug + std::cmp::Eq + From<usize> + Send>(
+    filename: &str,
+    weight_type: WeightType,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut lines = TextLines::read(filename)?;
+    let (number_of_nodes, number_of_edges) = read_graph_header(&mut lines)?;
+    let chunks = lines.parse_in_chunks(|file, number, line, arcs| {
+        parse_edge_line(file, number, line, number_of_nodes, &weight_type, arcs)
+    });
+    join_arcs(&lines, number_of_edges, chunks)
+}
+
+/// Reads the coordinates of a DDSG graph like [`try_read_coordinates`], but
+/// parses them [on all threads](crate::io#reading-in-parallel).
+///
+/// # Errors
+///
+/// Fails where [`try_read_coordinates`] fails.
+pub fn par_try_read_coordinates(filename: &str) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let mut lines = TextLines::read(filename)?;
+    let coordinate_count = read_coordinate_count(&mut lines)?;
+    let chunks = lines.parse_in_chunks(parse_coordinate_line);
+    join_coordinates(&lines, coordinate_count, chunks)
+}
+
+/// The number of nodes and edges announced by the line `d` and the header
+/// that start a graph.
+fn read_graph_header(lines: &mut impl LineReader) -> Result<(usize, usize), GraphFileError> {
+    let Some((number, first_line)) = lines.next_line()? else {
+        return Err(lines.missing("line `d`"));
+    };
+    if first_line.trim() != "d" {
+        return Err(lines.error(number, "expected `d` for a directed graph"));
+    }
+    let Some((number, second_line)) = lines.next_line()? else {
+        return Err(lines.missing("header"));
+    };
+    let sizes = second_line.split_ascii_whitespace().collect_vec();
+    if sizes.len() != 2 {
+        return Err(lines.error(number, "expected `<nodes> <edges>`"));
+    }
+    let number_of_nodes = lines.parse::<usize>(number, sizes[0], "node count")?;
+    let number_of_edges = lines.parse::<usize>(number, sizes[1], "edge count")?;
+    info!("expecting {number_of_nodes} nodes and {number_of_edges} edges");
+    Ok((number_of_nodes, number_of_edges))
+}
+
+/// Parses an edge into the arcs of its chunk, which also counts the edges
+/// that give none, closed ones and loops.
+fn parse_edge_line<T: std::cmp::Eq + From<usize>>(
+    file: &TextFile,
+    number: usize,
+    line: &str,
+    number_of_nodes: usize,
+    weight_type: &WeightType,
+    (edges, edge_count): &mut (Vec<InputEdge<T>>, usize),
+) -> Result<(), GraphFileError> {
+    let tokens = line.split_ascii_whitespace().collect_vec();
+    if tokens.is_empty() {
+        return Ok(());
+    }
+    if tokens.len() != 4 {
+        return Err(file.error(number, "expected `<source> <target> <weight> <direction>`"));
+    }
+    let node = |token: &str| {
+        let id = file.parse::<NodeID>(number, token, "node id")?;
+        if id >= number_of_nodes {
+            return Err(file.error(
+                number,
+                format!("node {id} is not one of the {number_of_nodes} nodes"),
+            ));
+        }
+        Ok(id)
+    };
+    let u = node(tokens[0])?;
+    let v = node(tokens[1])?;
+    let data = file.parse::<usize>(number, tokens[2], "weight")?;
+    let direction = file.parse::<i32>(number, tokens[3], "direction")?;
+    let direction = Direction::try_from(direction)
+        .map_err(|_| file.error(number, format!("{direction} is not a direction")))?;
+    *edge_count += 1;
+
+    // avoid eigenloops
+    if u == v {
+        return Ok(());
+    }
+    let data = || match weight_type {
+        WeightType::Unit => T::from(1),
+        WeightType::Original => T::from(data),
+    };
+    if matches!(direction, Direction::Both | Direction::Forward) {
+        edges.push(InputEdge::<T> {
+            source: u,
+            target: v,
+            data: data(),
+        });
+    }
+    if matches!(direction, Direction::Both | Direction::Reverse) {
+        edges.push(InputEdge::<T> {
+            source: v,
+            target: u,
+            data: data(),
+        });
+    }
+    Ok(())
+}
+
+/// The arcs of all chunks, whose edges have to be as many as announced.
+fn join_arcs<T: std::cmp::Eq>(
+    file: &TextFile,
+    number_of_edges: usize,
+    chunks: Vec<Chunk<(Vec<InputEdge<T>>, usize)>>,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let chunks = Chunk::states(chunks)?;
+    let input_edge_counter = chunks.iter().map(|(_, count)| count).sum();
+    file.count("edges", number_of_edges, input_edge_counter)?;
+    let edges = concat_chunks(chunks.into_iter().map(|(edges, _)| edges).collect());
+    info!(
+        "exploded {input_edge_counter} input edges into {} directed edges",
+        edges.len()
+    );
+    Ok(edges)
+}
+
+/// The number of coordinates on the first line of a coordinates file.
+fn read_coordinate_count(lines: &mut impl LineReader) -> Result<usize, GraphFileError> {
+    let Some((number, first_line)) = lines.next_line()? else {
+        return Err(lines.missing("coordinate count"));
+    };
+    let coordinate_count = lines.parse::<usize>(number, first_line.trim(), "coordinate count")?;
+    info!("expecting {coordinate_count} coordinates");
+    Ok(coordinate_count)
+}
+
+/// Parses a line into the coordinates of its chunk.
+fn parse_coordinate_line(
+    file: &TextFile,
+    number: usize,
+    line: &str,
+    coordinates: &mut NumberedCoordinates,
+) -> Result<(), GraphFileError> {
+    let tokens = line.split_ascii_whitespace().collect_vec();
+    if tokens.is_empty() {
+        return Ok(());
+    }
+    if tokens.len() != 3 {
+        return Err(file.error(number, "expected `<node> <lon> <lat>`"));
+    }
+    let id = file.parse::<NodeID>(number, tokens[0], "node id")?;
+    let lon = file.parse::<f64>(number, tokens[1], "longitude")? / 100_000.;
+    let lat = file.parse::<f64>(number, tokens[2], "latitude")? / 100_000.;
+    coordinates.push(file, number, id, FPCoordinate::new_from_lat_lon(lat, lon))
+}
+
+/// The coordinates of all chunks, whose nodes have to follow each other from
+/// zero, and of which there have to be as many as announced.
+fn join_coordinates(
+    file: &TextFile,
+    coordinate_count: usize,
+    chunks: Vec<Chunk<NumberedCoordinates>>,
+) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let coordinates = NumberedCoordinates::join(file, 0, chunks)?;
+    file.count("coordinates", coordinate_count, coordinates.len())?;
+    info!("loaded {coordinate_count} coordinates");
+    Ok(coordinates)
+}
+
+/// Writes arcs as a DDSG graph for [`read_graph`] to read back, each of them
+/// as an edge open in its own direction only.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+///
+/// # Panics
+///
+/// Panics if an arc leaves the `number_of_nodes` nodes.
+pub fn write_graph<T: std::cmp::Eq + std::fmt::Display>(
+    filename: &str,
+    number_of_nodes: usize,
+    edges: &[InputEdge<T>],
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    out.write_line("d")?;
+    out.write_line(format_args!("{number_of_nodes} {}", edges.len()))?;
+    for edge in edges {
+        assert!(
+            edge.source < number_of_nodes && edge.target < number_of_nodes,
+            "arc ({}, {}) leaves the {number_of_nodes} nodes",
+            edge.source,
+            edge.target
+        );
+        out.write_line(format_args!(
+            "{} {} {} {}",
+            edge.source,
+            edge.target,
+            edge.data,
+            Direction::Forward as i32
+        ))?;
+    }
+    out.finish()
+}
+
+/// Writes coordinates for [`read_coordinates`] to read back.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+pub fn write_coordinates(
+    filename: &str,
+    coordinates: &[FPCoordinate],
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    out.write_line(coordinates.len())?;
+    for (node, coordinate) in coordinates.iter().enumerate() {
+        // millionths of a degree in units of 10⁻⁵ degrees
+        out.write_line(format_args!(
+            "{node} {} {}",
+            f64::from(coordinate.lon) / 10.,
+            f64::from(coordinate.lat) / 10.
+        ))?;
+    }
+    out.finish()
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
     use tempfile::NamedTempFile;
 
-    use crate::ddsg::{Direction, WeightType, read_coordinates};
+    use crate::{
+        ddsg::{
+            Direction, WeightType, par_try_read_coordinates, par_try_read_graph, read_coordinates,
+            read_graph, try_read_coordinates, try_read_graph, write_coordinates, write_graph,
+        },
+        edge::InputEdge,
+        geometry::FPCoordinate,
+        io::GraphFileError,
+    };
 
     #[test]
     fn direction_try_from() {
@@ -222,9 +526,140 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn try_read_graph_matches_read_graph() {
+        let filename =
+            create_temp_file_with_content("d\n3 4\n0 1 10 1\n1 2 20 2\n2 0 30 3\n1 1 5 0\n");
+        let path = filename.path().to_str().unwrap();
+        let edges = try_read_graph::<usize>(path, WeightType::Original).unwrap();
+        assert_eq!(edges, read_graph::<usize>(path, WeightType::Original));
+        assert_eq!(edges.len(), 2);
+    }
+
+    #[test]
+    fn try_read_graph_reports_what_is_wrong() {
+        let read = |content: &str| {
+            let filename = create_temp_file_with_content(content);
+            try_read_graph::<usize>(filename.path().to_str().unwrap(), WeightType::Unit)
+                .unwrap_err()
+        };
+        assert!(matches!(
+            read("t\n2 1\n0 1 10 0\n"),
+            GraphFileError::Parse { line: 1, .. }
+        ));
+        assert!(matches!(
+            read("d\n2 1\n0 2 10 0\n"),
+            GraphFileError::Parse { line: 3, .. }
+        ));
+        assert!(matches!(
+            read("d\n2 2\n0 1 10 0\n1 0 10 7\n"),
+            GraphFileError::Parse { line: 4, .. }
+        ));
+        assert!(matches!(
+            read("d\n2 2\n0 1 10 0\n"),
+            GraphFileError::Count {
+                expected: 2,
+                found: 1,
+                ..
+            }
+        ));
+        assert!(matches!(read("d\n"), GraphFileError::Missing { .. }));
+    }
+
+    #[test]
+    fn try_read_coordinates_checks_ids_and_count() {
+        let filename = create_temp_file_with_content("2\n0 1000000 2000000\n1 3000000 4000000\n");
+        let path = filename.path().to_str().unwrap();
+        assert_eq!(try_read_coordinates(path).unwrap(), read_coordinates(path));
+
+        let filename = create_temp_file_with_content("2\n0 1000000 2000000\n2 3000000 4000000\n");
+        let error = try_read_coordinates(filename.path().to_str().unwrap()).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 3, .. }));
+
+        let filename = create_temp_file_with_content("3\n0 1000000 2000000\n");
+        let error = try_read_coordinates(filename.path().to_str().unwrap()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .ends_with("the header announces 3 coordinates, but there are 1")
+        );
+    }
+
+    #[test]
+    fn written_graph_and_coordinates_read_back() {
+        let edges = vec![
+            InputEdge::new(0, 1, 10_usize),
+            InputEdge::new(1, 0, 10),
+            InputEdge::new(2, 1, 20),
+        ];
+        let coordinates = vec![
+            FPCoordinate::new(20_000_000, 10_000_000),
+            FPCoordinate::new(40_000_000, 30_000_000),
+            FPCoordinate::new(-5_000_000, 170_000_000),
+        ];
+        let graph_file = NamedTempFile::new().unwrap();
+        let graph_path = graph_file.path().to_str().unwrap();
+        let coordinates_file = NamedTempFile::new().unwrap();
+        let coordinates_path = coordinates_file.path().to_str().unwrap();
+
+        write_graph(graph_path, 3, &edges).unwrap();
+        write_coordinates(coordinates_path, &coordinates).unwrap();
+        assert_eq!(
+            try_read_graph::<usize>(graph_path, WeightType::Original).unwrap(),
+            edges
+        );
+        assert_eq!(try_read_coordinates(coordinates_path).unwrap(), coordinates);
+    }
+
     fn create_temp_file_with_content(content: &str) -> NamedTempFile {
         let mut file = NamedTempFile::new().unwrap();
         write!(file, "{}", content).unwrap();
         file
     }
+
+    #[test]
+    fn par_try_read_matches_try_read() {
+        // edges of all directions, loops among them, on enough lines for
+        // every thread to get a few chunks
+        let mut graph = String::from("d\n1000 20000\n");
+        for i in 0..20_000 {
+            graph.push_str(&format!(
+                "{} {} {} {}\n",
+                i % 1000,
+                (7 * i) % 1000,
+                i,
+                i % 4
+            ));
+        }
+        let mut coordinates = String::from("1000\n");
+        for node in 0..1000 {
+            coordinates.push_str(&format!("{node} {} {}\n", node * 10, -node));
+        }
+        let graph_file = create_temp_file_with_content(&graph);
+        let graph_path = graph_file.path().to_str().unwrap();
+        let coordinates_file = create_temp_file_with_content(&coordinates);
+        let coordinates_path = coordinates_file.path().to_str().unwrap();
+
+        assert_eq!(
+            par_try_read_graph::<usize>(graph_path, WeightType::Original).unwrap(),
+            try_read_graph::<usize>(graph_path, WeightType::Original).unwrap()
+        );
+        assert_eq!(
+            par_try_read_coordinates(coordinates_path).unwrap(),
+            try_read_coordinates(coordinates_path).unwrap()
+        );
+
+        let broken =
+            create_temp_file_with_content(&graph.replace("d\n1000 20000", "d\n1000 19999"));
+        let path = broken.path().to_str().unwrap();
+        let error = par_try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert!(matches!(
+            error,
+            GraphFileError::Count {
+                expected: 19_999,
+                found: 20_000,
+                ..
+            }
+        ));
+    }
 }
@@ -1,13 +1,29 @@
 use itertools::Itertools;
 use log::{debug, info};
 
-use crate::{edge::InputEdge, geometry::FPCoordinate, graph::NodeID, io::read_lines};
+use crate::{
+    edge::InputEdge,
+    geometry::FPCoordinate,
+    graph::NodeID,
+    io::{
+        Chunk, GraphFileError, LineReader, NumberedCoordinates, NumberedLines, TextFile, TextLines,
+        TextWriter, concat_chunks, read_lines,
+    },
+};
 
 pub enum WeightType {
     Unit,
     Original,
 }
 
+/// Reads the arcs of a DIMACS graph, numbered from zero and without loops.
+///
+/// Lines that do not read as an arc are skipped. [`try_read_graph`] checks the
+/// file instead.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or a number does not parse.
 pub fn read_graph<T: std::cmp::Eq + From<usize>>(
     filename: &str,
     weight_type: WeightType,
@@ -68,6 +84,11 @@ pub fn read_graph<T: std::cmp::Eq + From<usize>>(
     edges
 }
 
+/// Reads the coordinates of a DIMACS graph, the one of node i at i - 1.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or a number does not parse.
 pub fn read_coordinates(filename: &str) -> Vec<FPCoordinate> {
     let mut coordinates = Vec::new();
     let mut comment_count = 0;
@@ -103,6 +124,295 @@ pub fn read_coordinates(filename: &str) -> Vec<FPCoordinate> {
     coordinates
 }
 
+/// Reads the arcs of a DIMACS graph like [`read_graph`], but reports what is
+/// wrong with the file rather than skipping or panicking on it.
+///
+/// The problem line `p sp <nodes> <arcs>` has to come before the first arc,
+/// every arc has to join two of the nodes it announces, and there have to be
+/// as many arcs as it announces. Loops count as arcs of the file, but are
+/// dropped as [`read_graph`] drops them.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, if a line is neither a comment, the
+/// problem line nor an arc, if a number does not parse or a node is out of
+/// range, or if the number of arcs is not the announced one.
+pub fn try_read_graph<T: std::cmp::Eq + From<usize>>(
+    filename: &str,
+    weight_type: WeightType,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut lines = NumberedLines::open(filename)?;
+    let (node_count, arc_count) = read_graph_problem_line(&mut lines)?;
+    let chunks = lines.parse_rest(|file, number, line, arcs| {
+        parse_arc_line(file, number, line, node_count, &weight_type, arcs)
+    })?;
+    join_arcs(&lines, arc_count, chunks)
+}
+
+/// Reads the coordinates of a DIMACS graph like [`read_coordinates`], but
+/// reports what is wrong with the file rather than panicking on it.
+///
+/// The problem line `p aux sp co <count>` has to come before the first
+/// coordinate, the nodes have to follow each other from 1, and there have to
+/// be as many of them as it announces.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, if a line is neither a comment, the
+/// problem line nor a coordinate, if a number does not parse, if a node is out
+/// of order, or if the number of coordinates is not the announced one.
+pub fn try_read_coordinates(filename: &str) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let mut lines = NumberedLines::open(filename)?;
+    let expected = read_coordinates_problem_line(&mut lines)?;
+    let chunks = lines.parse_rest(parse_coordinate_line)?;
+    join_coordinates(&lines, expected, chunks)
+}
+
+/// Reads the arcs of a DIMACS graph like [`try_read_graph`], but parses the
+/// arcs [on all threads](crate::io#reading-in-parallel).
+///
+/// # Errors
+///
+/// Fails where [`try_read_graph`] fails.
+pub fn par_try_read_graph<T: std::cmp::Eq + From<usize> + Send>(
+    filename: &str,
+    weight_type: WeightType,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut lines = TextLines::read(filename)?;
+    let (node_count, arc_count) = read_graph_problem_line(&mut lines)?;
+    let chunks = lines.parse_in_chunks(|file, number, line, arcs| {
+        parse_arc_line(file, number, line, node_count, &weight_type, arcs)
+    });
+    join_arcs(&lines, arc_count, chunks)
+}
+
+/// Reads the coordinates of a DIMACS graph like [`try_read_coordinates`],
+/// but parses them [on all threads](crate::io#reading-in-parallel).
+///
+/// # Errors
+///
+/// Fails where [`try_read_coordinates`] fails.
+pub fn par_try_read_coordinates(filename: &str) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let mut lines = TextLines::read(filename)?;
+    let expected = read_coordinates_problem_line(&mut lines)?;
+    let chunks = lines.parse_in_chunks(parse_coordinate_line);
+    join_coordinates(&lines, expected, chunks)
+}
+
+/// What the problem line of a file says, if only comments come before it and
+/// not the first `item`, whose line starts with `tag`.
+fn read_problem_line<H>(
+    lines: &mut impl LineReader,
+    (tag, item): (char, &str),
+    header: impl Fn(&TextFile, usize, &[&str]) -> Result<H, GraphFileError>,
+) -> Result<H, GraphFileError> {
+    while let Some((number, line)) = lines.next_line()? {
+        match line.chars().next() {
+            None | Some('c') => {}
+            Some('p') => {
+                return header(lines, number, &line.split_ascii_whitespace().collect_vec());
+            }
+            Some(first) if first == tag => {
+                return Err(lines.error(number, format!("{item} before the problem line")));
+            }
+            Some(_) => {
+                return Err(lines.error(number, format!("not a comment, problem line or {item}")));
+            }
+        }
+    }
+    Err(lines.missing("problem line"))
+}
+
+/// The number of nodes and arcs the problem line of a graph announces.
+fn read_graph_problem_line(lines: &mut impl LineReader) -> Result<(usize, usize), GraphFileError> {
+    read_problem_line(lines, ('a', "an arc"), |file, number, tokens| {
+        if tokens.len() != 4 || tokens[1] != "sp" {
+            return Err(file.error(number, "expected `p sp <nodes> <arcs>`"));
+        }
+        let node_count = file.parse::<usize>(number, tokens[2], "node count")?;
+        let arc_count = file.parse::<usize>(number, tokens[3], "arc count")?;
+        info!("expecting {node_count} nodes and {arc_count} edges");
+        Ok((node_count, arc_count))
+    })
+}
+
+/// The number of coordinates the problem line of a coordinates file
+/// announces.
+fn read_coordinates_problem_line(lines: &mut impl LineReader) -> Result<usize, GraphFileError> {
+    read_problem_line(lines, ('v', "a coordinate"), |file, number, tokens| {
+        if tokens.len() != 5 || tokens[1..4] != ["aux", "sp", "co"] {
+            return Err(file.error(number, "expected `p aux sp co <count>`"));
+        }
+        let count = file.parse::<usize>(number, tokens[4], "coordinate count")?;
+        info!("expecting {count} coordinates");
+        Ok(count)
+    })
+}
+
+/// Parses a line after the problem line of a graph into the arcs of its
+/// chunk, which also counts the loops among them, as they are arcs of the
+/// file.
+fn parse_arc_line<T: std::cmp::Eq + From<usize>>(
+    file: &TextFile,
+    number: usize,
+    line: &str,
+    node_count: usize,
+    weight_type: &WeightType,
+    (edges, arc_count): &mut (Vec<InputEdge<T>>, usize),
+) -> Result<(), GraphFileError> {
+    match line.chars().next() {
+        None | Some('c') => Ok(()),
+        Some('p') => Err(file.error(number, "a second problem line")),
+        Some('a') => {
+            let tokens = line.split_ascii_whitespace().collect_vec();
+            if tokens.len() != 4 {
+                return Err(file.error(number, "expected `a <source> <target> <weight>`"));
+            }
+            let node = |token: &str| {
+                let id = file.parse::<NodeID>(number, token, "node id")?;
+                if !(1..=node_count).contains(&id) {
+                    return Err(file.error(
+                        number,
+                        format!("node {id} is not one of the {node_count} nodes"),
+                    ));
+                }
+                // the DIMACS format numbers the nodes from 1
+                Ok(id - 1)
+            };
+            let source = node(tokens[1])?;
+            let target = node(tokens[2])?;
+            let data = file.parse::<usize>(number, tokens[3], "weight")?;
+            *arc_count += 1;
+            // avoid eigenloops
+            if source != target {
+                edges.push(InputEdge::<T> {
+                    source,
+                    target,
+                    data: match weight_type {
+                        WeightType::Unit => T::from(1),
+                        WeightType::Original => T::from(data),
+                    },
+                });
+            }
+            Ok(())
+        }
+        Some(_) => Err(file.error(number, "not a comment, problem line or an arc")),
+    }
+}
+
+/// The arcs of all chunks, of which there have to be as many as announced.
+fn join_arcs<T: std::cmp::Eq>(
+    file: &TextFile,
+    expected: usize,
+    chunks: Vec<Chunk<(Vec<InputEdge<T>>, usize)>>,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let chunks = Chunk::states(chunks)?;
+    file.count(
+        "arcs",
+        expected,
+        chunks.iter().map(|(_, count)| count).sum(),
+    )?;
+    Ok(concat_chunks(
+        chunks.into_iter().map(|(edges, _)| edges).collect(),
+    ))
+}
+
+/// Parses a line after the problem line of a coordinates file into the
+/// coordinates of its chunk.
+fn parse_coordinate_line(
+    file: &TextFile,
+    number: usize,
+    line: &str,
+    coordinates: &mut NumberedCoordinates,
+) -> Result<(), GraphFileError> {
+    match line.chars().next() {
+        None | Some('c') => Ok(()),
+        Some('p') => Err(file.error(number, "a second problem line")),
+        Some('v') => {
+            let tokens = line.split_ascii_whitespace().collect_vec();
+            if tokens.len() != 4 {
+                return Err(file.error(number, "expected `v <node> <lon> <lat>`"));
+            }
+            let id = file.parse::<NodeID>(number, tokens[1], "node id")?;
+            let lon = file.parse::<i32>(number, tokens[2], "longitude")?;
+            let lat = file.parse::<i32>(number, tokens[3], "latitude")?;
+            coordinates.push(file, number, id, FPCoordinate::new(lat, lon))
+        }
+        Some(_) => Err(file.error(number, "not a comment, problem line or a coordinate")),
+    }
+}
+
+/// The coordinates of all chunks, whose nodes have to follow each other from
+/// 1, and of which there have to be as many as announced.
+fn join_coordinates(
+    file: &TextFile,
+    expected: usize,
+    chunks: Vec<Chunk<NumberedCoordinates>>,
+) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let coordinates = NumberedCoordinates::join(file, 1, chunks)?;
+    file.count("coordinates", expected, coordinates.len())?;
+    Ok(coordinates)
+}
+
+/// Writes arcs as a DIMACS graph for [`read_graph`] to read back, the nodes
+/// numbered from 1 as the format has them.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+///
+/// # Panics
+///
+/// Panics if an arc leaves the `number_of_nodes` nodes.
+pub fn write_graph<T: std::cmp::Eq + std::fmt::Display>(
+    filename: &str,
+    number_of_nodes: usize,
+    edges: &[InputEdge<T>],
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    out.write_line("c written by toolbox-rs")?;
+    out.write_line(format_args!("p sp {number_of_nodes} {}", edges.len()))?;
+    for edge in edges {
+        assert!(
+            edge.source < number_of_nodes && edge.target < number_of_nodes,
+            "arc ({}, {}) leaves the {number_of_nodes} nodes",
+            edge.source,
+            edge.target
+        );
+        out.write_line(format_args!(
+            "a {} {} {}",
+            edge.source + 1,
+            edge.target + 1,
+            edge.data
+        ))?;
+    }
+    out.finish()
+}
+
+/// Writes coordinates for [`read_coordinates`] to read back, the one at i as
+/// that of node i + 1.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+pub fn write_coordinates(
+    filename: &str,
+    coordinates: &[FPCoordinate],
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    out.write_line("c written by toolbox-rs")?;
+    out.write_line(format_args!("p aux sp co {}", coordinates.len()))?;
+    for (node, coordinate) in coordinates.iter().enumerate() {
+        out.write_line(format_args!(
+            "v {} {} {}",
+            node + 1,
+            coordinate.lon,
+            coordinate.lat
+        ))?;
+    }
+    out.finish()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -313,4 +623,174 @@ mod tests {
         // Verify that the function panicked
         assert!(result.is_err());
     }
+
+    fn file_with(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{content}").unwrap();
+        file
+    }
+
+    #[test]
+    fn try_read_graph_matches_read_graph() {
+        let file =
+            file_with("c a square\np sp 4 5\na 1 2 10\na 2 3 20\na 3 4 30\na 4 1 40\na 2 2 50\n");
+        let path = file.path().to_str().unwrap();
+        let edges = try_read_graph::<usize>(path, WeightType::Original).unwrap();
+        assert_eq!(edges, read_graph::<usize>(path, WeightType::Original));
+        assert_eq!(edges.len(), 4);
+    }
+
+    #[test]
+    fn try_read_graph_reports_the_line() {
+        let file = file_with("p sp 4 2\na 1 2 1\na 1 x 1\n");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 3, .. }));
+        assert!(error.to_string().contains("`x` is not a node id"));
+
+        let file = file_with("p sp 4 2\na 1 2 1\na 1 5 1\n");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 3, .. }));
+
+        let file = file_with("a 1 2 1\np sp 4 1\n");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 1, .. }));
+    }
+
+    #[test]
+    fn try_read_graph_checks_the_header() {
+        let file = file_with("p sp 4 3\na 1 2 1\na 2 3 1\n");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), WeightType::Unit).unwrap_err();
+        assert!(matches!(
+            error,
+            GraphFileError::Count {
+                what: "arcs",
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+
+        let file = file_with("c nothing but a comment\n");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Missing { .. }));
+
+        let error = try_read_graph::<usize>("invalid_file.txt", WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Io { .. }));
+    }
+
+    #[test]
+    fn try_read_coordinates_checks_ids_and_count() {
+        let file = file_with("p aux sp co 2\nv 1 100 200\nv 2 150 250\n");
+        let coordinates = try_read_coordinates(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(
+            coordinates,
+            vec![FPCoordinate::new(200, 100), FPCoordinate::new(250, 150)]
+        );
+
+        let file = file_with("p aux sp co 2\nv 1 100 200\nv 3 150 250\n");
+        let error = try_read_coordinates(file.path().to_str().unwrap()).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 3, .. }));
+
+        let file = file_with("p aux sp co 3\nv 1 100 200\nv 2 150 250\n");
+        let error = try_read_coordinates(file.path().to_str().unwrap()).unwrap_err();
+        assert!(matches!(
+            error,
+            GraphFileError::Count {
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+
+        let file = file_with("v 1 100 200\n");
+        assert!(try_read_coordinates(file.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn written_graph_and_coordinates_read_back() {
+        let edges = vec![
+            InputEdge::new(0, 1, 10_usize),
+            InputEdge::new(1, 3, 20),
+            InputEdge::new(3, 0, 30),
+        ];
+        let coordinates = vec![
+            FPCoordinate::new(52_520_008, 13_404_954),
+            FPCoordinate::new(-33_868_820, 151_209_296),
+            FPCoordinate::new(0, 0),
+            FPCoordinate::new(40_730_610, -73_935_242),
+        ];
+        let dir = tempdir().unwrap();
+        let graph_path = dir.path().join("graph.gr");
+        let graph_path = graph_path.to_str().unwrap();
+        let coordinates_path = dir.path().join("graph.co");
+        let coordinates_path = coordinates_path.to_str().unwrap();
+
+        write_graph(graph_path, 4, &edges).unwrap();
+        write_coordinates(coordinates_path, &coordinates).unwrap();
+        assert_eq!(
+            try_read_graph::<usize>(graph_path, WeightType::Original).unwrap(),
+            edges
+        );
+        assert_eq!(try_read_coordinates(coordinates_path).unwrap(), coordinates);
+    }
+
+    #[test]
+    fn par_try_read_matches_try_read() {
+        // enough lines for every thread to get a few chunks, with comments,
+        // empty lines and loops in between
+        let mut graph = String::from("c a long graph\np sp 1000 20000\n");
+        for i in 0..20_000 {
+            if i % 1000 == 0 {
+                graph.push_str("c a comment\n\n");
+            }
+            graph.push_str(&format!(
+                "a {} {} {}\n",
+                i % 1000 + 1,
+                (7 * i) % 1000 + 1,
+                i % 13
+            ));
+        }
+        let mut coordinates = String::from("p aux sp co 1000\n");
+        for node in 1..=1000 {
+            coordinates.push_str(&format!("v {node} {} {}\n", 3 * node, -node));
+        }
+        let graph_file = file_with(&graph);
+        let graph_path = graph_file.path().to_str().unwrap();
+        let coordinates_file = file_with(&coordinates);
+        let coordinates_path = coordinates_file.path().to_str().unwrap();
+
+        let edges = par_try_read_graph::<usize>(graph_path, WeightType::Original).unwrap();
+        assert!(edges.len() < 20_000);
+        assert_eq!(
+            edges,
+            try_read_graph::<usize>(graph_path, WeightType::Original).unwrap()
+        );
+        assert_eq!(
+            par_try_read_coordinates(coordinates_path).unwrap(),
+            try_read_coordinates(coordinates_path).unwrap()
+        );
+
+        // what is wrong deep into a file is reported as the sequential
+        // reader reports it
+        let broken = file_with(&format!("{graph}a 1 x 1\n"));
+        let path = broken.path().to_str().unwrap();
+        assert_eq!(
+            par_try_read_graph::<usize>(path, WeightType::Unit)
+                .unwrap_err()
+                .to_string(),
+            try_read_graph::<usize>(path, WeightType::Unit)
+                .unwrap_err()
+                .to_string()
+        );
+        let broken = file_with(&coordinates.replace("v 600 ", "v 601 "));
+        let path = broken.path().to_str().unwrap();
+        let error = par_try_read_coordinates(path).unwrap_err();
+        assert!(error.to_string().contains("expected node 600, found 601"));
+        assert!(matches!(error, GraphFileError::Parse { line: 601, .. }));
+    }
 }
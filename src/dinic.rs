@@ -6,87 +6,166 @@
 //!
 //! The DFS restarts after it found an augmenting path on the tail of the
 //! saturated edge that is closest to the source.
+//!
+//! A finished computation can be warm-started: nodes can be added to the
+//! sources or sinks and capacities raised, and the next run continues from the
+//! flow at hand rather than from zero. Neither edit makes the flow infeasible,
+//! since it only lifts the conservation constraint at the new terminals and
+//! widens the arcs, so the run merely has to find the augmenting paths the edit
+//! opened. A sequence of cuts at growing source and sink sets costs little more
+//! than the last of them on its own.
 use crate::{
     edge::InputEdge,
-    graph::{EdgeArrayEntry, EdgeID, Graph, NodeID},
-    max_flow::{MaxFlow, ResidualArcData, ResidualEdgeData},
+    graph::{EdgeID, Graph, NodeID},
+    max_flow::{Capacity, MaxFlow, ResidualArcData, ResidualEdgeData, residual_graph},
     static_graph::StaticGraph,
 };
 use bitvec::vec::BitVec;
-use core::cmp::{max, min};
+use core::cmp::min;
 use log::debug;
-use std::{
-    collections::VecDeque,
-    sync::{
-        Arc,
-        atomic::{AtomicI32, Ordering},
-    },
-};
+use std::{collections::VecDeque, sync::Arc};
 
-pub struct Dinic {
-    residual_graph: StaticGraph<ResidualArcData>,
-    max_flow: i32,
+pub struct Dinic<C: Capacity = i32> {
+    residual_graph: StaticGraph<ResidualArcData<C>>,
+    max_flow: C,
     finished: bool,
     level: Vec<usize>,
     parents: Vec<NodeID>,
     /// arc on which the DFS entered a node, i.e. the arc (parents[v], v)
     parent_edge: Vec<u32>,
-    stack: Vec<(NodeID, i32)>,
+    stack: Vec<(NodeID, C)>,
     dfs_count: usize,
     bfs_count: usize,
     queue: VecDeque<NodeID>,
-    source: NodeID,
-    target: NodeID,
-    bound: Option<Arc<AtomicI32>>,
+    sources: Vec<NodeID>,
+    sinks: Vec<NodeID>,
+    is_source: BitVec,
+    is_sink: BitVec,
+    bound: Option<Arc<C::Atomic>>,
 }
 
-impl Dinic {
+impl<C: Capacity> Dinic<C> {
+    /// Makes `node` a source in addition to the ones there are, which counts
+    /// for [`MaxFlow::assignment`] as well. The next run continues from the
+    /// current flow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a node of the graph or is a sink.
+    pub fn add_source(&mut self, node: NodeID) {
+        assert!(!self.is_sink[node], "node {node} is a sink");
+        if !self.is_source[node] {
+            self.is_source.set(node, true);
+            self.sources.push(node);
+            self.finished = false;
+        }
+    }
+
+    /// Makes `node` a sink in addition to the ones there are, which counts for
+    /// [`MaxFlow::sink_assignment`] as well. The next run continues from the
+    /// current flow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a node of the graph or is a source.
+    pub fn add_sink(&mut self, node: NodeID) {
+        assert!(!self.is_source[node], "node {node} is a source");
+        if !self.is_sink[node] {
+            self.is_sink.set(node, true);
+            self.sinks.push(node);
+            self.finished = false;
+        }
+    }
+
+    /// Raises the capacity of the arc (`source`, `target`) by `amount`. The
+    /// next run continues from the current flow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the graph has no such arc.
+    pub fn add_capacity(&mut self, source: NodeID, target: NodeID, amount: C) {
+        let forward = self
+            .residual_graph
+            .find_edge_sorted(source, target)
+            .unwrap_or_else(|| panic!("no arc ({source}, {target})"));
+        self.residual_graph.data_mut(forward).capacity += amount;
+        // keep the cached capacity of the reverse arc in sync
+        let reverse = self
+            .residual_graph
+            .find_edge_sorted(target, source)
+            .expect("residual graph is not symmetric");
+        self.residual_graph.data_mut(reverse).reverse_capacity += amount;
+        self.finished = false;
+    }
+
+    /// The nodes the flow leaves from, in the order they were added.
+    pub fn sources(&self) -> &[NodeID] {
+        &self.sources
+    }
+
+    /// The nodes the flow arrives at, in the order they were added.
+    pub fn sinks(&self) -> &[NodeID] {
+        &self.sinks
+    }
+
     fn bfs(&mut self) -> bool {
         self.bfs_count += 1;
         // init
         self.level.fill(usize::MAX);
-        self.level[self.target] = 0;
-
         self.queue.clear();
-        self.queue.push_back(self.target);
+        for &sink in &self.sinks {
+            self.level[sink] = 0;
+            self.queue.push_back(sink);
+        }
 
         // label residual graph nodes in BFS order, but in reverse starting from the target
         while let Some(u) = self.queue.pop_front() {
             for edge in self.residual_graph.edge_range(u) {
                 let v = self.residual_graph.target(edge);
-                if v != self.source && self.level[v] != usize::MAX {
-                    // node v is not the source, and is already visited. Note the source can be reached multiple times
+                let is_source = self.is_source[v];
+                if !is_source && self.level[v] != usize::MAX {
+                    // node v is not a source, and is already visited. Note a source can be reached multiple times
                     continue;
                 }
 
                 // check capacity of reverse edge
                 let edge_capacity = self.residual_graph.data(edge).reverse_capacity;
-                if edge_capacity < 1 {
+                if edge_capacity <= C::zero() {
                     // no capacity to use on this edge
                     continue;
                 }
                 self.level[v] = self.level[u] + 1;
-                if v != self.source {
+                if !is_source {
                     self.queue.push_back(v);
                 }
             }
         }
+        let reached = self
+            .sources
+            .iter()
+            .filter(|&&source| self.level[source] != usize::MAX)
+            .count();
         debug!(
-            "BFS run {}, upper bound on path length: {}",
-            self.bfs_count, self.level[self.source]
+            "BFS run {}, {reached} of {} sources reached",
+            self.bfs_count,
+            self.sources.len()
         );
-        self.level[self.source] != usize::MAX
+        reached > 0
     }
 
-    fn dfs(&mut self) -> i32 {
+    fn dfs(&mut self) -> C {
         self.dfs_count += 1;
         self.stack.clear();
-        self.stack.push((self.source, i32::MAX));
-
         self.parents.fill(NodeID::MAX);
-        self.parents[self.source] = self.source;
+        for &source in &self.sources {
+            // paths do not pass through a source, reached or not
+            self.parents[source] = source;
+            if self.level[source] != usize::MAX {
+                self.stack.push((source, C::max_value()));
+            }
+        }
 
-        let mut blocking_flow = 0;
+        let mut blocking_flow = C::zero();
         while let Some((u, flow)) = self.stack.pop() {
             for edge in self.residual_graph.edge_range(u) {
                 let v = self.residual_graph.target(edge);
@@ -99,14 +178,14 @@ impl Dinic {
                     continue;
                 }
                 let available_capacity = self.residual_graph.data(edge).capacity;
-                if available_capacity == 0 {
+                if available_capacity == C::zero() {
                     // no capacity to use on this edge
                     continue;
                 }
                 self.parents[v] = u;
                 self.parent_edge[v] = edge as u32;
                 let flow = min(flow, available_capacity);
-                if v == self.target {
+                if self.is_sink[v] {
                     // The bottleneck that the stack carries is an upper bound
                     // rather than the capacity of the path: an earlier
                     // augmentation of this very DFS may have taken capacity off
@@ -120,9 +199,10 @@ impl Dinic {
                         flow = min(flow, self.residual_graph.data(arc).capacity);
                         node = self.parents[node];
                     }
-                    debug_assert!(flow > 0, "the augmenting path carries no flow");
+                    debug_assert!(flow > C::zero(), "the augmenting path carries no flow");
 
-                    // reached a target. Unpack path in reverse order, assign flow
+                    // reached a sink. Unpack path in reverse order, assign flow
+                    let sink = v;
                     let mut v = v; // mutable shadow
                     let mut closest_tail = u;
                     loop {
@@ -134,7 +214,7 @@ impl Dinic {
                         let residual = self.residual_graph.data_mut(fwd_edge);
                         residual.capacity -= flow;
                         residual.reverse_capacity += flow;
-                        if 0 == residual.capacity {
+                        if residual.capacity == C::zero() {
                             closest_tail = u;
                         }
                         // keep the cached capacities of the arc pair in sync
@@ -158,8 +238,8 @@ impl Dinic {
                     blocking_flow += flow;
                     debug!(" stack len before: {before}, after: {}", self.stack.len());
 
-                    // make target reachable again
-                    self.parents[self.target] = NodeID::MAX;
+                    // make the sink reachable again
+                    self.parents[sink] = NodeID::MAX;
                     self.dfs_count += 1;
 
                     break; // for edge
@@ -173,113 +253,24 @@ impl Dinic {
     }
 }
 
-impl MaxFlow for Dinic {
+impl<C: Capacity> MaxFlow for Dinic<C> {
+    type Capacity = C;
+
     fn from_edge_list(
-        edge_list: Vec<InputEdge<ResidualEdgeData>>,
+        edge_list: Vec<InputEdge<ResidualEdgeData<C>>>,
         source: NodeID,
         target: NodeID,
     ) -> Self {
-        debug_assert!(!edge_list.is_empty());
-
-        // The residual graph holds a reverse arc of zero capacity for each input
-        // arc. Instead of materializing those and then sorting 2|E| entries, the
-        // adjacency array is built directly by a counting sort in O(V + E).
-        let number_of_nodes = 1 + edge_list
-            .iter()
-            .map(|edge| max(edge.source, edge.target))
-            .max()
-            .expect("edge list is empty");
-        debug!("counting degrees of {number_of_nodes} nodes");
-
-        // count the residual degree of each node in node_array[node + 1], then
-        // turn the counts into the offsets of the adjacency blocks
-        let mut node_array = vec![0_usize; number_of_nodes + 1];
-        for edge in &edge_list {
-            node_array[edge.source + 1] += 1;
-            node_array[edge.target + 1] += 1;
-        }
-        for i in 1..node_array.len() {
-            node_array[i] += node_array[i - 1];
-        }
-
-        debug!("scattering {} arcs", 2 * edge_list.len());
-        // Scatter the arcs into their blocks. node_array[u] serves as the write
-        // cursor of node u and thus ends up pointing at the end of u's block.
-        // Each input arc contributes its capacity to the forward arc and to the
-        // cached reverse capacity of the reverse arc, which is why the cache
-        // comes for free.
-        let mut edge_array = vec![
-            EdgeArrayEntry {
-                target: 0,
-                data: ResidualArcData::default()
-            };
-            2 * edge_list.len()
-        ];
-        for edge in &edge_list {
-            let forward = node_array[edge.source];
-            node_array[edge.source] += 1;
-            edge_array[forward] = EdgeArrayEntry {
-                target: u32::try_from(edge.target).expect("the graph is too large to hold"),
-                data: ResidualArcData {
-                    capacity: edge.data.capacity,
-                    reverse_capacity: 0,
-                },
-            };
-
-            let reverse = node_array[edge.target];
-            node_array[edge.target] += 1;
-            edge_array[reverse] = EdgeArrayEntry {
-                target: u32::try_from(edge.source).expect("the graph is too large to hold"),
-                data: ResidualArcData {
-                    capacity: 0,
-                    reverse_capacity: edge.data.capacity,
-                },
-            };
-        }
-        drop(edge_list);
-
-        // each cursor now points at the end of its block, which is the begin of
-        // the next one. Shifting by one restores the adjacency array.
-        node_array.rotate_right(1);
-        node_array[0] = 0;
-
-        debug!("merging parallel arcs");
-        // sort each adjacency block by target and merge parallel arcs into a
-        // single one that carries the accumulated capacity. Note that this is
-        // fine, as we are looking to compute a node partition. Blocks are short,
-        // hence sorting them is cheap.
-        let mut write = 0;
-        let mut begin = 0;
-        for node in 0..number_of_nodes {
-            let end = node_array[node + 1];
-            edge_array[begin..end].sort_unstable_by_key(|entry| entry.target);
-
-            let block_begin = write;
-            for read in begin..end {
-                if write > block_begin && edge_array[write - 1].target == edge_array[read].target {
-                    edge_array[write - 1].data.capacity += edge_array[read].data.capacity;
-                    edge_array[write - 1].data.reverse_capacity +=
-                        edge_array[read].data.reverse_capacity;
-                } else {
-                    edge_array[write] = edge_array[read];
-                    write += 1;
-                }
-            }
-            begin = end;
-            node_array[node + 1] = write;
-        }
-        edge_array.truncate(write);
-        edge_array.shrink_to_fit();
-        debug!("residual graph has {write} arcs");
-
-        // the DFS stores arc ids in parent_edge as u32, so a larger graph would
-        // silently index the wrong arc
-        assert!(write <= u32::MAX as usize, "arc ids have to fit into u32");
-        let residual_graph = StaticGraph::from_adjacency_array(node_array, edge_array);
+        let residual_graph = residual_graph(edge_list);
+        let number_of_nodes = residual_graph.number_of_nodes();
+        let mut is_source = BitVec::repeat(false, number_of_nodes);
+        is_source.set(source, true);
+        let mut is_sink = BitVec::repeat(false, number_of_nodes);
+        is_sink.set(target, true);
 
         Self {
             residual_graph,
-            max_flow: 0,
+            max_flow: C::zero(),
             finished: false,
             level: Vec::with_capacity(number_of_nodes),
             parents: Vec::with_capacity(number_of_nodes),
@@ -288,14 +279,16 @@ impl MaxFlow for Dinic {
             dfs_count: 0,
             bfs_count: 0,
             queue: VecDeque::with_capacity(number_of_nodes),
-            source,
-            target,
+            sources: vec![source],
+            sinks: vec![target],
+            is_source,
+            is_sink,
             bound: None,
         }
     }
 
-    fn run_with_upper_bound(&mut self, bound: Arc<AtomicI32>) {
-        debug!("upper bound: {}", bound.load(Ordering::Relaxed));
+    fn run_with_upper_bound(&mut self, bound: Arc<C::Atomic>) {
+        debug!("upper bound: {}", C::load(&bound));
 
         self.bound = Some(bound);
         self.run()
@@ -313,12 +306,13 @@ impl MaxFlow for Dinic {
         self.parent_edge.resize(number_of_nodes, 0);
         self.level.resize(number_of_nodes, usize::MAX);
 
-        let mut flow = 0;
+        // a warm start carries on from the flow of the previous run
+        let mut flow = self.max_flow;
         while self.bfs() {
             flow += self.dfs();
             if let Some(bound) = &self.bound {
                 // break early if an upper bound is known to the computation
-                if flow > bound.load(Ordering::Relaxed) {
+                if flow > C::load(bound) {
                     debug!("aborting max flow computation at {flow}");
                     self.max_flow = flow;
                     return;
@@ -326,13 +320,13 @@ impl MaxFlow for Dinic {
             }
         }
         if let Some(bound) = &self.bound {
-            bound.fetch_min(flow, Ordering::Relaxed);
+            C::fetch_min(bound, flow);
         }
         self.max_flow = flow;
         self.finished = true;
     }
 
-    fn max_flow(&self) -> Result<i32, String> {
+    fn max_flow(&self) -> Result<C, String> {
         if !self.finished {
             return Err("Assigment was not computed.".to_string());
         }
@@ -348,17 +342,21 @@ impl MaxFlow for Dinic {
             return Err("Assigment was not computed.".to_string());
         }
 
-        // run a reachability analysis
+        // run a reachability analysis, from all sources that were added as well
         let mut reachable = BitVec::new();
         reachable.resize(self.residual_graph.number_of_nodes(), false);
-        let mut stack = vec![source];
-        stack.reserve(self.residual_graph.number_of_nodes());
-        reachable.set(source, true);
+        let mut stack = Vec::with_capacity(self.residual_graph.number_of_nodes());
+        for &node in std::iter::once(&source).chain(&self.sources) {
+            if !reachable[node] {
+                reachable.set(node, true);
+                stack.push(node);
+            }
+        }
         while let Some(node) = stack.pop() {
             for edge in self.residual_graph.edge_range(node) {
                 let target = self.residual_graph.target(edge);
                 let reached = reachable.get(target).unwrap();
-                if !reached && self.residual_graph.data(edge).capacity > 0 {
+                if !reached && self.residual_graph.data(edge).capacity > C::zero() {
                     stack.push(target);
                     reachable.set(target, true);
                 }
@@ -366,6 +364,34 @@ impl MaxFlow for Dinic {
         }
         Ok(reachable)
     }
+
+    fn sink_assignment(&self, target: NodeID) -> Result<BitVec, String> {
+        if !self.finished {
+            return Err("Assigment was not computed.".to_string());
+        }
+
+        // run a reachability analysis backwards, where the cached reverse
+        // capacity of (u, v) is the residual capacity of (v, u)
+        let mut reaching = BitVec::repeat(false, self.residual_graph.number_of_nodes());
+        let mut stack = Vec::new();
+        for &node in std::iter::once(&target).chain(&self.sinks) {
+            if !reaching[node] {
+                reaching.set(node, true);
+                stack.push(node);
+            }
+        }
+        while let Some(node) = stack.pop() {
+            for edge in self.residual_graph.edge_range(node) {
+                let source = self.residual_graph.target(edge);
+                if !reaching[source] && self.residual_graph.data(edge).reverse_capacity > C::zero()
+                {
+                    stack.push(source);
+                    reaching.set(source, true);
+                }
+            }
+        }
+        Ok(reaching)
+    }
 }
 
 #[cfg(test)]
@@ -377,6 +403,7 @@ mod tests {
     use crate::edmonds_karp::EdmondsKarp;
     use crate::max_flow::MaxFlow;
     use crate::max_flow::ResidualEdgeData;
+    use crate::min_cut::min_cut;
     use bitvec::bits;
     use bitvec::prelude::Lsb0;
     use rand::{RngExt, SeedableRng, prelude::StdRng};
@@ -538,6 +565,90 @@ mod tests {
         }
     }
 
+    /// The edges of [`layered_graph`] with the nodes of the first layer as
+    /// sources and the ones of the last as sinks, added one after the other.
+    /// Each warm run must agree with a cut computed from scratch.
+    #[test]
+    fn warm_start_matches_a_fresh_cut_as_terminals_are_added() {
+        let mut rng = StdRng::seed_from_u64(0xD1C);
+        for round in 0..10 {
+            let (width, depth) = (3 + round % 4, 4 + round % 5);
+            let (edges, source, target) = layered_graph(&mut rng, width, depth);
+            let mut warm = Dinic::from_edge_list(edges.clone(), source, target);
+            warm.run();
+
+            let (mut sources, mut sinks) = (vec![source], vec![target]);
+            for index in 0..width {
+                warm.add_source(1 + index);
+                sources.push(1 + index);
+                warm.add_sink(1 + (depth - 1) * width + index);
+                sinks.push(1 + (depth - 1) * width + index);
+                warm.run();
+
+                let cut = min_cut::<Dinic>(&edges, &sources, &sinks).unwrap();
+                assert_eq!(warm.max_flow(), Ok(cut.flow), "round {round}");
+                let source_side: Vec<usize> =
+                    warm.assignment(source).unwrap().iter_ones().collect();
+                assert_eq!(source_side, cut.source_side, "round {round}");
+                let sink_side: Vec<usize> =
+                    warm.sink_assignment(target).unwrap().iter_ones().collect();
+                assert_eq!(sink_side, cut.sink_side, "round {round}");
+            }
+            assert_eq!(warm.sources().len(), width + 1);
+            assert_eq!(warm.sinks().len(), width + 1);
+        }
+    }
+
+    #[test]
+    fn warm_start_after_raising_a_capacity() {
+        let mut edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(16)),
+            InputEdge::new(0, 2, ResidualEdgeData::new(13)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(10)),
+            InputEdge::new(1, 3, ResidualEdgeData::new(12)),
+            InputEdge::new(2, 1, ResidualEdgeData::new(4)),
+            InputEdge::new(2, 4, ResidualEdgeData::new(14)),
+            InputEdge::new(3, 2, ResidualEdgeData::new(9)),
+            InputEdge::new(3, 5, ResidualEdgeData::new(20)),
+            InputEdge::new(4, 3, ResidualEdgeData::new(7)),
+            InputEdge::new(4, 5, ResidualEdgeData::new(4)),
+        ];
+        let mut warm = Dinic::from_edge_list(edges.clone(), 0, 5);
+        warm.run();
+        assert_eq!(warm.max_flow(), Ok(23));
+
+        // widening a cut arc lets more through, up to the next narrowest cut
+        warm.add_capacity(4, 5, 10);
+        assert!(warm.max_flow().is_err());
+        warm.run();
+        edges[9].data.capacity += 10;
+        let mut fresh = Dinic::from_edge_list(edges, 0, 5);
+        fresh.run();
+        assert_eq!(warm.max_flow(), Ok(26));
+        assert_eq!(warm.max_flow(), fresh.max_flow());
+        assert_eq!(warm.assignment(0), fresh.assignment(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_source_cannot_become_a_sink() {
+        let edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(1)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(1)),
+        ];
+        let mut solver = Dinic::from_edge_list(edges, 0, 2);
+        solver.add_source(1);
+        solver.add_sink(1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn capacity_of_a_missing_arc_cannot_be_raised() {
+        let edges = vec![InputEdge::new(0, 1, ResidualEdgeData::new(1))];
+        let mut solver = Dinic::from_edge_list(edges, 0, 1);
+        solver.add_capacity(0, 2, 1);
+    }
+
     #[test]
     fn max_flow_clr() {
         let edges = vec![
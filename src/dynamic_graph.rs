@@ -3,9 +3,11 @@
 /// the end of the edge array if there is insufficient space  when adding an
 /// edge.
 use crate::{
-    edge::{Edge, EdgeData},
-    graph::{EdgeArrayEntry, EdgeID, Graph, NodeID},
+    edge::{Edge, EdgeData, InputEdge},
+    graph::{EdgeArrayEntry, EdgeID, Graph, INVALID_NODE_ID, MutableGraph, NodeID},
+    static_graph::StaticGraph,
 };
+use bitvec::vec::BitVec;
 use core::ops::Range;
 
 pub struct NodeArrayEntry {
@@ -32,6 +34,8 @@ const GROWTH_FACTOR: f64 = 1.1;
 pub struct DynamicGraph<T: Clone> {
     node_array: Vec<NodeArrayEntry>,
     edge_array: Vec<EdgeArrayEntry<T>>,
+    /// the tombstones of nodes that were removed since the last compaction
+    removed: BitVec,
 
     number_of_nodes: usize,
     number_of_edges: usize,
@@ -42,6 +46,7 @@ impl<T: Clone + Copy> Default for DynamicGraph<T> {
         Self {
             node_array: Vec::new(),
             edge_array: Vec::new(),
+            removed: BitVec::new(),
 
             number_of_nodes: 0,
             number_of_edges: 0,
@@ -53,8 +58,9 @@ impl<T: Clone + Copy> DynamicGraph<T> {
     /// In time O(V+E) check that the following invariants hold:
     /// a) the target node of each non-spare edge is smaller than the number of nodes, and
     /// b) the number of non-spare edges add up to the number of edges, and
-    /// c) the first_edge id at each node is valid
+    /// c) the edge slice of each node lies within the edge array
     /// d) the number of nodes is consistent with the node array size
+    /// e) removed nodes have no edges left
     pub fn check_integrity(&self) -> bool {
         self.edge_array
             .iter()
@@ -69,8 +75,13 @@ impl<T: Clone + Copy> DynamicGraph<T> {
             && self.node_array[..self.number_of_nodes]
                 .iter()
                 .filter(|entry| entry.edge_count > 0)
-                .all(|entry| entry.first_edge < self.number_of_edges)
+                .all(|entry| entry.slice_end() <= self.edge_array.len())
             && 2 + self.number_of_nodes == self.node_array.len()
+            && self.removed.len() == self.number_of_nodes
+            && self
+                .removed
+                .iter_ones()
+                .all(|node| self.node_array[node].edge_count == 0)
     }
 
     pub fn new(
@@ -91,6 +102,7 @@ impl<T: Clone + Copy> DynamicGraph<T> {
         let number_of_edges = input.len();
 
         let mut graph = DynamicGraph::<T> {
+            removed: BitVec::repeat(false, number_of_nodes),
             number_of_nodes,
             number_of_edges,
             ..Default::default()
@@ -132,11 +144,13 @@ impl<T: Clone + Copy> DynamicGraph<T> {
     }
 
     /// Inserts a node with an empty edge slice into the node array.
-    pub fn insert_node(&mut self) {
+    pub fn insert_node(&mut self) -> NodeID {
         self.node_array.push(NodeArrayEntry::new(
             self.node_array.last().unwrap().first_edge,
         ));
+        self.removed.push(false);
         self.number_of_nodes += 1;
+        self.number_of_nodes - 1
     }
 
     /// Inserts an edge into the graph by making sure that there's room one
@@ -145,6 +159,10 @@ impl<T: Clone + Copy> DynamicGraph<T> {
     /// Else, if one before the slice is a spare, move the last element over
     /// Else, resize the edge array sufficiently and relocate the slice at
     /// the beginning of the newly added extension of the edge array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either end is a removed node.
     pub fn insert_edge(&mut self, source: NodeID, target: NodeID, data: T) {
         // if the source of target nodes don't exist yet, then add them.
         while self.number_of_nodes <= source {
@@ -153,6 +171,8 @@ impl<T: Clone + Copy> DynamicGraph<T> {
         while self.number_of_nodes <= target {
             self.insert_node();
         }
+        assert!(!self.is_removed(source), "node {source} was removed");
+        assert!(!self.is_removed(target), "node {target} was removed");
 
         // check if array of outgoing edges needs to be moved to the end
         let NodeArrayEntry {
@@ -296,12 +316,137 @@ impl<T: Copy> Graph<T> for DynamicGraph<T> {
     }
 }
 
+impl<T: Copy> MutableGraph<T> for DynamicGraph<T> {
+    fn insert_node(&mut self) -> NodeID {
+        DynamicGraph::insert_node(self)
+    }
+
+    fn insert_edge(&mut self, source: NodeID, target: NodeID, data: T) {
+        DynamicGraph::insert_edge(self, source, target, data)
+    }
+
+    fn remove_edge(&mut self, source: NodeID, edge_to_delete: EdgeID) {
+        DynamicGraph::remove_edge(self, source, edge_to_delete)
+    }
+
+    fn update_or_insert_edge(&mut self, source: NodeID, target: NodeID, data: T) -> EdgeID {
+        if let Some(edge) = self.find_edge(source, target) {
+            self.edge_array[edge].data = data;
+            return edge;
+        }
+        self.insert_edge(source, target, data);
+        self.node_array[source].slice_end() - 1
+    }
+
+    /// Removes a node by making its edges spare and setting its tombstone.
+    /// Edges of other nodes that lead to it are left to [`Self::compact`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node does not exist or was removed before.
+    fn remove_node(&mut self, node: NodeID) {
+        assert!(!self.is_removed(node), "node {node} was removed");
+        for edge in self.edge_range(node) {
+            self.make_spare_edge(edge);
+        }
+        self.number_of_edges -= self.node_array[node].edge_count;
+        self.node_array[node].edge_count = 0;
+        self.removed.set(node, true);
+    }
+
+    fn is_removed(&self, node: NodeID) -> bool {
+        self.removed[node]
+    }
+
+    /// Rebuilds both arrays without spare edges, removed nodes and the edges
+    /// that lead to them. The edge slices keep their order.
+    fn compact(&mut self) -> Vec<NodeID> {
+        let mut new_id = vec![INVALID_NODE_ID; self.number_of_nodes];
+        let mut number_of_nodes = 0;
+        for node in self.removed.iter_zeros() {
+            new_id[node] = number_of_nodes;
+            number_of_nodes += 1;
+        }
+
+        let mut node_array = Vec::with_capacity(number_of_nodes + 2);
+        let mut edge_array = Vec::with_capacity(self.number_of_edges);
+        for node in self.removed.iter_zeros() {
+            let first_edge = edge_array.len();
+            edge_array.extend(self.edge_range(node).filter_map(|edge| {
+                let EdgeArrayEntry { target, data } = self.edge_array[edge];
+                let target = new_id[target as usize];
+                (target != INVALID_NODE_ID).then_some(EdgeArrayEntry {
+                    target: target as u32,
+                    data,
+                })
+            }));
+            node_array.push(NodeArrayEntry {
+                first_edge,
+                edge_count: edge_array.len() - first_edge,
+            });
+        }
+        // the node past the last and the sentinel
+        node_array.push(NodeArrayEntry::new(edge_array.len()));
+        node_array.push(NodeArrayEntry::new(edge_array.len()));
+
+        self.number_of_nodes = number_of_nodes;
+        self.number_of_edges = edge_array.len();
+        self.node_array = node_array;
+        self.edge_array = edge_array;
+        self.removed = BitVec::repeat(false, number_of_nodes);
+        debug_assert!(self.check_integrity());
+        new_id
+    }
+}
+
+impl<T: Ord + Copy> From<&DynamicGraph<T>> for StaticGraph<T> {
+    /// Freezes the graph. Each edge slice is sorted by target, as a static
+    /// graph wants it, and spare edges are left behind. A removed node stays
+    /// as a node without edges, so that the ids of the others hold, while the
+    /// edges that still lead to it are dropped.
+    fn from(graph: &DynamicGraph<T>) -> Self {
+        let mut node_array = Vec::with_capacity(graph.number_of_nodes() + 1);
+        let mut edge_array = Vec::with_capacity(graph.number_of_edges());
+        for node in graph.node_range() {
+            let first_edge = edge_array.len();
+            node_array.push(first_edge);
+            edge_array.extend(
+                graph
+                    .edge_range(node)
+                    .map(|edge| graph.edge_array[edge])
+                    .filter(|entry| !graph.is_removed(entry.target as NodeID)),
+            );
+            edge_array[first_edge..].sort_unstable_by_key(|entry| (entry.target, entry.data));
+        }
+        node_array.push(edge_array.len());
+        StaticGraph::from_adjacency_array(node_array, edge_array)
+    }
+}
+
+impl<T: Ord + Copy> From<&StaticGraph<T>> for DynamicGraph<T> {
+    /// Thaws the graph into one that can be changed, with the same node and
+    /// edge ids.
+    fn from(graph: &StaticGraph<T>) -> Self {
+        let edges: Vec<_> = graph
+            .node_range()
+            .flat_map(|node| {
+                graph
+                    .edge_range(node)
+                    .map(move |edge| InputEdge::new(node, graph.target(edge), *graph.data(edge)))
+            })
+            .collect();
+        Self::new_from_sorted_list(graph.number_of_nodes(), &edges)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::edge::InputEdge;
 
-    use crate::graph::EdgeID;
-    use crate::{dynamic_graph::DynamicGraph, graph::Graph};
+    use crate::graph::{EdgeID, INVALID_NODE_ID, MutableGraph};
+    use crate::{dynamic_graph::DynamicGraph, graph::Graph, static_graph::StaticGraph};
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+    use rustc_hash::FxHashMap;
 
     const EDGES: [InputEdge<i32>; 8] = [
         InputEdge {
@@ -520,4 +665,160 @@ mod tests {
         assert!(graph.find_edge(5, 1).is_some());
         assert!(graph.find_edge(2, 5).is_some());
     }
+
+    #[test]
+    fn update_or_insert_edge() {
+        type Graph = DynamicGraph<i32>;
+        let mut graph = Graph::new(6, EDGES.to_vec());
+
+        // an existing edge is updated in place
+        let edge = graph.find_edge(4, 5).unwrap();
+        assert_eq!(edge, graph.update_or_insert_edge(4, 5, 9));
+        assert_eq!(9, *graph.data(edge));
+        assert_eq!(8, graph.number_of_edges());
+
+        // a missing one is inserted
+        let edge = graph.update_or_insert_edge(4, 0, 11);
+        assert_eq!(Some(edge), graph.find_edge(4, 0));
+        assert_eq!(11, *graph.data(edge));
+        assert_eq!(9, graph.number_of_edges());
+        assert!(graph.check_integrity());
+    }
+
+    #[test]
+    fn remove_node_leaves_a_tombstone() {
+        type Graph = DynamicGraph<i32>;
+        let mut graph = Graph::new(6, EDGES.to_vec());
+
+        graph.remove_node(4);
+        assert!(graph.is_removed(4));
+        assert!(!graph.is_removed(5));
+        assert_eq!(6, graph.number_of_nodes());
+        assert_eq!(0, graph.out_degree(4));
+        assert_eq!(6, graph.number_of_edges());
+        // the edge into the removed node is kept until compaction
+        assert!(graph.find_edge(0, 4).is_some());
+        assert!(graph.check_integrity());
+    }
+
+    #[test]
+    fn compact_drops_tombstones_and_renumbers() {
+        type Graph = DynamicGraph<i32>;
+        let mut graph = Graph::new(6, EDGES.to_vec());
+        graph.insert_edge(3, 0, 5);
+        graph.remove_node(1);
+        graph.remove_node(4);
+
+        let new_id = graph.compact();
+        assert_eq!(new_id, vec![0, INVALID_NODE_ID, 1, 2, INVALID_NODE_ID, 3]);
+        assert_eq!(4, graph.number_of_nodes());
+        // 2 -> 3, 5 -> 3 and 3 -> 0 are left
+        assert_eq!(3, graph.number_of_edges());
+        assert_eq!(0, graph.out_degree(0));
+        assert_eq!(Some(6), graph.find_edge(1, 2).map(|edge| *graph.data(edge)));
+        assert_eq!(Some(7), graph.find_edge(3, 2).map(|edge| *graph.data(edge)));
+        assert_eq!(Some(5), graph.find_edge(2, 0).map(|edge| *graph.data(edge)));
+        assert!(graph.check_integrity());
+
+        // the compacted graph grows as any other
+        assert_eq!(4, graph.insert_node());
+        graph.insert_edge(4, 0, 1);
+        assert!(graph.find_edge(4, 0).is_some());
+        assert!(graph.check_integrity());
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_edge_into_a_removed_node() {
+        type Graph = DynamicGraph<i32>;
+        let mut graph = Graph::new(6, EDGES.to_vec());
+        graph.remove_node(2);
+        graph.insert_edge(0, 2, 1);
+    }
+
+    /// Random edits as a contraction makes them, against a map of the arcs.
+    #[test]
+    fn random_edits_match_a_map() {
+        type Graph = DynamicGraph<i32>;
+        let mut rng = StdRng::seed_from_u64(0xD96);
+        let nodes = 40;
+        let mut graph = Graph::new(nodes, Vec::<InputEdge<i32>>::new());
+        let mut arcs: FxHashMap<(usize, usize), i32> = FxHashMap::default();
+        let mut removed = vec![false; nodes];
+
+        for round in 0..2000 {
+            let (source, target) = (rng.random_range(0..nodes), rng.random_range(0..nodes));
+            if removed[source] || removed[target] {
+                continue;
+            }
+            match rng.random_range(0..10) {
+                0 if round > 1000 => {
+                    graph.remove_node(source);
+                    removed[source] = true;
+                    arcs.retain(|&(s, t), _| s != source && t != source);
+                }
+                1..=2 => {
+                    if let Some(edge) = graph.find_edge(source, target) {
+                        graph.remove_edge(source, edge);
+                        arcs.remove(&(source, target));
+                    }
+                }
+                _ => {
+                    let data = rng.random_range(0..100);
+                    graph.update_or_insert_edge(source, target, data);
+                    arcs.insert((source, target), data);
+                }
+            }
+        }
+
+        let new_id = graph.compact();
+        assert_eq!(graph.number_of_edges(), arcs.len());
+        assert_eq!(
+            graph.number_of_nodes(),
+            removed.iter().filter(|&&removed| !removed).count()
+        );
+        for ((source, target), data) in arcs {
+            let edge = graph
+                .find_edge(new_id[source], new_id[target])
+                .expect("arc was lost");
+            assert_eq!(data, *graph.data(edge));
+        }
+    }
+
+    #[test]
+    fn freeze_and_thaw() {
+        type Graph = DynamicGraph<i32>;
+        let mut graph = Graph::new(6, EDGES.to_vec());
+        // relocates the slice of 1, which is then out of order
+        graph.insert_edge(1, 0, 9);
+        graph.insert_edge(3, 4, 8);
+        graph.remove_node(5);
+
+        let frozen = StaticGraph::from(&graph);
+        assert!(frozen.check_integrity());
+        assert_eq!(6, frozen.number_of_nodes());
+        // 1 -> 5 and 4 -> 5 lead to the removed node, 5 -> 3 left with it
+        assert_eq!(7, frozen.number_of_edges());
+        assert_eq!(0, frozen.out_degree(5));
+        assert_eq!(
+            Some(9),
+            frozen.find_edge_sorted(1, 0).map(|e| *frozen.data(e))
+        );
+        assert_eq!(
+            Some(8),
+            frozen.find_edge_sorted(3, 4).map(|e| *frozen.data(e))
+        );
+
+        let thawed = Graph::from(&frozen);
+        assert!(thawed.check_integrity());
+        assert_eq!(frozen.number_of_nodes(), thawed.number_of_nodes());
+        assert_eq!(frozen.number_of_edges(), thawed.number_of_edges());
+        for node in frozen.node_range() {
+            assert_eq!(frozen.edge_range(node), thawed.edge_range(node));
+            for edge in frozen.edge_range(node) {
+                assert_eq!(frozen.target(edge), thawed.target(edge));
+                assert_eq!(frozen.data(edge), thawed.data(edge));
+            }
+        }
+    }
 }
@@ -2,32 +2,28 @@ use crate::{
     dfs::DFS,
     edge::InputEdge,
     graph::{Graph, NodeID},
-    max_flow::{MaxFlow, ResidualEdgeData},
+    max_flow::{Capacity, MaxFlow, ResidualEdgeData},
     static_graph::StaticGraph,
 };
 use bitvec::vec::BitVec;
 use itertools::Itertools;
 use log::{debug, warn};
-use std::{
-    sync::{
-        Arc,
-        atomic::{AtomicI32, Ordering},
-    },
-    time::Instant,
-};
+use std::{sync::Arc, time::Instant};
 
-pub struct EdmondsKarp {
-    residual_graph: StaticGraph<ResidualEdgeData>,
-    max_flow: i32,
+pub struct EdmondsKarp<C: Capacity = i32> {
+    residual_graph: StaticGraph<ResidualEdgeData<C>>,
+    max_flow: C,
     finished: bool,
     source: NodeID,
     target: NodeID,
-    bound: Option<Arc<AtomicI32>>,
+    bound: Option<Arc<C::Atomic>>,
 }
 
-impl MaxFlow for EdmondsKarp {
+impl<C: Capacity> MaxFlow for EdmondsKarp<C> {
+    type Capacity = C;
+
     fn from_edge_list(
-        mut edge_list: Vec<InputEdge<ResidualEdgeData>>,
+        mut edge_list: Vec<InputEdge<ResidualEdgeData<C>>>,
         source: NodeID,
         target: NodeID,
     ) -> Self {
@@ -38,7 +34,7 @@ impl MaxFlow for EdmondsKarp {
         edge_list.extend_from_within(..);
         edge_list.iter_mut().skip(number_of_edges).for_each(|edge| {
             edge.reverse();
-            edge.data.capacity = 0;
+            edge.data.capacity = C::zero();
         });
         debug!("into {} edges", edge_list.len());
 
@@ -64,15 +60,15 @@ impl MaxFlow for EdmondsKarp {
         // compute a node partition.
         Self {
             residual_graph: StaticGraph::new(edge_list),
-            max_flow: 0,
+            max_flow: C::zero(),
             finished: false,
             source,
             target,
             bound: None,
         }
     }
-    fn run_with_upper_bound(&mut self, bound: Arc<AtomicI32>) {
-        warn!("Upper bound {} is discarded", bound.load(Ordering::Relaxed));
+    fn run_with_upper_bound(&mut self, bound: Arc<C::Atomic>) {
+        warn!("Upper bound {} is discarded", C::load(&bound));
         self.bound = Some(bound);
         self.run()
     }
@@ -83,7 +79,8 @@ impl MaxFlow for EdmondsKarp {
             &[self.target],
             self.residual_graph.number_of_nodes(),
         );
-        let filter = |graph: &StaticGraph<ResidualEdgeData>, edge| graph.data(edge).capacity <= 0;
+        let filter =
+            |graph: &StaticGraph<ResidualEdgeData<C>>, edge| graph.data(edge).capacity <= C::zero();
         // let mut iteration = 0;
         while dfs.run_with_filter(&self.residual_graph, filter) {
             let start = Instant::now();
@@ -105,7 +102,7 @@ impl MaxFlow for EdmondsKarp {
                 .find_edge_unchecked(bootleneck_head_tail.1, bootleneck_head_tail.0);
             debug!("  bottleneck edge: {bottleneck_edge}");
             let path_flow = self.residual_graph.data(bottleneck_edge).capacity;
-            debug_assert!(path_flow > 0);
+            debug_assert!(path_flow > C::zero());
             debug!("min edge: {bottleneck_edge}, capacity: {path_flow}");
             // sum up flow
             self.max_flow += path_flow;
@@ -127,7 +124,7 @@ impl MaxFlow for EdmondsKarp {
         self.finished = true;
     }
 
-    fn max_flow(&self) -> Result<i32, String> {
+    fn max_flow(&self) -> Result<C, String> {
         if !self.finished {
             return Err("Assigment was not computed.".to_string());
         }
@@ -149,7 +146,7 @@ impl MaxFlow for EdmondsKarp {
             for edge in self.residual_graph.edge_range(node) {
                 let target = self.residual_graph.target(edge);
                 let reached = reachable.get(target).unwrap();
-                if !reached && self.residual_graph.data(edge).capacity > 0 {
+                if !reached && self.residual_graph.data(edge).capacity > C::zero() {
                     stack.push(target);
                     reachable.set(target, true);
                 }
@@ -157,6 +154,32 @@ impl MaxFlow for EdmondsKarp {
         }
         Ok(reachable)
     }
+
+    fn sink_assignment(&self, target: NodeID) -> Result<BitVec, String> {
+        if !self.finished {
+            return Err("Assigment was not computed.".to_string());
+        }
+
+        // run a reachability analysis backwards. The residual graph holds the
+        // reverse of every edge, which is where the capacity of (v, u) sits.
+        let mut reaching = BitVec::repeat(false, self.residual_graph.number_of_nodes());
+        let mut stack = vec![target];
+        reaching.set(target, true);
+        while let Some(node) = stack.pop() {
+            for edge in self.residual_graph.edge_range(node) {
+                let source = self.residual_graph.target(edge);
+                if reaching[source] {
+                    continue;
+                }
+                let reverse = self.residual_graph.find_edge_unchecked(source, node);
+                if self.residual_graph.data(reverse).capacity > C::zero() {
+                    stack.push(source);
+                    reaching.set(source, true);
+                }
+            }
+        }
+        Ok(reaching)
+    }
 }
 
 #[cfg(test)]
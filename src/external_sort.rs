@@ -0,0 +1,368 @@
+//! Sorting the arcs of a graph that does not fit into memory.
+//!
+//! [`StaticGraph::new`] sorts its whole input in memory, which for a planet
+//! is more memory than there is. The sorter here takes the arcs one at a time
+//! and holds a run of them at most. Each full run is radix sorted and spilled
+//! to a temporary file, and in the end the runs are merged by a loser tree,
+//! a record at a time, straight into the adjacency array. That is either a
+//! [`StaticGraph`], which then needs memory for itself but never for the
+//! list of arcs it was built from, or a file that [`MappedGraph`] maps.
+//!
+//! [`MappedGraph`]: crate::mapped_graph::MappedGraph
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{edge::InputEdge, external_sort::ExternalEdgeSorter, graph::Graph};
+//!
+//! // runs of two arcs, so that three arcs take two runs
+//! let mut sorter = ExternalEdgeSorter::new(2);
+//! sorter.push(InputEdge::new(2, 0, 5)).unwrap();
+//! sorter.push(InputEdge::new(0, 1, 3)).unwrap();
+//! sorter.push(InputEdge::new(1, 2, 4)).unwrap();
+//! assert_eq!(sorter.runs(), 1);
+//!
+//! let graph = sorter.into_static_graph().unwrap();
+//! assert_eq!(graph.number_of_nodes(), 3);
+//! assert_eq!(graph.target(graph.begin_edges(2)), 0);
+//! ```
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    edge::InputEdge,
+    graph::{EdgeArrayEntry, EdgeID, NodeID},
+    k_way_merge_iterator::KWayMergeIterator,
+    loser_tree::LoserTree,
+    mapped_graph,
+    rdx_sort::Sort,
+    static_graph::StaticGraph,
+};
+
+/// The bytes of an arc in a run: source, target and cost, four bytes each.
+const RECORD_SIZE: usize = 12;
+
+/// Sorts arcs in runs of bounded length and merges the runs off disk.
+pub struct ExternalEdgeSorter {
+    run_length: usize,
+    directory: Option<PathBuf>,
+    /// The arcs of the run being collected, each packed into one number that
+    /// sorts as the arc does, as that is what the radix sort sorts.
+    buffer: Vec<u128>,
+    runs: Vec<(File, usize)>,
+    number_of_edges: usize,
+}
+
+impl ExternalEdgeSorter {
+    /// A sorter that holds at most `run_length` arcs in memory, and spills
+    /// into the temporary directory of the system.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the run length is zero.
+    #[must_use]
+    pub fn new(run_length: usize) -> Self {
+        assert!(run_length > 0, "a run has to hold at least one arc");
+        Self {
+            run_length,
+            directory: None,
+            buffer: Vec::new(),
+            runs: Vec::new(),
+            number_of_edges: 0,
+        }
+    }
+
+    /// Spills into the given directory instead, which is what a disk large
+    /// enough for the runs is chosen by.
+    #[must_use]
+    pub fn in_directory(mut self, directory: impl AsRef<Path>) -> Self {
+        self.directory = Some(directory.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds an arc, spilling the run if it is full.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a full run cannot be written out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a node id does not fit into four bytes.
+    pub fn push(&mut self, edge: InputEdge<u32>) -> io::Result<()> {
+        self.buffer.push(pack(&edge));
+        self.number_of_edges += 1;
+        if self.buffer.len() == self.run_length {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// how many arcs were pushed so far
+    #[must_use]
+    pub fn number_of_edges(&self) -> usize {
+        self.number_of_edges
+    }
+
+    /// how many runs were spilled to disk so far
+    #[must_use]
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Merges the runs into an adjacency array in memory.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a run cannot be written out or read back.
+    pub fn into_static_graph(self) -> io::Result<StaticGraph<u32>> {
+        let mut offsets = Offsets::default();
+        let mut edge_array = Vec::with_capacity(self.number_of_edges);
+        self.merge(|edge| {
+            offsets.add(&edge);
+            edge_array.push(EdgeArrayEntry {
+                target: edge.target as u32,
+                data: edge.data,
+            });
+        })?;
+        Ok(StaticGraph::from_adjacency_array(
+            offsets.finish(),
+            edge_array,
+        ))
+    }
+
+    /// Merges the runs into a file for [`MappedGraph`] to map, holding no
+    /// more than the node array in memory.
+    ///
+    /// [`MappedGraph`]: crate::mapped_graph::MappedGraph
+    ///
+    /// # Errors
+    ///
+    /// Fails if a run cannot be written out or read back, or the file cannot
+    /// be written.
+    pub fn write_mapped_graph(self, filename: &str) -> io::Result<()> {
+        // the node array goes ahead of the arcs, but is known only after the
+        // last of them, so they wait in a file of their own
+        let mut edges = BufWriter::new(self.temporary_file()?);
+        let mut offsets = Offsets::default();
+        let mut written = Ok(());
+        self.merge(|edge| {
+            offsets.add(&edge);
+            if written.is_ok() {
+                written = edges
+                    .write_all(&(edge.target as u32).to_le_bytes())
+                    .and_then(|()| edges.write_all(&edge.data.to_le_bytes()));
+            }
+        })?;
+        written?;
+        let mut edges = edges.into_inner().map_err(io::IntoInnerError::into_error)?;
+        edges.rewind()?;
+
+        let offsets = offsets.finish();
+        let mut file = BufWriter::new(File::create(filename)?);
+        mapped_graph::write_header(&mut file, offsets.len() - 1, offsets[offsets.len() - 1])?;
+        for offset in &offsets {
+            file.write_all(&(*offset as u64).to_le_bytes())?;
+        }
+        io::copy(&mut BufReader::new(edges), &mut file)?;
+        file.flush()
+    }
+
+    /// Sorts the run collected so far and writes it out.
+    fn spill(&mut self) -> io::Result<()> {
+        self.buffer.rdx_sort();
+        let mut run = BufWriter::new(self.temporary_file()?);
+        for key in &self.buffer {
+            run.write_all(&key.to_le_bytes()[..RECORD_SIZE])?;
+        }
+        let mut run = run.into_inner().map_err(io::IntoInnerError::into_error)?;
+        run.rewind()?;
+        self.runs.push((run, self.buffer.len()));
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Hands every arc to `emit` in the order [`InputEdge`] sorts by.
+    fn merge(mut self, mut emit: impl FnMut(InputEdge<u32>)) -> io::Result<()> {
+        if self.runs.is_empty() {
+            // everything fit into one run, which need not see the disk
+            self.buffer.rdx_sort();
+            self.buffer.iter().for_each(|&key| emit(unpack(key)));
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        let mut runs = self
+            .runs
+            .into_iter()
+            .map(|(file, remaining)| Run {
+                reader: BufReader::new(file),
+                remaining,
+                error: None,
+            })
+            .collect::<Vec<_>>();
+        // a loser tree of a single leaf has no root to play at
+        let tree = LoserTree::with_capacity(runs.len().max(2));
+        KWayMergeIterator::new(&mut runs, tree).for_each(|key| emit(unpack(key)));
+        runs.into_iter()
+            .find_map(|run| run.error)
+            .map_or(Ok(()), Err)
+    }
+
+    fn temporary_file(&self) -> io::Result<File> {
+        match &self.directory {
+            Some(directory) => tempfile::tempfile_in(directory),
+            None => tempfile::tempfile(),
+        }
+    }
+}
+
+/// Packs an arc into a number that sorts as the arc does: by source, then
+/// target, then cost.
+fn pack(edge: &InputEdge<u32>) -> u128 {
+    let source = u32::try_from(edge.source).expect("the graph is too large to hold");
+    let target = u32::try_from(edge.target).expect("the graph is too large to hold");
+    (u128::from(source) << 64) | (u128::from(target) << 32) | u128::from(edge.data)
+}
+
+fn unpack(key: u128) -> InputEdge<u32> {
+    InputEdge::new(
+        (key >> 64) as u32 as NodeID,
+        (key >> 32) as u32 as NodeID,
+        key as u32,
+    )
+}
+
+/// A sorted run read back off disk, which keeps the first error it meets
+/// rather than ending the merge with a panic.
+struct Run {
+    reader: BufReader<File>,
+    remaining: usize,
+    error: Option<io::Error>,
+}
+
+impl Iterator for Run {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        if self.remaining == 0 || self.error.is_some() {
+            return None;
+        }
+        let mut record = [0; 16];
+        if let Err(error) = self.reader.read_exact(&mut record[..RECORD_SIZE]) {
+            self.error = Some(error);
+            return None;
+        }
+        self.remaining -= 1;
+        Some(u128::from_le_bytes(record))
+    }
+}
+
+/// The node array of arcs that arrive sorted by source.
+#[derive(Default)]
+struct Offsets {
+    first_edge: Vec<EdgeID>,
+    edges: usize,
+    nodes: usize,
+}
+
+impl Offsets {
+    fn add(&mut self, edge: &InputEdge<u32>) {
+        while self.first_edge.len() <= edge.source {
+            self.first_edge.push(self.edges);
+        }
+        self.edges += 1;
+        self.nodes = self.nodes.max(edge.source + 1).max(edge.target + 1);
+    }
+
+    /// The node array over every node an arc reaches, with the sentinel.
+    fn finish(mut self) -> Vec<EdgeID> {
+        self.first_edge.resize(self.nodes + 1, self.edges);
+        self.first_edge
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::Graph, mapped_graph::MappedGraph};
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+
+    fn random_edges(count: usize, nodes: usize) -> Vec<InputEdge<u32>> {
+        let mut rng = StdRng::seed_from_u64(46);
+        (0..count)
+            .map(|_| {
+                InputEdge::new(
+                    rng.random_range(0..nodes),
+                    rng.random_range(0..nodes),
+                    rng.random_range(1..1000),
+                )
+            })
+            .collect()
+    }
+
+    fn assert_same(graph: &impl Graph<u32>, expected: &StaticGraph<u32>) {
+        assert_eq!(graph.number_of_nodes(), expected.number_of_nodes());
+        assert_eq!(graph.number_of_edges(), expected.number_of_edges());
+        for node in expected.node_range() {
+            assert_eq!(graph.edge_range(node), expected.edge_range(node));
+            for edge in expected.edge_range(node) {
+                assert_eq!(graph.target(edge), expected.target(edge));
+                assert_eq!(graph.data(edge), expected.data(edge));
+            }
+        }
+    }
+
+    #[test]
+    fn runs_merge_into_the_graph_an_in_memory_sort_builds() {
+        let edges = random_edges(1000, 97);
+        let mut sorter = ExternalEdgeSorter::new(64);
+        for edge in &edges {
+            sorter.push(*edge).unwrap();
+        }
+        assert_eq!(sorter.runs(), 15);
+        assert_eq!(sorter.number_of_edges(), 1000);
+
+        let graph = sorter.into_static_graph().unwrap();
+        assert!(graph.check_integrity());
+        assert_same(&graph, &StaticGraph::new(edges));
+    }
+
+    #[test]
+    fn a_single_run_never_sees_the_disk() {
+        let edges = random_edges(50, 10);
+        let mut sorter = ExternalEdgeSorter::new(100);
+        for edge in &edges {
+            sorter.push(*edge).unwrap();
+        }
+        assert_eq!(sorter.runs(), 0);
+        assert_same(
+            &sorter.into_static_graph().unwrap(),
+            &StaticGraph::new(edges),
+        );
+
+        let empty = ExternalEdgeSorter::new(1).into_static_graph().unwrap();
+        assert_eq!(empty.number_of_nodes(), 0);
+    }
+
+    #[test]
+    fn runs_merge_into_a_file_that_maps() {
+        let edges = random_edges(500, 40);
+        let directory = tempfile::tempdir().unwrap();
+        let mut sorter = ExternalEdgeSorter::new(7).in_directory(directory.path());
+        for edge in &edges {
+            sorter.push(*edge).unwrap();
+        }
+        let path = directory.path().join("graph");
+        let path = path.to_str().unwrap();
+        sorter.write_mapped_graph(path).unwrap();
+
+        let mapped = MappedGraph::open(path).unwrap();
+        assert!(mapped.check_integrity());
+        assert_same(&mapped, &StaticGraph::new(edges));
+    }
+}
@@ -0,0 +1,576 @@
+//! Road networks read from GeoJSON, and the results of queries written to it.
+//!
+//! A road network is a `FeatureCollection` whose features are `LineString`s
+//! or `MultiLineString`s, each line a road given by the positions it passes
+//! through. Positions that are the same to a millionth of a degree are the
+//! same place, whichever line they are on. As in [`crate::osm_pbf`], a line
+//! is split into arcs at every place it shares with another line, and at its
+//! ends, such that the nodes of the graph are the junctions and dead ends of
+//! the network and the positions in between only give the shape of the roads.
+//!
+//! A road costs what [`GeoJsonKeys::weight`] names among the properties of
+//! its feature, shared among its arcs by their great-circle lengths, or if
+//! no property is named, its length in metres. Either way the cost is scaled
+//! and rounded as in the CSV reader. Features of other geometries, such as
+//! the points of a boundary, are passed over.
+//!
+//! The other way round, a [`GeoJsonWriter`] writes paths through a graph as
+//! `LineString`s and areas, such as the hull of what is in reach of a node,
+//! as `Polygon`s, each a feature with the properties given to it.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use toolbox_rs::{
+//!     convex_hull::monotone_chain,
+//!     edge::InputEdge,
+//!     geojson_io::{GeoJsonKeys, GeoJsonWriter, JsonValue, read_road_network},
+//!     static_graph::StaticGraph,
+//!     unidirectional_dijkstra::UnidirectionalDijkstra,
+//! };
+//!
+//! let network = read_road_network::<usize>("roads.geojson", &GeoJsonKeys::default()).unwrap();
+//! let edges = network
+//!     .edges
+//!     .iter()
+//!     .map(|edge| InputEdge::new(edge.source, edge.target, edge.data as u32))
+//!     .collect();
+//! let graph = StaticGraph::new(edges);
+//! let mut dijkstra = UnidirectionalDijkstra::new();
+//! let metres = dijkstra.run(&graph, 0, 42);
+//!
+//! let mut writer = GeoJsonWriter::create("route.geojson").unwrap();
+//! let path = dijkstra.retrieve_node_path(42).unwrap();
+//! writer
+//!     .write_path(&path, &network.coordinates, &[("metres", JsonValue::from(metres))])
+//!     .unwrap();
+//! // what was settled on the way, as an area
+//! let settled: Vec<_> = (0..network.coordinates.len())
+//!     .filter(|&node| dijkstra.distance(node) <= metres)
+//!     .map(|node| network.coordinates[node])
+//!     .collect();
+//! writer
+//!     .write_polygon(&monotone_chain(&settled), &[("within", JsonValue::from(metres))])
+//!     .unwrap();
+//! writer.finish().unwrap();
+//! ```
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+};
+
+use geojson::{Feature, FeatureReader, FeatureWriter, Geometry, GeometryValue, Position};
+use log::info;
+use thiserror::Error;
+
+use crate::{
+    csv::degrees,
+    edge::InputEdge,
+    geometry::FPCoordinate,
+    graph::NodeID,
+    great_circle::haversine,
+    io::scaled_weight,
+    osm_pbf::{Oneway, split_at_junctions},
+};
+
+pub use geojson::JsonValue;
+
+/// Why a GeoJSON file could not be read or written.
+#[derive(Debug, Error)]
+pub enum GeoJsonError {
+    #[error("{file}: {source}")]
+    Io {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{file}: {source}")]
+    Json {
+        file: String,
+        #[source]
+        source: geojson::Error,
+    },
+    #[error("{file}, feature {feature}: {reason}")]
+    Feature {
+        file: String,
+        feature: usize,
+        reason: String,
+    },
+}
+
+/// The names of the properties to read.
+#[derive(Clone, Debug)]
+pub struct GeoJsonKeys {
+    /// the cost of a road, or `None` for its length in metres
+    pub weight: Option<String>,
+    /// what a cost is multiplied by before it is rounded
+    pub scale: f64,
+    /// a property that, when `true`, `"yes"` or `1`, opens a road only in
+    /// the direction of its positions, and when `"-1"` only against it, or
+    /// `None` for every road to be open both ways
+    pub oneway: Option<String>,
+}
+
+impl Default for GeoJsonKeys {
+    fn default() -> Self {
+        Self {
+            weight: None,
+            scale: 1.,
+            oneway: Some("oneway".to_string()),
+        }
+    }
+}
+
+/// A road network read from GeoJSON.
+#[derive(Clone, Debug)]
+pub struct GeoJsonNetwork<T: Eq> {
+    /// the arcs in the directions their roads are open in
+    pub edges: Vec<InputEdge<T>>,
+    pub coordinates: Vec<FPCoordinate>,
+}
+
+/// A line of a feature, with the cost of the road and where it is open.
+struct Line {
+    feature: usize,
+    places: Vec<FPCoordinate>,
+    cost: Option<f64>,
+    oneway: Oneway,
+}
+
+/// Reads the road network of a GeoJSON feature collection.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or is not a feature collection, or the
+/// position or weight of a road does not read.
+pub fn read_road_network<T: Eq + From<usize>>(
+    filename: &str,
+    keys: &GeoJsonKeys,
+) -> Result<GeoJsonNetwork<T>, GeoJsonError> {
+    let file = File::open(filename).map_err(|source| GeoJsonError::Io {
+        file: filename.to_string(),
+        source,
+    })?;
+    let error = |feature, reason: String| GeoJsonError::Feature {
+        file: filename.to_string(),
+        feature,
+        reason,
+    };
+
+    let mut lines = Vec::new();
+    let mut passed_over = 0;
+    for (index, feature) in FeatureReader::from_reader(BufReader::new(file))
+        .features()
+        .enumerate()
+    {
+        let feature = feature.map_err(|source| GeoJsonError::Json {
+            file: filename.to_string(),
+            source,
+        })?;
+        let shapes = match feature.geometry.as_ref().map(|geometry| &geometry.value) {
+            Some(GeometryValue::LineString { coordinates }) => vec![coordinates],
+            Some(GeometryValue::MultiLineString { coordinates }) => coordinates.iter().collect(),
+            _ => {
+                passed_over += 1;
+                continue;
+            }
+        };
+        let cost = match &keys.weight {
+            None => None,
+            Some(key) => Some(
+                feature
+                    .property(key)
+                    .and_then(number)
+                    .ok_or_else(|| error(index, format!("the {key} is missing or no number")))?,
+            ),
+        };
+        let oneway = match &keys.oneway {
+            None => Oneway::No,
+            Some(key) => oneway(feature.property(key)),
+        };
+        for positions in shapes {
+            let places = positions
+                .iter()
+                .map(|position| match position.as_slice() {
+                    [lon, lat, ..] if lat.abs() <= 90. && lon.abs() <= 180. => {
+                        Ok(degrees(*lat, *lon))
+                    }
+                    other => Err(error(index, format!("{other:?} is not a position"))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            lines.push(Line {
+                feature: index,
+                places,
+                cost,
+                oneway,
+            });
+        }
+    }
+    info!(
+        "read {} lines, passed over {passed_over} other features",
+        lines.len()
+    );
+
+    let mut network = GeoJsonNetwork {
+        edges: Vec::new(),
+        coordinates: Vec::new(),
+    };
+    let kilometres = |places: &[FPCoordinate]| -> f64 {
+        places
+            .windows(2)
+            .map(|pair| {
+                let (lon1, lat1) = pair[0].to_lon_lat_pair();
+                let (lon2, lat2) = pair[1].to_lon_lat_pair();
+                haversine(lat1, lon1, lat2, lon2)
+            })
+            .sum()
+    };
+    let totals = lines
+        .iter()
+        .map(|line| kilometres(&line.places))
+        .collect::<Vec<_>>();
+    let node = |place: FPCoordinate| {
+        network.coordinates.push(place);
+        network.coordinates.len() - 1
+    };
+    let weight = |index: usize, piece: &[FPCoordinate]| {
+        let line = &lines[index];
+        let cost = match line.cost {
+            None => kilometres(piece) * 1000.,
+            // a road of no length costs all of it on its only arc
+            Some(cost) if totals[index] == 0. => cost,
+            Some(cost) => cost * kilometres(piece) / totals[index],
+        };
+        scaled_weight(cost, keys.scale).map_err(|reason| error(line.feature, reason))
+    };
+    let shapes = lines
+        .iter()
+        .map(|line| (line.places.as_slice(), line.oneway))
+        .collect::<Vec<_>>();
+    network.edges = split_at_junctions(&shapes, node, weight)?;
+    info!(
+        "split the lines into {} arcs between {} nodes",
+        network.edges.len(),
+        network.coordinates.len()
+    );
+    Ok(network)
+}
+
+/// A property as a number, which some tools write as text.
+fn number(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::Number(number) => number.as_f64(),
+        JsonValue::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn oneway(value: Option<&JsonValue>) -> Oneway {
+    match value {
+        Some(JsonValue::Bool(true)) => Oneway::Forward,
+        Some(JsonValue::Number(number)) if number.as_i64() == Some(1) => Oneway::Forward,
+        Some(JsonValue::Number(number)) if number.as_i64() == Some(-1) => Oneway::Reverse,
+        Some(JsonValue::String(text)) => match text.as_str() {
+            "yes" | "true" | "1" => Oneway::Forward,
+            "-1" => Oneway::Reverse,
+            _ => Oneway::No,
+        },
+        _ => Oneway::No,
+    }
+}
+
+/// A feature collection being written, whose errors say which file it was.
+pub struct GeoJsonWriter {
+    file: String,
+    writer: FeatureWriter<BufWriter<File>>,
+}
+
+impl GeoJsonWriter {
+    pub fn create(filename: &str) -> Result<Self, GeoJsonError> {
+        let file = File::create(filename).map_err(|source| GeoJsonError::Io {
+            file: filename.to_string(),
+            source,
+        })?;
+        Ok(Self {
+            file: filename.to_string(),
+            writer: FeatureWriter::from_writer(BufWriter::new(file)),
+        })
+    }
+
+    /// Writes a path of nodes, as [`crate::unidirectional_dijkstra`] and the
+    /// other searches retrieve them, as a `LineString` through the
+    /// coordinates of its nodes, or a `Point` if it is but one node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is empty or leads through a node without a
+    /// coordinate.
+    pub fn write_path(
+        &mut self,
+        path: &[NodeID],
+        coordinates: &[FPCoordinate],
+        properties: &[(&str, JsonValue)],
+    ) -> Result<(), GeoJsonError> {
+        assert!(!path.is_empty(), "an empty path has no geometry");
+        let mut positions: Vec<Position> = path
+            .iter()
+            .map(|&node| position(&coordinates[node]))
+            .collect();
+        let geometry = if positions.len() == 1 {
+            GeometryValue::Point {
+                coordinates: positions.remove(0),
+            }
+        } else {
+            GeometryValue::LineString {
+                coordinates: positions,
+            }
+        };
+        self.write(geometry, properties)
+    }
+
+    /// Writes the area within a ring of coordinates, such as a convex hull,
+    /// as a `Polygon`. The ring is closed if it is not already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ring has less than three coordinates.
+    pub fn write_polygon(
+        &mut self,
+        ring: &[FPCoordinate],
+        properties: &[(&str, JsonValue)],
+    ) -> Result<(), GeoJsonError> {
+        assert!(
+            ring.len() >= 3,
+            "{} coordinates do not enclose an area",
+            ring.len()
+        );
+        let mut positions: Vec<Position> = ring.iter().map(position).collect();
+        if ring.first() != ring.last() {
+            positions.push(position(&ring[0]));
+        }
+        self.write(
+            GeometryValue::Polygon {
+                coordinates: vec![positions],
+            },
+            properties,
+        )
+    }
+
+    /// Ends the collection, which dropping the writer would do without a
+    /// word if it failed.
+    pub fn finish(mut self) -> Result<(), GeoJsonError> {
+        self.writer.finish().map_err(|source| GeoJsonError::Json {
+            file: self.file.clone(),
+            source,
+        })
+    }
+
+    fn write(
+        &mut self,
+        geometry: GeometryValue,
+        properties: &[(&str, JsonValue)],
+    ) -> Result<(), GeoJsonError> {
+        let mut feature = Feature::from(Geometry::new(geometry));
+        for (key, value) in properties {
+            feature.set_property(*key, value.clone());
+        }
+        self.writer
+            .write_feature(&feature)
+            .map_err(|source| GeoJsonError::Json {
+                file: self.file.clone(),
+                source,
+            })
+    }
+}
+
+fn position(coordinate: &FPCoordinate) -> Position {
+    Position::from(coordinate.to_lon_lat_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use geojson::GeoJson;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn write_collection(features: &[&str]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{"type": "FeatureCollection", "features": [{}]}}"#,
+            features.join(",")
+        )
+        .unwrap();
+        file
+    }
+
+    fn line(coordinates: &str, properties: &str) -> String {
+        format!(
+            r#"{{"type": "Feature", "geometry": {{"type": "LineString", "coordinates": {coordinates}}}, "properties": {{{properties}}}}}"#
+        )
+    }
+
+    #[test]
+    fn lines_are_split_at_shared_positions() {
+        // a road from west to east, and one from the south that ends on it
+        let file = write_collection(&[
+            &line(
+                "[[13.0, 52.0], [13.1, 52.0], [13.2, 52.0]]",
+                r#""cost": 20"#,
+            ),
+            &line("[[13.1, 51.9], [13.1, 52.0]]", r#""cost": "7""#),
+            r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [13.0, 52.0]}, "properties": null}"#,
+        ]);
+        let keys = GeoJsonKeys {
+            weight: Some("cost".to_string()),
+            ..Default::default()
+        };
+        let network = read_road_network::<usize>(file.path().to_str().unwrap(), &keys).unwrap();
+
+        assert_eq!(
+            network.coordinates,
+            vec![
+                FPCoordinate::new(52_000_000, 13_000_000),
+                FPCoordinate::new(52_000_000, 13_100_000),
+                FPCoordinate::new(52_000_000, 13_200_000),
+                FPCoordinate::new(51_900_000, 13_100_000),
+            ]
+        );
+        // the west to east road is shared evenly among its two halves
+        assert_eq!(
+            network.edges,
+            vec![
+                InputEdge::new(0, 1, 10),
+                InputEdge::new(1, 0, 10),
+                InputEdge::new(1, 2, 10),
+                InputEdge::new(2, 1, 10),
+                InputEdge::new(3, 1, 7),
+                InputEdge::new(1, 3, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn lengths_in_metres_and_oneways() {
+        let file = write_collection(&[
+            &line(
+                "[[0.0, 0.0], [0.005, 0.0], [0.01, 0.0]]",
+                r#""oneway": true"#,
+            ),
+            &line("[[0.0, 0.01], [0.0, 0.0]]", r#""oneway": "-1""#),
+        ]);
+        let network =
+            read_road_network::<usize>(file.path().to_str().unwrap(), &GeoJsonKeys::default())
+                .unwrap();
+        // the middle of the first line is no junction
+        assert_eq!(network.coordinates.len(), 3);
+        // a hundredth of a degree on the equator is 1113m
+        assert_eq!(
+            network.edges,
+            vec![InputEdge::new(0, 1, 1113), InputEdge::new(0, 2, 1113)]
+        );
+    }
+
+    #[test]
+    fn multi_lines_and_broken_features() {
+        let file = write_collection(&[
+            r#"{"type": "Feature", "geometry": {"type": "MultiLineString", "coordinates": [[[0.0, 0.0], [0.0, 1.0]], [[1.0, 0.0], [1.0, 1.0]]]}, "properties": {"w": 2.5}}"#,
+        ]);
+        let keys = GeoJsonKeys {
+            weight: Some("w".to_string()),
+            scale: 10.,
+            oneway: None,
+        };
+        let network = read_road_network::<usize>(file.path().to_str().unwrap(), &keys).unwrap();
+        assert_eq!(network.coordinates.len(), 4);
+        assert_eq!(network.edges.len(), 4);
+        assert!(network.edges.iter().all(|edge| edge.data == 25));
+
+        let file = write_collection(&[&line("[[0.0, 0.0], [0.0, 1.0]]", r#""w": null"#)]);
+        let result = read_road_network::<usize>(file.path().to_str().unwrap(), &keys);
+        assert!(matches!(
+            result,
+            Err(GeoJsonError::Feature { feature: 0, reason, .. }) if reason.contains("the w is missing")
+        ));
+
+        let file = write_collection(&[&line("[[0.0, 0.0], [0.0, 100.0]]", r#""w": 1"#)]);
+        let result = read_road_network::<usize>(file.path().to_str().unwrap(), &keys);
+        assert!(
+            matches!(result, Err(GeoJsonError::Feature { reason, .. }) if reason.contains("is not a position"))
+        );
+
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, r#"{{"type": "FeatureCollection", "features": [{{"#).unwrap();
+        let result = read_road_network::<usize>(file.path().to_str().unwrap(), &keys);
+        assert!(matches!(result, Err(GeoJsonError::Json { .. })));
+    }
+
+    #[test]
+    fn paths_and_polygons_with_properties() {
+        let coordinates = [
+            FPCoordinate::new(52_000_000, 13_000_000),
+            FPCoordinate::new(52_000_000, 13_100_000),
+            FPCoordinate::new(52_100_000, 13_100_000),
+        ];
+        let file = NamedTempFile::new().unwrap();
+        let filename = file.path().to_str().unwrap();
+        let mut writer = GeoJsonWriter::create(filename).unwrap();
+        writer
+            .write_path(
+                &[0, 1, 2],
+                &coordinates,
+                &[("duration", JsonValue::from(42)), ("name", "route".into())],
+            )
+            .unwrap();
+        writer.write_path(&[1], &coordinates, &[]).unwrap();
+        writer
+            .write_polygon(&coordinates, &[("within", JsonValue::from(600))])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let text = std::fs::read_to_string(filename).unwrap();
+        let GeoJson::FeatureCollection(collection) = text.parse::<GeoJson>().unwrap() else {
+            panic!("not a feature collection");
+        };
+        let features = collection.features;
+        assert_eq!(features.len(), 3);
+
+        let Some(GeometryValue::LineString { coordinates: line }) = features[0]
+            .geometry
+            .as_ref()
+            .map(|geometry| &geometry.value)
+        else {
+            panic!("the path is no line string");
+        };
+        assert_eq!(line.len(), 3);
+        assert_eq!(line[1].as_slice(), &[13.1, 52.0]);
+        assert_eq!(features[0].property("duration"), Some(&JsonValue::from(42)));
+        assert_eq!(
+            features[0].property("name"),
+            Some(&JsonValue::from("route"))
+        );
+
+        assert!(matches!(
+            features[1]
+                .geometry
+                .as_ref()
+                .map(|geometry| &geometry.value),
+            Some(GeometryValue::Point { .. })
+        ));
+
+        let Some(GeometryValue::Polygon { coordinates: rings }) = features[2]
+            .geometry
+            .as_ref()
+            .map(|geometry| &geometry.value)
+        else {
+            panic!("the area is no polygon");
+        };
+        // closed by repeating the first position
+        assert_eq!(rings[0].len(), 4);
+        assert_eq!(rings[0][0], rings[0][3]);
+        assert_eq!(features[2].property("within"), Some(&JsonValue::from(600)));
+    }
+}
@@ -24,7 +24,9 @@ use std::fmt::Display;
 /// // Convert back to floating-point
 /// let (lon, lat) = ny.to_lon_lat_pair();
 /// ```
-#[derive(Clone, Copy, Debug, Eq, PartialEq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
 pub struct FPCoordinate {
     pub lat: i32,
     pub lon: i32,
@@ -0,0 +1,318 @@
+//! Gomory–Hu trees of undirected graphs, built after Gusfield.
+//!
+//! A Gomory–Hu tree spans the nodes of a graph such that the minimum cut
+//! between any two nodes has the value of the lightest tree edge on the path
+//! between them, and removing that edge splits the tree into the two sides of
+//! such a cut. The minimum cuts of all pairs of nodes thus come out of n - 1
+//! max-flow computations instead of n² of them.
+//!
+//! Gusfield's variant computes each of the flows on the input graph itself,
+//! rather than on a contracted copy as Gomory and Hu did. It starts with every
+//! node hanging off node 0 and, for each node s in turn, cuts s from its
+//! current parent t. The nodes that hang off t and fall on the side of s are
+//! moved below s, and if the parent of t falls on that side as well, s takes
+//! the place of t in the tree.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{edge::InputEdge, gomory_hu::GomoryHuTree, max_flow::ResidualEdgeData};
+//!
+//! // two triangles that share the edge between 1 and 2
+//! let edges = vec![
+//!     InputEdge::new(0, 1, ResidualEdgeData::new(3)),
+//!     InputEdge::new(0, 2, ResidualEdgeData::new(2)),
+//!     InputEdge::new(1, 2, ResidualEdgeData::new(1)),
+//!     InputEdge::new(1, 3, ResidualEdgeData::new(4)),
+//!     InputEdge::new(2, 3, ResidualEdgeData::new(5)),
+//! ];
+//! let tree = GomoryHuTree::new(&edges);
+//! assert_eq!(tree.min_cut_value(0, 3), 5);
+//! assert_eq!(tree.min_cut_value(1, 2), 7);
+//! ```
+use std::collections::VecDeque;
+
+use bitvec::vec::BitVec;
+use log::debug;
+
+use crate::{
+    dinic::Dinic,
+    edge::InputEdge,
+    graph::{Graph, NodeID},
+    max_flow::{Capacity, MaxFlow, ResidualEdgeData},
+    static_graph::StaticGraph,
+};
+
+/// The root of the tree, to which all nodes hang off before the first cut.
+const ROOT: NodeID = 0;
+
+/// A Gomory–Hu tree together with what is needed to answer min-cut queries on
+/// it.
+pub struct GomoryHuTree<C: Capacity = i32> {
+    /// the parent of each node in the tree, the root being its own
+    parent: Vec<NodeID>,
+    /// the value of the cut between each node and its parent
+    weight: Vec<C>,
+    /// the number of tree edges between each node and the root
+    depth: Vec<usize>,
+    tree: StaticGraph<C>,
+}
+
+impl<C: Capacity> GomoryHuTree<C> {
+    /// Builds the tree of an undirected graph. Each edge is taken to lead both
+    /// ways with its capacity, and parallel edges add up. The tree spans all
+    /// nodes up to the highest id an edge reaches.
+    #[must_use]
+    pub fn new(edges: &[InputEdge<ResidualEdgeData<C>>]) -> Self {
+        let number_of_nodes = edges
+            .iter()
+            .map(|edge| 1 + edge.source.max(edge.target))
+            .max()
+            .unwrap_or(0);
+
+        // both directions of each edge, without loops that no cut crosses
+        let arcs: Vec<_> = edges
+            .iter()
+            .filter(|edge| edge.source != edge.target)
+            .flat_map(|edge| [*edge, InputEdge::new(edge.target, edge.source, edge.data)])
+            .collect();
+        let reached = arcs.iter().map(|arc| 1 + arc.source).max().unwrap_or(0);
+
+        let mut parent = vec![ROOT; number_of_nodes];
+        let mut weight = vec![C::zero(); number_of_nodes];
+        for s in 1..number_of_nodes {
+            let t = parent[s];
+            let (flow, side) = if s >= reached || t >= reached {
+                // a node without arcs is cut off by nothing
+                (C::zero(), BitVec::repeat(false, number_of_nodes))
+            } else {
+                let mut solver = Dinic::from_edge_list(arcs.clone(), s, t);
+                solver.run();
+                (
+                    solver.max_flow().expect("flow was computed"),
+                    solver.assignment(s).expect("assignment was computed"),
+                )
+            };
+            // a node past the arcs is reached by none of them
+            let on_side = |node: NodeID| node == s || side.get(node).is_some_and(|bit| *bit);
+            debug!("cut between {s} and {t} has value {flow}");
+
+            weight[s] = flow;
+            for (node, parent) in parent.iter_mut().enumerate() {
+                if node != s && *parent == t && on_side(node) {
+                    *parent = s;
+                }
+            }
+            if t != ROOT && on_side(parent[t]) {
+                // s takes the place of t in the tree
+                parent[s] = parent[t];
+                parent[t] = s;
+                weight[s] = weight[t];
+                weight[t] = flow;
+            }
+        }
+
+        // the parents are not ordered by id, hence the depths by a traversal
+        let tree_edges: Vec<_> = (0..number_of_nodes)
+            .filter(|&node| node != ROOT)
+            .flat_map(|node| {
+                [
+                    InputEdge::new(node, parent[node], weight[node]),
+                    InputEdge::new(parent[node], node, weight[node]),
+                ]
+            })
+            .collect();
+        let tree = StaticGraph::new_with_nodes(number_of_nodes, tree_edges);
+        let mut depth = vec![0; number_of_nodes];
+        if number_of_nodes > 0 {
+            let mut queue = VecDeque::from([ROOT]);
+            while let Some(node) = queue.pop_front() {
+                for child in tree.edge_range(node).map(|edge| tree.target(edge)) {
+                    if parent[child] == node && child != ROOT {
+                        depth[child] = depth[node] + 1;
+                        queue.push_back(child);
+                    }
+                }
+            }
+        }
+
+        Self {
+            parent,
+            weight,
+            depth,
+            tree,
+        }
+    }
+
+    /// The tree itself, with each edge in both directions and the value of
+    /// its cut as data.
+    pub fn tree(&self) -> &StaticGraph<C> {
+        &self.tree
+    }
+
+    pub fn number_of_nodes(&self) -> usize {
+        self.parent.len()
+    }
+
+    /// The value of the minimum cut between `u` and `v`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `u` and `v` are the same node, which no cut separates.
+    pub fn min_cut_value(&self, u: NodeID, v: NodeID) -> C {
+        self.lightest_edge(u, v).1
+    }
+
+    /// The side of `u` of a minimum cut between `u` and `v`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `u` and `v` are the same node, which no cut separates.
+    pub fn min_cut(&self, u: NodeID, v: NodeID) -> BitVec {
+        let (child, _) = self.lightest_edge(u, v);
+        let cut_edge = (child, self.parent[child]);
+
+        let mut side = BitVec::repeat(false, self.number_of_nodes());
+        side.set(u, true);
+        let mut stack = vec![u];
+        while let Some(node) = stack.pop() {
+            for edge in self.tree.edge_range(node) {
+                let next = self.tree.target(edge);
+                if side[next] || cut_edge == (node, next) || cut_edge == (next, node) {
+                    continue;
+                }
+                side.set(next, true);
+                stack.push(next);
+            }
+        }
+        side
+    }
+
+    /// The lightest edge on the tree path between `u` and `v`, given by the
+    /// end that is the child of the other, and its weight.
+    fn lightest_edge(&self, mut u: NodeID, mut v: NodeID) -> (NodeID, C) {
+        assert_ne!(u, v, "no cut separates a node from itself");
+        let mut lightest = (NodeID::MAX, C::max_value());
+        while u != v {
+            if self.depth[u] < self.depth[v] {
+                std::mem::swap(&mut u, &mut v);
+            }
+            if self.weight[u] < lightest.1 || lightest.0 == NodeID::MAX {
+                lightest = (u, self.weight[u]);
+            }
+            u = self.parent[u];
+        }
+        lightest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+
+    fn undirected(pairs: &[(NodeID, NodeID, i32)]) -> Vec<InputEdge<ResidualEdgeData>> {
+        pairs
+            .iter()
+            .map(|&(source, target, capacity)| {
+                InputEdge::new(source, target, ResidualEdgeData::new(capacity))
+            })
+            .collect()
+    }
+
+    fn both_ways(edges: &[InputEdge<ResidualEdgeData>]) -> Vec<InputEdge<ResidualEdgeData>> {
+        edges
+            .iter()
+            .flat_map(|edge| [*edge, InputEdge::new(edge.target, edge.source, edge.data)])
+            .collect()
+    }
+
+    fn cut_capacity(edges: &[InputEdge<ResidualEdgeData>], side: &BitVec) -> i32 {
+        edges
+            .iter()
+            .filter(|edge| side[edge.source] != side[edge.target])
+            .map(|edge| edge.data.capacity)
+            .sum()
+    }
+
+    #[test]
+    fn all_pairs_match_dinic_on_random_graphs() {
+        let mut rng = StdRng::seed_from_u64(0x60_4D);
+        for round in 0..10 {
+            let nodes = 6 + round;
+            let mut pairs = Vec::new();
+            for u in 0..nodes {
+                for v in u + 1..nodes {
+                    if rng.random_range(0..100) < 35 {
+                        pairs.push((u, v, rng.random_range(1..=9)));
+                    }
+                }
+            }
+            // make sure the highest node is part of the graph
+            pairs.push((0, nodes - 1, 1));
+            let edges = undirected(&pairs);
+            let tree = GomoryHuTree::new(&edges);
+            assert_eq!(tree.number_of_nodes(), nodes);
+            assert_eq!(tree.tree().number_of_edges(), 2 * (nodes - 1));
+
+            for u in 0..nodes {
+                for v in u + 1..nodes {
+                    let mut solver = Dinic::from_edge_list(both_ways(&edges), u, v);
+                    solver.run();
+                    let value = tree.min_cut_value(u, v);
+                    assert_eq!(solver.max_flow(), Ok(value), "cut between {u} and {v}");
+                    assert_eq!(tree.min_cut_value(v, u), value);
+
+                    let side = tree.min_cut(u, v);
+                    assert!(side[u] && !side[v]);
+                    assert_eq!(cut_capacity(&edges, &side), value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_path_is_its_own_tree() {
+        let edges = undirected(&[(0, 1, 4), (1, 2, 2), (2, 3, 7)]);
+        let tree = GomoryHuTree::new(&edges);
+        assert_eq!(tree.min_cut_value(0, 1), 4);
+        assert_eq!(tree.min_cut_value(0, 3), 2);
+        assert_eq!(tree.min_cut_value(2, 3), 7);
+        assert_eq!(
+            tree.min_cut(3, 0).iter_ones().collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+    }
+
+    #[test]
+    fn separate_components_have_a_cut_of_zero() {
+        let edges = undirected(&[(0, 1, 4), (2, 3, 5), (3, 3, 9)]);
+        let tree = GomoryHuTree::new(&edges);
+        assert_eq!(tree.min_cut_value(0, 1), 4);
+        assert_eq!(tree.min_cut_value(2, 3), 5);
+        assert_eq!(tree.min_cut_value(1, 2), 0);
+        assert_eq!(
+            tree.min_cut(0, 3).iter_ones().collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn wide_capacities() {
+        let edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(3_000_000_000_u64)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(4_000_000_000)),
+            InputEdge::new(0, 2, ResidualEdgeData::new(2_000_000_000)),
+        ];
+        let tree = GomoryHuTree::new(&edges);
+        assert_eq!(tree.min_cut_value(0, 2), 5_000_000_000);
+        assert_eq!(tree.min_cut_value(1, 2), 6_000_000_000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_node_is_not_cut_from_itself() {
+        let tree = GomoryHuTree::new(&undirected(&[(0, 1, 1)]));
+        tree.min_cut_value(1, 1);
+    }
+}
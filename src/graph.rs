@@ -19,6 +19,42 @@ pub trait Graph<T> {
     fn data_mut(&mut self, e: EdgeID) -> &mut T;
     fn find_edge(&self, s: NodeID, t: NodeID) -> Option<EdgeID>;
     fn find_edge_unchecked(&self, s: NodeID, t: NodeID) -> EdgeID;
+
+    /// The arcs of `n` and their targets, in the order of the arc ids. A graph
+    /// that has to decode its targets walks the list in one go here, rather
+    /// than look up each target on its own.
+    fn out_arcs(&self, n: NodeID) -> impl Iterator<Item = (EdgeID, NodeID)> {
+        self.edge_range(n).map(move |e| (e, self.target(e)))
+    }
+}
+
+/// A graph whose nodes and arcs can be changed after it was built, as a
+/// contraction changes the graph it works on.
+///
+/// Removing a node leaves a tombstone behind. The node gives up its own arcs
+/// at once, but keeps its id, and the arcs of other nodes that lead to it stay
+/// where they are, as finding them would mean a search over all arcs. Both go
+/// once the graph is compacted, which renumbers the nodes that are left.
+pub trait MutableGraph<T>: Graph<T> {
+    /// Appends a node without arcs and returns its id.
+    fn insert_node(&mut self) -> NodeID;
+    /// Inserts the arc (`source`, `target`), adding nodes up to the larger of
+    /// the two if the graph does not hold them yet.
+    fn insert_edge(&mut self, source: NodeID, target: NodeID, data: T);
+    /// Removes the arc `edge`, which leaves `source`.
+    fn remove_edge(&mut self, source: NodeID, edge: EdgeID);
+    /// Sets the data of the arc (`source`, `target`) if there is one and
+    /// inserts it otherwise. Returns the id of the arc.
+    fn update_or_insert_edge(&mut self, source: NodeID, target: NodeID, data: T) -> EdgeID;
+    /// Removes `node` and the arcs that leave it, and leaves a tombstone.
+    fn remove_node(&mut self, node: NodeID);
+    /// Whether `node` was removed since the graph was last compacted.
+    fn is_removed(&self, node: NodeID) -> bool;
+    /// Drops the tombstones, the arcs that lead to them and all unused space,
+    /// and numbers the remaining nodes consecutively in their old order.
+    /// Returns the new id of each old node, [`INVALID_NODE_ID`] for the ones
+    /// that were removed.
+    fn compact(&mut self) -> Vec<NodeID>;
 }
 /// One arc of an adjacency array: where it goes, and what it costs.
 ///
@@ -31,7 +67,11 @@ pub trait Graph<T> {
 ///
 /// Four bytes reach four thousand million, which is more nodes than this crate
 /// can hold anyway: the cell tables address them with four bytes too.
+///
+/// The layout is fixed, target first, so that a mapped file can be read as a
+/// list of these as it lies.
 #[derive(Clone, Copy)]
+#[repr(C)]
 pub struct EdgeArrayEntry<EdgeDataT: Clone> {
     pub target: u32,
     pub data: EdgeDataT,
@@ -2,13 +2,75 @@ use std::fmt::Display;
 
 use clap::{Parser, ValueEnum};
 
+/// Parses the speed in km/h on a highway class, as `residential=30`.
+pub fn highway_speed(s: &str) -> Result<(String, f64), String> {
+    let (highway, speed) = s
+        .split_once('=')
+        .ok_or_else(|| format!("`{s}` isn't of the form <highway>=<km/h>"))?;
+    let speed: f64 = speed
+        .parse()
+        .map_err(|_| format!("`{speed}` isn't a number"))?;
+    if speed.is_nan() || speed < 0. {
+        return Err(format!("speed {speed} isn't a speed"));
+    }
+    Ok((highway.to_string(), speed))
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 pub enum InputFormat {
+    /// a CSV file of arcs, with the coordinates in a CSV file of nodes
+    Csv,
+    Dimacs,
+    Ddsg,
+    /// GraphML as NetworkX or Gephi write it, with the coordinates in the
+    /// same file
+    Graphml,
+    Metis,
+    /// an OpenStreetMap PBF extract, with the coordinates in the same file
+    Osm,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// the rkyv archives the other tools read
+    Toolbox,
+    Csv,
     Dimacs,
     Ddsg,
+    /// GraphML, with the coordinates in the same file
+    Graphml,
     Metis,
 }
 
+impl OutputFormat {
+    /// The extensions appended to the paths of the graph and of the
+    /// coordinates that were read, the latter `None` for a format that holds
+    /// the coordinates in the graph file.
+    pub fn extensions(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            OutputFormat::Toolbox => ("toolbox", Some("toolbox")),
+            OutputFormat::Csv => ("csv", Some("csv")),
+            OutputFormat::Dimacs => ("gr", Some("co")),
+            OutputFormat::Ddsg => ("ddsg", Some("xyz")),
+            OutputFormat::Graphml => ("graphml", None),
+            OutputFormat::Metis => ("graph", Some("xyz")),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Toolbox => write!(f, "toolbox"),
+            OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::Dimacs => write!(f, "dimacs"),
+            OutputFormat::Ddsg => write!(f, "ddsg"),
+            OutputFormat::Graphml => write!(f, "graphml"),
+            OutputFormat::Metis => write!(f, "metis"),
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Arguments {
@@ -20,15 +82,50 @@ pub struct Arguments {
     #[clap(short, long, action)]
     pub graph: String,
 
-    /// path to the input coordinates
+    /// path to the input coordinates, or for OSM and GraphML where to write
+    /// them, by default next to the graph
     #[clap(short, long, action)]
-    pub coordinates: String,
+    pub coordinates: Option<String>,
+
+    /// speed in km/h on a highway class when importing OSM, as
+    /// `residential=30`, where 0 leaves the class out
+    #[clap(long, value_parser = highway_speed)]
+    pub speed: Vec<(String, f64)>,
+
+    /// name of the column or attribute that holds the weight of an arc when
+    /// reading CSV or GraphML, and the one written for them
+    #[clap(long, default_value = "weight")]
+    pub weight: String,
+
+    /// read CSV or GraphML without weights, with every arc costing one
+    #[clap(long, action)]
+    pub unweighted: bool,
+
+    /// what a weight read from CSV or GraphML is multiplied by before it is
+    /// rounded, as `1000` for kilometres into metres
+    #[clap(long, default_value_t = 1.)]
+    pub weight_scale: f64,
+
+    /// format to convert into, written next to the input
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Toolbox)]
+    pub output_format: OutputFormat,
 }
 
 impl Display for Arguments {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "command line arguments:")?;
         writeln!(f, "graph: {}", self.graph)?;
-        writeln!(f, "coordinates: {}", self.coordinates)
+        if let Some(coordinates) = &self.coordinates {
+            writeln!(f, "coordinates: {coordinates}")?;
+        }
+        for (highway, speed) in &self.speed {
+            writeln!(f, "speed on {highway}: {speed} km/h")?;
+        }
+        if self.unweighted {
+            writeln!(f, "unweighted")?;
+        } else {
+            writeln!(f, "weight: {} scaled by {}", self.weight, self.weight_scale)?;
+        }
+        writeln!(f, "output format: {}", self.output_format)
     }
 }
@@ -1,17 +1,21 @@
 mod command_line;
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
+use std::error::Error;
 
 use env_logger::Env;
 use log::info;
-use rkyv::rancor;
 
-use crate::command_line::{Arguments, InputFormat};
-use toolbox_rs::{ddsg, dimacs, edge::InputEdge, metis};
+use crate::command_line::{Arguments, InputFormat, OutputFormat};
+use toolbox_rs::{
+    container::{ContainerWriter, section},
+    csv::{self, CsvColumns},
+    ddsg, dimacs,
+    edge::InputEdge,
+    graphml::{self, GraphMlKeys},
+    metis,
+    osm_pbf::{self, SpeedProfile},
+};
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
     println!(r#"     ___     _       _                    "#);
@@ -26,27 +30,123 @@ fn main() {
     let args = <Arguments as clap::Parser>::parse();
     info!("{args}");
 
-    let edges: Vec<InputEdge<usize>> = match args.input_format {
-        InputFormat::Ddsg => ddsg::read_graph(&args.graph, ddsg::WeightType::Original),
-        InputFormat::Dimacs => dimacs::read_graph(&args.graph, dimacs::WeightType::Original),
-        InputFormat::Metis => metis::read_graph(&args.graph, metis::WeightType::Original),
+    // the attribute the weights are read from, if any
+    let weight = (!args.unweighted).then(|| args.weight.clone());
+    let columns = CsvColumns {
+        weight: weight.clone(),
+        scale: args.weight_scale,
+        ..Default::default()
+    };
+    let keys = GraphMlKeys {
+        weight,
+        scale: args.weight_scale,
+        ..Default::default()
+    };
+    // where the coordinates go for an input that holds them in the graph file
+    let next_to_graph = || {
+        args.coordinates
+            .clone()
+            .unwrap_or_else(|| format!("{}.coordinates", args.graph))
     };
 
-    let coordinates = match args.input_format {
-        InputFormat::Ddsg => ddsg::read_coordinates(&args.coordinates),
-        InputFormat::Dimacs => dimacs::read_coordinates(&args.coordinates),
-        InputFormat::Metis => metis::read_coordinates(&args.coordinates),
+    // the coordinates of the formats that keep them apart from the graph
+    let coordinates_path = || {
+        args.coordinates
+            .clone()
+            .ok_or("the coordinates of the graph are needed")
     };
 
-    info!("writing edges into intermediate format");
-    let bytes = rkyv::to_bytes::<rancor::Error>(&edges).unwrap();
-    let mut f = BufWriter::new(File::create(args.graph + ".toolbox").unwrap());
-    f.write_all(&bytes).unwrap();
+    let (edges, coordinates, coordinates_base): (Vec<InputEdge<usize>>, _, _) =
+        match args.input_format {
+            InputFormat::Osm => {
+                let mut profile = SpeedProfile::car();
+                for (highway, speed) in &args.speed {
+                    profile.set_speed(highway, *speed);
+                }
+                let network = osm_pbf::read_road_network(&args.graph, &profile)?;
+                (network.edges, network.coordinates, next_to_graph())
+            }
+            InputFormat::Graphml => {
+                let graph = graphml::try_read_graph(&args.graph, &keys)?;
+                (graph.edges, graph.coordinates, next_to_graph())
+            }
+            InputFormat::Csv => {
+                let path = coordinates_path()?;
+                let edges = csv::try_read_graph(&args.graph, &columns)?;
+                (edges, csv::try_read_coordinates(&path, &columns)?, path)
+            }
+            InputFormat::Ddsg => {
+                let path = coordinates_path()?;
+                let edges = ddsg::par_try_read_graph(&args.graph, ddsg::WeightType::Original)?;
+                (edges, ddsg::par_try_read_coordinates(&path)?, path)
+            }
+            InputFormat::Dimacs => {
+                let path = coordinates_path()?;
+                let edges = dimacs::par_try_read_graph(&args.graph, dimacs::WeightType::Original)?;
+                (edges, dimacs::par_try_read_coordinates(&path)?, path)
+            }
+            InputFormat::Metis => {
+                let path = coordinates_path()?;
+                let edges = metis::par_try_read_graph(&args.graph, metis::WeightType::Original)?;
+                (edges, metis::par_try_read_coordinates(&path)?, path)
+            }
+        };
+
+    let (graph_extension, coordinates_extension) = args.output_format.extensions();
+    let graph_path = format!("{}.{graph_extension}", args.graph);
+    let coordinates_path = coordinates_extension
+        .map(|extension| format!("{coordinates_base}.{extension}"))
+        .unwrap_or_else(|| graph_path.clone());
+
+    // nodes without arcs past the last one still have coordinates
+    let number_of_nodes = edges
+        .iter()
+        .map(|edge| 1 + edge.source.max(edge.target))
+        .max()
+        .unwrap_or(0)
+        .max(coordinates.len());
+    info!(
+        "writing {number_of_nodes} nodes and {} arcs as {}",
+        edges.len(),
+        args.output_format
+    );
+    match args.output_format {
+        OutputFormat::Toolbox => {
+            let mut graph = ContainerWriter::new(number_of_nodes, edges.len());
+            graph.add_section(section::EDGES, &edges)?;
+            graph.write(&graph_path)?;
 
-    info!("writing coordinates into intermediate format");
-    let bytes = rkyv::to_bytes::<rancor::Error>(&coordinates).unwrap();
-    let mut f = BufWriter::new(File::create(args.coordinates + ".toolbox").unwrap());
-    f.write_all(&bytes).unwrap();
+            // a GraphML file may hold no coordinates at all
+            let mut nodes = ContainerWriter::new(coordinates.len(), 0);
+            nodes.add_section(section::COORDINATES, &coordinates)?;
+            nodes.write(&coordinates_path)?;
+        }
+        OutputFormat::Csv => {
+            csv::write_graph(&graph_path, &edges, &columns)?;
+            csv::write_coordinates(&coordinates_path, &coordinates, &columns)?;
+        }
+        OutputFormat::Dimacs => {
+            dimacs::write_graph(&graph_path, number_of_nodes, &edges)?;
+            dimacs::write_coordinates(&coordinates_path, &coordinates)?;
+        }
+        OutputFormat::Ddsg => {
+            ddsg::write_graph(&graph_path, number_of_nodes, &edges)?;
+            ddsg::write_coordinates(&coordinates_path, &coordinates)?;
+        }
+        OutputFormat::Graphml => {
+            graphml::write_graph(&graph_path, number_of_nodes, &edges, &coordinates, &keys)?;
+        }
+        OutputFormat::Metis => {
+            metis::write_graph(&graph_path, number_of_nodes, &edges)?;
+            metis::write_coordinates(&coordinates_path, &coordinates)?;
+        }
+    }
+    if coordinates_path == graph_path {
+        info!("wrote {graph_path}");
+    } else {
+        info!("wrote {graph_path} and {coordinates_path}");
+    }
 
     info!("done.");
+    Ok(())
 }
@@ -0,0 +1,378 @@
+//! Graphs made from other graphs: turned around, made symmetric, cut down to
+//! a set of nodes or to the largest strongly connected component, and
+//! renumbered.
+//!
+//! Each transform reads any [`Graph`] and builds a [`StaticGraph`] that holds
+//! as many nodes as the result is over, whether or not an arc reaches the last
+//! of them. The data of each arc travels with it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{
+//!     edge::InputEdge,
+//!     graph::Graph,
+//!     graph_transform::{reverse, symmetrize},
+//!     static_graph::StaticGraph,
+//! };
+//!
+//! let graph = StaticGraph::new(vec![
+//!     InputEdge::new(0, 1, 3),
+//!     InputEdge::new(1, 0, 4),
+//!     InputEdge::new(1, 2, 5),
+//! ]);
+//! let reverse = reverse(&graph);
+//! assert_eq!(reverse.find_edge(2, 1).map(|edge| *reverse.data(edge)), Some(5));
+//!
+//! // the two arcs between 0 and 1 are merged into the cheaper one
+//! let symmetric = symmetrize(&graph, |a: i32, b| a.min(b));
+//! assert_eq!(symmetric.number_of_edges(), 4);
+//! assert_eq!(symmetric.find_edge(1, 0).map(|edge| *symmetric.data(edge)), Some(3));
+//! ```
+use crate::{
+    edge::InputEdge,
+    graph::{EdgeID, Graph, INVALID_NODE_ID, NodeID},
+    node_ordering::NodeOrdering,
+    path_based_scc::PathBasedScc,
+    static_graph::StaticGraph,
+};
+
+/// All arcs of a graph, in the order the graph holds them.
+fn arcs_of<T: Ord + Copy>(graph: &impl Graph<T>) -> Vec<InputEdge<T>> {
+    let mut arcs = Vec::with_capacity(graph.number_of_edges());
+    for source in graph.node_range() {
+        for edge in graph.edge_range(source) {
+            arcs.push(InputEdge::new(
+                source,
+                graph.target(edge),
+                *graph.data(edge),
+            ));
+        }
+    }
+    arcs
+}
+
+/// The graph with every arc turned around.
+#[must_use]
+pub fn reverse<T: Ord + Copy>(graph: &impl Graph<T>) -> StaticGraph<T> {
+    let mut arcs = arcs_of(graph);
+    arcs.iter_mut().for_each(InputEdge::reverse);
+    StaticGraph::new_with_nodes(graph.number_of_nodes(), arcs)
+}
+
+/// The graph with each arc added the other way round as well. Arcs that end
+/// up parallel, because the graph held them both ways or twice already, are
+/// merged into one by folding their data with `merge` in the order of the
+/// data.
+#[must_use]
+pub fn symmetrize<T: Ord + Copy>(
+    graph: &impl Graph<T>,
+    merge: impl Fn(T, T) -> T,
+) -> StaticGraph<T> {
+    let mut arcs = arcs_of(graph);
+    let reversed: Vec<_> = arcs
+        .iter()
+        .filter(|arc| arc.source != arc.target)
+        .map(|arc| InputEdge::new(arc.target, arc.source, arc.data))
+        .collect();
+    arcs.extend(reversed);
+    arcs.sort_unstable();
+
+    let mut merged: Vec<InputEdge<T>> = Vec::with_capacity(arcs.len());
+    for arc in arcs {
+        match merged.last_mut() {
+            Some(last) if last.is_parallel_to(&arc) => last.data = merge(last.data, arc.data),
+            _ => merged.push(arc),
+        }
+    }
+    StaticGraph::new_with_nodes(graph.number_of_nodes(), merged)
+}
+
+/// The subgraph that `nodes` induce, i.e. those nodes and the arcs between
+/// them. The i-th node of the list is node i of the result.
+///
+/// # Panics
+///
+/// Panics if a node is listed twice or is not a node of the graph.
+#[must_use]
+pub fn induced_subgraph<T: Ord + Copy>(graph: &impl Graph<T>, nodes: &[NodeID]) -> StaticGraph<T> {
+    let mut arcs = Vec::new();
+    induced_arcs(graph, nodes, |arc, _| arcs.push(arc));
+    StaticGraph::new_with_nodes(nodes.len(), arcs)
+}
+
+/// The subgraph that `nodes` induce as [`induced_subgraph`] builds it, and
+/// the arc of `graph` that each of its arcs came from, to carry
+/// [`EdgeAttributes`](crate::attributes::EdgeAttributes) along.
+///
+/// # Panics
+///
+/// Panics if a node is listed twice or is not a node of the graph.
+#[must_use]
+pub fn induced_subgraph_with_origin<T: Ord + Copy>(
+    graph: &impl Graph<T>,
+    nodes: &[NodeID],
+) -> (StaticGraph<T>, Vec<EdgeID>) {
+    let mut arcs = Vec::new();
+    let mut old_edges = Vec::new();
+    induced_arcs(graph, nodes, |arc, edge| {
+        arcs.push(arc);
+        old_edges.push(edge);
+    });
+    with_origin(nodes.len(), arcs, &old_edges)
+}
+
+/// Hands each arc between `nodes`, numbered by their place in the list, to
+/// `keep` along with the arc of `graph` it is.
+fn induced_arcs<T: Eq + Copy>(
+    graph: &impl Graph<T>,
+    nodes: &[NodeID],
+    mut keep: impl FnMut(InputEdge<T>, EdgeID),
+) {
+    let mut new_id = vec![INVALID_NODE_ID; graph.number_of_nodes()];
+    for (new, &old) in nodes.iter().enumerate() {
+        assert_eq!(new_id[old], INVALID_NODE_ID, "node {old} is listed twice");
+        new_id[old] = new;
+    }
+
+    for (new, &old) in nodes.iter().enumerate() {
+        for edge in graph.edge_range(old) {
+            let target = new_id[graph.target(edge)];
+            if target != INVALID_NODE_ID {
+                keep(InputEdge::new(new, target, *graph.data(edge)), edge);
+            }
+        }
+    }
+}
+
+/// The same arcs, between the numbers `ordering` gives their ends.
+///
+/// # Panics
+///
+/// Panics if the ordering was worked out over fewer nodes than the graph has.
+#[must_use]
+pub fn renumber<T: Ord + Copy>(graph: &impl Graph<T>, ordering: &NodeOrdering) -> StaticGraph<T> {
+    let mut arcs = Vec::with_capacity(graph.number_of_edges());
+    renumbered_arcs(graph, ordering, |arc, _| arcs.push(arc));
+    StaticGraph::new_with_nodes(ordering.len(), arcs)
+}
+
+/// The graph renumbered as [`renumber`] does it, and the arc of `graph` that
+/// each of its arcs came from, to carry
+/// [`EdgeAttributes`](crate::attributes::EdgeAttributes) along.
+///
+/// # Panics
+///
+/// Panics if the ordering was worked out over fewer nodes than the graph has.
+#[must_use]
+pub fn renumber_with_origin<T: Ord + Copy>(
+    graph: &impl Graph<T>,
+    ordering: &NodeOrdering,
+) -> (StaticGraph<T>, Vec<EdgeID>) {
+    let mut arcs = Vec::with_capacity(graph.number_of_edges());
+    let mut old_edges = Vec::with_capacity(graph.number_of_edges());
+    renumbered_arcs(graph, ordering, |arc, edge| {
+        arcs.push(arc);
+        old_edges.push(edge);
+    });
+    with_origin(ordering.len(), arcs, &old_edges)
+}
+
+/// Hands each arc of `graph`, its ends numbered by `ordering`, to `keep`
+/// along with the arc it was.
+fn renumbered_arcs<T: Eq + Copy>(
+    graph: &impl Graph<T>,
+    ordering: &NodeOrdering,
+    mut keep: impl FnMut(InputEdge<T>, EdgeID),
+) {
+    for source in graph.node_range() {
+        let moved = ordering.new_of(source);
+        for edge in graph.edge_range(source) {
+            keep(
+                InputEdge::new(
+                    moved,
+                    ordering.new_of(graph.target(edge)),
+                    *graph.data(edge),
+                ),
+                edge,
+            );
+        }
+    }
+}
+
+/// Assembles a graph of `nodes` nodes out of `arcs`, the i-th of which came
+/// from arc `old_edges[i]`, and says for each arc of the result where it came
+/// from.
+fn with_origin<T: Ord + Copy>(
+    nodes: usize,
+    arcs: Vec<InputEdge<T>>,
+    old_edges: &[EdgeID],
+) -> (StaticGraph<T>, Vec<EdgeID>) {
+    let (graph, mut origin) = StaticGraph::new_with_origin(nodes, arcs);
+    for position in &mut origin {
+        *position = old_edges[*position];
+    }
+    (graph, origin)
+}
+
+/// The largest strongly connected component, as the subgraph it induces and
+/// the nodes it consists of, ordered by id. Of several components of the
+/// largest size, the one with the smallest node is taken.
+#[must_use]
+pub fn largest_scc<T: Ord + Copy, G: Graph<T> + 'static>(
+    graph: &G,
+) -> (StaticGraph<T>, Vec<NodeID>) {
+    let component = PathBasedScc::new().run(graph);
+
+    let mut size = vec![0_usize; graph.number_of_nodes()];
+    for &id in &component {
+        size[id] += 1;
+    }
+    let largest_size = size.iter().copied().max().unwrap_or(0);
+    // nodes are visited by id, so the first hit is the component of the
+    // smallest node among the largest ones
+    let nodes: Vec<NodeID> = match component.iter().find(|&&id| size[id] == largest_size) {
+        Some(&largest) => (0..component.len())
+            .filter(|&node| component[node] == largest)
+            .collect(),
+        None => Vec::new(),
+    };
+    (induced_subgraph(graph, &nodes), nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic_graph::DynamicGraph;
+
+    fn graph() -> StaticGraph<i32> {
+        // a cycle 0 -> 1 -> 2 -> 0, a cycle 3 <-> 4 and a lone node 5 that 2
+        // leads to
+        StaticGraph::new_with_nodes(
+            6,
+            vec![
+                InputEdge::new(0, 1, 1),
+                InputEdge::new(1, 2, 2),
+                InputEdge::new(2, 0, 3),
+                InputEdge::new(2, 5, 4),
+                InputEdge::new(3, 4, 5),
+                InputEdge::new(4, 3, 6),
+            ],
+        )
+    }
+
+    fn arcs(graph: &impl Graph<i32>) -> Vec<(NodeID, NodeID, i32)> {
+        let mut arcs: Vec<_> = arcs_of(graph)
+            .into_iter()
+            .map(|arc| (arc.source, arc.target, arc.data))
+            .collect();
+        arcs.sort_unstable();
+        arcs
+    }
+
+    #[test]
+    fn reverse_turns_every_arc_around() {
+        let reverse = reverse(&graph());
+        assert_eq!(reverse.number_of_nodes(), 6);
+        assert_eq!(
+            arcs(&reverse),
+            vec![
+                (0, 2, 3),
+                (1, 0, 1),
+                (2, 1, 2),
+                (3, 4, 6),
+                (4, 3, 5),
+                (5, 2, 4)
+            ]
+        );
+        assert_eq!(arcs(&super::reverse(&reverse)), arcs(&graph()));
+    }
+
+    #[test]
+    fn symmetrize_merges_parallel_arcs() {
+        let symmetric = symmetrize(&graph(), |a, b| a + b);
+        assert_eq!(
+            arcs(&symmetric),
+            vec![
+                (0, 1, 1),
+                (0, 2, 3),
+                (1, 0, 1),
+                (1, 2, 2),
+                (2, 0, 3),
+                (2, 1, 2),
+                (2, 5, 4),
+                (3, 4, 11),
+                (4, 3, 11),
+                (5, 2, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn symmetrize_keeps_a_loop_once() {
+        let graph = StaticGraph::new(vec![InputEdge::new(0, 0, 7), InputEdge::new(0, 1, 1)]);
+        let symmetric = symmetrize(&graph, |a: i32, b| a.max(b));
+        assert_eq!(arcs(&symmetric), vec![(0, 0, 7), (0, 1, 1), (1, 0, 1)]);
+    }
+
+    #[test]
+    fn induced_subgraph_keeps_the_arcs_between_its_nodes() {
+        let subgraph = induced_subgraph(&graph(), &[5, 2, 0]);
+        assert_eq!(subgraph.number_of_nodes(), 3);
+        // 2 -> 5, 2 -> 0 stay, in the numbering of the list
+        assert_eq!(arcs(&subgraph), vec![(1, 0, 4), (1, 2, 3)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn induced_subgraph_refuses_a_node_twice() {
+        let _ = induced_subgraph(&graph(), &[1, 1]);
+    }
+
+    #[test]
+    fn renumber_moves_both_ends() {
+        let ordering = NodeOrdering::from_places(vec![5, 4, 3, 2, 1, 0], 0);
+        let renumbered = renumber(&graph(), &ordering);
+        assert_eq!(renumbered.number_of_nodes(), 6);
+        assert_eq!(
+            arcs(&renumbered),
+            vec![
+                (1, 2, 6),
+                (2, 1, 5),
+                (3, 0, 4),
+                (3, 5, 3),
+                (4, 3, 2),
+                (5, 4, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn largest_scc_is_the_cycle_of_three() {
+        let (component, nodes) = largest_scc(&graph());
+        assert_eq!(nodes, vec![0, 1, 2]);
+        assert_eq!(arcs(&component), vec![(0, 1, 1), (1, 2, 2), (2, 0, 3)]);
+    }
+
+    #[test]
+    fn largest_scc_ties_go_to_the_smallest_node() {
+        let graph = StaticGraph::new(vec![
+            InputEdge::new(0, 2, 1),
+            InputEdge::new(1, 3, 1),
+            InputEdge::new(2, 0, 1),
+            InputEdge::new(3, 1, 1),
+        ]);
+        let (_, nodes) = largest_scc(&graph);
+        assert_eq!(nodes, vec![0, 2]);
+    }
+
+    #[test]
+    fn transforms_read_a_dynamic_graph() {
+        let mut graph = DynamicGraph::new(3, vec![InputEdge::new(0, 1, 1)]);
+        graph.insert_edge(1, 2, 2);
+        graph.insert_edge(2, 0, 3);
+        let (component, nodes) = largest_scc(&graph);
+        assert_eq!(nodes, vec![0, 1, 2]);
+        assert_eq!(component.number_of_edges(), 3);
+    }
+}
@@ -0,0 +1,795 @@
+//! Graphs as GraphML, the XML that NetworkX, Gephi and yEd exchange.
+//!
+//! A GraphML file declares its attributes as keys and then lists nodes and
+//! edges with the values of those keys as `data` children:
+//!
+//! ```xml
+//! <graphml>
+//!   <key id="d0" for="edge" attr.name="weight" attr.type="double"/>
+//!   <key id="d1" for="node" attr.name="lat" attr.type="double"/>
+//!   <key id="d2" for="node" attr.name="lon" attr.type="double"/>
+//!   <graph edgedefault="undirected">
+//!     <node id="a"><data key="d1">52.5</data><data key="d2">13.4</data></node>
+//!     ...
+//!     <edge source="a" target="b"><data key="d0">3.5</data></edge>
+//!   </graph>
+//! </graphml>
+//! ```
+//!
+//! The ids of the nodes are arbitrary text, and the nodes are numbered from
+//! zero in the order the file lists them. An undirected edge becomes an arc
+//! either way. [`GraphMlKeys`] names the attributes that hold the weight of
+//! an edge and the coordinates of a node, which are matched by their
+//! `attr.name` rather than the id of their key, as that id is whatever the
+//! writing tool made up. Weights are scaled and rounded as in the CSV reader.
+//!
+//! What is read is the first graph of the file. Nested graphs, hyperedges and
+//! ports are GraphML, but not graphs this crate can hold, and are rejected.
+use std::{collections::hash_map::Entry, fmt::Display, fs};
+
+use log::info;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    csv::degrees,
+    edge::InputEdge,
+    geometry::FPCoordinate,
+    graph::NodeID,
+    io::{GraphFileError, TextWriter, scaled_weight},
+};
+
+/// The names of the attributes to read and write.
+#[derive(Clone, Debug)]
+pub struct GraphMlKeys {
+    /// the cost of an edge, or `None` for every edge to cost one
+    pub weight: Option<String>,
+    /// what a weight is multiplied by before it is rounded
+    pub scale: f64,
+    pub lat: String,
+    pub lon: String,
+}
+
+impl Default for GraphMlKeys {
+    fn default() -> Self {
+        Self {
+            weight: Some("weight".to_string()),
+            scale: 1.,
+            lat: "lat".to_string(),
+            lon: "lon".to_string(),
+        }
+    }
+}
+
+/// The graph of a GraphML file.
+#[derive(Debug)]
+pub struct GraphMl<T: Eq> {
+    /// the arcs, without loops
+    pub edges: Vec<InputEdge<T>>,
+    /// the coordinates of the nodes, or none if the file has none
+    pub coordinates: Vec<FPCoordinate>,
+    /// the id of each node in the file
+    pub ids: Vec<String>,
+}
+
+/// Reads the first graph of a GraphML file.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, is not well formed, an edge leads to a
+/// node that is not listed, or the weight or a coordinate of an element is
+/// missing or does not parse.
+pub fn try_read_graph<T: Eq + From<usize>>(
+    filename: &str,
+    keys: &GraphMlKeys,
+) -> Result<GraphMl<T>, GraphFileError> {
+    let text = fs::read_to_string(filename).map_err(|source| GraphFileError::Io {
+        file: filename.to_string(),
+        source,
+    })?;
+    let error = |line, reason: String| GraphFileError::Parse {
+        file: filename.to_string(),
+        line,
+        reason,
+    };
+
+    // the key ids of the attributes asked for, and their defaults
+    let mut weight_key = None;
+    let mut lat_key = None;
+    let mut lon_key = None;
+    let mut defaults = FxHashMap::<String, (String, usize)>::default();
+
+    let mut directed = true;
+    let mut graphs = 0;
+    let mut graph_line = 0;
+    let mut node_of_id = FxHashMap::<String, NodeID>::default();
+    let mut ids = Vec::new();
+    let mut node_lines = Vec::new();
+    let mut lat = Vec::new();
+    let mut lon = Vec::new();
+    // edges by the ids of their ends, as a file may list them before those
+    let mut edges = Vec::new();
+
+    // the names of the open elements and the lines they were opened on
+    let mut open = Vec::new();
+    let mut element = Element::None;
+    // the key of the `data` or `default` being read, and its text
+    let mut value: Option<(Option<String>, String)> = None;
+
+    let mut tokens = Tokenizer::new(&text);
+    while let Some(token) = tokens
+        .next_token()
+        .map_err(|(line, reason)| error(line, reason))?
+    {
+        match token {
+            Token::Open {
+                name,
+                attributes,
+                empty,
+                line,
+            } => {
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|(name, _)| *name == key)
+                        .map(|(_, value)| value.clone())
+                };
+                let required = |key: &str| {
+                    attribute(key).ok_or_else(|| error(line, format!("<{name}> without {key}")))
+                };
+                match name {
+                    "key" => {
+                        let id = required("id")?;
+                        let attr_name = attribute("attr.name").unwrap_or_default();
+                        let domain = attribute("for").unwrap_or_else(|| "all".to_string());
+                        let on_edges = domain == "edge" || domain == "all";
+                        let on_nodes = domain == "node" || domain == "all";
+                        if on_edges && keys.weight.as_deref() == Some(attr_name.as_str()) {
+                            weight_key = Some(id.clone());
+                        }
+                        if on_nodes && attr_name == keys.lat {
+                            lat_key = Some(id.clone());
+                        }
+                        if on_nodes && attr_name == keys.lon {
+                            lon_key = Some(id.clone());
+                        }
+                        element = Element::Key(id);
+                    }
+                    "default" => value = Some((None, String::new())),
+                    "graph" => {
+                        graphs += 1;
+                        if graphs > 1 || !matches!(element, Element::None) {
+                            return Err(error(line, "a second or nested graph".to_string()));
+                        }
+                        directed = attribute("edgedefault").as_deref() != Some("undirected");
+                        graph_line = line;
+                    }
+                    "node" => {
+                        let id = required("id")?;
+                        match node_of_id.entry(id.clone()) {
+                            Entry::Occupied(_) => {
+                                return Err(error(line, format!("node {id} is listed twice")));
+                            }
+                            Entry::Vacant(entry) => {
+                                entry.insert(ids.len());
+                            }
+                        }
+                        ids.push(id);
+                        node_lines.push(line);
+                        lat.push(None);
+                        lon.push(None);
+                        element = Element::Node(ids.len() - 1);
+                    }
+                    "edge" => {
+                        let both_ways = match attribute("directed").as_deref() {
+                            Some("true") => false,
+                            Some("false") => true,
+                            _ => !directed,
+                        };
+                        edges.push(PendingEdge {
+                            source: required("source")?,
+                            target: required("target")?,
+                            both_ways,
+                            weight: None,
+                            line,
+                        });
+                        element = Element::Edge;
+                    }
+                    "data" => value = Some((Some(required("key")?), String::new())),
+                    "hyperedge" | "port" => {
+                        return Err(error(line, format!("<{name}> is not supported")));
+                    }
+                    _ => {}
+                }
+                if empty {
+                    close(name, &mut element, &mut value);
+                } else {
+                    open.push((name, line));
+                }
+            }
+            Token::Text(text) => {
+                if let Some((_, content)) = &mut value {
+                    content.push_str(&text);
+                }
+            }
+            Token::Close { name, .. } => {
+                match open.pop() {
+                    Some((opened, _)) if opened == name => {}
+                    Some((opened, line)) => {
+                        return Err(error(
+                            tokens.line,
+                            format!("</{name}> closes <{opened}> of line {line}"),
+                        ));
+                    }
+                    None => {
+                        return Err(error(tokens.line, format!("</{name}> closes nothing")));
+                    }
+                }
+                if let Some((key, content)) = close(name, &mut element, &mut value) {
+                    match (&element, key) {
+                        (Element::Key(id), None) => {
+                            defaults.insert(id.clone(), (content, tokens.line));
+                        }
+                        (Element::Node(node), Some(key)) => {
+                            if lat_key.as_ref() == Some(&key) {
+                                lat[*node] = Some((content, tokens.line));
+                            } else if lon_key.as_ref() == Some(&key) {
+                                lon[*node] = Some((content, tokens.line));
+                            }
+                        }
+                        (Element::Edge, Some(key)) if weight_key.as_ref() == Some(&key) => {
+                            edges.last_mut().expect("inside an edge").weight = Some(content);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    if let Some((name, line)) = open.pop() {
+        return Err(error(line, format!("<{name}> is not closed")));
+    }
+    if graphs == 0 {
+        return Err(GraphFileError::Missing {
+            file: filename.to_string(),
+            what: "graph",
+        });
+    }
+
+    let parse = |value: &str, line, what: &str| {
+        value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| error(line, format!("`{}` is not a {what}", value.trim())))
+    };
+
+    let default_of = |key: &Option<String>| key.as_ref().and_then(|key| defaults.get(key));
+    let coordinates = match (&lat_key, &lon_key) {
+        (None, None) => Vec::new(),
+        (Some(_), Some(_)) => {
+            let mut coordinates = Vec::with_capacity(ids.len());
+            for node in 0..ids.len() {
+                let value = |values: &[Option<(String, usize)>], key, what| match (
+                    &values[node],
+                    default_of(key),
+                ) {
+                    (Some((value, line)), _) => parse(value, *line, what),
+                    (None, Some((default, line))) => parse(default, *line, what),
+                    (None, None) => Err(error(
+                        node_lines[node],
+                        format!("node {} has no {what}", ids[node]),
+                    )),
+                };
+                let lat = value(&lat, &lat_key, "latitude")?;
+                let lon = value(&lon, &lon_key, "longitude")?;
+                coordinates.push(degrees(lat, lon));
+            }
+            coordinates
+        }
+        _ => {
+            return Err(error(
+                graph_line,
+                format!(
+                    "the nodes have only one of the attributes {} and {}",
+                    keys.lat, keys.lon
+                ),
+            ));
+        }
+    };
+
+    if let Some(name) = &keys.weight
+        && weight_key.is_none()
+    {
+        return Err(error(
+            graph_line,
+            format!("there is no edge attribute {name}"),
+        ));
+    }
+    let mut arcs = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let node = |id: &str| {
+            node_of_id
+                .get(id)
+                .copied()
+                .ok_or_else(|| error(edge.line, format!("node {id} is not listed")))
+        };
+        let source = node(&edge.source)?;
+        let target = node(&edge.target)?;
+        let weight = match (&edge.weight, default_of(&weight_key)) {
+            (Some(weight), _) => Some((weight, edge.line)),
+            (None, Some((default, line))) => Some((default, *line)),
+            (None, None) => None,
+        };
+        let data = match (&keys.weight, weight) {
+            (None, _) => 1,
+            (Some(_), Some((weight, line))) => {
+                scaled_weight(parse(weight, line, "weight")?, keys.scale)
+                    .map_err(|reason| error(line, reason))?
+            }
+            (Some(name), None) => {
+                return Err(error(edge.line, format!("an edge without {name}")));
+            }
+        };
+        // avoid eigenloops
+        if source == target {
+            continue;
+        }
+        arcs.push(InputEdge::new(source, target, T::from(data)));
+        if edge.both_ways {
+            arcs.push(InputEdge::new(target, source, T::from(data)));
+        }
+    }
+    info!("read {} nodes and {} arcs", ids.len(), arcs.len());
+    Ok(GraphMl {
+        edges: arcs,
+        coordinates,
+        ids,
+    })
+}
+
+/// Writes a directed graph for [`try_read_graph`] to read back, with nodes
+/// `n0`, `n1` and so on, and their coordinates unless there are none.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+///
+/// # Panics
+///
+/// Panics if there are coordinates, but not one per node.
+pub fn write_graph<T: Eq + Display>(
+    filename: &str,
+    number_of_nodes: usize,
+    edges: &[InputEdge<T>],
+    coordinates: &[FPCoordinate],
+    keys: &GraphMlKeys,
+) -> Result<(), GraphFileError> {
+    assert!(
+        coordinates.is_empty() || coordinates.len() == number_of_nodes,
+        "{} coordinates for {number_of_nodes} nodes",
+        coordinates.len()
+    );
+    let mut out = TextWriter::create(filename)?;
+    out.write_line(r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    out.write_line(r#"<!-- written by toolbox-rs -->"#)?;
+    out.write_line(r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+    let weight = keys.weight.as_deref().unwrap_or("weight");
+    out.write_line(format_args!(
+        r#"  <key id="weight" for="edge" attr.name="{}" attr.type="long"/>"#,
+        escaped(weight)
+    ))?;
+    if !coordinates.is_empty() {
+        for (id, name) in [("lat", &keys.lat), ("lon", &keys.lon)] {
+            out.write_line(format_args!(
+                r#"  <key id="{id}" for="node" attr.name="{}" attr.type="double"/>"#,
+                escaped(name)
+            ))?;
+        }
+    }
+    out.write_line(r#"  <graph id="G" edgedefault="directed">"#)?;
+    for node in 0..number_of_nodes {
+        match coordinates.get(node) {
+            Some(coordinate) => {
+                let (lon, lat) = coordinate.to_lon_lat_pair();
+                out.write_line(format_args!(
+                    r#"    <node id="n{node}"><data key="lat">{lat}</data><data key="lon">{lon}</data></node>"#
+                ))?;
+            }
+            None => out.write_line(format_args!(r#"    <node id="n{node}"/>"#))?,
+        }
+    }
+    for edge in edges {
+        assert!(
+            edge.source < number_of_nodes && edge.target < number_of_nodes,
+            "arc ({}, {}) leaves the {number_of_nodes} nodes",
+            edge.source,
+            edge.target
+        );
+        out.write_line(format_args!(
+            r#"    <edge source="n{}" target="n{}"><data key="weight">{}</data></edge>"#,
+            edge.source, edge.target, edge.data
+        ))?;
+    }
+    out.write_line("  </graph>")?;
+    out.write_line("</graphml>")?;
+    out.finish()
+}
+
+fn escaped(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The element whose `data` children are being read.
+enum Element {
+    None,
+    Key(String),
+    Node(usize),
+    Edge,
+}
+
+struct PendingEdge {
+    source: String,
+    target: String,
+    both_ways: bool,
+    weight: Option<String>,
+    line: usize,
+}
+
+/// Ends an element, handing back the key and text of a `data` or `default`
+/// that ends here.
+fn close(
+    name: &str,
+    element: &mut Element,
+    value: &mut Option<(Option<String>, String)>,
+) -> Option<(Option<String>, String)> {
+    match name {
+        "data" | "default" => value.take(),
+        "key" | "node" | "edge" => {
+            *element = Element::None;
+            None
+        }
+        _ => None,
+    }
+}
+
+enum Token<'a> {
+    Open {
+        name: &'a str,
+        attributes: Vec<(&'a str, String)>,
+        empty: bool,
+        line: usize,
+    },
+    Close {
+        name: &'a str,
+    },
+    Text(String),
+}
+
+/// The tags and texts of an XML document, which is as much of XML as GraphML
+/// needs: no DTD is read, and entities are the predefined ones and numbers.
+struct Tokenizer<'a> {
+    text: &'a str,
+    at: usize,
+    /// the line the tokenizer is on
+    line: usize,
+}
+
+type TokenResult<'a> = Result<Option<Token<'a>>, (usize, String)>;
+
+impl<'a> Tokenizer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            at: 0,
+            line: 1,
+        }
+    }
+
+    /// Moves past the next `until`, returning what was skipped.
+    fn skip_past(&mut self, until: &str) -> Result<&'a str, (usize, String)> {
+        let rest = &self.text[self.at..];
+        let Some(end) = rest.find(until) else {
+            return Err((self.line, format!("`{until}` is missing")));
+        };
+        let skipped = &rest[..end];
+        self.line += skipped.matches('\n').count();
+        self.at += end + until.len();
+        Ok(skipped)
+    }
+
+    fn next_token(&mut self) -> TokenResult<'a> {
+        loop {
+            let rest = &self.text[self.at..];
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                let line = self.line;
+                let text = &rest[..end];
+                self.line += text.matches('\n').count();
+                self.at += end;
+                return unescaped(text)
+                    .map(|text| Some(Token::Text(text)))
+                    .map_err(|reason| (line, reason));
+            }
+            if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.at += "<![CDATA[".len();
+                return Ok(Some(Token::Text(self.skip_past("]]>")?.to_string())));
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">")?;
+            } else if rest.starts_with("</") {
+                self.at += 2;
+                let name = self.skip_past(">")?.trim();
+                return Ok(Some(Token::Close { name }));
+            } else {
+                return self.open_tag().map(Some);
+            }
+        }
+    }
+
+    fn open_tag(&mut self) -> Result<Token<'a>, (usize, String)> {
+        let line = self.line;
+        let bytes = self.text.as_bytes();
+        // the end of the tag is the first `>` outside of a quoted value
+        let mut end = self.at + 1;
+        let mut quote = None;
+        while end < bytes.len() {
+            match (quote, bytes[end]) {
+                (None, b'>') => break,
+                (None, q @ (b'"' | b'\'')) => quote = Some(q),
+                (Some(q), c) if c == q => quote = None,
+                _ => {}
+            }
+            end += 1;
+        }
+        if end == bytes.len() {
+            return Err((line, "a tag that is not closed".to_string()));
+        }
+        let mut tag = &self.text[self.at + 1..end];
+        self.line += tag.matches('\n').count();
+        self.at = end + 1;
+
+        let empty = tag.ends_with('/');
+        if empty {
+            tag = &tag[..tag.len() - 1];
+        }
+        let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+        let name = &tag[..name_end];
+        // a namespace prefix does not change what an element is
+        let name = name.rsplit(':').next().unwrap_or(name);
+        let mut rest = tag[name_end..].trim_start();
+        let mut attributes = Vec::new();
+        while !rest.is_empty() {
+            let malformed = || (line, format!("a malformed attribute in <{name}>"));
+            let (key, after) = rest.split_once('=').ok_or_else(malformed)?;
+            let after = after.trim_start();
+            let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'');
+            let quote = quote.ok_or_else(malformed)?;
+            let value_end = after[1..].find(quote).ok_or_else(malformed)? + 1;
+            let value = unescaped(&after[1..value_end]).map_err(|reason| (line, reason))?;
+            attributes.push((key.trim(), value));
+            rest = after[value_end + 1..].trim_start();
+        }
+        Ok(Token::Open {
+            name,
+            attributes,
+            empty,
+            line,
+        })
+    }
+}
+
+/// Replaces the entities of a text.
+fn unescaped(text: &str) -> Result<String, String> {
+    if !text.contains('&') {
+        return Ok(text.to_string());
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or_else(|| "an entity without `;`".to_string())?
+            + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        out.push(c.ok_or_else(|| format!("an unknown entity &{entity};"))?);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn file_with(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    /// what NetworkX writes for a small undirected graph
+    const NETWORKX: &str = r#"<?xml version='1.0' encoding='utf-8'?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <key id="d2" for="edge" attr.name="length" attr.type="double" />
+  <key id="d1" for="node" attr.name="y" attr.type="double" />
+  <key id="d0" for="node" attr.name="x" attr.type="double" />
+  <graph edgedefault="undirected">
+    <!-- two nodes and a way between -->
+    <node id="Alexander&amp;platz">
+      <data key="d0">13.4132</data>
+      <data key="d1">52.5219</data>
+    </node>
+    <node id="Tor">
+      <data key="d0">13.3777</data>
+      <data key="d1"><![CDATA[52.5163]]></data>
+    </node>
+    <node id="Zoo"><data key="d0">13.3326</data><data key="d1">52.5079</data></node>
+    <edge source="Alexander&amp;platz" target="Tor">
+      <data key="d2">2.9</data>
+    </edge>
+    <edge source="Tor" target="Zoo" directed="true"><data key="d2">3.4</data></edge>
+    <edge source="Zoo" target="Zoo"><data key="d2">0.1</data></edge>
+  </graph>
+</graphml>
+"#;
+
+    fn networkx_keys() -> GraphMlKeys {
+        GraphMlKeys {
+            weight: Some("length".into()),
+            scale: 1000.,
+            lat: "y".into(),
+            lon: "x".into(),
+        }
+    }
+
+    #[test]
+    fn what_networkx_writes_reads() {
+        let file = file_with(NETWORKX);
+        let graph =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), &networkx_keys()).unwrap();
+        assert_eq!(graph.ids, vec!["Alexander&platz", "Tor", "Zoo"]);
+        assert_eq!(
+            graph.edges,
+            vec![
+                InputEdge::new(0, 1, 2900),
+                InputEdge::new(1, 0, 2900),
+                InputEdge::new(1, 2, 3400)
+            ]
+        );
+        assert_eq!(
+            graph.coordinates[1],
+            FPCoordinate::new(52_516_300, 13_377_700)
+        );
+    }
+
+    #[test]
+    fn missing_attributes_and_nodes_are_reported() {
+        let file = file_with(NETWORKX);
+        let path = file.path().to_str().unwrap();
+        let error = try_read_graph::<usize>(path, &GraphMlKeys::default()).unwrap_err();
+        assert!(error.to_string().contains("no edge attribute weight"));
+
+        let unweighted = GraphMlKeys {
+            weight: None,
+            ..networkx_keys()
+        };
+        let graph = try_read_graph::<usize>(path, &unweighted).unwrap();
+        assert!(graph.edges.iter().all(|edge| edge.data == 1));
+
+        let file = file_with(
+            &NETWORKX.replace(r#"target="Zoo" directed"#, r#"target="Ku'damm" directed"#),
+        );
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), &networkx_keys()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("line 20: node Ku'damm is not listed")
+        );
+
+        let file = file_with("<graphml><graph><node id='a'/></graph></graphml>");
+        let graph = try_read_graph::<usize>(
+            file.path().to_str().unwrap(),
+            &GraphMlKeys {
+                weight: None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(graph.coordinates.is_empty());
+
+        let file = file_with("<graphml><graph><node id='a' x=\"1/>");
+        assert!(try_read_graph::<usize>(file.path().to_str().unwrap(), &networkx_keys()).is_err());
+
+        let file = file_with("<graphml><graph><node id='a'/>");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), &networkx_keys()).unwrap_err();
+        assert!(error.to_string().contains("<graph> is not closed"));
+
+        let file = file_with("<graphml>\n<graph><node id='a'></edge></graph></graphml>");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), &networkx_keys()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("line 2: </edge> closes <node> of line 2")
+        );
+
+        let file = file_with("<graphml><graph/></graphml></graphml>");
+        let error =
+            try_read_graph::<usize>(file.path().to_str().unwrap(), &networkx_keys()).unwrap_err();
+        assert!(error.to_string().contains("</graphml> closes nothing"));
+    }
+
+    #[test]
+    fn defaults_stand_in_for_missing_data() {
+        let file = file_with(
+            r#"<graphml>
+  <key id="w" for="edge" attr.name="weight"><default>5</default></key>
+  <graph edgedefault="directed">
+    <node id="a"/><node id="b"/>
+    <edge source="a" target="b"/>
+    <edge source="b" target="a"><data key="w">7</data></edge>
+  </graph>
+</graphml>"#,
+        );
+        let graph = try_read_graph::<usize>(file.path().to_str().unwrap(), &GraphMlKeys::default())
+            .unwrap();
+        assert_eq!(
+            graph.edges,
+            vec![InputEdge::new(0, 1, 5), InputEdge::new(1, 0, 7)]
+        );
+    }
+
+    #[test]
+    fn written_graph_reads_back() {
+        let edges = vec![
+            InputEdge::new(0, 1, 3_usize),
+            InputEdge::new(1, 2, 4),
+            InputEdge::new(2, 0, 5),
+        ];
+        let coordinates = vec![
+            FPCoordinate::new(52_520_008, 13_404_954),
+            FPCoordinate::new(-33_868_820, 151_209_296),
+            FPCoordinate::new(1, -1),
+        ];
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let keys = GraphMlKeys {
+            weight: Some("travel <time>".into()),
+            ..Default::default()
+        };
+        write_graph(path, 3, &edges, &coordinates, &keys).unwrap();
+
+        let graph = try_read_graph::<usize>(path, &keys).unwrap();
+        assert_eq!(graph.edges, edges);
+        assert_eq!(graph.coordinates, coordinates);
+        assert_eq!(graph.ids, vec!["n0", "n1", "n2"]);
+    }
+}
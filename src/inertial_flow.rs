@@ -11,8 +11,8 @@ use crate::{
     edge::{InputEdge, TrivialEdge},
     geometry::FPCoordinate,
     graph::NodeID,
-    max_flow::{MaxFlow, ResidualEdgeData},
-    renumbering_table::RenumberingTable,
+    max_flow::{Capacity, MaxFlow, ResidualEdgeData},
+    min_cut::min_cut_with_upper_bound,
 };
 
 const ROTATED_COMPARATORS: [fn(i32, i32) -> i32; 4] = [
@@ -29,18 +29,22 @@ pub enum FlowError {
     EmptyGraph,
     /// the graph has more nodes than the node ids of a cell can address
     GraphTooLarge,
+    /// an arc carries more than the capacities of the max-flow solver hold
+    CapacityTooLarge(u32),
     String(String),
 }
 
+/// A cut of a cell and the flow across it, in the capacities of the max-flow
+/// solver that found it.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Flow {
-    pub flow: i32,
+pub struct Flow<C = i32> {
+    pub flow: C,
     pub balance: f64,
     pub left_ids: Vec<usize>,
     pub right_ids: Vec<usize>,
 }
 
-pub fn flow_cmp(a: &Flow, b: &Flow) -> std::cmp::Ordering {
+pub fn flow_cmp<C: Ord>(a: &Flow<C>, b: &Flow<C>) -> std::cmp::Ordering {
     if a.flow == b.flow {
         // note that a and b are inverted here on purpose:
         // balance is at most 0.5 and the closer the value the more balanced the partitions
@@ -67,10 +71,76 @@ pub fn sub_step(
     balance_factor: f64,
     upper_bound: Arc<AtomicI32>,
 ) -> Result<Flow, FlowError> {
+    weighted_sub_step::<Dinic>(
+        input_edges,
+        node_id_list,
+        coordinates,
+        axis,
+        &SubStepOptions::new(balance_factor, upper_bound),
+    )
+}
+
+/// What a cut of [`weighted_sub_step`] is measured by and held to. The same
+/// options serve all four axes of a cell. The bound is one of the capacity
+/// type `C` of the max-flow solver.
+#[derive(Clone, Debug)]
+pub struct SubStepOptions<'a, C: Capacity = i32> {
+    /// weight of each node, indexed by node id; one apiece if `None`
+    pub node_weights: Option<&'a [u32]>,
+    /// capacity of each arc, in the order of the edges; one apiece if `None`
+    pub capacities: Option<&'a [u32]>,
+    /// balance factor, i.e. how much of the weight gets contracted at each end
+    pub balance_factor: f64,
+    /// a global upperbound to the best inertial flow cut
+    pub upper_bound: Arc<C::Atomic>,
+}
+
+impl<C: Capacity> SubStepOptions<'_, C> {
+    /// Options of a cell whose nodes weigh and whose arcs carry one apiece.
+    pub fn new(balance_factor: f64, upper_bound: Arc<C::Atomic>) -> Self {
+        Self {
+            node_weights: None,
+            capacities: None,
+            balance_factor,
+            upper_bound,
+        }
+    }
+}
+
+/// Computes the inertial flow cut for a given orientation and balance, where
+/// nodes weigh and arcs carry what they are given rather than one apiece.
+///
+/// The sources and sinks are the nodes at either end of the axis that make up
+/// `balance_factor` of the weight of the cell, and the balance of the result
+/// is the share of the weight on its lighter side. A cut costs what its arcs
+/// carry, so a cut through a few minor roads is preferred to one through a
+/// motorway.
+///
+/// The cut is computed by the max-flow solver `M`, e.g. [`Dinic`], or
+/// [`crate::push_relabel::PushRelabel`] for large and dense cells. The flow
+/// is summed up in its capacities, such that a solver of `i64` capacities
+/// takes arcs that carry up to [`u32::MAX`] each.
+///
+/// The weights, capacities and bounds are given by `options`, the other
+/// arguments are those of [`sub_step`].
+pub fn weighted_sub_step<M: MaxFlow>(
+    input_edges: &[TrivialEdge],
+    node_id_list: &[usize],
+    coordinates: &[FPCoordinate],
+    axis: usize,
+    options: &SubStepOptions<M::Capacity>,
+) -> Result<Flow<M::Capacity>, FlowError> {
+    let SubStepOptions {
+        node_weights,
+        capacities,
+        balance_factor,
+        ref upper_bound,
+    } = *options;
     debug_assert!(axis < 4);
     debug_assert!(balance_factor > 0.);
     debug_assert!(balance_factor < 0.5);
     debug_assert!(coordinates.len() > 2);
+    debug_assert!(capacities.is_none_or(|capacities| capacities.len() == input_edges.len()));
 
     if axis >= 4 {
         return Err(FlowError::AxisOutOfBounds);
@@ -86,6 +156,7 @@ pub fn sub_step(
         // all of them at once.
         return Err(FlowError::GraphTooLarge);
     }
+    let weight_of = |id: usize| node_weights.map_or(1, |weights| u64::from(weights[id]));
 
     let comparator = ROTATED_COMPARATORS[axis];
     debug!("[{axis}] sorting cooefficient: {comparator:?}");
@@ -104,104 +175,94 @@ pub fn sub_step(
         .collect_vec();
     node_id_list.sort_unstable();
 
-    let size_of_contraction = max(1, (node_id_list.len() as f64 * balance_factor) as usize);
-    let sources = &node_id_list[0..size_of_contraction];
-    let targets = &node_id_list[node_id_list.len() - size_of_contraction..];
+    // Each end takes in the nodes up to its share of the weight, but at least
+    // one and never more than half of them, so that the ends stay apart even
+    // when a single node outweighs the rest.
+    let total_weight: u64 = node_id_list
+        .iter()
+        .map(|(_, id)| weight_of(*id as usize))
+        .sum();
+    let share = total_weight as f64 * balance_factor;
+    let contraction_size = |ids: &mut dyn Iterator<Item = &(i32, u32)>| {
+        let mut weight = 0;
+        let count = ids
+            .take_while(|(_, id)| {
+                weight += weight_of(*id as usize);
+                weight as f64 <= share
+            })
+            .count();
+        count.clamp(1, max(1, node_id_list.len() / 2))
+    };
+    let source_count = contraction_size(&mut node_id_list.iter());
+    let target_count = contraction_size(&mut node_id_list.iter().rev());
+    let sources = &node_id_list[0..source_count];
+    let targets = &node_id_list[node_id_list.len() - target_count..];
 
     debug_assert!(!sources.is_empty());
     debug_assert!(!targets.is_empty());
 
-    debug!("[{axis}] renumbering of inertial flow graph");
-    // let mut renumbering_table = vec![usize::MAX; coordinates.len()];
-    let mut renumbering_table =
-        RenumberingTable::new_with_size_hint(coordinates.len(), node_id_list.len());
-    // nodes in the in the graph have to be numbered consecutively.
-    // the mapping is input id -> dinic id
-
-    for (_projection, s) in sources {
-        renumbering_table.set(*s as usize, 0);
-    }
-    for (_projection, t) in targets {
-        renumbering_table.set(*t as usize, 1);
-    }
-
     // each thread holds their own copy of the edge set
-    let mut edges = input_edges
+    let edges = input_edges
         .iter()
-        .map(|edge| -> InputEdge<ResidualEdgeData> {
-            InputEdge::<ResidualEdgeData> {
+        .enumerate()
+        .map(|(index, edge)| {
+            let capacity = match capacities {
+                None => num::one(),
+                Some(capacities) => num::cast(capacities[index])
+                    .ok_or(FlowError::CapacityTooLarge(capacities[index]))?,
+            };
+            Ok(InputEdge {
                 source: edge.source as NodeID,
                 target: edge.target as NodeID,
-                data: ResidualEdgeData::new(1),
-            }
+                data: ResidualEdgeData::new(capacity),
+            })
         })
-        .collect_vec();
-    let mut current_id = 2;
-
-    for e in &mut edges {
-        // nodes in the in the graph have to be numbered consecutively
-        if !renumbering_table.contains_key(e.source) {
-            renumbering_table.set(e.source, current_id);
-            current_id += 1;
-        }
-        if !renumbering_table.contains_key(e.target) {
-            renumbering_table.set(e.target, current_id);
-            current_id += 1;
-        }
-        e.source = renumbering_table.get(e.source) as NodeID;
-        e.target = renumbering_table.get(e.target) as NodeID;
-    }
-    debug!("[{axis}] instantiating min-cut solver, epsilon 0.25");
-
-    // remove eigenloops especially from contracted regions
-    let edge_count_before = edges.len();
-    edges.retain(|edge| edge.source != edge.target);
-    debug!(
-        "[{axis}] eigenloop removal - edge count before {edge_count_before}, after {}",
-        edges.len()
-    );
-    edges.shrink_to_fit();
-
-    debug!("[{axis}] instantiating min-cut solver, epsilon {balance_factor}");
-    let mut max_flow_solver = Dinic::from_edge_list(edges, 0, 1);
-    debug!("[{axis}] instantiated min-cut solver");
-    max_flow_solver.run_with_upper_bound(upper_bound);
-
-    let max_flow = max_flow_solver.max_flow();
-
-    if let Err(message) = max_flow {
-        // Error is returned in case the search is aborted early
-        return Err(FlowError::String(message));
-    }
-    let flow = max_flow.expect("max flow computation did not run");
-
+        .collect::<Result<Vec<_>, _>>()?;
+
+    debug!("[{axis}] computing min cut, epsilon {balance_factor}");
+    let to_ids = |nodes: &[(i32, u32)]| nodes.iter().map(|(_, id)| *id as NodeID).collect_vec();
+    let cut = min_cut_with_upper_bound::<M>(
+        &edges,
+        &to_ids(sources),
+        &to_ids(targets),
+        upper_bound.clone(),
+    )
+    // Error is returned in case the search is aborted early
+    .map_err(|error| FlowError::String(error.to_string()))?;
+    let flow = cut.flow;
     debug!("[{axis}] computed max flow: {flow}");
-    let intermediate_assignment = max_flow_solver
-        .assignment(0)
-        .expect("max flow computation did not run");
+
+    // nodes that no edge of the cell is incident to are not part of the flow
+    // graph and thus on neither side of the cut, unless they are terminals
+    let mut incident_ids = edges
+        .iter()
+        .flat_map(|edge| [edge.source, edge.target])
+        .chain(to_ids(targets))
+        .collect_vec();
+    incident_ids.sort_unstable();
+    incident_ids.dedup();
 
     // TODO: don't copy, but partition in place
     let mut left_ids = Vec::new();
     let mut right_ids = Vec::new();
-    // nodes that no edge of the cell is incident to are not part of the flow
-    // graph and thus have no assignment
     let mut isolated_ids = Vec::new();
     for (_projection, id) in node_id_list {
         let id = id as usize;
-        if !renumbering_table.contains_key(id) {
-            isolated_ids.push(id);
-        } else if intermediate_assignment[renumbering_table.get(id)] {
+        if cut.source_side.binary_search(&id).is_ok() {
             left_ids.push(id);
-        } else {
+        } else if incident_ids.binary_search(&id).is_ok() {
             right_ids.push(id);
+        } else {
+            isolated_ids.push(id);
         }
     }
     // Isolated nodes are cut off either way, so they join the smaller side.
     // Dropping them here would take them out of the recursion and leave them
     // behind on a level of their own.
+    let weight_of_ids = |ids: &[usize]| ids.iter().map(|id| weight_of(*id)).sum::<u64>();
     if !isolated_ids.is_empty() {
         debug!("[{axis}] {} isolated nodes in cell", isolated_ids.len());
-        if left_ids.len() <= right_ids.len() {
+        if weight_of_ids(&left_ids) <= weight_of_ids(&right_ids) {
             left_ids.append(&mut isolated_ids);
         } else {
             right_ids.append(&mut isolated_ids);
@@ -211,8 +272,13 @@ pub fn sub_step(
     debug_assert!(!left_ids.is_empty());
     debug_assert!(!right_ids.is_empty());
 
-    let balance = std::cmp::min(left_ids.len(), right_ids.len()) as f64
-        / (left_ids.len() + right_ids.len()) as f64;
+    let (left_weight, right_weight) = (weight_of_ids(&left_ids), weight_of_ids(&right_ids));
+    // a cell of nothing but weightless nodes cannot be any better balanced
+    let balance = if left_weight + right_weight == 0 {
+        0.5
+    } else {
+        std::cmp::min(left_weight, right_weight) as f64 / (left_weight + right_weight) as f64
+    };
     debug!("[{axis}] balance: {balance}");
 
     Ok(Flow {
@@ -226,11 +292,18 @@ pub fn sub_step(
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
-    use std::sync::{Arc, atomic::AtomicI32};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicI32, AtomicI64},
+    };
 
     use crate::{
+        dinic::Dinic,
         geometry::FPCoordinate,
-        inertial_flow::{Flow, TrivialEdge, flow_cmp, sub_step},
+        inertial_flow::{
+            Flow, FlowError, SubStepOptions, TrivialEdge, flow_cmp, sub_step, weighted_sub_step,
+        },
+        push_relabel::PushRelabel,
     };
 
     static EDGES: [TrivialEdge; 14] = [
@@ -398,4 +471,134 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn unit_weights_cut_as_without_weights() {
+        let capacities = [1; 14];
+        let weights = [1; 6];
+        for axis in 0..4 {
+            let unweighted = sub_step(
+                &EDGES,
+                &NODE_ID_LIST,
+                &COORDINATES,
+                axis,
+                0.25,
+                Arc::new(AtomicI32::new(6)),
+            )
+            .expect("error should not happen");
+            let weighted = weighted_sub_step::<Dinic>(
+                &EDGES,
+                &NODE_ID_LIST,
+                &COORDINATES,
+                axis,
+                &SubStepOptions {
+                    node_weights: Some(&weights),
+                    capacities: Some(&capacities),
+                    ..SubStepOptions::new(0.25, Arc::new(AtomicI32::new(6)))
+                },
+            )
+            .expect("error should not happen");
+            assert_eq!(unweighted, weighted);
+        }
+    }
+
+    #[test]
+    fn capacities_steer_the_cut_off_the_bridge() {
+        // the bridge between the two triangles carries ten each way, so the
+        // cut goes through the triangle on the sink side instead
+        let mut capacities = [1; 14];
+        capacities[6] = 10;
+        capacities[7] = 10;
+        let result = weighted_sub_step::<Dinic>(
+            &EDGES,
+            &NODE_ID_LIST,
+            &COORDINATES,
+            3,
+            &SubStepOptions {
+                capacities: Some(&capacities),
+                ..SubStepOptions::new(0.25, Arc::new(AtomicI32::new(100)))
+            },
+        )
+        .expect("error should not happen");
+        assert_eq!(result.flow, 2);
+        assert_eq!(result.left_ids, vec![4, 5, 2, 3]);
+        assert_eq!(result.right_ids, vec![0, 1]);
+    }
+
+    #[test]
+    fn capacities_beyond_i32_need_a_wider_solver() {
+        // every arc carries more than an i32 holds
+        let capacities = [3_000_000_000; 14];
+        let result = weighted_sub_step::<Dinic<i64>>(
+            &EDGES,
+            &NODE_ID_LIST,
+            &COORDINATES,
+            3,
+            &SubStepOptions {
+                capacities: Some(&capacities),
+                ..SubStepOptions::new(0.25, Arc::new(AtomicI64::new(i64::MAX)))
+            },
+        )
+        .expect("error should not happen");
+        assert_eq!(result.flow, 3_000_000_000);
+
+        let result = weighted_sub_step::<Dinic>(
+            &EDGES,
+            &NODE_ID_LIST,
+            &COORDINATES,
+            3,
+            &SubStepOptions {
+                capacities: Some(&capacities),
+                ..SubStepOptions::new(0.25, Arc::new(AtomicI32::new(i32::MAX)))
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(FlowError::CapacityTooLarge(3_000_000_000))
+        ));
+    }
+
+    #[test]
+    fn balance_is_measured_by_weight() {
+        // two heavy nodes at the sink end pull the sources further in
+        let weights = [10, 10, 1, 1, 1, 1];
+        let result = weighted_sub_step::<Dinic>(
+            &EDGES,
+            &NODE_ID_LIST,
+            &COORDINATES,
+            3,
+            &SubStepOptions {
+                node_weights: Some(&weights),
+                ..SubStepOptions::new(0.25, Arc::new(AtomicI32::new(6)))
+            },
+        )
+        .expect("error should not happen");
+        assert_eq!(result.flow, 2);
+        assert_eq!(result.right_ids, vec![0, 1]);
+        assert_eq!(result.balance, 4. / 24.);
+    }
+
+    #[test]
+    fn push_relabel_cuts_as_dinic() {
+        for axis in 0..4 {
+            let dinic = sub_step(
+                &EDGES,
+                &NODE_ID_LIST,
+                &COORDINATES,
+                axis,
+                0.25,
+                Arc::new(AtomicI32::new(6)),
+            )
+            .expect("error should not happen");
+            let push_relabel = weighted_sub_step::<PushRelabel>(
+                &EDGES,
+                &NODE_ID_LIST,
+                &COORDINATES,
+                axis,
+                &SubStepOptions::new(0.25, Arc::new(AtomicI32::new(6))),
+            )
+            .expect("error should not happen");
+            assert_eq!(dinic, push_relabel);
+        }
+    }
 }
@@ -1,15 +1,40 @@
+//! Reading and writing the files of graphs, coordinates and what else the
+//! tools of this crate hand each other.
+//!
+//! # Reading in parallel
+//!
+//! The text formats, DIMACS, METIS and DDSG, can be read whole and parsed on
+//! all threads. The lines after the header are cut into chunks at line
+//! breaks, a few per thread for the work to even out, and each chunk knows the
+//! number of its first line. A line is parsed by the same function whether
+//! the file is read in chunks or one line after the other, and a line that
+//! does not parse ends its chunk with the error that names it. Whatever
+//! depends on the lines before a chunk, such as the number of the node a line
+//! is about, is counted from the start of the chunk and checked once the
+//! chunks are put back together in the order of the file. Hence a file is
+//! parsed once, even if it does not read, and the error is the one the
+//! sequential reader reports.
+
 use std::{
-    fs::File,
-    io::{self, BufRead, BufReader, Read, Write},
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    ops::Deref,
     path::Path,
+    str::FromStr,
 };
 
 use itertools::Itertools;
-use rkyv::rancor;
+use rayon::prelude::*;
+use rkyv::{rancor, util::AlignedVec};
+use thiserror::Error;
 
 use crate::{
+    container::{self, Container, ContainerWriter, section},
     edge::{InputEdge, StoredEdge, TrivialEdge},
+    geometry::FPCoordinate,
     graph::NodeID,
+    partition_id::{PartitionID, WidePartitionID},
 };
 
 // The output is wrapped in a Result to allow matching on errors
@@ -22,10 +47,415 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-pub fn read_graph_into_trivial_edges(filename: &str) -> Vec<TrivialEdge> {
+/// Why a graph or coordinates file in one of the text formats, DIMACS, METIS
+/// or DDSG, could not be read or written.
+#[derive(Debug, Error)]
+pub enum GraphFileError {
+    #[error("{file}: {source}")]
+    Io {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{file}, line {line}: {reason}")]
+    Parse {
+        file: String,
+        line: usize,
+        reason: String,
+    },
+    #[error("{file}: the {what} is missing")]
+    Missing { file: String, what: &'static str },
+    #[error("{file}: the header announces {expected} {what}, but there are {found}")]
+    Count {
+        file: String,
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// The name of a text file, which the errors about its lines carry.
+pub(crate) struct TextFile {
+    file: String,
+}
+
+impl TextFile {
+    pub(crate) fn new(filename: &str) -> Self {
+        Self {
+            file: filename.to_string(),
+        }
+    }
+
+    pub(crate) fn error(&self, line: usize, reason: impl Into<String>) -> GraphFileError {
+        GraphFileError::Parse {
+            file: self.file.clone(),
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn missing(&self, what: &'static str) -> GraphFileError {
+        GraphFileError::Missing {
+            file: self.file.clone(),
+            what,
+        }
+    }
+
+    /// Parses the token of a line, `what` naming it if it does not parse.
+    pub(crate) fn parse<T>(&self, line: usize, token: &str, what: &str) -> Result<T, GraphFileError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        token
+            .parse::<T>()
+            .map_err(|error| self.error(line, format!("`{token}` is not a {what}: {error}")))
+    }
+
+    /// Checks that there are as many of `what` as the header announced.
+    pub(crate) fn count(
+        &self,
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    ) -> Result<(), GraphFileError> {
+        if expected == found {
+            return Ok(());
+        }
+        Err(GraphFileError::Count {
+            file: self.file.clone(),
+            what,
+            expected,
+            found,
+        })
+    }
+
+    fn io_error(&self, source: io::Error) -> GraphFileError {
+        GraphFileError::Io {
+            file: self.file.clone(),
+            source,
+        }
+    }
+}
+
+/// Lines of a text file handed out one at a time with their numbers, counted
+/// from one, which is how the header of a file is read no matter how the
+/// lines after it are.
+pub(crate) trait LineReader: Deref<Target = TextFile> {
+    /// The next line and its number, or `None` past the last one.
+    fn next_line(&mut self) -> Result<Option<(usize, String)>, GraphFileError>;
+}
+
+/// What a chunk of lines was parsed into, up to the line that did not parse
+/// if there was one.
+pub(crate) struct Chunk<S> {
+    pub(crate) state: S,
+    pub(crate) result: Result<(), GraphFileError>,
+}
+
+impl<S> Chunk<S> {
+    /// What the chunks were parsed into, or the error of the first that did
+    /// not parse.
+    pub(crate) fn states(chunks: Vec<Self>) -> Result<Vec<S>, GraphFileError> {
+        chunks
+            .into_iter()
+            .map(|chunk| chunk.result.map(|()| chunk.state))
+            .collect()
+    }
+}
+
+/// The lines of a text file read one after the other.
+pub(crate) struct NumberedLines {
+    file: TextFile,
+    lines: io::Lines<BufReader<File>>,
+    number: usize,
+}
+
+impl NumberedLines {
+    pub(crate) fn open(filename: &str) -> Result<Self, GraphFileError> {
+        let file = TextFile::new(filename);
+        let lines = read_lines(filename).map_err(|source| file.io_error(source))?;
+        Ok(Self {
+            file,
+            lines,
+            number: 0,
+        })
+    }
+
+    /// Parses the lines that are left into a single chunk.
+    pub(crate) fn parse_rest<S, F>(&mut self, parse: F) -> Result<Vec<Chunk<S>>, GraphFileError>
+    where
+        S: Default,
+        F: Fn(&TextFile, usize, &str, &mut S) -> Result<(), GraphFileError>,
+    {
+        let mut state = S::default();
+        let mut result = Ok(());
+        while let Some((number, line)) = self.next_line()? {
+            result = parse(&self.file, number, &line, &mut state);
+            if result.is_err() {
+                break;
+            }
+        }
+        Ok(vec![Chunk { state, result }])
+    }
+}
+
+impl Deref for NumberedLines {
+    type Target = TextFile;
+
+    fn deref(&self) -> &TextFile {
+        &self.file
+    }
+}
+
+impl LineReader for NumberedLines {
+    fn next_line(&mut self) -> Result<Option<(usize, String)>, GraphFileError> {
+        match self.lines.next() {
+            None => Ok(None),
+            Some(Err(source)) => Err(self.file.io_error(source)),
+            Some(Ok(line)) => {
+                self.number += 1;
+                Ok(Some((self.number, line)))
+            }
+        }
+    }
+}
+
+/// A text file read whole, whose lines after the header are parsed on all
+/// threads as the [module](self#reading-in-parallel) describes.
+pub(crate) struct TextLines {
+    file: TextFile,
+    text: String,
+    offset: usize,
+    number: usize,
+}
+
+impl TextLines {
+    pub(crate) fn read(filename: &str) -> Result<Self, GraphFileError> {
+        let file = TextFile::new(filename);
+        let text = fs::read_to_string(filename).map_err(|source| file.io_error(source))?;
+        Ok(Self {
+            file,
+            text,
+            offset: 0,
+            number: 0,
+        })
+    }
+
+    /// Parses the lines that are left on all threads, into one state per
+    /// chunk in the order of the text.
+    pub(crate) fn parse_in_chunks<S, F>(&self, parse: F) -> Vec<Chunk<S>>
+    where
+        S: Default + Send,
+        F: Fn(&TextFile, usize, &str, &mut S) -> Result<(), GraphFileError> + Sync,
+    {
+        line_chunks(
+            &self.text[self.offset..],
+            self.number + 1,
+            4 * rayon::current_num_threads(),
+        )
+        .into_par_iter()
+        .map(|(first, chunk)| {
+            let mut state = S::default();
+            let result = chunk
+                .lines()
+                .enumerate()
+                .try_for_each(|(index, line)| parse(&self.file, first + index, line, &mut state));
+            Chunk { state, result }
+        })
+        .collect()
+    }
+}
+
+impl Deref for TextLines {
+    type Target = TextFile;
+
+    fn deref(&self) -> &TextFile {
+        &self.file
+    }
+}
+
+impl LineReader for TextLines {
+    fn next_line(&mut self) -> Result<Option<(usize, String)>, GraphFileError> {
+        let Some((line, rest)) = split_first_line(&self.text[self.offset..]) else {
+            return Ok(None);
+        };
+        let line = line.to_string();
+        self.offset = self.text.len() - rest.len();
+        self.number += 1;
+        Ok(Some((self.number, line)))
+    }
+}
+
+/// The coordinates of a chunk whose lines name their nodes, which have to
+/// follow each other.
+#[derive(Default)]
+pub(crate) struct NumberedCoordinates {
+    /// the line and node of the first coordinate of the chunk
+    first: Option<(usize, NodeID)>,
+    coordinates: Vec<FPCoordinate>,
+}
+
+impl NumberedCoordinates {
+    /// Adds the coordinate of a node, which has to be the one after the node
+    /// of the coordinate before.
+    pub(crate) fn push(
+        &mut self,
+        file: &TextFile,
+        line: usize,
+        node: NodeID,
+        coordinate: FPCoordinate,
+    ) -> Result<(), GraphFileError> {
+        let (_, first) = *self.first.get_or_insert((line, node));
+        let expected = first + self.coordinates.len();
+        if node != expected {
+            return Err(file.error(line, format!("expected node {expected}, found {node}")));
+        }
+        self.coordinates.push(coordinate);
+        Ok(())
+    }
+
+    /// The coordinates of all chunks, whose nodes have to follow each other
+    /// from `first_node` on.
+    pub(crate) fn join(
+        file: &TextFile,
+        first_node: NodeID,
+        chunks: Vec<Chunk<Self>>,
+    ) -> Result<Vec<FPCoordinate>, GraphFileError> {
+        let mut next = first_node;
+        let mut parts = Vec::with_capacity(chunks.len());
+        for Chunk { state, result } in chunks {
+            if let Some((line, node)) = state.first
+                && node != next
+            {
+                return Err(file.error(line, format!("expected node {next}, found {node}")));
+            }
+            result?;
+            next += state.coordinates.len();
+            parts.push(state.coordinates);
+        }
+        Ok(concat_chunks(parts))
+    }
+}
+
+/// The parts of a list that came in chunks, as one list, reusing the only
+/// part if there is just one.
+pub(crate) fn concat_chunks<T>(mut parts: Vec<Vec<T>>) -> Vec<T> {
+    if parts.len() == 1 {
+        return parts.pop().unwrap();
+    }
+    let mut list = Vec::with_capacity(parts.iter().map(Vec::len).sum());
+    for part in parts {
+        list.extend(part);
+    }
+    list
+}
+
+/// An arc cost given as a decimal, as the tools of analysts write them,
+/// scaled and rounded to the whole number the graphs of this crate hold.
+pub(crate) fn scaled_weight(value: f64, scale: f64) -> Result<usize, String> {
+    let scaled = (value * scale).round();
+    if !scaled.is_finite() || scaled < 0. || scaled > usize::MAX as f64 {
+        return Err(format!("weight {value} scaled by {scale} is not a cost"));
+    }
+    Ok(scaled as usize)
+}
+
+/// A buffered text file being written, whose errors say which file it was.
+pub(crate) struct TextWriter {
+    file: TextFile,
+    writer: BufWriter<File>,
+}
+
+impl TextWriter {
+    pub(crate) fn create(filename: &str) -> Result<Self, GraphFileError> {
+        let file = TextFile::new(filename);
+        let writer = File::create(filename).map_err(|source| file.io_error(source))?;
+        Ok(Self {
+            file,
+            writer: BufWriter::new(writer),
+        })
+    }
+
+    pub(crate) fn write_line(&mut self, line: impl Display) -> Result<(), GraphFileError> {
+        writeln!(self.writer, "{line}").map_err(|source| self.file.io_error(source))
+    }
+
+    /// Flushes what is still buffered, which dropping the writer would do
+    /// without a word if it failed.
+    pub(crate) fn finish(mut self) -> Result<(), GraphFileError> {
+        self.writer
+            .flush()
+            .map_err(|source| self.file.io_error(source))
+    }
+}
+
+/// The first line of a text without its line break, and the text after it,
+/// or `None` if the text is empty.
+fn split_first_line(text: &str) -> Option<(&str, &str)> {
+    if text.is_empty() {
+        return None;
+    }
+    let (line, rest) = text.split_once('\n').unwrap_or((text, ""));
+    Some((line.strip_suffix('\r').unwrap_or(line), rest))
+}
+
+/// Splits a text into about `parts` chunks that end at line breaks, each
+/// with the number of its first line, the text's counting from `first_line`.
+fn line_chunks(text: &str, first_line: usize, parts: usize) -> Vec<(usize, &str)> {
+    let size = text.len().div_ceil(parts.max(1)).max(1);
+    let mut chunks = Vec::with_capacity(parts);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest.as_bytes()[size.min(rest.len())..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(rest.len(), |position| size + position + 1);
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    let line_counts: Vec<usize> = chunks
+        .par_iter()
+        .map(|chunk| chunk.bytes().filter(|&byte| byte == b'\n').count())
+        .collect();
+    chunks
+        .into_iter()
+        .zip(line_counts)
+        .scan(first_line, |first, (chunk, lines)| {
+            let start = *first;
+            *first += lines;
+            Some((start, chunk))
+        })
+        .collect()
+}
+
+/// The bytes of an rkyv archive in a file, aligned as rkyv wants them.
+///
+/// The file is either the bare archive, as written before there were
+/// containers, or a container of a single section, which is then checked and
+/// unwrapped. Either way the readers below need not know which it is.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read, or is a container that is corrupt, of
+/// another version or of more than one section.
+fn read_archive(filename: &str) -> AlignedVec {
     let mut reader = BufReader::new(File::open(filename).unwrap());
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf).unwrap();
+    if container::is_container(&buf) {
+        return Container::from_bytes(filename, buf)
+            .and_then(|container| container.only_payload())
+            .unwrap_or_else(|error| panic!("{error}"));
+    }
+    let mut archive = AlignedVec::with_capacity(buf.len());
+    archive.extend_from_slice(&buf);
+    archive
+}
+
+pub fn read_graph_into_trivial_edges(filename: &str) -> Vec<TrivialEdge> {
+    let buf = read_archive(filename);
 
     let input_edges: Vec<InputEdge<usize>> =
         rkyv::from_bytes::<Vec<InputEdge<usize>>, rancor::Error>(&buf).unwrap();
@@ -45,9 +475,7 @@ where
     <Vec<T> as rkyv::Archive>::Archived: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rancor::Error>>
         + rkyv::Deserialize<Vec<T>, rancor::Strategy<rkyv::de::Pool, rancor::Error>>,
 {
-    let mut reader = BufReader::new(File::open(filename).unwrap());
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf).unwrap();
+    let buf = read_archive(filename);
     rkyv::from_bytes::<Vec<T>, rancor::Error>(&buf).unwrap()
 }
 
@@ -63,9 +491,7 @@ where
     <T as rkyv::Archive>::Archived: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rancor::Error>>
         + rkyv::Deserialize<T, rkyv::api::high::HighDeserializer<rancor::Error>>,
 {
-    let mut reader = BufReader::new(File::open(filename).unwrap());
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf).unwrap();
+    let buf = read_archive(filename);
     rkyv::from_bytes::<T, rancor::Error>(&buf).unwrap()
 }
 
@@ -109,7 +535,7 @@ where
         >,
 {
     let bytes = rkyv::to_bytes::<rancor::Error>(value).unwrap();
-    let mut file = std::io::BufWriter::new(File::create(filename).unwrap());
+    let mut file = BufWriter::new(File::create(filename).unwrap());
     file.write_all(&bytes).unwrap();
     file.flush().unwrap();
 }
@@ -132,6 +558,60 @@ where
     write_to_file(filename, values);
 }
 
+/// Reads the partition ids that chipper writes, in either of the two layouts
+/// [`write_partition_ids`] chooses from.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read, or holds neither a bare archive of
+/// [`PartitionID`]s nor a container with a section of [`WidePartitionID`]s.
+pub fn read_partition_ids(filename: &str) -> Vec<WidePartitionID> {
+    let mut bytes = Vec::new();
+    File::open(filename)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .unwrap_or_else(|error| panic!("{filename}: {error}"));
+    if container::is_container(&bytes) {
+        return Container::from_bytes(filename, bytes)
+            .and_then(|container| container.section(section::PARTITION))
+            .unwrap_or_else(|error| panic!("{error}"));
+    }
+    let mut archive: AlignedVec = AlignedVec::with_capacity(bytes.len());
+    archive.extend_from_slice(&bytes);
+    rkyv::from_bytes::<Vec<PartitionID>, rancor::Error>(&archive)
+        .unwrap_or_else(|error| panic!("{filename}: {error}"))
+        .into_iter()
+        .map(WidePartitionID::from)
+        .collect()
+}
+
+/// Writes partition ids for [`read_partition_ids`] to read back.
+///
+/// Ids no deeper than level 31 fit into a [`PartitionID`], and are written as
+/// the bare archive of them that chipper wrote before it could cut any deeper,
+/// so that what reads those files keeps reading them. Deeper ids are written
+/// as a container whose `partition` section is tagged as holding
+/// [`WidePartitionID`]s, which a reader of the narrow layout cannot mistake
+/// for what it expects.
+///
+/// # Panics
+///
+/// Panics if the file cannot be written.
+pub fn write_partition_ids(filename: &str, ids: &[WidePartitionID]) {
+    let narrow: Option<Vec<PartitionID>> = ids
+        .iter()
+        .map(|id| u32::try_from(id.0).ok().map(PartitionID))
+        .collect();
+    if let Some(narrow) = narrow {
+        write_vec_to_file(filename, &narrow);
+        return;
+    }
+    let mut writer = ContainerWriter::new(ids.len(), 0);
+    writer
+        .add_section(section::PARTITION, &ids.to_vec())
+        .and_then(|()| writer.write(filename))
+        .unwrap_or_else(|error| panic!("{error}"));
+}
+
 #[cfg(test)]
 mod tests {
     /// What was written has to read back as what it was, or an instance
@@ -162,6 +642,21 @@ mod tests {
         assert_eq!(read, written);
     }
 
+    #[test]
+    fn a_container_of_one_section_reads_like_a_bare_archive() {
+        use crate::container::{ContainerWriter, section};
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let written = vec![InputEdge::new(0, 1, 7_usize), InputEdge::new(1, 0, 9)];
+        let mut writer = ContainerWriter::new(2, written.len());
+        writer.add_section(section::EDGES, &written).unwrap();
+        writer.write(path).unwrap();
+
+        assert_eq!(read_vec_from_file::<InputEdge<usize>>(path), written);
+        assert_eq!(read_edges_from_file(path)[1], InputEdge::new(1, 0, 9_u32));
+        assert_eq!(read_graph_into_trivial_edges(path).len(), 2);
+    }
+
     use super::*;
     use std::io::Write;
     use tempfile::NamedTempFile;
@@ -314,4 +809,76 @@ mod tests {
         // Attempt to read the invalid data
         let _: Vec<i32> = read_vec_from_file(file.path().to_str().unwrap());
     }
+
+    #[test]
+    fn chunks_end_at_line_breaks() {
+        use super::{LineReader, TextLines, line_chunks, split_first_line};
+
+        let text: String = (0..1000).map(|i| format!("{i}\n")).collect();
+        for parts in [1, 3, 64, 5000] {
+            let chunks = line_chunks(&text, 1, parts);
+            assert_eq!(
+                chunks.iter().map(|(_, chunk)| *chunk).collect::<String>(),
+                text
+            );
+            assert!(chunks.iter().all(|(_, chunk)| chunk.ends_with('\n')));
+            // line i + 1 holds i
+            assert!(
+                chunks
+                    .iter()
+                    .all(|(first, chunk)| chunk.starts_with(&format!("{}\n", first - 1)))
+            );
+        }
+        let unterminated = line_chunks("a\nb", 3, 8);
+        assert_eq!(unterminated, vec![(3, "a\n"), (4, "b")]);
+        assert!(line_chunks("", 1, 8).is_empty());
+
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "header\n{text}x\n").unwrap();
+        let mut lines = TextLines::read(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(lines.next_line().unwrap(), Some((1, "header".to_string())));
+        let chunks = lines.parse_in_chunks(|file, number, line, numbers: &mut Vec<usize>| {
+            numbers.push(file.parse(number, line, "number")?);
+            Ok(())
+        });
+        let (last, parsed) = chunks.split_last().unwrap();
+        assert!(matches!(
+            last.result,
+            Err(GraphFileError::Parse { line: 1002, .. })
+        ));
+        assert!(parsed.iter().all(|chunk| chunk.result.is_ok()));
+        let parsed = parsed.iter().chain([last]).flat_map(|chunk| &chunk.state);
+        assert!(parsed.copied().eq(0..1000));
+
+        assert_eq!(split_first_line("p sp\r\na"), Some(("p sp", "a")));
+        assert_eq!(split_first_line("last"), Some(("last", "")));
+        assert_eq!(split_first_line(""), None);
+    }
+
+    #[test]
+    fn partition_ids_are_written_narrow_as_long_as_they_fit() {
+        use super::{read_partition_ids, write_partition_ids};
+        use crate::partition_id::{PartitionID, WidePartitionID};
+
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let shallow = vec![
+            WidePartitionID::root().left_child(),
+            WidePartitionID::new(u128::from(u32::MAX)),
+        ];
+        write_partition_ids(path, &shallow);
+        // what read the partitions of chipper before still reads them
+        assert_eq!(
+            read_vec_from_file::<PartitionID>(path),
+            vec![PartitionID(2), PartitionID(u32::MAX)]
+        );
+        assert_eq!(read_partition_ids(path), shallow);
+
+        let deep = vec![WidePartitionID::root(), WidePartitionID::new(1 << 100)];
+        write_partition_ids(path, &deep);
+        assert!(crate::container::is_container(
+            &std::fs::read(path).unwrap()
+        ));
+        assert_eq!(read_partition_ids(path), deep);
+    }
 }
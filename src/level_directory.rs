@@ -44,11 +44,34 @@
 //! ```
 use crate::graph::NodeID;
 use rkyv::{Archive, Deserialize, Serialize};
+use thiserror::Error;
 
 /// A cell of one level. Cells are numbered from zero per level, so an id only
 /// means something together with the level it belongs to.
 pub type CellId = u32;
 
+/// Why the cells of a number of levels do not make a hierarchy.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NestingError {
+    #[error("a hierarchy needs a level")]
+    NoLevels,
+    #[error("level {level} holds {found} nodes where the lowest holds {expected}")]
+    NodeCount {
+        level: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error(
+        "cell {cell} of level {level} lies in both cell {first} and cell {second} of the level above"
+    )]
+    NotNested {
+        level: usize,
+        cell: CellId,
+        first: CellId,
+        second: CellId,
+    },
+}
+
 /// The cells of a nested partition, level by level.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Archive, Serialize, Deserialize)]
 pub struct LevelDirectory {
@@ -71,6 +94,55 @@ impl LevelDirectory {
         directory
     }
 
+    /// Builds a directory from the cell of every node on every level, the
+    /// lowest level first, as a partitioner that knows nothing of a hierarchy
+    /// hands them out.
+    ///
+    /// The cell ids of a level are kept as they are given. A level that skips
+    /// an id has a cell of nothing under it, which is harmless, and which lies
+    /// in cell 0 of the level above for want of anything better.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no level is given, if the levels do not cover the same nodes,
+    /// or if a cell has nodes in two cells of the level above it.
+    pub fn from_levels(levels: &[Vec<CellId>]) -> Result<Self, NestingError> {
+        let (base, above) = levels.split_first().ok_or(NestingError::NoLevels)?;
+        let mut parents = Vec::with_capacity(above.len());
+        for (level, pair) in levels.windows(2).enumerate() {
+            let (lower, upper) = (&pair[0], &pair[1]);
+            if upper.len() != base.len() {
+                return Err(NestingError::NodeCount {
+                    level: level + 1,
+                    expected: base.len(),
+                    found: upper.len(),
+                });
+            }
+            let cells = lower.iter().max().map_or(0, |cell| *cell as usize + 1);
+            let mut parent_of = vec![CellId::MAX; cells];
+            for (&cell, &parent) in lower.iter().zip(upper) {
+                let known = &mut parent_of[cell as usize];
+                if *known == CellId::MAX {
+                    *known = parent;
+                } else if *known != parent {
+                    return Err(NestingError::NotNested {
+                        level,
+                        cell,
+                        first: *known,
+                        second: parent,
+                    });
+                }
+            }
+            for parent in &mut parent_of {
+                if *parent == CellId::MAX {
+                    *parent = 0;
+                }
+            }
+            parents.push(parent_of);
+        }
+        Ok(Self::new(base.clone(), parents))
+    }
+
     /// Whether every cell lies in one that the level above it actually has.
     fn is_consistent(&self) -> bool {
         let mut below = self.base.iter().max().map_or(0, |cell| *cell as usize + 1);
@@ -506,4 +578,47 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn the_cells_of_every_level_build_the_hierarchy() {
+        let levels = vec![vec![0, 0, 1, 2, 2], vec![0, 0, 0, 1, 1], vec![0; 5]];
+        let built = LevelDirectory::from_levels(&levels).expect("the levels nest");
+        assert_eq!(built, directory());
+    }
+
+    #[test]
+    fn a_skipped_cell_id_is_a_cell_of_nothing() {
+        let levels = vec![vec![0, 0, 3, 3], vec![1, 1, 0, 0]];
+        let built = LevelDirectory::from_levels(&levels).expect("the levels nest");
+        assert_eq!(built.cells_on_level(0), 4);
+        for (node, (&lower, &upper)) in levels[0].iter().zip(&levels[1]).enumerate() {
+            assert_eq!(built.cell_of(node, 0), lower);
+            assert_eq!(built.cell_of(node, 1), upper);
+        }
+    }
+
+    #[test]
+    fn levels_that_do_not_nest_are_refused() {
+        assert_eq!(
+            LevelDirectory::from_levels(&[]),
+            Err(NestingError::NoLevels)
+        );
+        assert_eq!(
+            LevelDirectory::from_levels(&[vec![0, 1], vec![0]]),
+            Err(NestingError::NodeCount {
+                level: 1,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            LevelDirectory::from_levels(&[vec![0, 0, 1], vec![0, 0, 0], vec![0, 1, 1]]),
+            Err(NestingError::NotNested {
+                level: 1,
+                cell: 0,
+                first: 0,
+                second: 1
+            })
+        );
+    }
 }
@@ -2,6 +2,7 @@ pub mod addressable_binary_heap;
 pub mod alpha_shape;
 pub mod as_bytes;
 pub mod assembly;
+pub mod attributes;
 pub mod bfs;
 pub mod bidirectional_dijkstra;
 pub mod bidirectional_mld_query;
@@ -13,8 +14,11 @@ pub mod border_levels;
 pub mod bounding_box;
 pub mod cell;
 pub mod complete_graph;
+pub mod compressed_graph;
+pub mod container;
 pub mod convex_hull;
 pub mod count_min_sketch;
+pub mod csv;
 pub mod customization;
 pub mod cycle_check;
 pub mod ddsg;
@@ -26,12 +30,17 @@ pub mod dynamic_graph;
 pub mod edge;
 pub mod edmonds_karp;
 pub mod enumerative_source_coding;
+pub mod external_sort;
 pub mod fast_hash_trait;
 pub mod fenwick;
 pub mod fibonacci_hash;
 pub mod ford_fulkerson;
+pub mod geojson_io;
 pub mod geometry;
+pub mod gomory_hu;
 pub mod graph;
+pub mod graph_transform;
+pub mod graphml;
 pub mod great_circle;
 pub mod grid_graph;
 pub mod heap_stats;
@@ -44,6 +53,7 @@ pub mod level_directory;
 pub mod linked_list;
 pub mod loser_tree;
 pub mod lru;
+pub mod mapped_graph;
 pub mod math;
 pub mod max_flow;
 pub mod medium_size_hash_set;
@@ -53,19 +63,26 @@ pub mod merge_entry;
 pub mod merge_tree;
 pub mod metis;
 pub mod metric;
+pub mod min_cost_flow;
+pub mod min_cut;
 pub mod mld_query;
 pub mod mvt;
 pub mod node_ordering;
 pub mod one_iterator;
 pub mod one_to_many_dijkstra;
+pub mod osm_pbf;
 pub mod packed_partition;
+pub mod partition_file;
 pub mod partition_id;
+pub mod partition_quality;
 pub mod path_based_scc;
 pub mod polyline;
 pub mod prim_complete_graph;
+pub mod push_relabel;
 pub mod r_tree;
 pub mod rdx_sort;
 pub mod renumbering_table;
+pub mod repartition;
 pub mod run_iterator;
 pub mod single_linked_list;
 pub mod space_filling_curve;
@@ -79,6 +96,7 @@ pub mod unidirectional_dijkstra;
 pub mod union_find;
 pub mod vector_tile;
 pub mod wgs84;
+pub mod zlib;
 
 #[macro_export]
 macro_rules! invoke_macro_for_types {
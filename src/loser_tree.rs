@@ -19,7 +19,17 @@ impl<T: Clone + Ord + PartialOrd> LoserTree<T> {
         let mut losers = Vec::with_capacity(size - 1);
         let mut leaves = Vec::with_capacity(size);
 
-        losers.resize(size - 1, 0);
+        // Each internal node starts out naming a leaf of its own subtree, the
+        // leftmost one. Naming leaf zero instead lets a subtree that was never
+        // played in claim a leaf of another, which then wins a match on both
+        // sides and hides the real winner of a leaf pushed next to it.
+        let internal_nodes = size - 1;
+        losers.extend((0..internal_nodes).map(|mut node| {
+            while node < internal_nodes {
+                node = 2 * node + 1;
+            }
+            node - internal_nodes
+        }));
         leaves.resize(size, None);
         Self {
             losers,
@@ -232,4 +242,19 @@ mod tests {
         let tree = LoserTree::<i32>::with_capacity(3);
         assert_eq!(tree.capacity(), 4); // nächste Zweierpotenz
     }
+
+    #[test]
+    fn leaves_past_the_last_pushed_one_hide_nothing() {
+        // five lists in a tree of eight leaves: the three leaves that are
+        // never filled must not hide the fifth list behind the first
+        let mut lists: Vec<_> = (0..5)
+            .map(|list| (0..7).map(|i| i * 5 + list).collect::<Vec<_>>().into_iter())
+            .collect();
+        let merged: Vec<_> = crate::k_way_merge_iterator::KWayMergeIterator::new(
+            &mut lists,
+            LoserTree::with_capacity(5),
+        )
+        .collect();
+        assert_eq!(merged, (0..35).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,387 @@
+//! An adjacency array that is mapped from a file rather than read.
+//!
+//! Reading a continent's graph means reading every byte of it into a list of
+//! arcs, sorting that and copying it into the adjacency array, which takes
+//! minutes and holds the graph twice at its peak. The file written here is
+//! the adjacency array itself, laid out as [`StaticGraph`] holds it:
+//!
+//! ```text
+//! magic           8 bytes  "TBXGRAPH"
+//! version         u32
+//! cost width      u32      bytes per arc cost, four
+//! nodes           u64
+//! edges           u64
+//! node array      u64 per node and one more, where its arcs start
+//! edge array      target and cost, u32 each, per arc
+//! ```
+//!
+//! All numbers are little endian. Opening the file maps it and checks its
+//! header and size, which takes no time however large it is, and the pages
+//! are read by the operating system as a search touches them. A second
+//! process that maps the same file shares them.
+//!
+//! [`StaticGraph`]: crate::static_graph::StaticGraph
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{
+//!     edge::InputEdge, graph::Graph, mapped_graph::{self, MappedGraph},
+//!     static_graph::StaticGraph,
+//! };
+//!
+//! let graph = StaticGraph::new(vec![InputEdge::new(0, 1, 3_u32), InputEdge::new(1, 2, 4)]);
+//! let file = tempfile::NamedTempFile::new().unwrap();
+//! let path = file.path().to_str().unwrap();
+//! mapped_graph::write_graph(path, &graph).unwrap();
+//!
+//! let mapped = MappedGraph::open(path).unwrap();
+//! assert_eq!(mapped.number_of_nodes(), 3);
+//! assert_eq!(*mapped.data(mapped.find_edge(1, 2).unwrap()), 4);
+//! ```
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+};
+
+use memmap2::Mmap;
+use thiserror::Error;
+
+use crate::graph::{EdgeArrayEntry, EdgeID, Graph, NodeID};
+
+/// The bytes every mapped graph starts with.
+pub const MAGIC: [u8; 8] = *b"TBXGRAPH";
+
+/// The version of the layout written, and the only one read.
+pub const VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 32;
+
+/// Why a graph could not be mapped or written.
+#[derive(Debug, Error)]
+pub enum MappedGraphError {
+    #[error("{file}: {source}")]
+    Io {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{file}: not a mapped graph")]
+    NotAGraph { file: String },
+    #[error("{file}: format version {found}, but version {supported} is the one supported")]
+    Version {
+        file: String,
+        found: u32,
+        supported: u32,
+    },
+    #[error("{file}: {reason}")]
+    Malformed { file: String, reason: String },
+}
+
+/// Writes a graph for [`MappedGraph::open`] to map.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+pub fn write_graph<G: Graph<u32>>(filename: &str, graph: &G) -> Result<(), MappedGraphError> {
+    let io_error = |source| MappedGraphError::Io {
+        file: filename.to_string(),
+        source,
+    };
+    let mut file = BufWriter::new(File::create(filename).map_err(io_error)?);
+    write_header(&mut file, graph.number_of_nodes(), graph.number_of_edges()).map_err(io_error)?;
+
+    for node in graph.node_range() {
+        file.write_all(&(graph.begin_edges(node) as u64).to_le_bytes())
+            .map_err(io_error)?;
+    }
+    file.write_all(&(graph.number_of_edges() as u64).to_le_bytes())
+        .map_err(io_error)?;
+
+    for node in graph.node_range() {
+        for edge in graph.edge_range(node) {
+            let target = u32::try_from(graph.target(edge)).expect("the graph is too large to hold");
+            file.write_all(&target.to_le_bytes()).map_err(io_error)?;
+            file.write_all(&graph.data(edge).to_le_bytes())
+                .map_err(io_error)?;
+        }
+    }
+    file.flush().map_err(io_error)
+}
+
+/// Writes the header of a graph of the given size, which the node array and
+/// then the edge array follow.
+pub(crate) fn write_header(
+    out: &mut impl Write,
+    number_of_nodes: usize,
+    number_of_edges: usize,
+) -> io::Result<()> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&(size_of::<u32>() as u32).to_le_bytes());
+    header.extend_from_slice(&(number_of_nodes as u64).to_le_bytes());
+    header.extend_from_slice(&(number_of_edges as u64).to_le_bytes());
+    out.write_all(&header)
+}
+
+/// A read-only adjacency array over the bytes of a mapped file.
+///
+/// It answers like the [`StaticGraph`] it was written from, and anything that
+/// searches a [`Graph<u32>`], the customization included, runs on it as it
+/// is. The arcs cannot be changed, and [`Graph::data_mut`] panics.
+///
+/// [`StaticGraph`]: crate::static_graph::StaticGraph
+pub struct MappedGraph {
+    map: Mmap,
+    number_of_nodes: usize,
+    number_of_edges: usize,
+}
+
+impl MappedGraph {
+    /// Maps a graph written by [`write_graph`].
+    ///
+    /// Only the header and the size of the file are checked, which is what
+    /// makes this instant. A node array that is out of order panics when it
+    /// is walked rather than reading out of bounds, and
+    /// [`MappedGraph::check_integrity`] checks all of it up front.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be mapped, is not a mapped graph, is of
+    /// another version or is not as long as its header says.
+    pub fn open(filename: &str) -> Result<Self, MappedGraphError> {
+        let file_name = filename.to_string();
+        let malformed = |reason: String| MappedGraphError::Malformed {
+            file: filename.to_string(),
+            reason,
+        };
+        if cfg!(target_endian = "big") {
+            return Err(malformed(
+                "a mapped graph is little endian, and so has to be the machine".to_string(),
+            ));
+        }
+        let file = File::open(filename).map_err(|source| MappedGraphError::Io {
+            file: file_name.clone(),
+            source,
+        })?;
+        // Safety: the map is only ever read, and the file is one the tools
+        // wrote and do not change while it is mapped. Another process that
+        // changes it underneath is outside of what this can guard against.
+        let map = unsafe { Mmap::map(&file) }.map_err(|source| MappedGraphError::Io {
+            file: file_name.clone(),
+            source,
+        })?;
+
+        if map.len() < HEADER_SIZE || map[..8] != MAGIC {
+            return Err(MappedGraphError::NotAGraph { file: file_name });
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(map[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(map[at..at + 8].try_into().unwrap());
+        let version = u32_at(8);
+        if version != VERSION {
+            return Err(MappedGraphError::Version {
+                file: file_name,
+                found: version,
+                supported: VERSION,
+            });
+        }
+        let width = u32_at(12);
+        if width as usize != size_of::<u32>() {
+            return Err(malformed(format!(
+                "the arcs cost {width} bytes each, not {}",
+                size_of::<u32>()
+            )));
+        }
+        let number_of_nodes = usize::try_from(u64_at(16)).expect("the graph is too large to hold");
+        let number_of_edges = usize::try_from(u64_at(24)).expect("the graph is too large to hold");
+        let expected = number_of_nodes
+            .checked_add(1)
+            .and_then(|entries| entries.checked_mul(size_of::<u64>()))
+            .and_then(|nodes| {
+                number_of_edges
+                    .checked_mul(size_of::<EdgeArrayEntry<u32>>())
+                    .and_then(|edges| nodes.checked_add(edges))
+            })
+            .and_then(|body| body.checked_add(HEADER_SIZE));
+        if expected != Some(map.len()) {
+            return Err(malformed(format!(
+                "the header announces {number_of_nodes} nodes and {number_of_edges} arcs, which do not fit {} bytes",
+                map.len()
+            )));
+        }
+
+        Ok(Self {
+            map,
+            number_of_nodes,
+            number_of_edges,
+        })
+    }
+
+    /// Checks what [`StaticGraph::check_integrity`] checks, in time O(V+E).
+    ///
+    /// [`StaticGraph::check_integrity`]: crate::static_graph::StaticGraph::check_integrity
+    pub fn check_integrity(&self) -> bool {
+        let nodes = self.node_array();
+        nodes.first() == Some(&0)
+            && nodes.last() == Some(&(self.number_of_edges as u64))
+            && nodes.windows(2).all(|pair| pair[0] <= pair[1])
+            && self.node_range().all(|node| {
+                let targets = &self.edge_array()[self.edge_range(node)];
+                targets
+                    .iter()
+                    .all(|edge| (edge.target as usize) < self.number_of_nodes)
+                    && targets
+                        .windows(2)
+                        .all(|pair| pair[0].target <= pair[1].target)
+            })
+    }
+
+    fn node_array(&self) -> &[u64] {
+        let bytes = &self.map[HEADER_SIZE..HEADER_SIZE + (self.number_of_nodes + 1) * 8];
+        // Safety: the map starts on a page and the node array eight bytes
+        // into it, so it is aligned, its length was checked on opening, and
+        // every bit pattern is a valid u64 of a little endian machine.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast(), self.number_of_nodes + 1) }
+    }
+
+    fn edge_array(&self) -> &[EdgeArrayEntry<u32>] {
+        let start = HEADER_SIZE + (self.number_of_nodes + 1) * 8;
+        let bytes = &self.map[start..];
+        // Safety: the edge array starts eight bytes aligned, an entry is two
+        // u32 in that order as it is `repr(C)`, its length was checked on
+        // opening, and every bit pattern is a valid entry.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast(), self.number_of_edges) }
+    }
+}
+
+impl Graph<u32> for MappedGraph {
+    fn node_range(&self) -> Range<NodeID> {
+        0..self.number_of_nodes
+    }
+
+    fn edge_range(&self, n: NodeID) -> Range<EdgeID> {
+        self.begin_edges(n)..self.end_edges(n)
+    }
+
+    fn number_of_nodes(&self) -> usize {
+        self.number_of_nodes
+    }
+
+    fn number_of_edges(&self) -> usize {
+        self.number_of_edges
+    }
+
+    fn begin_edges(&self, n: NodeID) -> EdgeID {
+        self.node_array()[n] as EdgeID
+    }
+
+    fn end_edges(&self, n: NodeID) -> EdgeID {
+        self.node_array()[n + 1] as EdgeID
+    }
+
+    fn out_degree(&self, n: NodeID) -> usize {
+        self.end_edges(n) - self.begin_edges(n)
+    }
+
+    fn target(&self, e: EdgeID) -> NodeID {
+        self.edge_array()[e].target as NodeID
+    }
+
+    fn data(&self, e: EdgeID) -> &u32 {
+        &self.edge_array()[e].data
+    }
+
+    fn data_mut(&mut self, _: EdgeID) -> &mut u32 {
+        panic!("a mapped graph is read-only")
+    }
+
+    fn find_edge(&self, s: NodeID, t: NodeID) -> Option<EdgeID> {
+        if s >= self.number_of_nodes {
+            return None;
+        }
+        self.edge_range(s).find(|&edge| self.target(edge) == t)
+    }
+
+    fn find_edge_unchecked(&self, s: NodeID, t: NodeID) -> EdgeID {
+        self.find_edge(s, t).unwrap_or(EdgeID::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        customization::Customization, grid_graph::grid, mld_query::MldQuery,
+        static_graph::StaticGraph, unidirectional_dijkstra::UnidirectionalDijkstra,
+    };
+    use tempfile::NamedTempFile;
+
+    fn mapped(graph: &StaticGraph<u32>) -> (NamedTempFile, MappedGraph) {
+        let file = NamedTempFile::new().unwrap();
+        write_graph(file.path().to_str().unwrap(), graph).unwrap();
+        let mapped = MappedGraph::open(file.path().to_str().unwrap()).unwrap();
+        (file, mapped)
+    }
+
+    #[test]
+    fn a_mapped_graph_answers_like_the_one_written() {
+        let (graph, _) = grid(8, false);
+        let (_file, mapped) = mapped(&graph);
+        assert!(mapped.check_integrity());
+        assert_eq!(mapped.number_of_nodes(), graph.number_of_nodes());
+        assert_eq!(mapped.number_of_edges(), graph.number_of_edges());
+        for node in graph.node_range() {
+            assert_eq!(mapped.edge_range(node), graph.edge_range(node));
+            for edge in graph.edge_range(node) {
+                assert_eq!(mapped.target(edge), graph.target(edge));
+                assert_eq!(mapped.data(edge), graph.data(edge));
+            }
+        }
+        assert_eq!(mapped.find_edge(0, 1), graph.find_edge(0, 1));
+        assert_eq!(mapped.find_edge(64, 1), None);
+    }
+
+    #[test]
+    fn searches_and_the_customization_run_on_a_mapped_graph() {
+        let (graph, directory) = grid(8, true);
+        let (_file, mapped) = mapped(&graph);
+        let plain = UnidirectionalDijkstra::new().run(&graph, 0, 63);
+        assert_eq!(UnidirectionalDijkstra::new().run(&mapped, 0, 63), plain);
+
+        let customization = Customization::new(mapped, directory);
+        let mut query = MldQuery::new();
+        query.run(&customization, 0, &[63]);
+        assert_eq!(query.distance(63), plain);
+    }
+
+    #[test]
+    fn files_that_are_not_mapped_graphs_are_reported() {
+        let (graph, _) = grid(4, true);
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        write_graph(path, &graph).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+
+        std::fs::write(path, &bytes[..bytes.len() - 4]).unwrap();
+        assert!(matches!(
+            MappedGraph::open(path),
+            Err(MappedGraphError::Malformed { .. })
+        ));
+
+        let mut newer = bytes.clone();
+        newer[8..12].copy_from_slice(&2_u32.to_le_bytes());
+        std::fs::write(path, &newer).unwrap();
+        assert!(matches!(
+            MappedGraph::open(path),
+            Err(MappedGraphError::Version { found: 2, .. })
+        ));
+
+        std::fs::write(path, b"not a graph at all, not even close").unwrap();
+        assert!(matches!(
+            MappedGraph::open(path),
+            Err(MappedGraphError::NotAGraph { .. })
+        ));
+    }
+}
@@ -1,19 +1,64 @@
-use std::sync::{Arc, atomic::AtomicI32};
+use std::{
+    fmt::{Debug, Display},
+    iter::Sum,
+    sync::{
+        Arc,
+        atomic::{AtomicI32, AtomicI64, AtomicU64, Ordering},
+    },
+};
 
 use crate::{
     edge::{EdgeWithData, InputEdge},
-    graph::NodeID,
+    graph::{EdgeArrayEntry, NodeID},
+    static_graph::StaticGraph,
 };
 use bitvec::vec::BitVec;
+use core::cmp::max;
 use log::debug;
+use num::{PrimInt, traits::NumAssign};
+
+/// The integer type that the capacities of a max-flow problem are given in.
+///
+/// Capacities of 32 bits are what a graph of unit arcs needs, but the arcs of
+/// a contracted coarse graph carry the sum of what they stand for, which
+/// overflows them quickly. Each type comes with the atomic that an upper bound
+/// to the flow is shared in between the computations of several threads.
+pub trait Capacity: PrimInt + NumAssign + Sum + Default + Debug + Display + Send + Sync {
+    type Atomic: Send + Sync;
+
+    /// Reads the upper bound.
+    fn load(bound: &Self::Atomic) -> Self;
+    /// Lowers the upper bound to `value`, if that is below it.
+    fn fetch_min(bound: &Self::Atomic, value: Self);
+}
+
+macro_rules! capacity {
+    ($capacity:ty, $atomic:ty) => {
+        impl Capacity for $capacity {
+            type Atomic = $atomic;
+
+            fn load(bound: &Self::Atomic) -> Self {
+                bound.load(Ordering::Relaxed)
+            }
+
+            fn fetch_min(bound: &Self::Atomic, value: Self) {
+                bound.fetch_min(value, Ordering::Relaxed);
+            }
+        }
+    };
+}
+
+capacity!(i32, AtomicI32);
+capacity!(i64, AtomicI64);
+capacity!(u64, AtomicU64);
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ResidualEdgeData {
-    pub capacity: i32,
+pub struct ResidualEdgeData<C: Capacity = i32> {
+    pub capacity: C,
 }
 
-impl ResidualEdgeData {
-    pub fn new(capacity: i32) -> ResidualEdgeData {
+impl<C: Capacity> ResidualEdgeData<C> {
+    pub fn new(capacity: C) -> ResidualEdgeData<C> {
         ResidualEdgeData { capacity }
     }
 }
@@ -21,20 +66,133 @@ impl ResidualEdgeData {
 /// An arc of a residual graph that caches the capacity of its reverse arc. The
 /// BFS of a max-flow computation checks the reverse capacity of every arc it
 /// relaxes and looking that arc up dominated its run time. Note that caching is
-/// free of charge, as the padding of the adjacency array entry is used up.
+/// free of charge for 32-bit capacities, as the padding of the adjacency array
+/// entry is used up. Wider capacities widen the entry either way.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ResidualArcData {
-    pub capacity: i32,
-    pub reverse_capacity: i32,
+pub struct ResidualArcData<C: Capacity = i32> {
+    pub capacity: C,
+    pub reverse_capacity: C,
+}
+
+/// Builds the residual graph of an edge list, i.e. each arc together with a
+/// reverse arc of zero capacity, sorted by target within each adjacency block.
+/// Parallel arcs are merged into one that carries their accumulated capacity.
+pub fn residual_graph<C: Capacity>(
+    edge_list: Vec<InputEdge<ResidualEdgeData<C>>>,
+) -> StaticGraph<ResidualArcData<C>> {
+    debug_assert!(!edge_list.is_empty());
+
+    // The residual graph holds a reverse arc of zero capacity for each input
+    // arc. Instead of materializing those and then sorting 2|E| entries, the
+    // adjacency array is built directly by a counting sort in O(V + E).
+    let number_of_nodes = 1 + edge_list
+        .iter()
+        .map(|edge| max(edge.source, edge.target))
+        .max()
+        .expect("edge list is empty");
+    debug!("counting degrees of {number_of_nodes} nodes");
+
+    // count the residual degree of each node in node_array[node + 1], then
+    // turn the counts into the offsets of the adjacency blocks
+    let mut node_array = vec![0_usize; number_of_nodes + 1];
+    for edge in &edge_list {
+        node_array[edge.source + 1] += 1;
+        node_array[edge.target + 1] += 1;
+    }
+    for i in 1..node_array.len() {
+        node_array[i] += node_array[i - 1];
+    }
+
+    debug!("scattering {} arcs", 2 * edge_list.len());
+    // Scatter the arcs into their blocks. node_array[u] serves as the write
+    // cursor of node u and thus ends up pointing at the end of u's block.
+    // Each input arc contributes its capacity to the forward arc and to the
+    // cached reverse capacity of the reverse arc, which is why the cache
+    // comes for free.
+    let mut edge_array = vec![
+        EdgeArrayEntry {
+            target: 0,
+            data: ResidualArcData::default()
+        };
+        2 * edge_list.len()
+    ];
+    for edge in &edge_list {
+        let forward = node_array[edge.source];
+        node_array[edge.source] += 1;
+        edge_array[forward] = EdgeArrayEntry {
+            target: u32::try_from(edge.target).expect("the graph is too large to hold"),
+            data: ResidualArcData {
+                capacity: edge.data.capacity,
+                reverse_capacity: C::zero(),
+            },
+        };
+
+        let reverse = node_array[edge.target];
+        node_array[edge.target] += 1;
+        edge_array[reverse] = EdgeArrayEntry {
+            target: u32::try_from(edge.source).expect("the graph is too large to hold"),
+            data: ResidualArcData {
+                capacity: C::zero(),
+                reverse_capacity: edge.data.capacity,
+            },
+        };
+    }
+    drop(edge_list);
+
+    // each cursor now points at the end of its block, which is the begin of
+    // the next one. Shifting by one restores the adjacency array.
+    node_array.rotate_right(1);
+    node_array[0] = 0;
+
+    debug!("merging parallel arcs");
+    // sort each adjacency block by target and merge parallel arcs into a
+    // single one that carries the accumulated capacity. Note that this is
+    // fine, as we are looking to compute a node partition. Blocks are short,
+    // hence sorting them is cheap.
+    let mut write = 0;
+    let mut begin = 0;
+    for node in 0..number_of_nodes {
+        let end = node_array[node + 1];
+        edge_array[begin..end].sort_unstable_by_key(|entry| entry.target);
+
+        let block_begin = write;
+        for read in begin..end {
+            if write > block_begin && edge_array[write - 1].target == edge_array[read].target {
+                let merged = edge_array[read].data;
+                edge_array[write - 1].data.capacity += merged.capacity;
+                edge_array[write - 1].data.reverse_capacity += merged.reverse_capacity;
+            } else {
+                edge_array[write] = edge_array[read];
+                write += 1;
+            }
+        }
+        begin = end;
+        node_array[node + 1] = write;
+    }
+    edge_array.truncate(write);
+    edge_array.shrink_to_fit();
+    debug!("residual graph has {write} arcs");
+
+    // the DFS stores arc ids in parent_edge as u32, so a larger graph would
+    // silently index the wrong arc
+    assert!(write <= u32::MAX as usize, "arc ids have to fit into u32");
+    StaticGraph::from_adjacency_array(node_array, edge_array)
 }
 
 pub trait MaxFlow {
+    type Capacity: Capacity;
+
     fn run(&mut self);
-    fn run_with_upper_bound(&mut self, bound: Arc<AtomicI32>);
-    fn max_flow(&self) -> Result<i32, String>;
+    fn run_with_upper_bound(&mut self, bound: Arc<<Self::Capacity as Capacity>::Atomic>);
+    fn max_flow(&self) -> Result<Self::Capacity, String>;
+    /// The nodes that `source` reaches in the residual graph, i.e. the
+    /// smallest source side of a minimum cut.
     fn assignment(&self, source: NodeID) -> Result<BitVec, String>;
+    /// The nodes that reach `target` in the residual graph, i.e. the smallest
+    /// sink side of a minimum cut.
+    fn sink_assignment(&self, target: NodeID) -> Result<BitVec, String>;
     fn from_edge_list(
-        edges: Vec<InputEdge<ResidualEdgeData>>,
+        edges: Vec<InputEdge<ResidualEdgeData<Self::Capacity>>>,
         source: NodeID,
         sink: NodeID,
     ) -> Self;
@@ -42,14 +200,14 @@ pub trait MaxFlow {
         input_edges: &[E],
         source: NodeID,
         target: NodeID,
-        function: impl Fn(&E) -> ResidualEdgeData,
+        function: impl Fn(&E) -> ResidualEdgeData<Self::Capacity>,
     ) -> Self
     where
         Self: Sized,
     {
         debug_assert!(!input_edges.is_empty());
         debug!("instantiating max-flow solver");
-        let edge_list: Vec<InputEdge<ResidualEdgeData>> = input_edges
+        let edge_list: Vec<InputEdge<ResidualEdgeData<Self::Capacity>>> = input_edges
             .iter()
             .map(move |edge| InputEdge {
                 source: edge.source(),
@@ -62,3 +220,76 @@ pub trait MaxFlow {
         Self::from_edge_list(edge_list, source, target)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bitvec::{bits, prelude::Lsb0};
+
+    use crate::{
+        dinic::Dinic,
+        edge::InputEdge,
+        edmonds_karp::EdmondsKarp,
+        ford_fulkerson::FordFulkerson,
+        max_flow::{Capacity, MaxFlow, ResidualEdgeData},
+        push_relabel::PushRelabel,
+    };
+
+    /// Two routes that carry three billion units together, which is more than
+    /// an i32 holds.
+    fn wide_edges<C: Capacity>() -> Vec<InputEdge<ResidualEdgeData<C>>> {
+        let capacity = |value: u64| ResidualEdgeData::new(C::from(value).unwrap());
+        vec![
+            InputEdge::new(0, 1, capacity(2_000_000_000)),
+            InputEdge::new(0, 2, capacity(2_000_000_000)),
+            InputEdge::new(1, 3, capacity(1_500_000_000)),
+            InputEdge::new(2, 3, capacity(1_500_000_000)),
+            InputEdge::new(1, 2, capacity(700_000_000)),
+        ]
+    }
+
+    /// Runs a solver over [`wide_edges`], and once more under an upper bound
+    /// that it either lowers to the flow or leaves as it is.
+    fn carries_beyond_i32<M: MaxFlow>(lowers_bound: bool)
+    where
+        <M::Capacity as Capacity>::Atomic: From<M::Capacity>,
+    {
+        let three_billion: M::Capacity = num::cast(3_000_000_000_u64).unwrap();
+        let mut max_flow_solver = M::from_edge_list(wide_edges(), 0, 3);
+        max_flow_solver.run();
+        assert_eq!(max_flow_solver.max_flow(), Ok(three_billion));
+        assert_eq!(
+            max_flow_solver.assignment(0),
+            Ok(bits![1, 1, 1, 0].to_bitvec())
+        );
+        assert_eq!(
+            max_flow_solver.sink_assignment(3),
+            Ok(bits![0, 0, 0, 1].to_bitvec())
+        );
+
+        let unbounded = <M::Capacity as num::Bounded>::max_value();
+        let bound = Arc::new(<M::Capacity as Capacity>::Atomic::from(unbounded));
+        let mut max_flow_solver = M::from_edge_list(wide_edges(), 0, 3);
+        max_flow_solver.run_with_upper_bound(bound.clone());
+        assert_eq!(max_flow_solver.max_flow(), Ok(three_billion));
+        let expected = if lowers_bound {
+            three_billion
+        } else {
+            unbounded
+        };
+        assert_eq!(M::Capacity::load(&bound), expected);
+    }
+
+    #[test]
+    fn max_flow_beyond_i32() {
+        carries_beyond_i32::<Dinic<i64>>(true);
+        carries_beyond_i32::<Dinic<u64>>(true);
+        carries_beyond_i32::<EdmondsKarp<i64>>(false);
+        carries_beyond_i32::<EdmondsKarp<u64>>(false);
+        carries_beyond_i32::<FordFulkerson<i64>>(false);
+        carries_beyond_i32::<FordFulkerson<u64>>(false);
+        carries_beyond_i32::<PushRelabel<i64>>(true);
+        carries_beyond_i32::<PushRelabel<u64>>(true);
+    }
+}
@@ -1,7 +1,15 @@
 use itertools::Itertools;
 use log::info;
 
-use crate::{edge::InputEdge, geometry::FPCoordinate, graph::NodeID, io::read_lines};
+use crate::{
+    edge::InputEdge,
+    geometry::FPCoordinate,
+    graph::NodeID,
+    io::{
+        Chunk, GraphFileError, LineReader, NumberedLines, TextFile, TextLines, TextWriter,
+        concat_chunks, read_lines,
+    },
+};
 
 pub enum WeightType {
     Unit,
@@ -29,6 +37,13 @@ impl TryFrom<i32> for Direction {
     }
 }
 
+/// Reads the arcs of an unweighted METIS graph, numbered from zero and
+/// without loops.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read, a number does not parse or a node is out
+/// of range. [`try_read_graph`] reports those instead.
 pub fn read_graph<T: std::cmp::Eq + From<usize>>(
     filename: &str,
     _weight_type: WeightType,
@@ -71,6 +86,12 @@ pub fn read_graph<T: std::cmp::Eq + From<usize>>(
     edges
 }
 
+/// Reads one coordinate per line, longitude and latitude in units of 10⁻⁵
+/// degrees.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or a number does not parse.
 pub fn read_coordinates(filename: &str) -> Vec<FPCoordinate> {
     let mut coordinates = Vec::new();
     for line in read_lines(filename).expect("could not load coordinates file") {
@@ -85,6 +106,310 @@ pub fn read_coordinates(filename: &str) -> Vec<FPCoordinate> {
     coordinates
 }
 
+/// Whether a line is one METIS takes for a comment.
+fn is_comment(line: &str) -> bool {
+    line.starts_with('%')
+}
+
+/// Reads the arcs of an unweighted METIS graph like [`read_graph`], but
+/// reports what is wrong with the file rather than panicking on it.
+///
+/// The header `<nodes> <edges> [<format>]` is followed by one adjacency list
+/// per node, an empty line for a node without neighbours. Each undirected edge
+/// is listed at both of its ends, hence the lists have to hold twice as many
+/// entries as the header announces edges. Lines starting with `%` are
+/// comments.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, if the header is missing or announces
+/// weights, if a number does not parse or a node is out of range, or if the
+/// number of lists or their entries is not the announced one.
+pub fn try_read_graph<T: std::cmp::Eq + From<usize>>(
+    filename: &str,
+    _weight_type: WeightType,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut lines = NumberedLines::open(filename)?;
+    let (number_of_nodes, number_of_edges) = read_header(&mut lines)?;
+    let chunks = lines.parse_rest(|file, number, line, lists| {
+        parse_list_line(file, number, line, number_of_nodes, lists)
+    })?;
+    join_lists(&lines, number_of_nodes, number_of_edges, chunks)
+}
+
+/// Reads coordinates like [`read_coordinates`], but reports what is wrong with
+/// the file rather than panicking on it.
+///
+/// The file has no header, so the number of coordinates is for the caller to
+/// check against the number of nodes of the graph.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or a line does not hold a longitude, a
+/// latitude and possibly a height.
+pub fn try_read_coordinates(filename: &str) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let mut lines = NumberedLines::open(filename)?;
+    let chunks = lines.parse_rest(parse_coordinate_line)?;
+    Ok(concat_chunks(Chunk::states(chunks)?))
+}
+
+/// Reads the arcs of an unweighted METIS graph like [`try_read_graph`], but
+/// parses the adjacency lists [on all threads](crate::io#reading-in-parallel).
+///
+/// # Errors
+///
+/// Fails where [`try_read_graph`] fails.
+pub fn par_try_read_graph<T: std::cmp::Eq + From<usize> + Send>(
+    filename: &str,
+    _weight_type: WeightType,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let mut lines = TextLines::read(filename)?;
+    let (number_of_nodes, number_of_edges) = read_header(&mut lines)?;
+    let chunks = lines.parse_in_chunks(|file, number, line, lists| {
+        parse_list_line(file, number, line, number_of_nodes, lists)
+    });
+    join_lists(&lines, number_of_nodes, number_of_edges, chunks)
+}
+
+/// Reads coordinates like [`try_read_coordinates`], but parses them [on all
+/// threads](crate::io#reading-in-parallel).
+///
+/// # Errors
+///
+/// Fails where [`try_read_coordinates`] fails.
+pub fn par_try_read_coordinates(filename: &str) -> Result<Vec<FPCoordinate>, GraphFileError> {
+    let lines = TextLines::read(filename)?;
+    let chunks = lines.parse_in_chunks(parse_coordinate_line);
+    Ok(concat_chunks(Chunk::states(chunks)?))
+}
+
+/// The number of nodes and edges the header of a graph announces.
+fn read_header(lines: &mut impl LineReader) -> Result<(usize, usize), GraphFileError> {
+    while let Some((number, line)) = lines.next_line()? {
+        if is_comment(&line) {
+            continue;
+        }
+        let tokens = line.split_ascii_whitespace().collect_vec();
+        if !(2..=3).contains(&tokens.len()) {
+            return Err(lines.error(number, "expected `<nodes> <edges> [<format>]`"));
+        }
+        if let Some(format) = tokens.get(2)
+            && !format.trim_start_matches('0').is_empty()
+        {
+            return Err(lines.error(
+                number,
+                format!("format `{format}` has weights, which are not supported"),
+            ));
+        }
+        return Ok((
+            lines.parse::<usize>(number, tokens[0], "node count")?,
+            lines.parse::<usize>(number, tokens[1], "edge count")?,
+        ));
+    }
+    Err(lines.missing("header"))
+}
+
+/// The arcs of the adjacency lists in a chunk, their sources counted from
+/// the first list of the chunk.
+struct Lists<T: std::cmp::Eq> {
+    edges: Vec<InputEdge<T>>,
+    lists: usize,
+    entries: usize,
+    /// the line and list of the last list of the chunk that is not empty
+    last_filled: Option<(usize, usize)>,
+}
+
+impl<T: std::cmp::Eq> Default for Lists<T> {
+    fn default() -> Self {
+        Self {
+            edges: Vec::new(),
+            lists: 0,
+            entries: 0,
+            last_filled: None,
+        }
+    }
+}
+
+/// Parses an adjacency list into the arcs of its chunk.
+fn parse_list_line<T: std::cmp::Eq + From<usize>>(
+    file: &TextFile,
+    number: usize,
+    line: &str,
+    number_of_nodes: usize,
+    chunk: &mut Lists<T>,
+) -> Result<(), GraphFileError> {
+    if is_comment(line) {
+        return Ok(());
+    }
+    let source = chunk.lists;
+    for token in line.split_ascii_whitespace() {
+        let id = file.parse::<NodeID>(number, token, "node id")?;
+        if !(1..=number_of_nodes).contains(&id) {
+            return Err(file.error(
+                number,
+                format!("node {id} is not one of the {number_of_nodes} nodes"),
+            ));
+        }
+        chunk.entries += 1;
+        // whether this is a loop is only known once the source is shifted by
+        // the lists before the chunk
+        chunk.edges.push(InputEdge {
+            source,
+            target: id - 1,
+            data: T::from(1),
+        });
+        chunk.last_filled = Some((number, source));
+    }
+    chunk.lists += 1;
+    Ok(())
+}
+
+/// The arcs of the adjacency lists of all chunks, which have to be one per
+/// node, though empty ones may follow, and hold twice as many entries as
+/// there are edges.
+fn join_lists<T: std::cmp::Eq>(
+    file: &TextFile,
+    number_of_nodes: usize,
+    number_of_edges: usize,
+    chunks: Vec<Chunk<Lists<T>>>,
+) -> Result<Vec<InputEdge<T>>, GraphFileError> {
+    let chunks = Chunk::states(chunks)?;
+    let mut lists = 0;
+    let mut last_filled = None;
+    for chunk in &chunks {
+        if let Some((line, list)) = chunk.last_filled {
+            last_filled = Some((line, lists + list));
+        }
+        lists += chunk.lists;
+    }
+    if let Some((line, list)) = last_filled
+        && list >= number_of_nodes
+    {
+        return Err(file.error(
+            line,
+            format!("more adjacency lists than the {number_of_nodes} nodes"),
+        ));
+    }
+    file.count(
+        "adjacency lists",
+        number_of_nodes,
+        lists.min(number_of_nodes),
+    )?;
+    let entries = chunks.iter().map(|chunk| chunk.entries).sum();
+    file.count(
+        "adjacency entries, twice its edges,",
+        2 * number_of_edges,
+        entries,
+    )?;
+
+    let mut first = 0;
+    let mut parts = Vec::with_capacity(chunks.len());
+    for mut chunk in chunks {
+        for edge in &mut chunk.edges {
+            edge.source += first;
+        }
+        // avoid eigenloops
+        chunk.edges.retain(|edge| edge.source != edge.target);
+        first += chunk.lists;
+        parts.push(chunk.edges);
+    }
+    let edges = concat_chunks(parts);
+    info!("loaded {} directed edges", edges.len());
+    Ok(edges)
+}
+
+/// Parses a line into the coordinates of its chunk.
+fn parse_coordinate_line(
+    file: &TextFile,
+    number: usize,
+    line: &str,
+    coordinates: &mut Vec<FPCoordinate>,
+) -> Result<(), GraphFileError> {
+    if is_comment(line) || line.trim().is_empty() {
+        return Ok(());
+    }
+    let tokens = line.split_ascii_whitespace().collect_vec();
+    if !(2..=3).contains(&tokens.len()) {
+        return Err(file.error(number, "expected `<lon> <lat> [<height>]`"));
+    }
+    let lon = file.parse::<f64>(number, tokens[0], "longitude")? / 100_000.;
+    let lat = file.parse::<f64>(number, tokens[1], "latitude")? / 100_000.;
+    if let Some(height) = tokens.get(2) {
+        file.parse::<f64>(number, height, "height")?;
+    }
+    coordinates.push(FPCoordinate::new_from_lat_lon(lat, lon));
+    Ok(())
+}
+
+/// Writes arcs as an unweighted METIS graph for [`read_graph`] to read back.
+///
+/// METIS graphs are undirected, so each arc is written as an edge listed at
+/// both of its ends. An arc and its reverse, as well as parallel arcs, make a
+/// single edge, loops are dropped, and so is what the arcs carry.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+///
+/// # Panics
+///
+/// Panics if an arc leaves the `number_of_nodes` nodes.
+pub fn write_graph<T>(
+    filename: &str,
+    number_of_nodes: usize,
+    edges: &[InputEdge<T>],
+) -> Result<(), GraphFileError>
+where
+    T: std::cmp::Eq,
+{
+    let mut neighbors = vec![Vec::new(); number_of_nodes];
+    for edge in edges {
+        assert!(
+            edge.source < number_of_nodes && edge.target < number_of_nodes,
+            "arc ({}, {}) leaves the {number_of_nodes} nodes",
+            edge.source,
+            edge.target
+        );
+        if edge.source != edge.target {
+            neighbors[edge.source].push(edge.target);
+            neighbors[edge.target].push(edge.source);
+        }
+    }
+    for list in &mut neighbors {
+        list.sort_unstable();
+        list.dedup();
+    }
+    let entries: usize = neighbors.iter().map(Vec::len).sum();
+
+    let mut out = TextWriter::create(filename)?;
+    out.write_line(format_args!("{number_of_nodes} {}", entries / 2))?;
+    for list in &neighbors {
+        out.write_line(list.iter().map(|node| node + 1).join(" "))?;
+    }
+    out.finish()
+}
+
+/// Writes coordinates for [`read_coordinates`] to read back, one per line.
+///
+/// # Errors
+///
+/// Fails if the file cannot be written.
+pub fn write_coordinates(
+    filename: &str,
+    coordinates: &[FPCoordinate],
+) -> Result<(), GraphFileError> {
+    let mut out = TextWriter::create(filename)?;
+    for coordinate in coordinates {
+        // millionths of a degree in units of 10⁻⁵ degrees
+        out.write_line(format_args!(
+            "{} {}",
+            f64::from(coordinate.lon) / 10.,
+            f64::from(coordinate.lat) / 10.
+        ))?;
+    }
+    out.finish()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,4 +546,168 @@ mod tests {
         assert_eq!(Direction::Reverse as i32, 2);
         assert_eq!(Direction::Closed as i32, 3);
     }
+
+    #[test]
+    fn try_read_graph_checks_the_header() {
+        let graph_content = "% a path\n3 2\n2\n1 3\n2\n";
+        let tmp_file = NamedTempFile::new().unwrap();
+        write(tmp_file.path(), graph_content).unwrap();
+        let path = tmp_file.path().to_str().unwrap();
+        let edges = try_read_graph::<usize>(path, WeightType::Unit).unwrap();
+        let arcs = edges
+            .iter()
+            .map(|edge| (edge.source, edge.target))
+            .collect_vec();
+        assert_eq!(arcs, vec![(0, 1), (1, 0), (1, 2), (2, 1)]);
+
+        // the lists of read_graph_valid hold eight entries, four edges rather than eight
+        write(tmp_file.path(), "4 8\n3 2\n2 3\n1 3\n1 2\n").unwrap();
+        let error = try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert!(matches!(
+            error,
+            GraphFileError::Count {
+                expected: 16,
+                found: 8,
+                ..
+            }
+        ));
+
+        write(tmp_file.path(), "3 1\n2\n1\n").unwrap();
+        let error = try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert!(matches!(
+            error,
+            GraphFileError::Count {
+                what: "adjacency lists",
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+
+        write(tmp_file.path(), "2 1 011\n2 5\n1 5\n").unwrap();
+        let error = try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 1, .. }));
+    }
+
+    #[test]
+    fn try_read_graph_reports_the_line() {
+        let tmp_file = NamedTempFile::new().unwrap();
+        let path = tmp_file.path().to_str().unwrap();
+
+        write(tmp_file.path(), "2 1\n3\n1\n").unwrap();
+        let error = try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 2, .. }));
+        assert!(
+            error
+                .to_string()
+                .contains("node 3 is not one of the 2 nodes")
+        );
+
+        write(tmp_file.path(), "2 1\n2\n1\n1\n").unwrap();
+        let error = try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 4, .. }));
+
+        let error = try_read_graph::<usize>("nonexistent_file.txt", WeightType::Unit).unwrap_err();
+        assert!(matches!(error, GraphFileError::Io { .. }));
+    }
+
+    #[test]
+    fn try_read_coordinates_reports_the_line() {
+        let tmp_file = NamedTempFile::new().unwrap();
+        let path = tmp_file.path().to_str().unwrap();
+        write(tmp_file.path(), "1234567 4567890 0\n2345678 5678901\n").unwrap();
+        assert_eq!(try_read_coordinates(path).unwrap(), read_coordinates(path));
+
+        write(tmp_file.path(), "1234567 4567890\n2345678\n").unwrap();
+        let error = try_read_coordinates(path).unwrap_err();
+        assert!(matches!(error, GraphFileError::Parse { line: 2, .. }));
+    }
+
+    #[test]
+    fn written_graph_and_coordinates_read_back() {
+        // a triangle given one way, its reverse arc twice, and a loop
+        let edges = vec![
+            InputEdge::new(0, 1, 7_usize),
+            InputEdge::new(1, 2, 7),
+            InputEdge::new(2, 0, 7),
+            InputEdge::new(0, 2, 7),
+            InputEdge::new(3, 3, 7),
+        ];
+        let coordinates = vec![
+            FPCoordinate::new(52_520_008, 13_404_954),
+            FPCoordinate::new(-33_868_820, 151_209_296),
+        ];
+        let graph_file = NamedTempFile::new().unwrap();
+        let graph_path = graph_file.path().to_str().unwrap();
+        let coordinates_file = NamedTempFile::new().unwrap();
+        let coordinates_path = coordinates_file.path().to_str().unwrap();
+
+        write_graph(graph_path, 5, &edges).unwrap();
+        write_coordinates(coordinates_path, &coordinates).unwrap();
+
+        let edges = try_read_graph::<usize>(graph_path, WeightType::Unit).unwrap();
+        let arcs = edges
+            .iter()
+            .map(|edge| (edge.source, edge.target))
+            .collect_vec();
+        assert_eq!(arcs, vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]);
+
+        // reading truncates to millionths, which may cost the last digit
+        let read = try_read_coordinates(coordinates_path).unwrap();
+        assert_eq!(read.len(), coordinates.len());
+        for (read, written) in read.iter().zip(&coordinates) {
+            assert!((read.lat - written.lat).abs() <= 1);
+            assert!((read.lon - written.lon).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn par_try_read_matches_try_read() {
+        // a ring with a loop, listed twice at its node, and comments and
+        // empty lines past the last list
+        let n = 5000;
+        let mut graph = format!("% a ring\n{n} {}\n", n + 1);
+        let mut coordinates = String::from("% coordinates\n");
+        for node in 1..=n {
+            let before = (node + n - 2) % n + 1;
+            let after = node % n + 1;
+            if node == 42 {
+                graph.push_str(&format!("{before} 42 {after} 42\n"));
+            } else {
+                graph.push_str(&format!("{before} {after}\n"));
+            }
+            if node % 1000 == 0 {
+                graph.push_str("% a comment\n");
+            }
+            coordinates.push_str(&format!("{} {} {}\n", node * 10, node * 20, node));
+        }
+        graph.push_str("\n\n");
+        let tmp_file = NamedTempFile::new().unwrap();
+        let path = tmp_file.path().to_str().unwrap();
+
+        write(tmp_file.path(), &graph).unwrap();
+        let edges = par_try_read_graph::<usize>(path, WeightType::Unit).unwrap();
+        assert_eq!(edges.len(), 2 * n);
+        assert_eq!(
+            edges,
+            try_read_graph::<usize>(path, WeightType::Unit).unwrap()
+        );
+
+        write(tmp_file.path(), &coordinates).unwrap();
+        assert_eq!(
+            par_try_read_coordinates(path).unwrap(),
+            try_read_coordinates(path).unwrap()
+        );
+
+        // a list past the last node
+        write(tmp_file.path(), format!("{graph}1\n")).unwrap();
+        let error = par_try_read_graph::<usize>(path, WeightType::Unit).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            try_read_graph::<usize>(path, WeightType::Unit)
+                .unwrap_err()
+                .to_string()
+        );
+        assert!(error.to_string().contains("more adjacency lists"));
+    }
 }
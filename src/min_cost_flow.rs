@@ -0,0 +1,591 @@
+//! A Min-Cost Max-Flow computation by successive shortest paths, as used for
+//! assignment and transport problems, e.g. which vehicle goes to which depot.
+//!
+//! Each round sends flow along a cheapest path from the source to the target
+//! in the residual graph. The paths are found by Dijkstra's algorithm on costs
+//! that are reduced by node potentials, which keeps them non-negative even on
+//! the reverse arcs that carry the negated cost. Should the input hold arcs of
+//! negative cost, the potentials are primed with a single Bellman-Ford run.
+//!
+//! Unlike the max-flow solvers, parallel arcs are not merged, as they may well
+//! differ in cost. Each input arc keeps a residual arc of its own, which is
+//! also what the flow per arc is read from.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{edge::InputEdge, min_cost_flow::{CostEdgeData, MinCostFlow}};
+//!
+//! // two vehicles, 1 and 2, and two depots, 3 and 4, each taking one
+//! let edges = vec![
+//!     InputEdge::new(0, 1, CostEdgeData::new(1, 0)),
+//!     InputEdge::new(0, 2, CostEdgeData::new(1, 0)),
+//!     InputEdge::new(1, 3, CostEdgeData::new(1, 4)),
+//!     InputEdge::new(1, 4, CostEdgeData::new(1, 1)),
+//!     InputEdge::new(2, 3, CostEdgeData::new(1, 2)),
+//!     InputEdge::new(2, 4, CostEdgeData::new(1, 2)),
+//!     InputEdge::new(3, 5, CostEdgeData::new(1, 0)),
+//!     InputEdge::new(4, 5, CostEdgeData::new(1, 0)),
+//! ];
+//! let mut solver = MinCostFlow::from_edge_list(edges, 0, 5);
+//! solver.run();
+//! assert_eq!(solver.flow(), Ok(2));
+//! assert_eq!(solver.cost(), Ok(3));
+//! assert_eq!(solver.arc_flows().unwrap(), vec![1, 1, 0, 1, 1, 0, 1, 1]);
+//! ```
+use core::cmp::{max, min};
+
+use log::debug;
+
+use crate::{
+    addressable_binary_heap::AddressableHeap,
+    edge::{EdgeWithData, InputEdge},
+    graph::{EdgeArrayEntry, EdgeID, Graph, NodeID},
+    static_graph::StaticGraph,
+};
+
+/// Capacity and cost per unit of flow of an input arc.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CostEdgeData {
+    pub capacity: i32,
+    pub cost: i32,
+}
+
+impl CostEdgeData {
+    pub fn new(capacity: i32, cost: i32) -> CostEdgeData {
+        CostEdgeData { capacity, cost }
+    }
+}
+
+/// An arc of the residual graph. A reverse arc carries the negated cost of
+/// its input arc.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct CostArcData {
+    capacity: i32,
+    cost: i32,
+}
+
+pub struct MinCostFlow {
+    residual_graph: StaticGraph<CostArcData>,
+    /// the reverse arc of each arc
+    reverse: Vec<u32>,
+    /// the forward arc of each input arc, in input order
+    arc_of_input: Vec<u32>,
+    potential: Vec<i64>,
+    heap: AddressableHeap<NodeID, i64, EdgeID>,
+    flow: i32,
+    cost: i64,
+    rounds: usize,
+    finished: bool,
+    source: NodeID,
+    target: NodeID,
+}
+
+impl MinCostFlow {
+    pub fn from_edge_list(
+        edge_list: Vec<InputEdge<CostEdgeData>>,
+        source: NodeID,
+        target: NodeID,
+    ) -> Self {
+        debug_assert!(!edge_list.is_empty());
+        let number_of_nodes = 1 + edge_list
+            .iter()
+            .map(|edge| max(edge.source, edge.target))
+            .chain([source, target])
+            .max()
+            .expect("edge list is empty");
+
+        assert!(
+            2 * edge_list.len() <= u32::MAX as usize,
+            "arc ids have to fit into u32"
+        );
+        // Input arc i yields the forward arc 2i and the reverse arc 2i + 1,
+        // each as a pair of tail and head.
+        let arc_ends = |arc: usize| {
+            let edge = &edge_list[arc / 2];
+            if arc.is_multiple_of(2) {
+                (edge.source, edge.target)
+            } else {
+                (edge.target, edge.source)
+            }
+        };
+
+        // Two passes of counting sort, first by head and then stable by tail,
+        // put the arcs into their adjacency blocks sorted by target, which is
+        // what a static graph expects. Parallel arcs stay apart.
+        let counting_sort = |arcs: &[usize], key: &dyn Fn(usize) -> NodeID| {
+            let mut offsets = vec![0_usize; number_of_nodes + 1];
+            for &arc in arcs {
+                offsets[key(arc) + 1] += 1;
+            }
+            for i in 1..offsets.len() {
+                offsets[i] += offsets[i - 1];
+            }
+            let mut sorted = vec![0; arcs.len()];
+            for &arc in arcs {
+                sorted[offsets[key(arc)]] = arc;
+                offsets[key(arc)] += 1;
+            }
+            sorted
+        };
+        let arcs = (0..2 * edge_list.len()).collect::<Vec<_>>();
+        let arcs = counting_sort(&arcs, &|arc| arc_ends(arc).1);
+        let arcs = counting_sort(&arcs, &|arc| arc_ends(arc).0);
+
+        let mut node_array = vec![0_usize; number_of_nodes + 1];
+        let mut position = vec![0_u32; arcs.len()];
+        let mut edge_array = Vec::with_capacity(arcs.len());
+        for (index, &arc) in arcs.iter().enumerate() {
+            let (tail, head) = arc_ends(arc);
+            node_array[tail + 1] += 1;
+            position[arc] = index as u32;
+            let data = edge_list[arc / 2].data;
+            debug_assert!(data.capacity >= 0, "capacities are non-negative");
+            edge_array.push(EdgeArrayEntry {
+                target: u32::try_from(head).expect("the graph is too large to hold"),
+                data: if arc.is_multiple_of(2) {
+                    CostArcData {
+                        capacity: data.capacity,
+                        cost: data.cost,
+                    }
+                } else {
+                    CostArcData {
+                        capacity: 0,
+                        cost: -data.cost,
+                    }
+                },
+            });
+        }
+        for i in 1..node_array.len() {
+            node_array[i] += node_array[i - 1];
+        }
+        // the partner of arc 2i is 2i + 1 and vice versa
+        let reverse = arcs
+            .iter()
+            .map(|&arc| position[arc ^ 1])
+            .collect::<Vec<_>>();
+        let arc_of_input = position.iter().step_by(2).copied().collect();
+        debug!(
+            "residual graph has {number_of_nodes} nodes and {} arcs",
+            edge_array.len()
+        );
+
+        Self {
+            residual_graph: StaticGraph::from_adjacency_array(node_array, edge_array),
+            reverse,
+            arc_of_input,
+            potential: vec![0; number_of_nodes],
+            heap: AddressableHeap::new(),
+            flow: 0,
+            cost: 0,
+            rounds: 0,
+            finished: false,
+            source,
+            target,
+        }
+    }
+
+    pub fn from_generic_edge_list<E: EdgeWithData>(
+        input_edges: &[E],
+        source: NodeID,
+        target: NodeID,
+        function: impl Fn(&E) -> CostEdgeData,
+    ) -> Self {
+        debug_assert!(!input_edges.is_empty());
+        let edge_list = input_edges
+            .iter()
+            .map(|edge| InputEdge {
+                source: edge.source(),
+                target: edge.target(),
+                data: function(edge),
+            })
+            .collect();
+        Self::from_edge_list(edge_list, source, target)
+    }
+
+    /// Sends as much flow as possible from the source to the target, at the
+    /// least cost among all flows of that value.
+    pub fn run(&mut self) {
+        self.run_with_flow_limit(i32::MAX);
+    }
+
+    /// Sends up to `limit` units of flow, at the least cost among all flows of
+    /// the value that was sent. This is what a transport problem with a given
+    /// demand wants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the residual graph holds a cycle of negative cost, as there is
+    /// no cheapest flow then.
+    pub fn run_with_flow_limit(&mut self, limit: i32) {
+        let has_negative_costs = (0..self.residual_graph.number_of_edges()).any(|edge| {
+            let data = self.residual_graph.data(edge);
+            data.capacity > 0 && data.cost < 0
+        });
+        if has_negative_costs {
+            self.bellman_ford();
+        }
+
+        while self.flow < limit {
+            self.rounds += 1;
+            let Some(distance) = self.dijkstra() else {
+                break;
+            };
+
+            // find the bottleneck of the path, and the flow still wanted
+            let mut amount = limit - self.flow;
+            let mut node = self.target;
+            while node != self.source {
+                let edge = *self.heap.data(node);
+                amount = min(amount, self.residual_graph.data(edge).capacity);
+                node = self.residual_graph.target(self.reverse[edge] as EdgeID);
+            }
+            debug_assert!(amount > 0);
+
+            // assign it to the residual graph
+            let mut node = self.target;
+            while node != self.source {
+                let edge = *self.heap.data(node);
+                self.residual_graph.data_mut(edge).capacity -= amount;
+                let reverse = self.reverse[edge] as EdgeID;
+                self.residual_graph.data_mut(reverse).capacity += amount;
+                node = self.residual_graph.target(reverse);
+            }
+            self.flow += amount;
+            // the reduced cost of the path is its cost, less what the
+            // potentials of its ends take off
+            let path_cost = distance - self.potential[self.source] + self.potential[self.target];
+            self.cost += i64::from(amount) * path_cost;
+
+            // nodes that were settled are as far from the source as they were
+            // found, all others at least as far as the target
+            for node in 0..self.residual_graph.number_of_nodes() {
+                self.potential[node] += if self.heap.removed(node) {
+                    self.heap.weight(node)
+                } else {
+                    distance
+                };
+            }
+        }
+        debug!(
+            "sent {} units at cost {} in {} rounds",
+            self.flow, self.cost, self.rounds
+        );
+        self.finished = true;
+    }
+
+    /// Runs Dijkstra on the reduced costs until the target is settled, and
+    /// returns its distance if it is reachable at all.
+    fn dijkstra(&mut self) -> Option<i64> {
+        self.heap.clear();
+        self.heap.insert(self.source, 0, EdgeID::MAX);
+        while !self.heap.is_empty() {
+            let u = self.heap.delete_min();
+            let distance = self.heap.weight(u);
+            if u == self.target {
+                return Some(distance);
+            }
+            for edge in self.residual_graph.edge_range(u) {
+                let data = self.residual_graph.data(edge);
+                if data.capacity <= 0 {
+                    continue;
+                }
+                let v = self.residual_graph.target(edge);
+                let reduced_cost = i64::from(data.cost) + self.potential[u] - self.potential[v];
+                debug_assert!(reduced_cost >= 0, "potentials are not feasible");
+                self.heap
+                    .insert_or_decrease(v, distance + reduced_cost, edge);
+            }
+        }
+        None
+    }
+
+    /// Primes the potentials with the distances from the source, which makes
+    /// all reduced costs non-negative.
+    fn bellman_ford(&mut self) {
+        let number_of_nodes = self.residual_graph.number_of_nodes();
+        let mut distance = vec![i64::MAX; number_of_nodes];
+        distance[self.source] = 0;
+        for round in 0..number_of_nodes {
+            let mut changed = false;
+            for u in 0..number_of_nodes {
+                if distance[u] == i64::MAX {
+                    continue;
+                }
+                for edge in self.residual_graph.edge_range(u) {
+                    let data = self.residual_graph.data(edge);
+                    let v = self.residual_graph.target(edge);
+                    if data.capacity > 0 && distance[u] + i64::from(data.cost) < distance[v] {
+                        distance[v] = distance[u] + i64::from(data.cost);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+            assert!(
+                round + 1 < number_of_nodes,
+                "the graph holds a cycle of negative cost"
+            );
+        }
+        // nodes the source does not reach are never scanned, and any potential
+        // does for them
+        for (potential, distance) in self.potential.iter_mut().zip(distance) {
+            *potential = if distance == i64::MAX { 0 } else { distance };
+        }
+    }
+
+    /// The value of the flow.
+    pub fn flow(&self) -> Result<i32, String> {
+        if !self.finished {
+            return Err("Flow was not computed.".to_string());
+        }
+        Ok(self.flow)
+    }
+
+    /// The total cost of the flow, i.e. the sum of flow times cost over all
+    /// arcs.
+    pub fn cost(&self) -> Result<i64, String> {
+        if !self.finished {
+            return Err("Flow was not computed.".to_string());
+        }
+        Ok(self.cost)
+    }
+
+    /// The flow on each input arc, in the order of the input.
+    pub fn arc_flows(&self) -> Result<Vec<i32>, String> {
+        if !self.finished {
+            return Err("Flow was not computed.".to_string());
+        }
+        // the reverse arc started out empty, thus it holds what went forward
+        Ok(self
+            .arc_of_input
+            .iter()
+            .map(|&arc| {
+                self.residual_graph
+                    .data(self.reverse[arc as usize] as EdgeID)
+                    .capacity
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dinic::Dinic,
+        edge::{EdgeData, InputEdge},
+        max_flow::{MaxFlow, ResidualEdgeData},
+        min_cost_flow::{CostEdgeData, MinCostFlow},
+    };
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+
+    /// A bipartite assignment of `costs.len()` workers to as many jobs.
+    fn assignment_problem(costs: &[Vec<i32>]) -> Vec<InputEdge<CostEdgeData>> {
+        let n = costs.len();
+        let target = 2 * n + 1;
+        let mut edges = Vec::new();
+        for (worker, row) in costs.iter().enumerate() {
+            edges.push(InputEdge::new(0, 1 + worker, CostEdgeData::new(1, 0)));
+            for (job, &cost) in row.iter().enumerate() {
+                edges.push(InputEdge::new(
+                    1 + worker,
+                    1 + n + job,
+                    CostEdgeData::new(1, cost),
+                ));
+            }
+        }
+        for job in 0..n {
+            edges.push(InputEdge::new(1 + n + job, target, CostEdgeData::new(1, 0)));
+        }
+        edges
+    }
+
+    /// The cost of the cheapest assignment, found by trying all of them.
+    fn cheapest_assignment(costs: &[Vec<i32>]) -> i64 {
+        fn search(costs: &[Vec<i32>], worker: usize, taken: &mut Vec<bool>) -> i64 {
+            if worker == costs.len() {
+                return 0;
+            }
+            let mut best = i64::MAX;
+            for job in 0..costs.len() {
+                if !taken[job] {
+                    taken[job] = true;
+                    let rest = search(costs, worker + 1, taken);
+                    best = best.min(i64::from(costs[worker][job]) + rest);
+                    taken[job] = false;
+                }
+            }
+            best
+        }
+        search(costs, 0, &mut vec![false; costs.len()])
+    }
+
+    #[test]
+    fn assignment_matches_exhaustive_search() {
+        let mut rng = StdRng::seed_from_u64(0xC057);
+        for round in 0..20 {
+            let n = 2 + round % 5;
+            let costs = (0..n)
+                .map(|_| (0..n).map(|_| rng.random_range(0..50)).collect())
+                .collect::<Vec<Vec<i32>>>();
+            let edges = assignment_problem(&costs);
+
+            let mut solver = MinCostFlow::from_edge_list(edges.clone(), 0, 2 * n + 1);
+            solver.run();
+            assert_eq!(solver.flow(), Ok(n as i32), "round {round}");
+            assert_eq!(
+                solver.cost(),
+                Ok(cheapest_assignment(&costs)),
+                "round {round}"
+            );
+
+            // the flow per arc adds up to the cost, and respects the capacities
+            let flows = solver.arc_flows().unwrap();
+            let cost: i64 = edges
+                .iter()
+                .zip(&flows)
+                .map(|(edge, &flow)| {
+                    assert!((0..=edge.data.capacity).contains(&flow));
+                    i64::from(flow) * i64::from(edge.data.cost)
+                })
+                .sum();
+            assert_eq!(solver.cost(), Ok(cost), "round {round}");
+        }
+    }
+
+    #[test]
+    fn flow_matches_dinic_and_leaves_no_negative_cycle() {
+        let mut rng = StdRng::seed_from_u64(0x5EED);
+        for round in 0..20 {
+            let number_of_nodes = 10 + round;
+            let mut edges = Vec::new();
+            for source in 0..number_of_nodes {
+                for _ in 0..3 {
+                    let target = rng.random_range(0..number_of_nodes);
+                    if source != target {
+                        edges.push(InputEdge::new(
+                            source,
+                            target,
+                            CostEdgeData::new(rng.random_range(1..=9), rng.random_range(0..20)),
+                        ));
+                    }
+                }
+            }
+            let target = number_of_nodes - 1;
+
+            let mut dinic = Dinic::from_edge_list(
+                edges
+                    .iter()
+                    .map(|edge| {
+                        InputEdge::new(
+                            edge.source,
+                            edge.target,
+                            ResidualEdgeData::new(edge.data.capacity),
+                        )
+                    })
+                    .collect(),
+                0,
+                target,
+            );
+            dinic.run();
+            let mut solver = MinCostFlow::from_edge_list(edges.clone(), 0, target);
+            solver.run();
+            assert_eq!(solver.flow(), dinic.max_flow(), "round {round}");
+
+            // a flow is cheapest if and only if the residual graph holds no
+            // cycle of negative cost, which Bellman-Ford would run into
+            let flows = solver.arc_flows().unwrap();
+            let residual = edges
+                .iter()
+                .zip(&flows)
+                .flat_map(|(edge, &flow)| {
+                    [
+                        (
+                            edge.source,
+                            edge.target,
+                            edge.data.capacity - flow,
+                            edge.data.cost,
+                        ),
+                        (edge.target, edge.source, flow, -edge.data.cost),
+                    ]
+                })
+                .filter(|&(_, _, capacity, _)| capacity > 0)
+                .collect::<Vec<_>>();
+            let mut distance = vec![0_i64; number_of_nodes];
+            for _ in 0..number_of_nodes {
+                for &(u, v, _, cost) in &residual {
+                    distance[v] = distance[v].min(distance[u] + i64::from(cost));
+                }
+            }
+            for &(u, v, _, cost) in &residual {
+                assert!(
+                    distance[u] + i64::from(cost) >= distance[v],
+                    "round {round}: negative cycle left"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flow_limit_sends_the_cheapest_units() {
+        // two routes, a cheap narrow one and an expensive wide one
+        let edges = vec![
+            InputEdge::new(0, 1, CostEdgeData::new(2, 1)),
+            InputEdge::new(1, 3, CostEdgeData::new(2, 1)),
+            InputEdge::new(0, 2, CostEdgeData::new(5, 3)),
+            InputEdge::new(2, 3, CostEdgeData::new(5, 3)),
+        ];
+        let mut solver = MinCostFlow::from_edge_list(edges.clone(), 0, 3);
+        solver.run_with_flow_limit(3);
+        assert_eq!(solver.flow(), Ok(3));
+        assert_eq!(solver.cost(), Ok(2 * 2 + 6));
+        assert_eq!(solver.arc_flows().unwrap(), vec![2, 2, 1, 1]);
+
+        let mut solver = MinCostFlow::from_edge_list(edges, 0, 3);
+        solver.run();
+        assert_eq!(solver.flow(), Ok(7));
+        assert_eq!(solver.cost(), Ok(2 * 2 + 5 * 6));
+    }
+
+    #[test]
+    fn parallel_arcs_keep_their_costs() {
+        let edges = vec![
+            InputEdge::new(0, 1, CostEdgeData::new(1, 10)),
+            InputEdge::new(0, 1, CostEdgeData::new(1, 1)),
+            InputEdge::new(0, 1, CostEdgeData::new(1, 5)),
+        ];
+        let mut solver = MinCostFlow::from_edge_list(edges, 0, 1);
+        solver.run_with_flow_limit(2);
+        assert_eq!(solver.cost(), Ok(6));
+        assert_eq!(solver.arc_flows().unwrap(), vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn negative_costs_prime_the_potentials() {
+        // the detour over node 2 pays off
+        let edges = vec![
+            InputEdge::new(0, 1, 4),
+            InputEdge::new(0, 2, 1),
+            InputEdge::new(2, 1, -3),
+            InputEdge::new(1, 3, 1),
+        ];
+        let mut solver = MinCostFlow::from_generic_edge_list(&edges, 0, 3, |edge| {
+            CostEdgeData::new(1, *edge.data())
+        });
+        solver.run();
+        assert_eq!(solver.flow(), Ok(1));
+        assert_eq!(solver.cost(), Ok(-1));
+        assert_eq!(solver.arc_flows().unwrap(), vec![0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn flow_not_computed() {
+        let edges = vec![InputEdge::new(0, 1, CostEdgeData::new(1, 1))];
+        let solver = MinCostFlow::from_edge_list(edges, 0, 1);
+        assert!(solver.flow().is_err());
+        assert!(solver.cost().is_err());
+        assert!(solver.arc_flows().is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Max flow and min cut between a set of sources and a set of sinks.
+//!
+//! The max-flow solvers take a single source and a single sink. A problem with
+//! many of either is handed to them with each set contracted into one node,
+//! which is what a super-source with arcs of unbounded capacity to all sources
+//! amounts to, only without the arcs. All arcs within a set vanish with the
+//! contraction, while parallel arcs that it creates are merged by the solver.
+//!
+//! The result is given in the ids of the input, as the nodes either side of
+//! the cut reaches in the residual graph, and as the arcs of the input that
+//! cross it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{
+//!     dinic::Dinic,
+//!     edge::InputEdge,
+//!     max_flow::ResidualEdgeData,
+//!     min_cut::min_cut,
+//! };
+//!
+//! // a path 0 - 1 - 2 - 3 - 4 that narrows between 2 and 3
+//! let edges = vec![
+//!     InputEdge::new(0, 1, ResidualEdgeData::new(5)),
+//!     InputEdge::new(1, 2, ResidualEdgeData::new(5)),
+//!     InputEdge::new(2, 3, ResidualEdgeData::new(1)),
+//!     InputEdge::new(3, 4, ResidualEdgeData::new(5)),
+//! ];
+//! let cut = min_cut::<Dinic>(&edges, &[0, 1], &[4]).unwrap();
+//! assert_eq!(cut.flow, 1);
+//! assert_eq!(cut.source_side, vec![0, 1, 2]);
+//! assert_eq!(cut.sink_side, vec![3, 4]);
+//! assert_eq!(cut.cut_arcs, vec![2]);
+//! ```
+use std::sync::Arc;
+
+use itertools::Itertools;
+use log::debug;
+use num::Zero;
+use thiserror::Error;
+
+use crate::{
+    edge::InputEdge,
+    graph::NodeID,
+    max_flow::{Capacity, MaxFlow, ResidualEdgeData},
+    renumbering_table::RenumberingTable,
+};
+
+/// The contracted id of all sources.
+const SOURCE: NodeID = 0;
+/// The contracted id of all sinks.
+const SINK: NodeID = 1;
+
+/// A minimum cut and the value of the flow that saturates it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinCut<C: Capacity = i32> {
+    pub flow: C,
+    /// the nodes the sources reach in the residual graph, sources included,
+    /// sorted by id
+    pub source_side: Vec<NodeID>,
+    /// the nodes that reach the sinks in the residual graph, sinks included,
+    /// sorted by id
+    pub sink_side: Vec<NodeID>,
+    /// the positions of the input arcs that lead from the source side to the
+    /// rest of the graph. Their capacities add up to the flow.
+    pub cut_arcs: Vec<usize>,
+}
+
+/// Why a cut could not be computed.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MinCutError {
+    #[error("no source was given")]
+    NoSources,
+    #[error("no sink was given")]
+    NoSinks,
+    #[error("node {0} is a source and a sink")]
+    SourceIsSink(NodeID),
+    #[error("the flow exceeds its upper bound")]
+    BoundExceeded,
+}
+
+/// Computes a minimum cut between `sources` and `sinks` with the solver `M`.
+///
+/// # Errors
+///
+/// Fails if either set is empty or if they share a node.
+pub fn min_cut<M: MaxFlow>(
+    edges: &[InputEdge<ResidualEdgeData<M::Capacity>>],
+    sources: &[NodeID],
+    sinks: &[NodeID],
+) -> Result<MinCut<M::Capacity>, MinCutError> {
+    solve::<M>(edges, sources, sinks, None)
+}
+
+/// Computes a minimum cut between `sources` and `sinks` with the solver `M`,
+/// but gives up once the flow exceeds `bound`, which is lowered to the flow if
+/// it does not. Bounds shared among the cuts of several tries let all of them
+/// stop once one found a better cut, see [`MaxFlow::run_with_upper_bound`].
+///
+/// # Errors
+///
+/// Fails if either set is empty, if they share a node, or if the flow exceeds
+/// the bound.
+pub fn min_cut_with_upper_bound<M: MaxFlow>(
+    edges: &[InputEdge<ResidualEdgeData<M::Capacity>>],
+    sources: &[NodeID],
+    sinks: &[NodeID],
+    bound: Arc<<M::Capacity as Capacity>::Atomic>,
+) -> Result<MinCut<M::Capacity>, MinCutError> {
+    solve::<M>(edges, sources, sinks, Some(bound))
+}
+
+fn solve<M: MaxFlow>(
+    edges: &[InputEdge<ResidualEdgeData<M::Capacity>>],
+    sources: &[NodeID],
+    sinks: &[NodeID],
+    bound: Option<Arc<<M::Capacity as Capacity>::Atomic>>,
+) -> Result<MinCut<M::Capacity>, MinCutError> {
+    if sources.is_empty() {
+        return Err(MinCutError::NoSources);
+    }
+    if sinks.is_empty() {
+        return Err(MinCutError::NoSinks);
+    }
+
+    // The ids of the input may be spread over a much larger graph than the
+    // one at hand, as with the cells of a partition. The table keeps itself
+    // small in that case.
+    let universe_size = 1 + edges
+        .iter()
+        .flat_map(|edge| [edge.source, edge.target])
+        .chain(sources.iter().copied())
+        .chain(sinks.iter().copied())
+        .max()
+        .expect("sources are not empty");
+    let usage_bound = universe_size.min(2 * edges.len() + sources.len() + sinks.len());
+    let mut renumbering_table = RenumberingTable::new_with_size_hint(universe_size, usage_bound);
+    for &source in sources {
+        renumbering_table.set(source, SOURCE);
+    }
+    for &sink in sinks {
+        if renumbering_table.contains_key(sink) {
+            return Err(MinCutError::SourceIsSink(sink));
+        }
+        renumbering_table.set(sink, SINK);
+    }
+
+    // number the other nodes consecutively, and remember what they were
+    let mut original_ids = Vec::new();
+    let mut contracted = Vec::with_capacity(edges.len());
+    let mut renumber = |node: NodeID, original_ids: &mut Vec<NodeID>| {
+        if !renumbering_table.contains_key(node) {
+            renumbering_table.set(node, 2 + original_ids.len());
+            original_ids.push(node);
+        }
+        renumbering_table.get(node)
+    };
+    for edge in edges {
+        let source = renumber(edge.source, &mut original_ids);
+        let target = renumber(edge.target, &mut original_ids);
+        // arcs within a contracted set, and eigenloops, carry no flow
+        if source != target {
+            contracted.push(InputEdge::new(source, target, edge.data));
+        }
+    }
+    debug!(
+        "contracted {} sources and {} sinks, {} of {} arcs left",
+        sources.len(),
+        sinks.len(),
+        contracted.len(),
+        edges.len()
+    );
+
+    if contracted.is_empty() {
+        // nothing connects the sets, and the cut is empty
+        return Ok(MinCut {
+            flow: M::Capacity::zero(),
+            source_side: sources.iter().copied().sorted_unstable().dedup().collect(),
+            sink_side: sinks.iter().copied().sorted_unstable().dedup().collect(),
+            cut_arcs: Vec::new(),
+        });
+    }
+
+    let mut solver = M::from_edge_list(contracted, SOURCE, SINK);
+    match bound {
+        Some(bound) => solver.run_with_upper_bound(bound),
+        None => solver.run(),
+    }
+    let flow = solver.max_flow().map_err(|_| MinCutError::BoundExceeded)?;
+    let source_assignment = solver
+        .assignment(SOURCE)
+        .expect("max flow computation did not run");
+    let sink_assignment = solver
+        .sink_assignment(SINK)
+        .expect("max flow computation did not run");
+
+    // each side is its terminals and the nodes that the solver put there
+    let side = |assignment: &bitvec::vec::BitVec, terminals: &[NodeID]| {
+        terminals
+            .iter()
+            .copied()
+            .chain(
+                original_ids
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| assignment[2 + index])
+                    .map(|(_, &node)| node),
+            )
+            .sorted_unstable()
+            .dedup()
+            .collect::<Vec<_>>()
+    };
+    let source_side = side(&source_assignment, sources);
+    let sink_side = side(&sink_assignment, sinks);
+
+    let on_source_side = |node: NodeID| source_assignment[renumbering_table.get(node)];
+    let cut_arcs = edges
+        .iter()
+        .positions(|edge| on_source_side(edge.source) && !on_source_side(edge.target))
+        .collect::<Vec<_>>();
+    debug_assert_eq!(
+        cut_arcs
+            .iter()
+            .map(|&arc| edges[arc].data.capacity)
+            .sum::<M::Capacity>(),
+        flow
+    );
+
+    Ok(MinCut {
+        flow,
+        source_side,
+        sink_side,
+        cut_arcs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, atomic::AtomicI32};
+
+    use crate::{
+        dinic::Dinic,
+        edge::InputEdge,
+        edmonds_karp::EdmondsKarp,
+        ford_fulkerson::FordFulkerson,
+        max_flow::ResidualEdgeData,
+        min_cut::{MinCutError, min_cut, min_cut_with_upper_bound},
+        push_relabel::PushRelabel,
+    };
+
+    fn both_ways(pairs: &[(usize, usize, i32)]) -> Vec<InputEdge<ResidualEdgeData>> {
+        pairs
+            .iter()
+            .flat_map(|&(source, target, capacity)| {
+                [
+                    InputEdge::new(source, target, ResidualEdgeData::new(capacity)),
+                    InputEdge::new(target, source, ResidualEdgeData::new(capacity)),
+                ]
+            })
+            .collect()
+    }
+
+    /// Two triangles 0, 1, 2 and 3, 4, 5 joined by a bridge 2 - 3, and a node
+    /// 6 that hangs off 4.
+    fn two_triangles() -> Vec<InputEdge<ResidualEdgeData>> {
+        both_ways(&[
+            (0, 1, 3),
+            (1, 2, 3),
+            (2, 0, 3),
+            (2, 3, 2),
+            (3, 4, 3),
+            (4, 5, 3),
+            (5, 3, 3),
+            (4, 6, 1),
+        ])
+    }
+
+    #[test]
+    fn all_solvers_find_the_bridge() {
+        let edges = two_triangles();
+        let cuts = [
+            min_cut::<Dinic>(&edges, &[0, 1], &[5, 6]).unwrap(),
+            min_cut::<PushRelabel>(&edges, &[0, 1], &[5, 6]).unwrap(),
+            min_cut::<EdmondsKarp>(&edges, &[0, 1], &[5, 6]).unwrap(),
+            min_cut::<FordFulkerson>(&edges, &[0, 1], &[5, 6]).unwrap(),
+        ];
+        for cut in &cuts {
+            assert_eq!(cut.flow, 2);
+            assert_eq!(cut.source_side, vec![0, 1, 2]);
+            assert_eq!(cut.sink_side, vec![3, 4, 5, 6]);
+            assert_eq!(cut.cut_arcs, vec![6]);
+        }
+    }
+
+    #[test]
+    fn nodes_between_the_sides_are_on_neither() {
+        // a path 0 - 1 - 2 - 3 - 4 whose ends are the bottlenecks
+        let edges = both_ways(&[(0, 1, 1), (1, 2, 5), (2, 3, 5), (3, 4, 1)]);
+        let cut = min_cut::<Dinic>(&edges, &[0], &[4]).unwrap();
+        assert_eq!(cut.flow, 1);
+        assert_eq!(cut.source_side, vec![0]);
+        assert_eq!(cut.sink_side, vec![4]);
+        assert_eq!(cut.cut_arcs, vec![0]);
+    }
+
+    #[test]
+    fn ids_from_a_larger_graph_are_kept() {
+        let edges = both_ways(&[(1000, 2000, 4), (2000, 3000, 1), (3000, 4000, 4)]);
+        let cut = min_cut::<Dinic>(&edges, &[1000], &[4000]).unwrap();
+        assert_eq!(cut.flow, 1);
+        assert_eq!(cut.source_side, vec![1000, 2000]);
+        assert_eq!(cut.sink_side, vec![3000, 4000]);
+        assert_eq!(cut.cut_arcs, vec![2]);
+    }
+
+    #[test]
+    fn disconnected_sets_have_an_empty_cut() {
+        let edges = both_ways(&[(0, 1, 1), (2, 3, 1)]);
+        let cut = min_cut::<Dinic>(&edges, &[1, 0], &[3, 2]).unwrap();
+        assert_eq!(cut.flow, 0);
+        assert_eq!(cut.source_side, vec![0, 1]);
+        assert_eq!(cut.sink_side, vec![2, 3]);
+        assert!(cut.cut_arcs.is_empty());
+    }
+
+    #[test]
+    fn an_unreachable_sink_is_still_on_the_sink_side() {
+        let edges = both_ways(&[(0, 1, 1), (1, 2, 1)]);
+        let cut = min_cut::<Dinic>(&edges, &[0], &[7]).unwrap();
+        assert_eq!(cut.flow, 0);
+        assert_eq!(cut.source_side, vec![0, 1, 2]);
+        assert_eq!(cut.sink_side, vec![7]);
+        assert!(cut.cut_arcs.is_empty());
+    }
+
+    #[test]
+    fn malformed_sets_are_refused() {
+        let edges = two_triangles();
+        assert_eq!(
+            min_cut::<Dinic>(&edges, &[], &[5]),
+            Err(MinCutError::NoSources)
+        );
+        assert_eq!(
+            min_cut::<Dinic>(&edges, &[0], &[]),
+            Err(MinCutError::NoSinks)
+        );
+        assert_eq!(
+            min_cut::<Dinic>(&edges, &[0, 3], &[3, 5]),
+            Err(MinCutError::SourceIsSink(3))
+        );
+    }
+
+    #[test]
+    fn a_bound_below_the_flow_gives_up() {
+        let edges = two_triangles();
+        let bound = Arc::new(AtomicI32::new(1));
+        assert_eq!(
+            min_cut_with_upper_bound::<Dinic>(&edges, &[0], &[5], bound),
+            Err(MinCutError::BoundExceeded)
+        );
+
+        let bound = Arc::new(AtomicI32::new(10));
+        let cut = min_cut_with_upper_bound::<PushRelabel>(&edges, &[0], &[5], bound.clone());
+        assert_eq!(cut.map(|cut| cut.flow), Ok(2));
+        assert_eq!(bound.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+}
@@ -130,7 +130,7 @@ impl<S: HeapStats<NodeID>> MldSearch<S> {
     ///
     /// A second run over the same partition finds the room already there and
     /// the entries already put back by `clear`.
-    fn make_room_for(&mut self, customization: &Customization) {
+    fn make_room_for<G: Graph<u32>>(&mut self, customization: &Customization<G>) {
         let levels = customization.directory().levels();
         let mut at = Vec::with_capacity(levels + 1);
         let mut total = 0;
@@ -156,9 +156,9 @@ impl<S: HeapStats<NodeID>> MldSearch<S> {
     ///
     /// Panics if a level of the partition has no cells worked out for it,
     /// which would mean a directory that does not describe the graph.
-    pub fn run(
+    pub fn run<G: Graph<u32>>(
         &mut self,
-        customization: &Customization,
+        customization: &Customization<G>,
         source: NodeID,
         targets: &[NodeID],
     ) -> bool {
@@ -249,9 +249,9 @@ impl<S: HeapStats<NodeID>> MldSearch<S> {
 
     /// The arcs across the cell, which the customization worked out.
     #[inline(never)]
-    fn relax_across_cell(
+    fn relax_across_cell<G: Graph<u32>>(
         &mut self,
-        customization: &Customization,
+        customization: &Customization<G>,
         partition: &PackedPartition,
         node: NodeID,
         distance: usize,
@@ -0,0 +1,1033 @@
+//! Road networks read from OpenStreetMap PBF extracts.
+//!
+//! A PBF file is a sequence of blobs, each preceded by the length of a small
+//! header that gives its type and size. A blob holds its content either as it
+//! is or compressed with zlib. The first one, an `OSMHeader`, lists the
+//! features a reader needs, and the `OSMData` blobs that follow each hold a
+//! primitive block: a table of the strings its elements use, and groups of
+//! nodes, ways or relations that refer to the strings by their index. Nodes
+//! come either one by one or as dense nodes, their ids and coordinates delta
+//! coded in columns. All of it is protobuf, which is read here by hand, as is
+//! the compression by [`crate::zlib`].
+//!
+//! The import reads the file twice. The first pass keeps the ways a
+//! [`SpeedProfile`] takes, the second the coordinates of the nodes they pass
+//! through. A way is then split into arcs at every node it shares with
+//! another way, and at its ends, such that the nodes of the graph are the
+//! junctions and dead ends of the road network and the nodes in between only
+//! give the shape of the roads. An arc is as long as the great-circle lengths
+//! of its pieces add up to, and it takes as long as that length at the speed
+//! the profile gives its road.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use toolbox_rs::osm_pbf::{SpeedProfile, read_road_network};
+//!
+//! let mut profile = SpeedProfile::car();
+//! // keep out of residential streets
+//! profile.set_speed("residential", 0.);
+//! let network = read_road_network::<usize>("berlin-latest.osm.pbf", &profile).unwrap();
+//! println!(
+//!     "{} nodes and {} arcs",
+//!     network.coordinates.len(),
+//!     network.edges.len()
+//! );
+//! ```
+use std::{
+    convert::Infallible,
+    fs::File,
+    hash::Hash,
+    io::{self, BufReader, Read},
+};
+
+use log::info;
+use rustc_hash::{FxHashMap, FxHashSet};
+use thiserror::Error;
+
+use crate::{
+    edge::InputEdge, geometry::FPCoordinate, graph::NodeID, great_circle::haversine, zlib,
+};
+
+/// The largest blob header the format allows.
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// The largest blob the format allows, compressed or not.
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+/// The features of a file a reader may be asked for that are understood here.
+const SUPPORTED_FEATURES: [&str; 2] = ["OsmSchema-V0.6", "DenseNodes"];
+
+/// Why a PBF file could not be read.
+#[derive(Debug, Error)]
+pub enum PbfError {
+    #[error("{file}: {source}")]
+    Io {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{file}, blob {blob}: {reason}")]
+    Blob {
+        file: String,
+        blob: usize,
+        reason: String,
+    },
+}
+
+/// Which roads a vehicle takes and how fast, by the `highway` tag of a way.
+#[derive(Clone, Debug)]
+pub struct SpeedProfile {
+    /// km/h by highway class
+    speeds: FxHashMap<String, f64>,
+}
+
+impl SpeedProfile {
+    /// A profile for cars, with speeds in km/h as they are typical of the
+    /// road classes, not as the law allows.
+    #[must_use]
+    pub fn car() -> Self {
+        let speeds = [
+            ("motorway", 90.),
+            ("motorway_link", 45.),
+            ("trunk", 85.),
+            ("trunk_link", 40.),
+            ("primary", 65.),
+            ("primary_link", 30.),
+            ("secondary", 55.),
+            ("secondary_link", 25.),
+            ("tertiary", 40.),
+            ("tertiary_link", 20.),
+            ("unclassified", 25.),
+            ("residential", 25.),
+            ("living_street", 10.),
+            ("service", 15.),
+        ];
+        Self {
+            speeds: speeds
+                .into_iter()
+                .map(|(highway, speed)| (highway.to_string(), speed))
+                .collect(),
+        }
+    }
+
+    /// Sets the speed in km/h on roads of a highway class. A speed of zero
+    /// keeps the vehicle off them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the speed is negative or not a number.
+    pub fn set_speed(&mut self, highway: &str, speed: f64) {
+        assert!(speed >= 0., "speed {speed} of {highway} is not a speed");
+        if speed == 0. {
+            self.speeds.remove(highway);
+        } else {
+            self.speeds.insert(highway.to_string(), speed);
+        }
+    }
+
+    /// The speed in km/h on roads of a highway class, if the vehicle takes
+    /// them.
+    #[must_use]
+    pub fn speed(&self, highway: &str) -> Option<f64> {
+        self.speeds.get(highway).copied()
+    }
+
+    /// The speed on a way and the direction it is open in, if the vehicle
+    /// takes it at all.
+    fn classify(&self, tags: &[(&str, &str)]) -> Option<(f64, Oneway)> {
+        let tag = |key: &str| {
+            tags.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| *value)
+        };
+        let highway = tag("highway")?;
+        let speed = self.speed(highway)?;
+        if tag("area") == Some("yes") || matches!(tag("access"), Some("no" | "private")) {
+            return None;
+        }
+        let oneway = match tag("oneway") {
+            Some("yes" | "true" | "1") => Oneway::Forward,
+            Some("-1" | "reverse") => Oneway::Reverse,
+            Some("no" | "false" | "0") => Oneway::No,
+            _ if highway == "motorway" || tag("junction") == Some("roundabout") => Oneway::Forward,
+            _ => Oneway::No,
+        };
+        Some((speed, oneway))
+    }
+}
+
+impl Default for SpeedProfile {
+    fn default() -> Self {
+        Self::car()
+    }
+}
+
+/// The directions a way is open in, relative to the order of its nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Oneway {
+    No,
+    Forward,
+    Reverse,
+}
+
+/// A road network read from OSM.
+#[derive(Clone, Debug)]
+pub struct RoadNetwork<T: std::cmp::Eq> {
+    /// the arcs in the directions their roads are open in, each weighted by
+    /// the time to travel it in tenths of a second
+    pub edges: Vec<InputEdge<T>>,
+    pub coordinates: Vec<FPCoordinate>,
+    /// the OSM id of each node
+    pub osm_ids: Vec<i64>,
+}
+
+/// A way the profile takes.
+struct Road {
+    refs: Vec<i64>,
+    speed: f64,
+    oneway: Oneway,
+}
+
+/// Reads the road network of a PBF extract that the profile takes.
+///
+/// Ways whose nodes are not all in the extract, as at the border of a clipped
+/// region, are cut where nodes are missing.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, if it needs a feature that is not
+/// supported, or if a blob is not a valid blob or block.
+pub fn read_road_network<T: std::cmp::Eq + From<usize>>(
+    filename: &str,
+    profile: &SpeedProfile,
+) -> Result<RoadNetwork<T>, PbfError> {
+    let mut roads = Vec::new();
+    for_each_block(filename, |block| {
+        block.for_each_way(|tags, refs| {
+            if let Some((speed, oneway)) = profile.classify(tags) {
+                roads.push(Road {
+                    refs,
+                    speed,
+                    oneway,
+                });
+            }
+        })
+    })?;
+    info!("kept {} ways", roads.len());
+
+    let mut locations: FxHashMap<i64, FPCoordinate> = roads
+        .iter()
+        .flat_map(|road| &road.refs)
+        .map(|&id| (id, FPCoordinate::new(0, 0)))
+        .collect();
+    let mut located = FxHashSet::default();
+    for_each_block(filename, |block| {
+        block.for_each_node(|id, coordinate| {
+            if let Some(location) = locations.get_mut(&id) {
+                *location = coordinate;
+                located.insert(id);
+            }
+        })
+    })?;
+    info!(
+        "located {} of the {} nodes on them",
+        located.len(),
+        locations.len()
+    );
+
+    // the stretches of the roads whose nodes are in the extract
+    let pieces: Vec<(&[i64], &Road)> = roads
+        .iter()
+        .flat_map(|road| {
+            road.refs
+                .split(|id| !located.contains(id))
+                .filter(|piece| piece.len() > 1)
+                .map(move |piece| (piece, road))
+        })
+        .collect();
+
+    let mut network = RoadNetwork {
+        edges: Vec::new(),
+        coordinates: Vec::new(),
+        osm_ids: Vec::new(),
+    };
+    let lines = pieces
+        .iter()
+        .map(|&(piece, road)| (piece, road.oneway))
+        .collect::<Vec<_>>();
+    let node = |id: i64| {
+        network.coordinates.push(locations[&id]);
+        network.osm_ids.push(id);
+        network.osm_ids.len() - 1
+    };
+    let deciseconds = |line: usize, piece: &[i64]| {
+        let kilometers: f64 = piece
+            .windows(2)
+            .map(|pair| {
+                let (lon1, lat1) = locations[&pair[0]].to_lon_lat_pair();
+                let (lon2, lat2) = locations[&pair[1]].to_lon_lat_pair();
+                haversine(lat1, lon1, lat2, lon2)
+            })
+            .sum();
+        Ok((kilometers / pieces[line].1.speed * 36_000.)
+            .round()
+            .max(1.) as usize)
+    };
+    let Ok(edges) = split_at_junctions::<_, _, _, Infallible>(&lines, node, deciseconds);
+    network.edges = edges;
+    info!(
+        "split the roads into {} arcs between {} nodes",
+        network.edges.len(),
+        network.coordinates.len()
+    );
+    Ok(network)
+}
+
+/// Splits lines of places into arcs at every place that more than one of them
+/// passes through, and at their ends, such that the nodes of the graph are the
+/// junctions and dead ends and the places in between only give the shape. A
+/// place is whatever tells two positions apart, e.g. the id of an OSM node.
+///
+/// `node` is called once for each place that becomes a node, in the order
+/// they are met, and returns its id. `cost` prices the stretch of a line,
+/// given by its index, from one node to the next, the places in between
+/// included. A stretch that comes back to where it started would give a loop
+/// and is passed over.
+pub(crate) fn split_at_junctions<P, C, T, E>(
+    lines: &[(&[P], Oneway)],
+    mut node: impl FnMut(P) -> NodeID,
+    mut cost: impl FnMut(usize, &[P]) -> Result<C, E>,
+) -> Result<Vec<InputEdge<T>>, E>
+where
+    P: Copy + Eq + Hash,
+    C: Copy,
+    T: Eq + From<C>,
+{
+    let mut uses = FxHashMap::<P, u32>::default();
+    for (places, _) in lines {
+        for &place in *places {
+            *uses.entry(place).or_default() += 1;
+        }
+        // the ends are nodes of the graph whether they are shared or not
+        if let (Some(&first), Some(&last)) = (places.first(), places.last()) {
+            *uses.entry(first).or_default() += 1;
+            *uses.entry(last).or_default() += 1;
+        }
+    }
+
+    let mut node_of = FxHashMap::<P, NodeID>::default();
+    let mut edges = Vec::new();
+    for (line, &(places, oneway)) in lines.iter().enumerate() {
+        let mut start = 0;
+        for end in 1..places.len() {
+            if uses[&places[end]] < 2 {
+                continue;
+            }
+            let piece = &places[start..=end];
+            start = end;
+            if piece[0] == places[end] {
+                continue;
+            }
+            let weight = cost(line, piece)?;
+            let source = *node_of.entry(piece[0]).or_insert_with(|| node(piece[0]));
+            let target = *node_of
+                .entry(places[end])
+                .or_insert_with(|| node(places[end]));
+            if oneway != Oneway::Reverse {
+                edges.push(InputEdge::new(source, target, T::from(weight)));
+            }
+            if oneway != Oneway::Forward {
+                edges.push(InputEdge::new(target, source, T::from(weight)));
+            }
+        }
+    }
+    Ok(edges)
+}
+
+/// Reads a file blob by blob and hands each primitive block to `f`, after
+/// checking that the header asks for nothing that is not supported.
+fn for_each_block(
+    filename: &str,
+    mut f: impl FnMut(&PrimitiveBlock) -> Result<(), String>,
+) -> Result<(), PbfError> {
+    let io_error = |source| PbfError::Io {
+        file: filename.to_string(),
+        source,
+    };
+    let mut reader = BufReader::new(File::open(filename).map_err(io_error)?);
+
+    for blob in 0.. {
+        let blob_error = |reason: String| PbfError::Blob {
+            file: filename.to_string(),
+            blob,
+            reason,
+        };
+        let mut size = [0; 4];
+        match read_fully(&mut reader, &mut size).map_err(io_error)? {
+            0 => return Ok(()),
+            4 => {}
+            _ => return Err(blob_error("the file ends within a blob".into())),
+        }
+        let header_size = u32::from_be_bytes(size) as usize;
+        if header_size > MAX_HEADER_SIZE {
+            return Err(blob_error(format!(
+                "a header of {header_size} bytes is larger than allowed"
+            )));
+        }
+        let header = read_exactly(&mut reader, header_size)
+            .map_err(io_error)?
+            .ok_or_else(|| blob_error("the file ends within a blob".into()))?;
+        let (kind, data_size) = blob_header(&header).map_err(blob_error)?;
+        if data_size > MAX_BLOB_SIZE {
+            return Err(blob_error(format!(
+                "a blob of {data_size} bytes is larger than allowed"
+            )));
+        }
+        let data = read_exactly(&mut reader, data_size)
+            .map_err(io_error)?
+            .ok_or_else(|| blob_error("the file ends within a blob".into()))?;
+        let data = blob_data(&data).map_err(blob_error)?;
+
+        match kind.as_str() {
+            "OSMHeader" => check_features(&data).map_err(blob_error)?,
+            "OSMData" => f(&PrimitiveBlock::new(&data).map_err(blob_error)?).map_err(blob_error)?,
+            // blobs of unknown types are for other readers
+            _ => {}
+        }
+    }
+    unreachable!("blobs are read until the file ends")
+}
+
+/// Fills `buffer` as far as the reader goes, returning how far that was.
+fn read_fully(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(filled)
+}
+
+/// Reads `size` bytes, or `None` if the reader ends before.
+fn read_exactly(reader: &mut impl Read, size: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut buffer = vec![0; size];
+    Ok((read_fully(reader, &mut buffer)? == size).then_some(buffer))
+}
+
+/// The type of a blob and its size, from its header.
+fn blob_header(header: &[u8]) -> Result<(String, usize), String> {
+    let (mut kind, mut size) = (None, None);
+    let mut message = Message::new(header);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => kind = Some(utf8(bytes)?.to_string()),
+            (3, Field::Varint(value)) => size = Some(value as usize),
+            _ => {}
+        }
+    }
+    Ok((
+        kind.ok_or("the header gives no type")?,
+        size.ok_or("the header gives no size")?,
+    ))
+}
+
+/// The content of a blob, decompressed if it has to be.
+fn blob_data(blob: &[u8]) -> Result<Vec<u8>, String> {
+    let mut message = Message::new(blob);
+    let mut raw_size = None;
+    let mut data = None;
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => data = Some(bytes.to_vec()),
+            (2, Field::Varint(size)) => raw_size = Some(size as usize),
+            (3, Field::Bytes(bytes)) => {
+                data = Some(zlib::decompress(bytes).map_err(|error| error.to_string())?);
+            }
+            (4 | 6 | 7, _) => {
+                return Err("only blobs that are raw or compressed with zlib are supported".into());
+            }
+            _ => {}
+        }
+    }
+    let data = data.ok_or("the blob holds no data")?;
+    if let Some(raw_size) = raw_size
+        && raw_size != data.len()
+    {
+        return Err(format!(
+            "the blob holds {} bytes instead of {raw_size}",
+            data.len()
+        ));
+    }
+    Ok(data)
+}
+
+/// Checks that the header block asks for no more than is supported.
+fn check_features(header: &[u8]) -> Result<(), String> {
+    let mut message = Message::new(header);
+    while let Some((number, field)) = message.next_field()? {
+        if let (4, Field::Bytes(feature)) = (number, field) {
+            let feature = utf8(feature)?;
+            if !SUPPORTED_FEATURES.contains(&feature) {
+                return Err(format!(
+                    "the file needs feature {feature}, which is not supported"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A block of nodes and ways with the strings they refer to.
+struct PrimitiveBlock<'a> {
+    strings: Vec<&'a str>,
+    groups: Vec<&'a [u8]>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl<'a> PrimitiveBlock<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, String> {
+        let mut block = Self {
+            strings: Vec::new(),
+            groups: Vec::new(),
+            granularity: 100,
+            lat_offset: 0,
+            lon_offset: 0,
+        };
+        let mut message = Message::new(data);
+        while let Some((number, field)) = message.next_field()? {
+            match (number, field) {
+                (1, Field::Bytes(table)) => {
+                    let mut table = Message::new(table);
+                    while let Some((number, field)) = table.next_field()? {
+                        if let (1, Field::Bytes(string)) = (number, field) {
+                            // tags are text, but a string that is not will
+                            // not be one of the tags looked at
+                            block
+                                .strings
+                                .push(std::str::from_utf8(string).unwrap_or(""));
+                        }
+                    }
+                }
+                (2, Field::Bytes(group)) => block.groups.push(group),
+                (17, Field::Varint(value)) => block.granularity = value as i64,
+                (19, Field::Varint(value)) => block.lat_offset = value as i64,
+                (20, Field::Varint(value)) => block.lon_offset = value as i64,
+                _ => {}
+            }
+        }
+        Ok(block)
+    }
+
+    fn string(&self, index: u64) -> Result<&'a str, String> {
+        self.strings
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("string {index} is not in the table"))
+    }
+
+    /// The coordinate of a node from its latitude and longitude in units of
+    /// the granularity.
+    fn coordinate(&self, lat: i64, lon: i64) -> FPCoordinate {
+        // nanodegrees to the millionths of a degree of a coordinate
+        let micro = |offset: i64, value: i64| {
+            ((offset + self.granularity * value) as f64 / 1000.).round() as i32
+        };
+        FPCoordinate::new(micro(self.lat_offset, lat), micro(self.lon_offset, lon))
+    }
+
+    fn for_each_node(&self, mut f: impl FnMut(i64, FPCoordinate)) -> Result<(), String> {
+        for group in &self.groups {
+            let mut message = Message::new(group);
+            while let Some((number, field)) = message.next_field()? {
+                match (number, field) {
+                    (1, Field::Bytes(node)) => {
+                        let (mut id, mut lat, mut lon) = (0, 0, 0);
+                        let mut node = Message::new(node);
+                        while let Some((number, field)) = node.next_field()? {
+                            match (number, field) {
+                                (1, Field::Varint(value)) => id = zigzag(value),
+                                (8, Field::Varint(value)) => lat = zigzag(value),
+                                (9, Field::Varint(value)) => lon = zigzag(value),
+                                _ => {}
+                            }
+                        }
+                        f(id, self.coordinate(lat, lon));
+                    }
+                    (2, Field::Bytes(dense)) => {
+                        let (mut ids, mut lats, mut lons) = (Vec::new(), Vec::new(), Vec::new());
+                        let mut dense = Message::new(dense);
+                        while let Some((number, field)) = dense.next_field()? {
+                            match (number, field) {
+                                (1, Field::Bytes(packed)) => ids = deltas(packed)?,
+                                (8, Field::Bytes(packed)) => lats = deltas(packed)?,
+                                (9, Field::Bytes(packed)) => lons = deltas(packed)?,
+                                _ => {}
+                            }
+                        }
+                        if ids.len() != lats.len() || ids.len() != lons.len() {
+                            return Err(format!(
+                                "dense nodes give {} ids, {} latitudes and {} longitudes",
+                                ids.len(),
+                                lats.len(),
+                                lons.len()
+                            ));
+                        }
+                        for ((id, lat), lon) in ids.into_iter().zip(lats).zip(lons) {
+                            f(id, self.coordinate(lat, lon));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the tags and the node ids of each way to `f`.
+    fn for_each_way(&self, mut f: impl FnMut(&[(&str, &str)], Vec<i64>)) -> Result<(), String> {
+        for group in &self.groups {
+            let mut message = Message::new(group);
+            while let Some((number, field)) = message.next_field()? {
+                let (3, Field::Bytes(way)) = (number, field) else {
+                    continue;
+                };
+                let (mut keys, mut values, mut refs) = (Vec::new(), Vec::new(), Vec::new());
+                let mut way = Message::new(way);
+                while let Some((number, field)) = way.next_field()? {
+                    match (number, field) {
+                        (2, Field::Bytes(packed)) => keys = varints(packed)?,
+                        (3, Field::Bytes(packed)) => values = varints(packed)?,
+                        (8, Field::Bytes(packed)) => refs = deltas(packed)?,
+                        _ => {}
+                    }
+                }
+                if keys.len() != values.len() {
+                    return Err(format!(
+                        "a way has {} keys but {} values",
+                        keys.len(),
+                        values.len()
+                    ));
+                }
+                let tags = keys
+                    .into_iter()
+                    .zip(values)
+                    .map(|(key, value)| Ok((self.string(key)?, self.string(value)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                f(&tags, refs);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A field of a protobuf message as it is on the wire.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// a number of 32 or 64 bits, which no field read here is
+    Fixed,
+}
+
+/// The fields of a protobuf message, one after the other.
+struct Message<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, String> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let key = varint(&mut self.data)?;
+        let field = match key & 7 {
+            0 => Field::Varint(varint(&mut self.data)?),
+            1 => self.skip(8)?,
+            2 => {
+                let length = varint(&mut self.data)? as usize;
+                let bytes = self
+                    .data
+                    .get(..length)
+                    .ok_or("a field reaches past its message")?;
+                self.data = &self.data[length..];
+                Field::Bytes(bytes)
+            }
+            5 => self.skip(4)?,
+            wire_type => return Err(format!("wire type {wire_type} is not supported")),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+
+    fn skip(&mut self, length: usize) -> Result<Field<'a>, String> {
+        if self.data.len() < length {
+            return Err("a field reaches past its message".into());
+        }
+        self.data = &self.data[length..];
+        Ok(Field::Fixed)
+    }
+}
+
+fn varint(data: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or("a number is cut short")?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("a number is longer than ten bytes".into())
+}
+
+fn zigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(bytes).map_err(|error| error.to_string())
+}
+
+/// The numbers of a packed field.
+fn varints(mut packed: &[u8]) -> Result<Vec<u64>, String> {
+    let mut values = Vec::new();
+    while !packed.is_empty() {
+        values.push(varint(&mut packed)?);
+    }
+    Ok(values)
+}
+
+/// The signed numbers of a packed field, each given as its difference to the
+/// one before.
+fn deltas(packed: &[u8]) -> Result<Vec<i64>, String> {
+    let mut last = 0_i64;
+    Ok(varints(packed)?
+        .into_iter()
+        .map(|delta| {
+            last = last.wrapping_add(zigzag(delta));
+            last
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn varint_field(out: &mut Vec<u8>, number: u64, value: u64) {
+        push_varint(out, number << 3);
+        push_varint(out, value);
+    }
+
+    fn bytes_field(out: &mut Vec<u8>, number: u64, bytes: &[u8]) {
+        push_varint(out, number << 3 | 2);
+        push_varint(out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    }
+
+    fn packed_field(out: &mut Vec<u8>, number: u64, values: impl IntoIterator<Item = u64>) {
+        let mut packed = Vec::new();
+        for value in values {
+            push_varint(&mut packed, value);
+        }
+        bytes_field(out, number, &packed);
+    }
+
+    fn unzigzag(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    fn delta_coded(values: &[i64]) -> Vec<u64> {
+        let mut last = 0;
+        values
+            .iter()
+            .map(|&value| {
+                let delta = value - last;
+                last = value;
+                unzigzag(delta)
+            })
+            .collect()
+    }
+
+    /// A zlib stream of a single stored block.
+    fn stored_zlib(data: &[u8]) -> Vec<u8> {
+        let length = data.len() as u16;
+        let mut out = vec![0x78, 0x01, 0x01];
+        out.extend(length.to_le_bytes());
+        out.extend((!length).to_le_bytes());
+        out.extend_from_slice(data);
+        out.extend(zlib::adler32(data).to_be_bytes());
+        out
+    }
+
+    fn push_blob(file: &mut Vec<u8>, kind: &str, content: &[u8], compress: bool) {
+        let mut blob = Vec::new();
+        varint_field(&mut blob, 2, content.len() as u64);
+        if compress {
+            bytes_field(&mut blob, 3, &stored_zlib(content));
+        } else {
+            bytes_field(&mut blob, 1, content);
+        }
+        let mut header = Vec::new();
+        bytes_field(&mut header, 1, kind.as_bytes());
+        varint_field(&mut header, 3, blob.len() as u64);
+        file.extend((header.len() as u32).to_be_bytes());
+        file.extend(header);
+        file.extend(blob);
+    }
+
+    fn header_block(features: &[&str]) -> Vec<u8> {
+        let mut block = Vec::new();
+        for feature in features {
+            bytes_field(&mut block, 4, feature.as_bytes());
+        }
+        block
+    }
+
+    /// The tags of a way and the ids of its nodes.
+    type TestWay<'a> = (&'a [(&'a str, &'a str)], &'a [i64]);
+
+    /// A block of nodes given in degrees and of ways, in the default
+    /// granularity.
+    fn data_block(nodes: &[(i64, f64, f64)], ways: &[TestWay]) -> Vec<u8> {
+        let mut strings = vec![""];
+        let mut index = |string| {
+            strings
+                .iter()
+                .position(|s| *s == string)
+                .unwrap_or_else(|| {
+                    strings.push(string);
+                    strings.len() - 1
+                }) as u64
+        };
+
+        // the first node on its own, the others dense
+        let mut group = Vec::new();
+        let (id, lat, lon) = nodes[0];
+        let mut node = Vec::new();
+        varint_field(&mut node, 1, unzigzag(id));
+        varint_field(&mut node, 8, unzigzag((lat * 1e7).round() as i64));
+        varint_field(&mut node, 9, unzigzag((lon * 1e7).round() as i64));
+        bytes_field(&mut group, 1, &node);
+        let mut dense = Vec::new();
+        let column = |f: fn(&(i64, f64, f64)) -> i64| {
+            delta_coded(&nodes[1..].iter().map(f).collect::<Vec<_>>())
+        };
+        packed_field(&mut dense, 1, column(|node| node.0));
+        packed_field(&mut dense, 8, column(|node| (node.1 * 1e7).round() as i64));
+        packed_field(&mut dense, 9, column(|node| (node.2 * 1e7).round() as i64));
+        bytes_field(&mut group, 2, &dense);
+
+        let mut way_group = Vec::new();
+        for (way_id, (tags, refs)) in ways.iter().enumerate() {
+            let mut way = Vec::new();
+            varint_field(&mut way, 1, way_id as u64);
+            packed_field(&mut way, 2, tags.iter().map(|(key, _)| index(key)));
+            packed_field(&mut way, 3, tags.iter().map(|(_, value)| index(value)));
+            packed_field(&mut way, 8, delta_coded(refs));
+            bytes_field(&mut way_group, 3, &way);
+        }
+
+        let mut table = Vec::new();
+        for string in &strings {
+            bytes_field(&mut table, 1, string.as_bytes());
+        }
+        let mut block = Vec::new();
+        bytes_field(&mut block, 1, &table);
+        bytes_field(&mut block, 2, &group);
+        bytes_field(&mut block, 2, &way_group);
+        block
+    }
+
+    fn pbf_file(content: &[u8]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        file
+    }
+
+    /// A primary road through a junction with a oneway street that bends
+    /// towards its end, a footway on from there, and a street that leaves the
+    /// extract.
+    fn town() -> Vec<u8> {
+        let nodes = [
+            (1, 52.50, 13.40),
+            (2, 52.50, 13.41),
+            (3, 52.50, 13.42),
+            (4, 52.51, 13.41),
+            (5, 52.505, 13.415),
+            (6, 52.52, 13.41),
+        ];
+        let primary: &[(&str, &str)] = &[("highway", "primary")];
+        let oneway: &[(&str, &str)] = &[("highway", "residential"), ("oneway", "yes")];
+        let footway: &[(&str, &str)] = &[("highway", "footway")];
+        let residential: &[(&str, &str)] = &[("highway", "residential")];
+        let ways: [TestWay; 4] = [
+            (primary, &[1, 2, 3]),
+            (oneway, &[2, 5, 4]),
+            (footway, &[4, 6]),
+            (residential, &[3, 99]),
+        ];
+        let mut file = Vec::new();
+        push_blob(
+            &mut file,
+            "OSMHeader",
+            &header_block(&["OsmSchema-V0.6", "DenseNodes"]),
+            false,
+        );
+        push_blob(&mut file, "OSMData", &data_block(&nodes, &ways), true);
+        file
+    }
+
+    fn deciseconds(from: (f64, f64), to: (f64, f64), speed: f64) -> usize {
+        (haversine(from.0, from.1, to.0, to.1) / speed * 36_000.).round() as usize
+    }
+
+    #[test]
+    fn ways_are_split_at_junctions() {
+        let file = pbf_file(&town());
+        let network =
+            read_road_network::<usize>(file.path().to_str().unwrap(), &SpeedProfile::car())
+                .unwrap();
+
+        let mut ids = network.osm_ids.clone();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        let node = |id: i64| network.osm_ids.iter().position(|&osm| osm == id).unwrap();
+        assert_eq!(
+            network.coordinates[node(4)],
+            FPCoordinate::new(52_510_000, 13_410_000)
+        );
+
+        let mut arcs: Vec<_> = network
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    network.osm_ids[edge.source],
+                    network.osm_ids[edge.target],
+                    edge.data,
+                )
+            })
+            .collect();
+        arcs.sort_unstable();
+        let primary = deciseconds((52.50, 13.40), (52.50, 13.41), 65.);
+        let bend = (haversine(52.50, 13.41, 52.505, 13.415)
+            + haversine(52.505, 13.415, 52.51, 13.41))
+            / 25.
+            * 36_000.;
+        assert_eq!(
+            arcs,
+            vec![
+                (1, 2, primary),
+                (2, 1, primary),
+                (2, 3, deciseconds((52.50, 13.41), (52.50, 13.42), 65.)),
+                (2, 4, bend.round() as usize),
+                (3, 2, deciseconds((52.50, 13.41), (52.50, 13.42), 65.)),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_profile_decides_which_roads_are_taken() {
+        let file = pbf_file(&town());
+        let mut profile = SpeedProfile::car();
+        profile.set_speed("residential", 0.);
+        profile.set_speed("footway", 5.);
+        assert_eq!(profile.speed("residential"), None);
+
+        let network = read_road_network::<usize>(file.path().to_str().unwrap(), &profile).unwrap();
+        // without the oneway street, 2 is no junction and 4 ends the footway
+        let mut arcs: Vec<_> = network
+            .edges
+            .iter()
+            .map(|edge| (network.osm_ids[edge.source], network.osm_ids[edge.target]))
+            .collect();
+        arcs.sort_unstable();
+        assert_eq!(arcs, vec![(1, 3), (3, 1), (4, 6), (6, 4)]);
+    }
+
+    #[test]
+    fn oneway_tags() {
+        let profile = SpeedProfile::car();
+        let classify = |tags: &[(&str, &str)]| profile.classify(tags).map(|(_, oneway)| oneway);
+        assert_eq!(classify(&[("highway", "primary")]), Some(Oneway::No));
+        assert_eq!(
+            classify(&[("highway", "primary"), ("oneway", "-1")]),
+            Some(Oneway::Reverse)
+        );
+        assert_eq!(classify(&[("highway", "motorway")]), Some(Oneway::Forward));
+        assert_eq!(
+            classify(&[("highway", "motorway"), ("oneway", "no")]),
+            Some(Oneway::No)
+        );
+        assert_eq!(
+            classify(&[("highway", "tertiary"), ("junction", "roundabout")]),
+            Some(Oneway::Forward)
+        );
+        assert_eq!(
+            classify(&[("highway", "service"), ("access", "private")]),
+            None
+        );
+        assert_eq!(classify(&[("building", "yes")]), None);
+    }
+
+    #[test]
+    fn broken_files_are_reported() {
+        let path = |file: &NamedTempFile| file.path().to_str().unwrap().to_string();
+        let profile = SpeedProfile::car();
+
+        let mut content = Vec::new();
+        push_blob(
+            &mut content,
+            "OSMHeader",
+            &header_block(&["OsmSchema-V0.6", "HistoricalInformation"]),
+            false,
+        );
+        let file = pbf_file(&content);
+        let error = read_road_network::<usize>(&path(&file), &profile).unwrap_err();
+        assert!(matches!(error, PbfError::Blob { blob: 0, .. }));
+        assert!(error.to_string().contains("HistoricalInformation"));
+
+        let content = town();
+        let file = pbf_file(&content[..content.len() - 10]);
+        let error = read_road_network::<usize>(&path(&file), &profile).unwrap_err();
+        assert!(matches!(error, PbfError::Blob { blob: 1, .. }));
+
+        let error = read_road_network::<usize>("no_such_file.osm.pbf", &profile).unwrap_err();
+        assert!(matches!(error, PbfError::Io { .. }));
+    }
+
+    #[test]
+    fn protobuf_numbers() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            push_varint(&mut bytes, value);
+            assert_eq!(varint(&mut bytes.as_slice()), Ok(value));
+        }
+        for value in [0, -1, 1, -64, i64::MIN, i64::MAX] {
+            assert_eq!(zigzag(unzigzag(value)), value);
+        }
+        assert!(varint(&mut [0x80_u8].as_slice()).is_err());
+        assert_eq!(deltas(&[2, 2, 3]), Ok(vec![1, 2, 0]));
+    }
+}
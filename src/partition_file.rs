@@ -0,0 +1,182 @@
+//! Partitions computed elsewhere, read into a [`LevelDirectory`].
+//!
+//! METIS and KaHIP both write a partition as a `.part` file of one line per
+//! node, the i-th line holding the block of the i-th node. Neither knows of a
+//! hierarchy, so a nested partition comes as one file per level, each of them
+//! over all nodes of the graph. Reading them finest first and checking that
+//! every cell of a level lies in a single cell of the next gives a directory
+//! that [`crate::packed_partition::PackedPartition`], the customization and the
+//! `renumber` and `sound` tools take as if `chipper` had assembled it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::io::Write;
+//! use toolbox_rs::partition_file::read_hierarchy;
+//!
+//! let mut fine = tempfile::NamedTempFile::new().unwrap();
+//! write!(fine, "0\n0\n1\n2\n2\n").unwrap();
+//! let mut coarse = tempfile::NamedTempFile::new().unwrap();
+//! write!(coarse, "0\n0\n0\n1\n1\n").unwrap();
+//!
+//! let directory = read_hierarchy(&[fine.path(), coarse.path()]).unwrap();
+//! assert_eq!(directory.levels(), 2);
+//! assert_eq!(directory.cell_of(3, 0), 2);
+//! assert_eq!(directory.cell_of(3, 1), 1);
+//! ```
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::{
+    io::read_lines,
+    level_directory::{CellId, LevelDirectory, NestingError},
+};
+
+/// Why a partition could not be read.
+#[derive(Debug, Error)]
+pub enum PartitionFileError {
+    #[error("{file}: {source}")]
+    Io {
+        file: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{file}, line {line}: {reason}")]
+    Parse {
+        file: String,
+        line: usize,
+        reason: String,
+    },
+    #[error("{0}")]
+    Nesting(#[from] NestingError),
+}
+
+/// Reads a METIS or KaHIP `.part` file, the block of one node per line.
+///
+/// Blank lines and lines starting with `%`, which METIS takes for comments,
+/// are skipped.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or a line does not hold a block id.
+pub fn read_part_file<P: AsRef<Path>>(path: P) -> Result<Vec<CellId>, PartitionFileError> {
+    let file = path.as_ref().display().to_string();
+    let lines = read_lines(&path).map_err(|source| PartitionFileError::Io {
+        file: file.clone(),
+        source,
+    })?;
+
+    let mut cells = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = line.map_err(|source| PartitionFileError::Io {
+            file: file.clone(),
+            source,
+        })?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+        let cell = line
+            .parse::<CellId>()
+            .map_err(|error| PartitionFileError::Parse {
+                file: file.clone(),
+                line: index + 1,
+                reason: format!("`{line}` is not a block id: {error}"),
+            })?;
+        cells.push(cell);
+    }
+    Ok(cells)
+}
+
+/// Reads a single `.part` file as a hierarchy of one level.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or a line does not hold a block id.
+pub fn read_partition<P: AsRef<Path>>(path: P) -> Result<LevelDirectory, PartitionFileError> {
+    read_hierarchy(&[path])
+}
+
+/// Reads one `.part` file per level, the finest first, into a directory.
+///
+/// # Errors
+///
+/// Fails if a file cannot be read, if the files do not cover the same nodes,
+/// or if a cell of one level has nodes in two cells of the next.
+pub fn read_hierarchy<P: AsRef<Path>>(paths: &[P]) -> Result<LevelDirectory, PartitionFileError> {
+    let levels = paths
+        .iter()
+        .map(read_part_file)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(LevelDirectory::from_levels(&levels)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::NamedTempFile;
+
+    fn part_file(content: &str) -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        write(file.path(), content).unwrap();
+        file
+    }
+
+    #[test]
+    fn a_part_file_is_one_block_per_line() {
+        let file = part_file("3\n1\n\n% a comment\n2\n");
+        assert_eq!(read_part_file(file.path()).unwrap(), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn a_line_that_is_no_block_is_reported_with_its_number() {
+        let file = part_file("0\n1\nx\n");
+        let error = read_part_file(file.path()).unwrap_err();
+        assert!(matches!(error, PartitionFileError::Parse { line: 3, .. }));
+        assert!(
+            error
+                .to_string()
+                .contains(", line 3: `x` is not a block id")
+        );
+    }
+
+    #[test]
+    fn a_missing_file_names_the_file() {
+        let error = read_part_file("nonexistent_file.part").unwrap_err();
+        assert!(matches!(error, PartitionFileError::Io { .. }));
+        assert!(error.to_string().starts_with("nonexistent_file.part: "));
+    }
+
+    #[test]
+    fn a_single_file_is_a_single_level() {
+        let file = part_file("0\n1\n1\n0\n");
+        let directory = read_partition(file.path()).unwrap();
+        assert_eq!(directory.levels(), 1);
+        assert_eq!(directory.cells_on_level(0), 2);
+        assert!(directory.same_cell(0, 3, 0));
+    }
+
+    #[test]
+    fn levels_that_do_not_nest_are_refused() {
+        let fine = part_file("0\n0\n1\n1\n");
+        let coarse = part_file("0\n1\n1\n1\n");
+        let error = read_hierarchy(&[fine.path(), coarse.path()]).unwrap_err();
+        assert!(matches!(
+            error,
+            PartitionFileError::Nesting(NestingError::NotNested { .. })
+        ));
+    }
+
+    #[test]
+    fn levels_over_different_nodes_are_refused() {
+        let fine = part_file("0\n0\n1\n1\n");
+        let coarse = part_file("0\n0\n0\n");
+        let error = read_hierarchy(&[fine.path(), coarse.path()]).unwrap_err();
+        assert!(matches!(
+            error,
+            PartitionFileError::Nesting(NestingError::NodeCount { .. })
+        ));
+    }
+}
@@ -1,132 +1,180 @@
-use core::cmp::max;
 use rkyv::{Archive, Deserialize, Serialize};
 use std::{
     fmt::Display,
     hash::Hash,
+    num::TryFromIntError,
     ops::{BitAnd, BitOr},
 };
 
-/// represents the hiearchical partition id scheme. The root id has ID 1 and
-/// children are shifted to the left by one and plus 0/1. The parent child
-/// relationship can thus be queried in constant time.
-#[derive(
-    Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Archive, Serialize, Deserialize,
-)]
-pub struct PartitionID(pub u32);
-
-impl PartitionID {
-    /// Returns the root id
-    pub fn root() -> PartitionID {
-        PartitionID(1)
-    }
-
-    /// Returns the parent of a given ID.
-    /// Note that the parent of the root id is always 1
-    pub fn parent(&self) -> PartitionID {
-        let new_id = max(1, self.0 >> 1);
-        PartitionID::new(new_id)
-    }
-
-    pub fn parent_at_level(&self, level: u32) -> PartitionID {
-        let parent = self.0 & (0xffff_ffff ^ ((1 << level) - 1));
-        PartitionID::new(parent)
-    }
-
-    /// Returns a left-right ordered tuple of children for a given ID
-    pub fn children(&self) -> (PartitionID, PartitionID) {
-        let temp = self.0 << 1;
-        (PartitionID(temp), PartitionID(temp + 1))
-    }
-
-    /// Returns the left child of a ID
-    pub fn left_child(&self) -> PartitionID {
-        let temp = self.0 << 1;
-        PartitionID(temp)
-    }
-
-    /// Returns the right child of a ID
-    pub fn right_child(&self) -> PartitionID {
-        let temp = self.0 << 1;
-        PartitionID(temp + 1)
-    }
-
-    /// Transform ID to its left-most descendant k levels down
-    pub fn make_leftmost_descendant(&mut self, k: usize) {
-        self.0 <<= k;
-    }
-
-    /// Transform ID to its right-most descendant k levels down
-    pub fn make_rightmost_descendant(&mut self, k: usize) {
-        self.make_leftmost_descendant(k);
-        self.0 += (1 << k) - 1;
-    }
-
-    /// Transform the ID into its left child
-    pub fn make_left_child(&mut self) {
-        self.make_leftmost_descendant(1);
-    }
-
-    /// Transform the ID into its right child
-    pub fn make_right_child(&mut self) {
-        self.make_rightmost_descendant(1);
-    }
-
-    /// Returns a new PartitionID from an u32
-    pub fn new(id: u32) -> Self {
-        // the id scheme is designed in a way that the number of leading zeros is always odd
-        debug_assert!(id != 0);
-        PartitionID(id)
-    }
-
-    /// The level in this scheme is defined by the the number of leading zeroes.
-    pub fn level(&self) -> u8 {
-        // magic number 31 := 32 - 1, as 1 is the root's ID
-        (31 - self.0.leading_zeros()).try_into().unwrap()
-    }
-
-    /// Returns whether the ID id a left child
-    pub fn is_left_child(&self) -> bool {
-        self.0.is_multiple_of(2)
-    }
+// Both id types share the scheme and all of its queries; they only differ in
+// the width of the word that holds the id and thus in the deepest level.
+macro_rules! partition_id {
+    ($(#[$meta:meta])* $name:ident, $word:ty) => {
+        $(#[$meta])*
+        #[derive(
+            Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Archive, Serialize, Deserialize,
+        )]
+        pub struct $name(pub $word);
+
+        impl $name {
+            /// The deepest level an id can be on
+            pub const MAX_LEVEL: u8 = <$word>::BITS as u8 - 1;
+
+            /// Returns the root id
+            pub fn root() -> $name {
+                $name(1)
+            }
+
+            /// Returns the parent of a given ID.
+            /// Note that the parent of the root id is always 1
+            pub fn parent(&self) -> $name {
+                let new_id = core::cmp::max(1, self.0 >> 1);
+                $name::new(new_id)
+            }
+
+            pub fn parent_at_level(&self, level: u32) -> $name {
+                let parent = self.0 & !((1 << level) - 1);
+                $name::new(parent)
+            }
+
+            /// Returns a left-right ordered tuple of children for a given ID
+            pub fn children(&self) -> ($name, $name) {
+                let temp = self.0 << 1;
+                ($name(temp), $name(temp + 1))
+            }
+
+            /// Returns the left child of a ID
+            pub fn left_child(&self) -> $name {
+                let temp = self.0 << 1;
+                $name(temp)
+            }
+
+            /// Returns the right child of a ID
+            pub fn right_child(&self) -> $name {
+                let temp = self.0 << 1;
+                $name(temp + 1)
+            }
+
+            /// Transform ID to its left-most descendant k levels down
+            pub fn make_leftmost_descendant(&mut self, k: usize) {
+                self.0 <<= k;
+            }
+
+            /// Transform ID to its right-most descendant k levels down
+            pub fn make_rightmost_descendant(&mut self, k: usize) {
+                self.make_leftmost_descendant(k);
+                self.0 += (1 << k) - 1;
+            }
+
+            /// Transform the ID into its left child
+            pub fn make_left_child(&mut self) {
+                self.make_leftmost_descendant(1);
+            }
+
+            /// Transform the ID into its right child
+            pub fn make_right_child(&mut self) {
+                self.make_rightmost_descendant(1);
+            }
+
+            #[doc = concat!("Returns a new ", stringify!($name), " from an ", stringify!($word))]
+            pub fn new(id: $word) -> Self {
+                // the id scheme is designed in a way that the number of leading zeros is always odd
+                debug_assert!(id != 0);
+                $name(id)
+            }
+
+            /// The level in this scheme is defined by the the number of leading zeroes.
+            pub fn level(&self) -> u8 {
+                // the root's ID 1 has all but the lowest bit zero
+                (Self::MAX_LEVEL as u32 - self.0.leading_zeros()).try_into().unwrap()
+            }
+
+            /// Returns whether the ID id a left child
+            pub fn is_left_child(&self) -> bool {
+                self.0.is_multiple_of(2)
+            }
+
+            /// Returns whether the ID id a right child
+            pub fn is_right_child(&self) -> bool {
+                self.0 % 2 == 1
+            }
+
+            // Returns the lowest common ancestor of this and the other ID
+            pub fn lowest_common_ancestor(&self, other: &$name) -> $name {
+                let mut left = *self;
+                let mut right = *other;
+
+                let left_level = left.level();
+                let right_level = right.level();
+
+                if left_level > right_level {
+                    left.0 >>= left_level - right_level;
+                }
+                if right_level > left_level {
+                    right.0 >>= right_level - left_level;
+                }
+
+                // the ids are on the same level now, and the ancestor is what is left
+                // of them once the bits they differ in are shifted out
+                let differing = <$word>::BITS - (left.0 ^ right.0).leading_zeros();
+                $name(left.0 >> differing)
+            }
+
+            pub fn extract_bit(&self, index: usize) -> bool {
+                let mask = 1 << index;
+                mask & self.0 > 0
+            }
+        }
 
-    /// Returns whether the ID id a right child
-    pub fn is_right_child(&self) -> bool {
-        self.0 % 2 == 1
-    }
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
 
-    // Returns the lowest common ancestor of this and the other ID
-    pub fn lowest_common_ancestor(&self, other: &PartitionID) -> PartitionID {
-        let mut left = *self;
-        let mut right = *other;
+        impl core::fmt::Binary for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                let val = self.0;
+                core::fmt::Binary::fmt(&val, f) // delegate to the word's implementation
+            }
+        }
 
-        let left_level = left.level();
-        let right_level = right.level();
+        impl BitAnd for $name {
+            type Output = Self;
 
-        if left_level > right_level {
-            left.0 >>= left_level - right_level;
-        }
-        if right_level > left_level {
-            right.0 >>= right_level - left_level;
+            fn bitand(self, rhs: Self) -> Self::Output {
+                Self(self.0 & rhs.0)
+            }
         }
 
-        while left != right {
-            left = left.parent();
-            right = right.parent();
-        }
-        left
-    }
+        impl BitOr for $name {
+            type Output = Self;
 
-    pub fn extract_bit(&self, index: usize) -> bool {
-        let mask = 1 << index;
-        mask & self.0 > 0
-    }
+            fn bitor(self, rhs: Self) -> Self::Output {
+                Self(self.0 | rhs.0)
+            }
+        }
+    };
 }
 
-impl Display for PartitionID {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
+partition_id!(
+    /// represents the hiearchical partition id scheme. The root id has ID 1 and
+    /// children are shifted to the left by one and plus 0/1. The parent child
+    /// relationship can thus be queried in constant time.
+    PartitionID,
+    u32
+);
+
+partition_id!(
+    /// The same hierarchical id scheme as [`PartitionID`], packed into a u128.
+    ///
+    /// A [`PartitionID`] shifts left by one per level and so runs out of room
+    /// after 31 bisections. The wide id has room for 127 of them, which is the
+    /// width of the word a [`crate::packed_partition::PackedPartition`] uses, and
+    /// answers the same questions in the same constant time.
+    WidePartitionID,
+    u128
+);
 
 impl From<PartitionID> for usize {
     fn from(s: PartitionID) -> usize {
@@ -134,33 +182,24 @@ impl From<PartitionID> for usize {
     }
 }
 
-impl core::fmt::Binary for PartitionID {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let val = self.0;
-        core::fmt::Binary::fmt(&val, f) // delegate to u32's implementation
+impl From<PartitionID> for WidePartitionID {
+    fn from(id: PartitionID) -> WidePartitionID {
+        WidePartitionID(id.0.into())
     }
 }
 
-impl BitAnd for PartitionID {
-    type Output = Self;
+impl TryFrom<WidePartitionID> for usize {
+    type Error = TryFromIntError;
 
-    fn bitand(self, rhs: Self) -> Self::Output {
-        Self(self.0 & rhs.0)
-    }
-}
-
-impl BitOr for PartitionID {
-    type Output = Self;
-
-    fn bitor(self, rhs: Self) -> Self::Output {
-        Self(self.0 | rhs.0)
+    fn try_from(s: WidePartitionID) -> Result<usize, Self::Error> {
+        s.0.try_into()
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::partition_id::PartitionID;
+    use crate::partition_id::{PartitionID, WidePartitionID};
 
     #[test]
     fn parent_id() {
@@ -455,4 +494,77 @@ mod tests {
         assert_eq!(node_8.lowest_common_ancestor(&node_5), node_2); // Uncle relationship
         assert_eq!(node_8.lowest_common_ancestor(&node_7), node_1); // Different subtrees
     }
+    #[test]
+    fn wide_parent_and_children() {
+        let id = WidePartitionID::new(0b0101_0101_0101_0101);
+        assert_eq!(id.level(), 14);
+        let (left, right) = id.children();
+        assert_eq!(left, id.left_child());
+        assert_eq!(right, id.right_child());
+        assert_eq!(left.parent(), id);
+        assert_eq!(right.parent(), id);
+        assert!(left.is_left_child());
+        assert!(right.is_right_child());
+        assert_eq!(WidePartitionID::root().parent(), WidePartitionID::root());
+    }
+
+    #[test]
+    fn wide_agrees_with_narrow() {
+        for raw in [1_u32, 2, 3, 12345, 0b1000, 0x7fff_ffff] {
+            let narrow = PartitionID::new(raw);
+            let wide = WidePartitionID::from(narrow);
+            assert_eq!(wide.level(), narrow.level());
+            assert_eq!(wide.parent(), narrow.parent().into());
+            assert_eq!(wide.left_child(), narrow.left_child().into());
+            assert_eq!(wide.right_child(), narrow.right_child().into());
+            for other in [1_u32, 5, 0b1001, 12344] {
+                let other = PartitionID::new(other);
+                assert_eq!(
+                    wide.lowest_common_ancestor(&other.into()),
+                    narrow.lowest_common_ancestor(&other).into()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wide_beyond_31_levels() {
+        let mut id = WidePartitionID::root();
+        id.make_rightmost_descendant(100);
+        assert_eq!(id.level(), 100);
+        id.make_left_child();
+        assert_eq!(id.level(), 101);
+        assert!(id.is_left_child());
+
+        let mut deepest = WidePartitionID::root();
+        deepest.make_leftmost_descendant(WidePartitionID::MAX_LEVEL as usize);
+        assert_eq!(deepest.level(), WidePartitionID::MAX_LEVEL);
+
+        let mut sibling = id;
+        sibling.make_right_child();
+        let mut cousin = id.parent();
+        cousin.make_rightmost_descendant(1);
+        assert_eq!(id.lowest_common_ancestor(&sibling), id);
+        assert_eq!(id.lowest_common_ancestor(&deepest), WidePartitionID::root());
+        assert_eq!(id.left_child().lowest_common_ancestor(&cousin), id.parent());
+    }
+
+    #[test]
+    fn wide_parent_at_level() {
+        let id = WidePartitionID::new(u128::MAX);
+        assert_eq!(id.parent_at_level(0), id);
+        assert_eq!(id.parent_at_level(100).0, u128::MAX << 100);
+        assert!(!id.parent_at_level(100).extract_bit(99));
+        assert!(id.parent_at_level(100).extract_bit(100));
+    }
+
+    #[test]
+    fn wide_try_into_usize_and_display() {
+        let id = WidePartitionID(12345);
+        assert_eq!(usize::try_from(id), Ok(12345));
+        // an id deeper than a usize holds does not convert rather than panic
+        assert!(usize::try_from(WidePartitionID::new(1 << 100)).is_err());
+        assert_eq!(format!("{id}"), "12345");
+        assert_eq!(format!("{id:b}"), "11000000111001");
+    }
 }
@@ -0,0 +1,380 @@
+//! How good a nested partition is, level by level.
+//!
+//! Comparing two settings of a partitioner by eye, or by the run time of the
+//! queries on top of it, mixes up what the partition is with everything else
+//! that happens to a query. The numbers here are read off the partition and
+//! the graph alone:
+//!
+//! - the cut: how many arcs run between two cells of a level
+//! - the boundary: how many border nodes each cell has, a border node being
+//!   one that an arc of the cut leaves or reaches
+//! - the imbalance: how far the largest cell is above the mean
+//! - the connectivity: how many pieces each cell falls into along its own arcs
+//! - the boundary weighted cost, `E[B^2]/E[B]`
+//!
+//! The last one is what [`crate::assembly`] argues matters. A search steps over
+//! a cell by walking the clique between its border nodes, and it reaches a cell
+//! about as often as that cell has border nodes, so what a step costs on
+//! average is the mean boundary weighted by boundary and not the mean boundary.
+//! The gap between the two is what a skewed partition costs.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::{
+//!     edge::TrivialEdge, level_directory::LevelDirectory, partition_quality::evaluate,
+//! };
+//!
+//! // a path of four nodes, cut in the middle
+//! let arcs = [(0, 1), (1, 2), (2, 3)]
+//!     .map(|(source, target)| TrivialEdge { source, target });
+//! let directory = LevelDirectory::new(vec![0, 0, 1, 1], vec![]);
+//!
+//! let quality = evaluate(&arcs, &directory, None);
+//! assert_eq!(quality.levels[0].cut_arcs, 1);
+//! assert_eq!(quality.levels[0].border_nodes(), 2);
+//! assert_eq!(quality.levels[0].imbalance(), 0.);
+//! ```
+use std::fmt::Write;
+
+use crate::{
+    edge::Edge,
+    graph::NodeID,
+    level_directory::{CellId, LevelDirectory},
+    union_find::UnionFind,
+};
+
+/// What one cell of a level looks like.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CellQuality {
+    /// how many nodes the cell holds, or what they weigh
+    pub size: usize,
+    /// how many of its nodes an arc of the cut leaves or reaches
+    pub border_nodes: usize,
+    /// how many pieces the cell falls into along its own arcs
+    pub pieces: usize,
+}
+
+/// What one level of a partition looks like.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelQuality {
+    pub level: usize,
+    /// every cell of the level, by cell id
+    pub cells: Vec<CellQuality>,
+    /// how many arcs run between two cells of the level
+    pub cut_arcs: usize,
+}
+
+impl LevelQuality {
+    /// The border nodes of all cells together.
+    #[must_use]
+    pub fn border_nodes(&self) -> usize {
+        self.cells.iter().map(|cell| cell.border_nodes).sum()
+    }
+
+    /// The largest boundary of a cell.
+    #[must_use]
+    pub fn max_border_nodes(&self) -> usize {
+        self.cells
+            .iter()
+            .map(|cell| cell.border_nodes)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The mean boundary of a cell.
+    #[must_use]
+    pub fn mean_border_nodes(&self) -> f64 {
+        if self.cells.is_empty() {
+            return 0.;
+        }
+        self.border_nodes() as f64 / self.cells.len() as f64
+    }
+
+    /// What stepping over a cell walks on average, given that a cell is
+    /// reached in proportion to its boundary: `E[B^2]/E[B]`.
+    #[must_use]
+    pub fn boundary_weighted_cost(&self) -> f64 {
+        let total = self.border_nodes();
+        if total == 0 {
+            return 0.;
+        }
+        let squared: f64 = self
+            .cells
+            .iter()
+            .map(|cell| (cell.border_nodes * cell.border_nodes) as f64)
+            .sum();
+        squared / total as f64
+    }
+
+    /// The size of the largest cell.
+    #[must_use]
+    pub fn max_size(&self) -> usize {
+        self.cells.iter().map(|cell| cell.size).max().unwrap_or(0)
+    }
+
+    /// How far the largest cell is above the mean, as a fraction of the mean.
+    /// A level of cells of one size has an imbalance of zero.
+    #[must_use]
+    pub fn imbalance(&self) -> f64 {
+        let total: usize = self.cells.iter().map(|cell| cell.size).sum();
+        if total == 0 {
+            return 0.;
+        }
+        let mean = total as f64 / self.cells.len() as f64;
+        self.max_size() as f64 / mean - 1.
+    }
+
+    /// How many cells fall into more than one piece.
+    #[must_use]
+    pub fn disconnected_cells(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.pieces > 1).count()
+    }
+}
+
+/// What every level of a partition looks like, the lowest level first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartitionQuality {
+    pub levels: Vec<LevelQuality>,
+}
+
+impl PartitionQuality {
+    /// A summary of every level as a JSON document, one object per level.
+    ///
+    /// The cells are summed up rather than listed, as the lowest level of a
+    /// continent holds hundreds of thousands of them.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n  \"levels\": [");
+        for (index, level) in self.levels.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "\n    {{\"level\": {}, \"cells\": {}, \"cut_arcs\": {}, \"border_nodes\": {}, \
+                 \"mean_border_nodes\": {}, \"max_border_nodes\": {}, \
+                 \"boundary_weighted_cost\": {}, \"max_size\": {}, \"imbalance\": {}, \
+                 \"disconnected_cells\": {}}}",
+                level.level,
+                level.cells.len(),
+                level.cut_arcs,
+                level.border_nodes(),
+                json_number(level.mean_border_nodes()),
+                level.max_border_nodes(),
+                json_number(level.boundary_weighted_cost()),
+                level.max_size(),
+                json_number(level.imbalance()),
+                level.disconnected_cells(),
+            )
+            .expect("writing to a string does not fail");
+        }
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+}
+
+/// JSON knows no infinity and no NaN, and neither is a quality of anything.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{value}")
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Measures every level of a partition on the graph it partitions.
+///
+/// The arcs are walked whichever way round they run, as a cell is crossed by
+/// a path that may take either. `node_weights` gives what each node weighs,
+/// and each weighs one without them.
+///
+/// # Panics
+///
+/// Panics if an arc reaches past the nodes of the directory, or if the weights
+/// do not hold one for every node.
+#[must_use]
+pub fn evaluate<E: Edge<ID = NodeID>>(
+    arcs: &[E],
+    directory: &LevelDirectory,
+    node_weights: Option<&[u32]>,
+) -> PartitionQuality {
+    let nodes = directory.number_of_nodes();
+    if let Some(weights) = node_weights {
+        assert_eq!(weights.len(), nodes, "a node without a weight");
+    }
+
+    // the cells of a level are read off the level below rather than asked of
+    // the directory per node, which would walk the parents of every node once
+    // for every level above it
+    let mut cell_of_node: Vec<CellId> = (0..nodes).map(|node| directory.cell_of(node, 0)).collect();
+    let mut levels = Vec::with_capacity(directory.levels());
+    for level in 0..directory.levels() {
+        if level > 0 {
+            let parents = directory.parents_on_level(level - 1);
+            for cell in &mut cell_of_node {
+                *cell = parents[*cell as usize];
+            }
+        }
+        levels.push(evaluate_level(
+            arcs,
+            level,
+            directory.cells_on_level(level),
+            &cell_of_node,
+            node_weights,
+        ));
+    }
+    PartitionQuality { levels }
+}
+
+fn evaluate_level<E: Edge<ID = NodeID>>(
+    arcs: &[E],
+    level: usize,
+    cell_count: usize,
+    cell_of_node: &[CellId],
+    node_weights: Option<&[u32]>,
+) -> LevelQuality {
+    let nodes = cell_of_node.len();
+    let mut cells = vec![CellQuality::default(); cell_count];
+    for (node, &cell) in cell_of_node.iter().enumerate() {
+        cells[cell as usize].size += node_weights.map_or(1, |weights| weights[node] as usize);
+    }
+
+    let mut cut_arcs = 0;
+    let mut on_border = vec![false; nodes];
+    let mut union = UnionFind::new(nodes);
+    for arc in arcs {
+        let (source, target) = (arc.source(), arc.target());
+        if cell_of_node[source] == cell_of_node[target] {
+            union.union(source, target);
+        } else {
+            cut_arcs += 1;
+            on_border[source] = true;
+            on_border[target] = true;
+        }
+    }
+
+    // a piece is counted on its first node, which is the one its root is
+    // first seen for
+    let mut counted = vec![false; nodes];
+    for node in 0..nodes {
+        let cell = cell_of_node[node] as usize;
+        if on_border[node] {
+            cells[cell].border_nodes += 1;
+        }
+        let root = union.find(node);
+        if !counted[root] {
+            counted[root] = true;
+            cells[cell].pieces += 1;
+        }
+    }
+
+    LevelQuality {
+        level,
+        cells,
+        cut_arcs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edge::TrivialEdge;
+
+    fn edge(source: usize, target: usize) -> TrivialEdge {
+        TrivialEdge { source, target }
+    }
+
+    /// ```text
+    ///  level 1:        0           1
+    ///                 / \          |
+    ///  level 0:      0   1         2
+    ///               /|   |\       /|\
+    ///  nodes:      0 1   2 3     4 5 6
+    /// ```
+    /// with arcs 0-1, 1-2, 2-3, 3-4, 4-5 both ways round, which leaves node 6
+    /// a piece of its own within cell 2.
+    fn example() -> (Vec<TrivialEdge>, LevelDirectory) {
+        let mut arcs = Vec::new();
+        for (source, target) in [(0, 1), (1, 2), (2, 3), (3, 4), (4, 5)] {
+            arcs.push(edge(source, target));
+            arcs.push(edge(target, source));
+        }
+        let directory = LevelDirectory::new(vec![0, 0, 1, 1, 2, 2, 2], vec![vec![0, 0, 1]]);
+        (arcs, directory)
+    }
+
+    #[test]
+    fn every_level_is_measured() {
+        let (arcs, directory) = example();
+        let quality = evaluate(&arcs, &directory, None);
+        assert_eq!(quality.levels.len(), 2);
+
+        let lowest = &quality.levels[0];
+        assert_eq!(lowest.cut_arcs, 4);
+        assert_eq!(
+            lowest.cells,
+            vec![
+                CellQuality {
+                    size: 2,
+                    border_nodes: 1,
+                    pieces: 1
+                },
+                CellQuality {
+                    size: 2,
+                    border_nodes: 2,
+                    pieces: 1
+                },
+                CellQuality {
+                    size: 3,
+                    border_nodes: 1,
+                    pieces: 2
+                },
+            ]
+        );
+        assert_eq!(lowest.border_nodes(), 4);
+        assert_eq!(lowest.max_border_nodes(), 2);
+        assert_eq!(lowest.disconnected_cells(), 1);
+        // (1 + 4 + 1) / 4
+        assert_eq!(lowest.boundary_weighted_cost(), 1.5);
+        // the largest of 3 against a mean of 7/3
+        assert!((lowest.imbalance() - (3. / (7. / 3.) - 1.)).abs() < 1e-12);
+
+        let top = &quality.levels[1];
+        assert_eq!(top.cut_arcs, 2);
+        assert_eq!(top.cells.len(), 2);
+        assert_eq!(top.border_nodes(), 2);
+        assert_eq!(top.disconnected_cells(), 1);
+    }
+
+    #[test]
+    fn weights_count_towards_the_size() {
+        let (arcs, directory) = example();
+        let weights = [1, 1, 1, 1, 5, 5, 5];
+        let quality = evaluate(&arcs, &directory, Some(&weights));
+        assert_eq!(quality.levels[0].cells[2].size, 15);
+        assert_eq!(quality.levels[1].max_size(), 15);
+    }
+
+    #[test]
+    fn a_level_in_one_cell_has_no_cut() {
+        let (arcs, _) = example();
+        let directory = LevelDirectory::new(vec![0; 7], vec![]);
+        let level = &evaluate(&arcs, &directory, None).levels[0];
+        assert_eq!(level.cut_arcs, 0);
+        assert_eq!(level.border_nodes(), 0);
+        assert_eq!(level.boundary_weighted_cost(), 0.);
+        assert_eq!(level.imbalance(), 0.);
+    }
+
+    #[test]
+    fn the_report_names_every_level() {
+        let (arcs, directory) = example();
+        let json = evaluate(&arcs, &directory, None).to_json();
+        assert!(json.starts_with("{\n  \"levels\": ["));
+        assert!(json.contains("{\"level\": 0, \"cells\": 3, \"cut_arcs\": 4,"));
+        assert!(json.contains("{\"level\": 1, \"cells\": 2, \"cut_arcs\": 2,"));
+        assert!(json.contains("\"boundary_weighted_cost\": 1.5,"));
+        assert_eq!(json.matches("\"level\"").count(), 2);
+    }
+}
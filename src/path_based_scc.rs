@@ -70,7 +70,9 @@ impl PathBasedScc {
 
     fn dfs_iterative<T>(&mut self, start: usize, graph: &(impl Graph<T> + 'static)) {
         let mut dfs_stack = vec![DfsState::Visit(start)];
-        let mut edge_indices = vec![0usize; graph.number_of_nodes()];
+        // the arcs that are left to explore of each node on the DFS path,
+        // walked in one go, as a compressed graph decodes them in order
+        let mut arcs = Vec::new();
 
         while let Some(state) = dfs_stack.pop() {
             match state {
@@ -79,17 +81,15 @@ impl PathBasedScc {
                     self.stack.push(v);
                     self.scc[v] = self.stack.len() - 1;
                     self.bounds.push(self.scc[v]);
+                    arcs.push(graph.out_arcs(v));
                     dfs_stack.push(DfsState::ProcessNeighbors(v));
                 }
 
                 DfsState::ProcessNeighbors(v) => {
-                    let edges: Vec<_> = graph.edge_range(v).collect();
-                    if edge_indices[v] < edges.len() {
-                        let e = edges[edge_indices[v]];
-                        edge_indices[v] += 1;
+                    let next = arcs.last_mut().expect("DFS path is empty").next();
+                    if let Some((_, w)) = next {
                         dfs_stack.push(DfsState::ProcessNeighbors(v));
 
-                        let w = graph.target(e);
                         if self.scc[w] == usize::MAX {
                             dfs_stack.push(DfsState::Visit(w));
                         } else {
@@ -108,6 +108,7 @@ impl PathBasedScc {
                 }
 
                 DfsState::Finalize(v) => {
+                    arcs.pop();
                     if Some(&self.scc[v]) == self.bounds.last() {
                         self.bounds.pop();
                         self.component -= 1;
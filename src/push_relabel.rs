@@ -0,0 +1,651 @@
+//! A Max-Flow computation implementing Goldberg and Tarjan's push-relabel
+//! algorithm with the highest-label selection rule of Cherkassky and Goldberg.
+//! The implementation at hand is distinguished by three factors:
+//! 1) Active nodes are discharged highest label first, which bounds the number
+//!    of pushes that do not saturate their arc by O(V² √E).
+//! 2) The gap heuristic lifts all nodes above an empty label at once, as
+//!    none of them can reach the target anymore.
+//! 3) Global relabeling recomputes the exact distances to the target in a
+//!    single BFS, once the relabels since the last one did enough work.
+//!
+//! The first phase computes a maximum preflow, which already gives the value
+//! of the flow and is where early termination on an upper bound happens. The
+//! second phase returns the excess that is stuck on the source side of the
+//! cut, so that the residual graph holds a flow and [`MaxFlow::assignment`]
+//! reports the same cut as the augmenting-path solvers do.
+//!
+//! Augmenting-path solvers spend most of their time on long paths through
+//! the dense cut problems that inertial flow produces on the coarse levels of
+//! a large graph, while push-relabel works locally and does not care.
+use crate::{
+    edge::InputEdge,
+    graph::{EdgeID, Graph, NodeID},
+    max_flow::{Capacity, MaxFlow, ResidualArcData, ResidualEdgeData, residual_graph},
+    static_graph::StaticGraph,
+};
+use bitvec::vec::BitVec;
+use core::cmp::min;
+use log::debug;
+use std::{collections::VecDeque, sync::Arc};
+
+/// The work of a relabel is its node's degree plus this constant.
+const RELABEL_WORK: usize = 12;
+/// A global relabel is due once the relabels did work of twice
+/// `NODE_WORK * V + E` since the last one.
+const NODE_WORK: usize = 6;
+
+pub struct PushRelabel<C: Capacity = i32> {
+    residual_graph: StaticGraph<ResidualArcData<C>>,
+    /// the reverse arc of each arc, i.e. the arc (v, u) of the arc (u, v)
+    reverse: Vec<u32>,
+    excess: Vec<C>,
+    label: Vec<usize>,
+    /// the arc of each node at which its next discharge resumes
+    current: Vec<EdgeID>,
+    /// the active nodes by label, below the number of nodes
+    active: Vec<Vec<NodeID>>,
+    /// all nodes by label, below the number of nodes, to find the gaps
+    buckets: Vec<Vec<NodeID>>,
+    /// position of each node in its bucket
+    slot: Vec<usize>,
+    /// upper bound to the highest label of an active node
+    highest: usize,
+    /// upper bound to the highest label of a node in a bucket
+    max_label: usize,
+    work: usize,
+    push_count: usize,
+    relabel_count: usize,
+    gap_count: usize,
+    global_relabel_count: usize,
+    max_flow: C,
+    finished: bool,
+    source: NodeID,
+    target: NodeID,
+    bound: Option<Arc<C::Atomic>>,
+}
+
+impl<C: Capacity> PushRelabel<C> {
+    /// Pushes `amount` units of flow over arc `edge` of node `u`.
+    fn push(&mut self, u: NodeID, edge: EdgeID, amount: C) {
+        self.push_count += 1;
+        let v = self.residual_graph.target(edge);
+        let residual = self.residual_graph.data_mut(edge);
+        residual.capacity -= amount;
+        residual.reverse_capacity += amount;
+        // keep the cached capacities of the arc pair in sync
+        let residual = self.residual_graph.data_mut(self.reverse[edge] as EdgeID);
+        residual.capacity += amount;
+        residual.reverse_capacity -= amount;
+        // the source has all the excess it needs, and an unsigned capacity
+        // could not count it down
+        if u != self.source {
+            self.excess[u] -= amount;
+        }
+        self.excess[v] += amount;
+    }
+
+    fn insert(&mut self, node: NodeID) {
+        let label = self.label[node];
+        self.slot[node] = self.buckets[label].len();
+        self.buckets[label].push(node);
+        self.max_label = self.max_label.max(label);
+    }
+
+    fn remove(&mut self, node: NodeID) {
+        let bucket = &mut self.buckets[self.label[node]];
+        let slot = self.slot[node];
+        bucket.swap_remove(slot);
+        if let Some(&moved) = bucket.get(slot) {
+            self.slot[moved] = slot;
+        }
+    }
+
+    /// Labels every node with its distance to the target in the residual
+    /// graph, and every node that cannot reach it with the number of nodes.
+    fn global_relabel(&mut self) {
+        self.global_relabel_count += 1;
+        self.work = 0;
+        let number_of_nodes = self.residual_graph.number_of_nodes();
+
+        self.label.fill(number_of_nodes);
+        self.label[self.target] = 0;
+        let mut queue = VecDeque::from([self.target]);
+        while let Some(u) = queue.pop_front() {
+            for edge in self.residual_graph.edge_range(u) {
+                let v = self.residual_graph.target(edge);
+                // the arc (v, u) has residual capacity left if the cached
+                // reverse capacity of (u, v) does
+                if v == self.source
+                    || self.label[v] != number_of_nodes
+                    || self.residual_graph.data(edge).reverse_capacity <= C::zero()
+                {
+                    continue;
+                }
+                self.label[v] = self.label[u] + 1;
+                queue.push_back(v);
+            }
+        }
+
+        self.buckets.iter_mut().for_each(Vec::clear);
+        self.active.iter_mut().for_each(Vec::clear);
+        self.highest = 0;
+        self.max_label = 0;
+        for node in 0..number_of_nodes {
+            self.current[node] = self.residual_graph.begin_edges(node);
+            if node == self.source || self.label[node] >= number_of_nodes {
+                continue;
+            }
+            self.insert(node);
+            if node != self.target && self.excess[node] > C::zero() {
+                self.active[self.label[node]].push(node);
+                self.highest = self.highest.max(self.label[node]);
+            }
+        }
+    }
+
+    /// Lifts node `u` to one above its lowest neighbour in the residual graph,
+    /// or all nodes above its label to the number of nodes if it leaves a gap.
+    fn relabel(&mut self, u: NodeID) {
+        self.relabel_count += 1;
+        let number_of_nodes = self.residual_graph.number_of_nodes();
+        let old_label = self.label[u];
+        self.work += RELABEL_WORK + self.residual_graph.out_degree(u);
+        self.remove(u);
+
+        if self.buckets[old_label].is_empty() {
+            // no node is left on the label of u, thus none of the nodes above
+            // it can reach the target anymore
+            self.gap_count += 1;
+            for label in old_label + 1..=self.max_label {
+                for node in self.buckets[label].drain(..) {
+                    self.label[node] = number_of_nodes;
+                }
+                self.active[label].clear();
+            }
+            self.label[u] = number_of_nodes;
+            self.max_label = old_label - 1;
+            return;
+        }
+
+        let mut new_label = number_of_nodes;
+        for edge in self.residual_graph.edge_range(u) {
+            let v = self.residual_graph.target(edge);
+            if self.residual_graph.data(edge).capacity > C::zero() && self.label[v] + 1 < new_label
+            {
+                new_label = self.label[v] + 1;
+                self.current[u] = edge;
+            }
+        }
+        self.label[u] = new_label;
+        if new_label < number_of_nodes {
+            self.insert(u);
+        }
+    }
+
+    /// Pushes the excess of node `u` to its neighbours, relabeling it
+    /// whenever it has no admissible arc left.
+    fn discharge(&mut self, u: NodeID) {
+        let number_of_nodes = self.residual_graph.number_of_nodes();
+        while self.excess[u] > C::zero() {
+            if self.current[u] == self.residual_graph.end_edges(u) {
+                self.relabel(u);
+                if self.label[u] >= number_of_nodes {
+                    // the excess of u cannot reach the target
+                    break;
+                }
+                continue;
+            }
+            let edge = self.current[u];
+            let v = self.residual_graph.target(edge);
+            let capacity = self.residual_graph.data(edge).capacity;
+            if capacity > C::zero() && self.label[u] == self.label[v] + 1 {
+                if self.excess[v] == C::zero() && v != self.target {
+                    self.active[self.label[v]].push(v);
+                    self.highest = self.highest.max(self.label[v]);
+                }
+                self.push(u, edge, min(self.excess[u], capacity));
+            } else {
+                self.current[u] += 1;
+            }
+        }
+    }
+
+    fn next_active(&mut self) -> Option<NodeID> {
+        loop {
+            if let Some(node) = self.active[self.highest].pop() {
+                return Some(node);
+            }
+            if self.highest == 0 {
+                return None;
+            }
+            self.highest -= 1;
+        }
+    }
+
+    /// Returns the excess that the first phase left on the source side of the
+    /// cut to the source, which turns the maximum preflow into a flow.
+    fn return_excess(&mut self) {
+        let number_of_nodes = self.residual_graph.number_of_nodes();
+        let mut stack = (0..number_of_nodes)
+            .filter(|&node| {
+                node != self.source && node != self.target && self.excess[node] > C::zero()
+            })
+            .collect::<Vec<_>>();
+        if stack.is_empty() {
+            return;
+        }
+        debug!("returning the excess of {} nodes", stack.len());
+
+        // label every node with its distance to the source. Any node with
+        // excess reaches the source, as its excess came from there.
+        let unreached = 2 * number_of_nodes;
+        self.label.fill(unreached);
+        self.label[self.source] = 0;
+        let mut queue = VecDeque::from([self.source]);
+        while let Some(u) = queue.pop_front() {
+            for edge in self.residual_graph.edge_range(u) {
+                let v = self.residual_graph.target(edge);
+                if self.label[v] != unreached
+                    || self.residual_graph.data(edge).reverse_capacity <= C::zero()
+                {
+                    continue;
+                }
+                self.label[v] = self.label[u] + 1;
+                queue.push_back(v);
+            }
+        }
+        for node in 0..number_of_nodes {
+            self.current[node] = self.residual_graph.begin_edges(node);
+        }
+
+        while let Some(u) = stack.pop() {
+            while self.excess[u] > C::zero() {
+                if self.current[u] == self.residual_graph.end_edges(u) {
+                    self.relabel_count += 1;
+                    self.current[u] = self.residual_graph.begin_edges(u);
+                    self.label[u] = self
+                        .residual_graph
+                        .edge_range(u)
+                        .filter(|&edge| self.residual_graph.data(edge).capacity > C::zero())
+                        .map(|edge| self.label[self.residual_graph.target(edge)] + 1)
+                        .min()
+                        .expect("node with excess has no residual arc");
+                    continue;
+                }
+                let edge = self.current[u];
+                let v = self.residual_graph.target(edge);
+                let capacity = self.residual_graph.data(edge).capacity;
+                if capacity > C::zero() && self.label[u] == self.label[v] + 1 {
+                    if self.excess[v] == C::zero() && v != self.source {
+                        stack.push(v);
+                    }
+                    self.push(u, edge, min(self.excess[u], capacity));
+                } else {
+                    self.current[u] += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<C: Capacity> MaxFlow for PushRelabel<C> {
+    type Capacity = C;
+
+    fn from_edge_list(
+        edge_list: Vec<InputEdge<ResidualEdgeData<C>>>,
+        source: NodeID,
+        target: NodeID,
+    ) -> Self {
+        let residual_graph = residual_graph(edge_list);
+        let number_of_nodes = residual_graph.number_of_nodes();
+
+        // every push updates both arcs of a pair. The adjacency blocks are
+        // sorted, but a binary search on each push is still too much.
+        let reverse = (0..number_of_nodes)
+            .flat_map(|u| residual_graph.edge_range(u).map(move |edge| (u, edge)))
+            .map(|(u, edge)| {
+                let reverse = residual_graph
+                    .find_edge_sorted(residual_graph.target(edge), u)
+                    .expect("residual graph is not symmetric");
+                u32::try_from(reverse).expect("arc ids have to fit into u32")
+            })
+            .collect();
+
+        Self {
+            residual_graph,
+            reverse,
+            excess: vec![C::zero(); number_of_nodes],
+            label: vec![0; number_of_nodes],
+            current: vec![0; number_of_nodes],
+            active: vec![Vec::new(); number_of_nodes],
+            buckets: vec![Vec::new(); number_of_nodes],
+            slot: vec![0; number_of_nodes],
+            highest: 0,
+            max_label: 0,
+            work: 0,
+            push_count: 0,
+            relabel_count: 0,
+            gap_count: 0,
+            global_relabel_count: 0,
+            max_flow: C::zero(),
+            finished: false,
+            source,
+            target,
+            bound: None,
+        }
+    }
+
+    fn run_with_upper_bound(&mut self, bound: Arc<C::Atomic>) {
+        debug!("upper bound: {}", C::load(&bound));
+
+        self.bound = Some(bound);
+        self.run()
+    }
+
+    fn run(&mut self) {
+        let number_of_nodes = self.residual_graph.number_of_nodes();
+        let number_of_edges = self.residual_graph.number_of_edges();
+        debug!("residual graph size: V {number_of_nodes}, E {number_of_edges}");
+
+        // saturate all arcs out of the source
+        for edge in self.residual_graph.edge_range(self.source) {
+            let capacity = self.residual_graph.data(edge).capacity;
+            if capacity > C::zero() {
+                self.push(self.source, edge, capacity);
+            }
+        }
+        self.global_relabel();
+
+        let global_relabel_threshold = 2 * (NODE_WORK * number_of_nodes + number_of_edges);
+        while let Some(u) = self.next_active() {
+            self.discharge(u);
+            if let Some(bound) = &self.bound {
+                // the flow into the target only grows, thus it is a lower bound
+                // to the max flow, and the search can stop once it is too large
+                if self.excess[self.target] > C::load(bound) {
+                    debug!(
+                        "aborting max flow computation at {}",
+                        self.excess[self.target]
+                    );
+                    self.max_flow = self.excess[self.target];
+                    return;
+                }
+            }
+            if self.work > global_relabel_threshold {
+                self.global_relabel();
+            }
+        }
+        let flow = self.excess[self.target];
+        self.return_excess();
+
+        if let Some(bound) = &self.bound {
+            C::fetch_min(bound, flow);
+        }
+        self.max_flow = flow;
+        self.finished = true;
+    }
+
+    fn max_flow(&self) -> Result<C, String> {
+        if !self.finished {
+            return Err("Assigment was not computed.".to_string());
+        }
+        debug!(
+            "finished in {} pushes, {} relabels, {} gaps and {} global relabels",
+            self.push_count, self.relabel_count, self.gap_count, self.global_relabel_count
+        );
+        Ok(self.max_flow)
+    }
+
+    fn assignment(&self, source: NodeID) -> Result<BitVec, String> {
+        if !self.finished {
+            return Err("Assigment was not computed.".to_string());
+        }
+
+        // run a reachability analysis
+        let mut reachable = BitVec::new();
+        reachable.resize(self.residual_graph.number_of_nodes(), false);
+        let mut stack = vec![source];
+        stack.reserve(self.residual_graph.number_of_nodes());
+        reachable.set(source, true);
+        while let Some(node) = stack.pop() {
+            for edge in self.residual_graph.edge_range(node) {
+                let target = self.residual_graph.target(edge);
+                let reached = reachable.get(target).unwrap();
+                if !reached && self.residual_graph.data(edge).capacity > C::zero() {
+                    stack.push(target);
+                    reachable.set(target, true);
+                }
+            }
+        }
+        Ok(reachable)
+    }
+
+    fn sink_assignment(&self, target: NodeID) -> Result<BitVec, String> {
+        if !self.finished {
+            return Err("Assigment was not computed.".to_string());
+        }
+
+        // run a reachability analysis backwards, where the cached reverse
+        // capacity of (u, v) is the residual capacity of (v, u)
+        let mut reaching = BitVec::repeat(false, self.residual_graph.number_of_nodes());
+        let mut stack = vec![target];
+        reaching.set(target, true);
+        while let Some(node) = stack.pop() {
+            for edge in self.residual_graph.edge_range(node) {
+                let source = self.residual_graph.target(edge);
+                if !reaching[source] && self.residual_graph.data(edge).reverse_capacity > C::zero()
+                {
+                    stack.push(source);
+                    reaching.set(source, true);
+                }
+            }
+        }
+        Ok(reaching)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicI32;
+
+    use crate::dinic::Dinic;
+    use crate::edge::InputEdge;
+    use crate::edmonds_karp::EdmondsKarp;
+    use crate::max_flow::MaxFlow;
+    use crate::max_flow::ResidualEdgeData;
+    use crate::push_relabel::PushRelabel;
+    use bitvec::bits;
+    use bitvec::prelude::Lsb0;
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+
+    /// A random graph where every node has a handful of arcs to arbitrary
+    /// other nodes, so that plenty of excess gets stuck in dead ends and has
+    /// to find its way back to the source.
+    fn random_graph(
+        rng: &mut StdRng,
+        number_of_nodes: usize,
+    ) -> (Vec<InputEdge<ResidualEdgeData>>, usize, usize) {
+        let mut edges = Vec::new();
+        for source in 0..number_of_nodes {
+            for _ in 0..rng.random_range(1..=4) {
+                let target = rng.random_range(0..number_of_nodes);
+                if source != target {
+                    edges.push(InputEdge::new(
+                        source,
+                        target,
+                        ResidualEdgeData::new(rng.random_range(1..=9)),
+                    ));
+                }
+            }
+        }
+        (edges, 0, number_of_nodes - 1)
+    }
+
+    /// The capacity of the cut that `assignment` induces on `edges`.
+    fn cut_capacity(
+        edges: &[InputEdge<ResidualEdgeData>],
+        assignment: &bitvec::vec::BitVec,
+    ) -> i32 {
+        edges
+            .iter()
+            .filter(|edge| assignment[edge.source] && !assignment[edge.target])
+            .map(|edge| edge.data.capacity)
+            .sum()
+    }
+
+    #[test]
+    fn max_flow_clr() {
+        let edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(16)),
+            InputEdge::new(0, 2, ResidualEdgeData::new(13)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(10)),
+            InputEdge::new(1, 3, ResidualEdgeData::new(12)),
+            InputEdge::new(2, 1, ResidualEdgeData::new(4)),
+            InputEdge::new(2, 4, ResidualEdgeData::new(14)),
+            InputEdge::new(3, 2, ResidualEdgeData::new(9)),
+            InputEdge::new(3, 5, ResidualEdgeData::new(20)),
+            InputEdge::new(4, 3, ResidualEdgeData::new(7)),
+            InputEdge::new(4, 5, ResidualEdgeData::new(4)),
+        ];
+
+        let source = 0;
+        let target = 5;
+        let mut max_flow_solver = PushRelabel::from_edge_list(edges, source, target);
+        max_flow_solver.run();
+
+        // it's OK to expect the solver to have run
+        let max_flow = max_flow_solver
+            .max_flow()
+            .expect("max flow computation did not run");
+        assert_eq!(23, max_flow);
+
+        // it's OK to expect the solver to have run
+        let assignment = max_flow_solver
+            .assignment(source)
+            .expect("assignment computation did not run");
+        assert_eq!(assignment, bits![1, 1, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn max_flow_ita() {
+        let edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(5)),
+            InputEdge::new(0, 4, ResidualEdgeData::new(7)),
+            InputEdge::new(0, 5, ResidualEdgeData::new(6)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(4)),
+            InputEdge::new(1, 7, ResidualEdgeData::new(3)),
+            InputEdge::new(4, 7, ResidualEdgeData::new(4)),
+            InputEdge::new(4, 6, ResidualEdgeData::new(1)),
+            InputEdge::new(5, 6, ResidualEdgeData::new(5)),
+            InputEdge::new(2, 3, ResidualEdgeData::new(3)),
+            InputEdge::new(7, 3, ResidualEdgeData::new(7)),
+            InputEdge::new(6, 7, ResidualEdgeData::new(1)),
+            InputEdge::new(6, 3, ResidualEdgeData::new(6)),
+        ];
+
+        let source = 0;
+        let target = 3;
+        let mut max_flow_solver = PushRelabel::from_edge_list(edges, source, target);
+        max_flow_solver.run();
+
+        // it's OK to expect the solver to have run
+        let max_flow = max_flow_solver
+            .max_flow()
+            .expect("max flow computation did not run");
+        assert_eq!(15, max_flow);
+
+        // it's OK to expect the solver to have run
+        let assignment = max_flow_solver
+            .assignment(source)
+            .expect("assignment computation did not run");
+        assert_eq!(assignment, bits![1, 0, 0, 0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn max_flow_matches_dinic_and_edmonds_karp_on_random_graphs() {
+        let mut rng = StdRng::seed_from_u64(0x5EED);
+        for round in 0..50 {
+            let (edges, source, target) = random_graph(&mut rng, 10 + round);
+
+            let mut reference = EdmondsKarp::from_edge_list(edges.clone(), source, target);
+            reference.run();
+            let expected = reference
+                .max_flow()
+                .expect("max flow computation did not run");
+
+            let mut dinic = Dinic::from_edge_list(edges.clone(), source, target);
+            dinic.run();
+
+            let mut solver = PushRelabel::from_edge_list(edges.clone(), source, target);
+            solver.run();
+            let max_flow = solver.max_flow().expect("max flow computation did not run");
+            assert_eq!(max_flow, expected, "round {round}");
+
+            // all solvers leave a flow behind, and thus report the same cut
+            let assignment = solver
+                .assignment(source)
+                .expect("assignment computation did not run");
+            assert_eq!(
+                assignment,
+                dinic.assignment(source).unwrap(),
+                "round {round}"
+            );
+            assert!(assignment[source], "round {round}");
+            assert!(!assignment[target], "round {round}");
+            assert_eq!(cut_capacity(&edges, &assignment), max_flow, "round {round}");
+        }
+    }
+
+    #[test]
+    fn heuristics_kick_in_on_larger_graphs() {
+        let mut rng = StdRng::seed_from_u64(0xF10);
+        let (edges, source, target) = random_graph(&mut rng, 5000);
+
+        let mut dinic = Dinic::from_edge_list(edges.clone(), source, target);
+        dinic.run();
+        let mut solver = PushRelabel::from_edge_list(edges, source, target);
+        solver.run();
+
+        assert_eq!(solver.max_flow(), dinic.max_flow());
+        assert!(solver.global_relabel_count > 1);
+        assert!(solver.gap_count > 0);
+    }
+
+    #[test]
+    fn max_flow_with_upper_bound_aborts() {
+        let edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(16)),
+            InputEdge::new(0, 2, ResidualEdgeData::new(13)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(10)),
+            InputEdge::new(1, 3, ResidualEdgeData::new(12)),
+            InputEdge::new(2, 1, ResidualEdgeData::new(4)),
+            InputEdge::new(2, 4, ResidualEdgeData::new(14)),
+            InputEdge::new(3, 2, ResidualEdgeData::new(9)),
+            InputEdge::new(3, 5, ResidualEdgeData::new(20)),
+            InputEdge::new(4, 3, ResidualEdgeData::new(7)),
+            InputEdge::new(4, 5, ResidualEdgeData::new(4)),
+        ];
+
+        let mut max_flow_solver = PushRelabel::from_edge_list(edges, 0, 5);
+        max_flow_solver.run_with_upper_bound(Arc::new(AtomicI32::new(5)));
+        assert!(max_flow_solver.max_flow().is_err());
+        assert!(max_flow_solver.assignment(0).is_err());
+    }
+
+    #[test]
+    fn max_flow_lowers_upper_bound() {
+        let edges = vec![
+            InputEdge::new(0, 1, ResidualEdgeData::new(7)),
+            InputEdge::new(1, 2, ResidualEdgeData::new(3)),
+            InputEdge::new(0, 3, ResidualEdgeData::new(2)),
+            InputEdge::new(3, 2, ResidualEdgeData::new(9)),
+        ];
+
+        let bound = Arc::new(AtomicI32::new(100));
+        let mut max_flow_solver = PushRelabel::from_edge_list(edges, 0, 2);
+        max_flow_solver.run_with_upper_bound(bound.clone());
+        assert_eq!(max_flow_solver.max_flow(), Ok(5));
+        assert_eq!(bound.load(std::sync::atomic::Ordering::Relaxed), 5);
+    }
+}
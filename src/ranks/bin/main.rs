@@ -56,8 +56,8 @@ use toolbox_rs::{
     bidirectional_mld_query::{BidirectionalMldQuery, TrackedBidirectionalMldQuery},
     border_levels::BorderLevels,
     customization::Customization,
-    edge::InputEdge,
     graph::{Graph, NodeID},
+    graph_transform,
     heap_stats::{Counters, RankTargets},
     io,
     level_directory::LevelDirectory,
@@ -373,7 +373,7 @@ fn run_time(args: &Time) -> Result<(), Box<dyn Error>> {
                     moved.on_a_border(),
                     100.0 * moved.on_a_border() as f64 / moved.len() as f64
                 );
-                graph = renumbered_of(&graph, &moved);
+                graph = graph_transform::renumber(&graph, &moved);
                 let directory = moved.renumber_directory(&directory);
                 // the pairs arrived as the numbers the input had
                 for pair in &mut pairs {
@@ -397,7 +397,7 @@ fn run_time(args: &Time) -> Result<(), Box<dyn Error>> {
                 time_bidirectional(&graph, &graph, &pairs, args.warmup)
             } else {
                 warn!("the graph is directed, so a reversed copy is being built");
-                let reverse = reverse_of(&graph);
+                let reverse = graph_transform::reverse(&graph);
                 time_bidirectional(&graph, &reverse, &pairs, args.warmup)
             }
         }
@@ -407,7 +407,7 @@ fn run_time(args: &Time) -> Result<(), Box<dyn Error>> {
         }
         Engine::BidirectionalMld => {
             let directory = directory.expect("a directory was read for the cells");
-            let reverse = reverse_of(&graph);
+            let reverse = graph_transform::reverse(&graph);
             info!("turned {} arcs around", reverse.number_of_edges());
             time_bidirectional_mld(graph, reverse, directory, &pairs, args.warmup)
         }
@@ -502,7 +502,7 @@ fn run_scans(args: &Scans) -> Result<(), Box<dyn Error>> {
     info!("read {} pairs from {}", pairs.len(), args.input);
 
     let reverse = match args.engine {
-        Engine::BidirectionalMld => Some(reverse_of(&graph)),
+        Engine::BidirectionalMld => Some(graph_transform::reverse(&graph)),
         _ => None,
     };
     let customization = Customization::new(graph, directory);
@@ -566,37 +566,6 @@ fn is_symmetric(graph: &StaticGraph<u32>) -> bool {
     })
 }
 
-/// The same arcs, between the numbers a renumbering gave their ends.
-///
-/// Walked off the graph rather than kept beside it: a continent is forty odd
-/// million arcs, and holding a second copy of them to renumber costs more than
-/// building the one that is wanted.
-fn renumbered_of(graph: &StaticGraph<u32>, ordering: &NodeOrdering) -> StaticGraph<u32> {
-    let mut edges = Vec::with_capacity(graph.number_of_edges());
-    for u in graph.node_range() {
-        let moved = ordering.new_of(u);
-        for edge in graph.edge_range(u) {
-            edges.push(InputEdge::new(
-                moved,
-                ordering.new_of(graph.target(edge)),
-                *graph.data(edge),
-            ));
-        }
-    }
-    StaticGraph::new(edges)
-}
-
-/// The graph with every arc turned around.
-fn reverse_of(graph: &StaticGraph<u32>) -> StaticGraph<u32> {
-    let mut edges = Vec::with_capacity(graph.number_of_edges());
-    for u in graph.node_range() {
-        for edge in graph.edge_range(u) {
-            edges.push(InputEdge::new(graph.target(edge), u, *graph.data(edge)));
-        }
-    }
-    StaticGraph::new(edges)
-}
-
 /// The same pairs, run from both ends at once.
 ///
 /// This is the yardstick that costs nothing to have: no preprocessing, no
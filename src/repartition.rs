@@ -0,0 +1,415 @@
+//! Repairing a nested partition after a handful of arcs changed, rather than
+//! cutting the whole graph again.
+//!
+//! An edit to the arcs leaves the sizes of the cells as they were, but it can
+//! cut a cell in two, when an arc that held it together is gone, or join cells
+//! that were apart. Everything downstream of the partition is keyed by cell
+//! id, so what matters besides repairing the cells is that the ids of all cells
+//! the edit did not touch stay what they were: only then can the customization
+//! of the untouched cells be kept.
+//!
+//! # How
+//!
+//! The lowest level goes first. The cells an edited arc touches are taken
+//! apart into the pieces they consist of under the new arcs, and those pieces
+//! are assembled again with [`assemble_connected`], but only with the other
+//! touched cells of the same parent. A cell of the result therefore never
+//! straddles two cells of the level above, and the hierarchy stays nested.
+//! The new cells take over the ids of the cells they were built from, and a
+//! level that comes out with more cells than it had before numbers the extra
+//! ones past the end. A level that comes out with fewer leaves an id behind
+//! that holds no node.
+//!
+//! The parents of the touched cells are what may have changed on the level
+//! above, so they are repaired the same way, with the cells below as what is
+//! taken apart and assembled, and so on to the topmost level.
+//!
+//! The touched cells are thus rebuilt by agglomerating their pieces, not by
+//! bisecting them again. Agglomeration is what puts a level together in the
+//! first place, and only it can be confined to the cells of one parent. The
+//! pieces weigh what their nodes weigh and are joined by what their arcs
+//! carry, so a directory assembled by weight is repaired by weight, and the
+//! sizes of its levels bound the same thing they did before.
+use std::collections::BTreeMap;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    assembly::{assemble_connected, fragments, weighted_cell_graph},
+    edge::TrivialEdge,
+    level_directory::{CellId, LevelDirectory},
+};
+
+/// Repairs the cells a set of changed arcs touches, level by level, and keeps
+/// the ids of all other cells.
+///
+/// `arcs` is the graph after the edit, and `changed` the arcs that were added
+/// or removed by it. `node_weights`, `capacities` and `sizes` are what the
+/// levels were assembled with, as given to [`weighted_cell_graph`] and
+/// [`assemble_connected`].
+///
+/// # Panics
+///
+/// Panics if there is not one size per level, if `node_weights` does not hold
+/// a weight for every node or `capacities` a capacity for every arc, or if an
+/// arc reaches past the nodes of the directory.
+#[must_use]
+pub fn repartition(
+    directory: &LevelDirectory,
+    arcs: &[TrivialEdge],
+    changed: &[TrivialEdge],
+    node_weights: Option<&[u32]>,
+    capacities: Option<&[u32]>,
+    sizes: &[usize],
+) -> LevelDirectory {
+    assert_eq!(
+        sizes.len(),
+        directory.levels(),
+        "one size per level expected"
+    );
+    let nodes = directory.number_of_nodes();
+    if let Some(weights) = node_weights {
+        assert_eq!(weights.len(), nodes, "a node without a weight");
+    }
+    if let Some(capacities) = capacities {
+        assert_eq!(capacities.len(), arcs.len(), "an arc without a capacity");
+    }
+    let graph = WeightedArcs {
+        arcs,
+        node_weights,
+        capacities,
+    };
+    let levels = directory.levels();
+
+    let mut base: Vec<CellId> = (0..nodes).map(|node| directory.cell_of(node, 0)).collect();
+    let mut parents: Vec<Vec<CellId>> = (0..levels - 1)
+        .map(|level| directory.parents_on_level(level).to_vec())
+        .collect();
+
+    let mut touched: FxHashSet<CellId> = changed
+        .iter()
+        .flat_map(|arc| [base[arc.source], base[arc.target]])
+        .collect();
+
+    // on the lowest level the nodes are what is taken apart and assembled
+    let unit_of_node: Vec<CellId> = (0..nodes)
+        .map(|node| CellId::try_from(node).expect("more nodes than cell ids"))
+        .collect();
+    let mut cells_on_level = directory.cells_on_level(0);
+    touched = reassemble(
+        &graph,
+        &unit_of_node,
+        &mut base,
+        parents.first_mut(),
+        &mut cells_on_level,
+        &touched,
+        sizes[0],
+    );
+
+    // above it, the cells of the level below are
+    let mut cell_of_node = base.clone();
+    for level in 1..levels {
+        if touched.is_empty() {
+            break;
+        }
+        let (below, above) = parents.split_at_mut(level);
+        let mut cells_on_level = directory.cells_on_level(level);
+        touched = reassemble(
+            &graph,
+            &cell_of_node,
+            &mut below[level - 1],
+            above.first_mut(),
+            &mut cells_on_level,
+            &touched,
+            sizes[level],
+        );
+        for cell in &mut cell_of_node {
+            *cell = below[level - 1][*cell as usize];
+        }
+    }
+
+    LevelDirectory::new(base, parents)
+}
+
+/// The arcs after the edit, and what the nodes weigh and the arcs carry if
+/// not one each.
+struct WeightedArcs<'a> {
+    arcs: &'a [TrivialEdge],
+    node_weights: Option<&'a [u32]>,
+    capacities: Option<&'a [u32]>,
+}
+
+/// Assembles the units of the touched cells of one level again, parent by
+/// parent, and returns the cells of the level above that now hold changed
+/// cells.
+///
+/// `unit_of_node` says which unit each node belongs to, and `cell_of_unit`
+/// which cell of the level each unit is in. The ids of the touched cells are
+/// handed on to what they become, and new cells are numbered from
+/// `cells_on_level` on and given the parent of the cells they were built from.
+fn reassemble(
+    graph: &WeightedArcs,
+    unit_of_node: &[CellId],
+    cell_of_unit: &mut [CellId],
+    mut parent_of_cell: Option<&mut Vec<CellId>>,
+    cells_on_level: &mut usize,
+    touched: &FxHashSet<CellId>,
+    size: usize,
+) -> FxHashSet<CellId> {
+    let parent_of = |cell: CellId, parents: &Option<&mut Vec<CellId>>| {
+        parents.as_ref().map_or(0, |parents| parents[cell as usize])
+    };
+
+    // the touched cells of one parent are assembled together, and in the
+    // order of their parents so that the fresh ids do not depend on hashing
+    let mut groups: BTreeMap<CellId, Vec<CellId>> = BTreeMap::new();
+    for &cell in touched {
+        groups
+            .entry(parent_of(cell, &parent_of_cell))
+            .or_default()
+            .push(cell);
+    }
+    let group_of_cell: FxHashMap<CellId, usize> = groups
+        .values()
+        .enumerate()
+        .flat_map(|(group, cells)| cells.iter().map(move |&cell| (cell, group)))
+        .collect();
+
+    // the units of the touched cells, numbered within their group
+    let mut units: Vec<Vec<usize>> = vec![Vec::new(); groups.len()];
+    let mut local_of_unit: FxHashMap<usize, usize> = FxHashMap::default();
+    let mut sizes: Vec<Vec<u32>> = vec![Vec::new(); groups.len()];
+    for (node, &unit) in unit_of_node.iter().enumerate() {
+        let unit = unit as usize;
+        let Some(&group) = group_of_cell.get(&cell_of_unit[unit]) else {
+            continue;
+        };
+        let local = *local_of_unit.entry(unit).or_insert_with(|| {
+            units[group].push(unit);
+            sizes[group].push(0);
+            units[group].len() - 1
+        });
+        sizes[group][local] += graph.node_weights.map_or(1, |weights| weights[node]);
+    }
+
+    // the arcs between two units of the same group, and what they carry
+    let mut local_arcs: Vec<Vec<TrivialEdge>> = vec![Vec::new(); groups.len()];
+    let mut local_capacities: Vec<Vec<u32>> = vec![Vec::new(); groups.len()];
+    for (index, arc) in graph.arcs.iter().enumerate() {
+        let (source, target) = (
+            unit_of_node[arc.source] as usize,
+            unit_of_node[arc.target] as usize,
+        );
+        let (Some(&source_local), Some(&target_local)) =
+            (local_of_unit.get(&source), local_of_unit.get(&target))
+        else {
+            continue;
+        };
+        let group = group_of_cell[&cell_of_unit[source]];
+        if source != target && group == group_of_cell[&cell_of_unit[target]] {
+            local_arcs[group].push(TrivialEdge {
+                source: source_local,
+                target: target_local,
+            });
+            if let Some(capacities) = graph.capacities {
+                local_capacities[group].push(capacities[index]);
+            }
+        }
+    }
+
+    let mut touched_above = FxHashSet::default();
+    for (group, (&parent, old_cells)) in groups.iter().enumerate() {
+        let units = &units[group];
+        let old_cell_of_unit: Vec<CellId> = units.iter().map(|&unit| cell_of_unit[unit]).collect();
+        let pieces = fragments(units.len(), &local_arcs[group], &old_cell_of_unit);
+        let cells = weighted_cell_graph(
+            &local_arcs[group],
+            &pieces,
+            Some(&sizes[group]),
+            graph.capacities.map(|_| local_capacities[group].as_slice()),
+        );
+        let assembled = assemble_connected(&cells, &pieces, &[size]);
+
+        let mut ids = old_cells.clone();
+        ids.sort_unstable();
+        let mut id_of = FxHashMap::default();
+        for (local, &unit) in units.iter().enumerate() {
+            let new_cell = assembled.cell_of(local, 0) as usize;
+            let id = *id_of.entry(new_cell).or_insert_with(|| {
+                if let Some(&id) = ids.get(new_cell) {
+                    return id;
+                }
+                let id = CellId::try_from(*cells_on_level).expect("more cells than cell ids");
+                *cells_on_level += 1;
+                if let Some(parents) = parent_of_cell.as_mut() {
+                    parents.push(parent);
+                }
+                id
+            });
+            cell_of_unit[unit] = id;
+        }
+        if parent_of_cell.is_some() {
+            touched_above.insert(parent);
+        }
+    }
+    touched_above
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::{cell_graph, fragments};
+
+    fn both_ways(pairs: &[(usize, usize)]) -> Vec<TrivialEdge> {
+        pairs
+            .iter()
+            .flat_map(|&(source, target)| {
+                [
+                    TrivialEdge { source, target },
+                    TrivialEdge {
+                        source: target,
+                        target: source,
+                    },
+                ]
+            })
+            .collect()
+    }
+
+    /// A grid of the given width and height, node `x + y * width`.
+    fn grid(width: usize, height: usize) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let node = x + y * width;
+                if x + 1 < width {
+                    pairs.push((node, node + 1));
+                }
+                if y + 1 < height {
+                    pairs.push((node, node + width));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn assembled(arcs: &[TrivialEdge], nodes: usize, sizes: &[usize]) -> LevelDirectory {
+        // every node its own cell to start with
+        let singles: Vec<CellId> = (0..nodes as CellId).collect();
+        let pieces = fragments(nodes, arcs, &singles);
+        assemble_connected(&cell_graph(arcs, &pieces), &pieces, sizes)
+    }
+
+    fn cells_hold_together(directory: &LevelDirectory, arcs: &[TrivialEdge]) -> bool {
+        (0..directory.levels()).all(|level| {
+            let cell_of: Vec<CellId> = (0..directory.number_of_nodes())
+                .map(|node| directory.cell_of(node, level))
+                .collect();
+            let pieces = fragments(cell_of.len(), arcs, &cell_of);
+            let mut piece_of_cell = FxHashMap::default();
+            cell_of
+                .iter()
+                .zip(&pieces)
+                .all(|(cell, piece)| *piece_of_cell.entry(*cell).or_insert(*piece) == *piece)
+        })
+    }
+
+    #[test]
+    fn an_edit_that_changes_nothing_changes_no_id() {
+        let arcs = both_ways(&grid(8, 8));
+        let directory = assembled(&arcs, 64, &[4, 16]);
+        let changed = both_ways(&[(0, 1)]);
+        let repaired = repartition(&directory, &arcs, &changed, None, None, &[4, 16]);
+        let touched = [directory.cell_of(0, 1), directory.cell_of(1, 1)];
+        for node in 0..64 {
+            if !touched.contains(&directory.cell_of(node, 1)) {
+                for level in 0..2 {
+                    assert_eq!(
+                        repaired.cell_of(node, level),
+                        directory.cell_of(node, level)
+                    );
+                }
+            }
+        }
+        assert!(cells_hold_together(&repaired, &arcs));
+    }
+
+    #[test]
+    fn a_cell_cut_in_two_is_repaired_and_the_rest_keeps_its_ids() {
+        let pairs = grid(8, 8);
+        let arcs = both_ways(&pairs);
+        let sizes = [8, 32];
+        let directory = assembled(&arcs, 64, &sizes);
+
+        // take out every arc inside the cell of node 0, which cuts it apart
+        let cell = directory.cell_of(0, 0);
+        let (removed, kept): (Vec<_>, Vec<_>) = pairs.iter().partition(|&&(source, target)| {
+            directory.cell_of(source, 0) == cell && directory.cell_of(target, 0) == cell
+        });
+        let arcs_after = both_ways(&kept);
+        let changed = both_ways(&removed);
+        assert!(!cells_hold_together(&directory, &arcs_after));
+
+        let repaired = repartition(&directory, &arcs_after, &changed, None, None, &sizes);
+        assert!(cells_hold_together(&repaired, &arcs_after));
+        for node in 0..64 {
+            if directory.cell_of(node, 1) != directory.cell_of(0, 1) {
+                for level in 0..2 {
+                    assert_eq!(
+                        repaired.cell_of(node, level),
+                        directory.cell_of(node, level),
+                        "node {node} on level {level} was not touched"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_single_level_is_repaired_on_its_own() {
+        let directory = LevelDirectory::new(vec![0, 0, 0, 1], vec![]);
+        let arcs_after = both_ways(&[(0, 1), (2, 3)]);
+        let repaired = repartition(
+            &directory,
+            &arcs_after,
+            &both_ways(&[(1, 2)]),
+            None,
+            None,
+            &[3],
+        );
+
+        assert_eq!(repaired.cell_of(0, 0), 0);
+        assert_eq!(repaired.cell_of(1, 0), 0);
+        // node 2 was cut off its cell and is numbered past the end, while the
+        // cell of node 3 was not touched and is left as it was
+        assert_eq!(repaired.cell_of(2, 0), 2);
+        assert_eq!(repaired.cell_of(3, 0), 1);
+        assert!(cells_hold_together(&repaired, &arcs_after));
+    }
+
+    #[test]
+    fn a_directory_assembled_by_weight_is_repaired_by_weight() {
+        // two pairs of nodes that weigh two each, in cells of up to four
+        let weights = [2, 2, 2, 2];
+        let arcs = both_ways(&[(0, 1), (2, 3)]);
+        let pieces = fragments(4, &arcs, &[0, 1, 2, 3]);
+        let cells = weighted_cell_graph(&arcs, &pieces, Some(&weights), None);
+        let directory = assemble_connected(&cells, &pieces, &[4]);
+        assert_ne!(directory.cell_of(1, 0), directory.cell_of(2, 0));
+
+        // an arc joins the pairs, which by count would fit into one cell
+        let changed = both_ways(&[(1, 2)]);
+        let arcs_after = both_ways(&[(0, 1), (1, 2), (2, 3)]);
+        let by_count = repartition(&directory, &arcs_after, &changed, None, None, &[4]);
+        assert_eq!(by_count.cell_of(1, 0), by_count.cell_of(2, 0));
+        let by_weight = repartition(
+            &directory,
+            &arcs_after,
+            &changed,
+            Some(&weights),
+            Some(&[1, 1, 1, 1, 1, 1]),
+            &[4],
+        );
+        assert_ne!(by_weight.cell_of(1, 0), by_weight.cell_of(2, 0));
+        assert_eq!(by_weight.cell_of(0, 0), by_weight.cell_of(1, 0));
+    }
+}
@@ -9,7 +9,7 @@ use rayon::prelude::*;
 use rustc_hash::{FxHashMap, FxHashSet};
 use toolbox_rs::{
     bounding_box::BoundingBox, convex_hull::monotone_chain, edge::InputEdge,
-    geometry::FPCoordinate, io, partition_id::PartitionID, space_filling_curve::zorder_cmp,
+    geometry::FPCoordinate, io, partition_id::WidePartitionID, space_filling_curve::zorder_cmp,
 };
 
 // TODO: tool to generate all the runtime data
@@ -28,7 +28,7 @@ pub fn main() {
     let args = <Arguments as clap::Parser>::parse();
     info!("{args}");
 
-    let partition_ids = io::read_vec_from_file::<PartitionID>(&args.partition_file);
+    let partition_ids = io::read_partition_ids(&args.partition_file);
     info!("loaded {} partition ids", partition_ids.len());
 
     let coordinates = io::read_vec_from_file::<FPCoordinate>(&args.coordinates_file);
@@ -52,7 +52,7 @@ pub fn main() {
 
     if !args.convex_cells_geojson.is_empty() {
         info!("generating convex hulls");
-        let mut cells: FxHashMap<PartitionID, Vec<usize>> = FxHashMap::default();
+        let mut cells: FxHashMap<WidePartitionID, Vec<usize>> = FxHashMap::default();
         for (i, partition_id) in partition_ids.iter().enumerate() {
             if !cells.contains_key(partition_id) {
                 cells.insert(*partition_id, Vec::new());
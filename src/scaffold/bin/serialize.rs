@@ -2,10 +2,12 @@ use std::{fs::File, io::BufWriter};
 
 use geojson::{Feature, FeatureWriter, Geometry, GeometryValue, feature::Id};
 use itertools::Itertools;
-use toolbox_rs::{bounding_box::BoundingBox, geometry::FPCoordinate, partition_id::PartitionID};
+use toolbox_rs::{
+    bounding_box::BoundingBox, geometry::FPCoordinate, partition_id::WidePartitionID,
+};
 
 pub(crate) fn convex_cell_hull_geojson(
-    hulls: &[(Vec<FPCoordinate>, BoundingBox, &PartitionID)],
+    hulls: &[(Vec<FPCoordinate>, BoundingBox, &WidePartitionID)],
     filename: &str,
 ) {
     let file = BufWriter::new(File::create(filename).expect("output file cannot be opened"));
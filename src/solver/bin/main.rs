@@ -26,6 +26,14 @@ struct Args {
     /// Whether to run the test cases
     #[clap(short, long)]
     test: bool,
+
+    /// Path of a TSPLIB .tour file to write the computed tour to
+    #[clap(long, value_parser)]
+    tour_output: Option<PathBuf>,
+
+    /// Path of a TSPLIB .tour file, e.g. the optimal tour, to compare against
+    #[clap(long, value_parser)]
+    reference_tour: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -51,86 +59,91 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("Input file required when not in test mode");
     println!("Reading TSP file: {}", input_path.display());
     let start = Instant::now();
-    let instance =
-        tsplib::read_tsp_file(input_path.to_str().unwrap()).expect("graph could not be read");
-    let (sites, matrix): (Option<Vec<tsplib::TspSite>>, Option<Vec<Vec<i32>>>) = match instance {
-        tsplib::TspInstance::Coordinates(sites) => (Some(sites), None),
-        tsplib::TspInstance::ExplicitMatrix(matrix) => (None, Some(matrix)),
-    };
-    if let Some(ref sites) = sites {
-        println!("Read {} sites in {:?}", sites.len(), start.elapsed());
-    } else if let Some(ref matrix) = matrix {
-        println!(
-            "Read explicit distance matrix ({}x{}) in {:?}",
-            matrix.len(),
-            matrix[0].len(),
-            start.elapsed()
-        );
+    let problem =
+        tsplib::read_problem(input_path.to_str().unwrap()).expect("graph could not be read");
+    println!(
+        "Read {:?} problem '{}' with {} nodes and {:?} edge weights in {:?}",
+        problem.problem_type,
+        problem.name,
+        problem.dimension,
+        problem.edge_weight_type,
+        start.elapsed()
+    );
+    if problem.problem_type == tsplib::ProblemType::Cvrp {
+        println!("Note: solving the CVRP as a TSP, capacities and demands are ignored");
     }
 
     // Build a complete graph with distances between all sites
     let start = Instant::now();
     println!("Building distance matrix...");
-    let num_nodes = if let Some(ref s) = sites {
-        s.len()
-    } else {
-        matrix.as_ref().unwrap().len()
-    };
+    let num_nodes = problem.dimension;
     let mut graph = CompleteGraph::new(num_nodes);
-
-    if let Some(ref sites) = sites {
-        // Compute distances between all pairs of sites (coordinate mode)
-        for i in 0..num_nodes {
-            for j in 0..num_nodes {
-                if i == j {
-                    continue; // Skip diagonal
-                }
-                let distance = tsplib::euclidean_distance(&sites[i], &sites[j]);
-                *graph.get_mut(i, j) = distance;
-            }
-        }
-    } else if let Some(ref matrix) = matrix {
-        // Use explicit matrix
-        for (i, row) in matrix.iter().enumerate().take(num_nodes) {
-            for (j, value) in row.iter().enumerate().take(num_nodes) {
-                *graph.get_mut(i, j) = *value;
+    for i in 0..num_nodes {
+        for j in 0..num_nodes {
+            if i == j {
+                continue; // Skip diagonal
             }
+            *graph.get_mut(i, j) = problem.distance(i, j);
         }
     }
     println!("Built distance matrix in {:?}", start.elapsed());
 
     // Solve TSP using a simple algorithm (nearest neighbor heuristic)
-    if args.nearest_neighbor {
+    let tour = if args.nearest_neighbor {
         let start = Instant::now();
-        let (_tour, length) = solve_nearest_neighbor(&graph);
+        let (tour, length) = solve_nearest_neighbor(&graph);
         println!(
             "Nearest neighbor tour length: {} (computed in {:?})",
             length,
             start.elapsed()
         );
-        println!("Tour length: {:?}", _tour.len());
+        println!("Tour length: {:?}", tour.len());
+        Some(tour)
     } else if args.brute_force {
         let start = Instant::now();
-        let (_tour, length) = solve_brute_force(&graph);
+        let (tour, length) = solve_brute_force(&graph);
         println!(
             "Brute force tour length: {} (computed in {:?})",
             length,
             start.elapsed()
         );
-        println!("Tour length: {:?}", _tour.len());
+        println!("Tour length: {:?}", tour.len());
+        Some(tour)
     } else if args.dynamic_programming {
         let start = Instant::now();
-        let (_tour, length) = solve_dynamic_programming(&graph);
+        let (tour, length) = solve_dynamic_programming(&graph);
         println!(
             "Dynamic programming tour length: {} (computed in {:?})",
             length,
             start.elapsed()
         );
-        println!("Tour length: {:?}", _tour.len());
+        println!("Tour length: {:?}", tour.len());
+        Some(tour)
     } else {
         println!(
             "No solving method specified. Use --nearest-neighbor for nearest neighbor heuristic, --brute-force for optimal solution (small instances only), or --dynamic-programming for optimal solution (medium-sized instances)."
         );
+        None
+    };
+
+    if let Some(reference) = &args.reference_tour {
+        let reference = tsplib::read_tour_file(reference.to_str().unwrap())?;
+        let reference_length = problem.tour_length(&reference.nodes);
+        println!("Reference tour length: {reference_length}");
+        if let Some(tour) = &tour {
+            let gap = (problem.tour_length(tour) - reference_length) as f64
+                / reference_length as f64
+                * 100.0;
+            println!("Gap to reference tour: {gap:.2}%");
+        }
+    }
+    if let (Some(path), Some(tour)) = (&args.tour_output, tour) {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        tsplib::write_tour_file(path.to_str().unwrap(), &tsplib::Tour { name, nodes: tour })?;
+        println!("Wrote tour to {}", path.display());
     }
 
     Ok(())
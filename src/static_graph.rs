@@ -102,6 +102,22 @@ impl<T: Ord + Copy> StaticGraph<T> {
         Self::assemble(nodes, input)
     }
 
+    /// Assembles a graph like [`Self::new_with_nodes`] and tells where each arc
+    /// came from: the i-th entry is the position in `input` of arc i.
+    ///
+    /// Sorting the input moves its arcs, and whatever is kept about them beside
+    /// the graph has to move along. Arcs that compare equal keep the order they
+    /// were given in.
+    pub fn new_with_origin<E: Edge<ID = NodeID> + EdgeData<DATA = T> + Ord>(
+        nodes: usize,
+        input: Vec<E>,
+    ) -> (Self, Vec<EdgeID>) {
+        let mut indexed: Vec<(E, EdgeID)> = input.into_iter().zip(0..).collect();
+        indexed.sort_by(|a, b| a.0.cmp(&b.0));
+        let (input, origin): (Vec<E>, Vec<EdgeID>) = indexed.into_iter().unzip();
+        (Self::assemble(nodes, input), origin)
+    }
+
     /// Assembles a graph from a prebuilt adjacency array. The caller has to
     /// guarantee that `node_array` is non-decreasing, starts at zero, ends at
     /// `edge_array.len()` and that the targets within each adjacency block are
@@ -8,7 +8,6 @@ struct DFSNode {
     caller: NodeID,
     index: usize,
     lowlink: usize,
-    neighbor: usize,
     on_stack: bool,
 }
 
@@ -18,7 +17,6 @@ impl DFSNode {
             caller: NodeID::MAX,
             index: usize::MAX,
             lowlink: usize::MAX,
-            neighbor: usize::MAX,
             on_stack: false,
         }
     }
@@ -58,19 +56,18 @@ impl Tarjan {
             self.stack_push(n, NodeID::MAX, index);
             index += 1;
             let mut last = n;
+            // the arcs that are left to explore of each node on the DFS path,
+            // walked in one go, as a compressed graph decodes them in order
+            let mut arcs = vec![graph.out_arcs(n)];
 
             loop {
-                if self.dfs_state[last].neighbor < graph.out_degree(last) {
-                    let e = graph
-                        .edge_range(last)
-                        .nth(self.dfs_state[last].neighbor)
-                        .expect("edge range exhausted");
-                    let w = graph.target(e);
-                    self.dfs_state[last].neighbor += 1;
+                let next = arcs.last_mut().expect("DFS path is empty").next();
+                if let Some((_, w)) = next {
                     if self.dfs_state[w].index == usize::MAX {
                         self.stack_push(w, last, index);
                         index += 1;
                         last = w;
+                        arcs.push(graph.out_arcs(w));
                     } else if self.dfs_state[w].on_stack {
                         self.dfs_state[last].lowlink =
                             min(self.dfs_state[last].lowlink, self.dfs_state[w].index);
@@ -92,6 +89,7 @@ impl Tarjan {
                         info!("detected SCC of size {size}");
                     }
 
+                    arcs.pop();
                     let new_last = self.dfs_state[last].caller;
                     if new_last != NodeID::MAX {
                         self.dfs_state[new_last].lowlink = min(
@@ -111,7 +109,6 @@ impl Tarjan {
 
     fn stack_push(&mut self, w: NodeID, caller: NodeID, index: usize) {
         self.dfs_state[w].caller = caller;
-        self.dfs_state[w].neighbor = 0;
         self.dfs_state[w].index = index;
         self.dfs_state[w].lowlink = index;
         self.dfs_state[w].on_stack = true;
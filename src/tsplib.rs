@@ -1,7 +1,11 @@
 use log::debug;
 
 use crate::{geometry::IPoint2D, io::read_lines};
-use std::str::FromStr;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    str::FromStr,
+};
 
 /// A site in a TSP problem, consisting of an ID and integer coordinates
 #[derive(Debug, Clone)]
@@ -10,6 +14,18 @@ pub struct TspSite {
     pub coordinate: IPoint2D,
 }
 
+/// A node of a TSPLIB problem with its coordinates as given in the file.
+///
+/// Two-dimensional problems leave `z` at zero. For GEO problems `x` holds the
+/// latitude and `y` the longitude, both in the DDD.MM notation of TSPLIB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TspNode {
+    pub id: usize,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
 #[derive(Debug)]
 pub enum TspError {
     IoError(std::io::Error),
@@ -22,6 +38,17 @@ impl From<std::io::Error> for TspError {
     }
 }
 
+impl std::fmt::Display for TspError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TspError::IoError(error) => write!(f, "I/O error: {error}"),
+            TspError::ParseError(reason) => write!(f, "TSPLIB parse error: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for TspError {}
+
 /// Represents a TSP instance, which can be either a set of coordinates or an explicit distance matrix
 #[derive(Debug, Clone)]
 pub enum TspInstance {
@@ -29,174 +56,583 @@ pub enum TspInstance {
     ExplicitMatrix(Vec<Vec<i32>>),
 }
 
-/// Parse a TSP file containing site coordinates or explicit edge weights.
+/// The TYPE of a TSPLIB problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemType {
+    /// Symmetric travelling salesman problem
+    Tsp,
+    /// Asymmetric travelling salesman problem
+    Atsp,
+    /// Hamiltonian cycle problem
+    Hcp,
+    /// Capacitated vehicle routing problem
+    Cvrp,
+}
+
+impl FromStr for ProblemType {
+    type Err = TspError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "TSP" => Ok(ProblemType::Tsp),
+            "ATSP" => Ok(ProblemType::Atsp),
+            "HCP" => Ok(ProblemType::Hcp),
+            "CVRP" => Ok(ProblemType::Cvrp),
+            other => Err(TspError::ParseError(format!(
+                "Unsupported problem TYPE: {other}"
+            ))),
+        }
+    }
+}
+
+/// The EDGE_WEIGHT_TYPE of a TSPLIB problem, i.e. how the distance between two
+/// nodes is obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeWeightType {
+    /// Distances are listed in the EDGE_WEIGHT_SECTION
+    Explicit,
+    Euc2D,
+    Euc3D,
+    Max2D,
+    Max3D,
+    Man2D,
+    Man3D,
+    Ceil2D,
+    /// Great circle distance on an idealised sphere
+    Geo,
+    /// Pseudo-Euclidean distance of the att instances
+    Att,
+}
+
+impl EdgeWeightType {
+    /// Number of coordinates per node that this weight type reads.
+    pub fn dimensions(&self) -> usize {
+        match self {
+            EdgeWeightType::Euc3D | EdgeWeightType::Max3D | EdgeWeightType::Man3D => 3,
+            _ => 2,
+        }
+    }
+
+    /// Computes the distance between two nodes with the rounding prescribed
+    /// by TSPLIB. Explicit weights have no metric and always yield zero.
+    pub fn distance(&self, a: &TspNode, b: &TspNode) -> i32 {
+        let xd = a.x - b.x;
+        let yd = a.y - b.y;
+        let zd = a.z - b.z;
+        match self {
+            EdgeWeightType::Explicit => 0,
+            EdgeWeightType::Euc2D => nint((xd * xd + yd * yd).sqrt()),
+            EdgeWeightType::Euc3D => nint((xd * xd + yd * yd + zd * zd).sqrt()),
+            EdgeWeightType::Max2D => nint(xd.abs()).max(nint(yd.abs())),
+            EdgeWeightType::Max3D => nint(xd.abs()).max(nint(yd.abs())).max(nint(zd.abs())),
+            EdgeWeightType::Man2D => nint(xd.abs() + yd.abs()),
+            EdgeWeightType::Man3D => nint(xd.abs() + yd.abs() + zd.abs()),
+            EdgeWeightType::Ceil2D => (xd * xd + yd * yd).sqrt().ceil() as i32,
+            EdgeWeightType::Geo => geo_distance(a, b),
+            EdgeWeightType::Att => {
+                let r = ((xd * xd + yd * yd) / 10.0).sqrt();
+                let t = nint(r);
+                if (t as f64) < r { t + 1 } else { t }
+            }
+        }
+    }
+}
+
+impl FromStr for EdgeWeightType {
+    type Err = TspError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "EXPLICIT" => Ok(EdgeWeightType::Explicit),
+            "EUC_2D" => Ok(EdgeWeightType::Euc2D),
+            "EUC_3D" => Ok(EdgeWeightType::Euc3D),
+            "MAX_2D" => Ok(EdgeWeightType::Max2D),
+            "MAX_3D" => Ok(EdgeWeightType::Max3D),
+            "MAN_2D" => Ok(EdgeWeightType::Man2D),
+            "MAN_3D" => Ok(EdgeWeightType::Man3D),
+            "CEIL_2D" => Ok(EdgeWeightType::Ceil2D),
+            "GEO" => Ok(EdgeWeightType::Geo),
+            "ATT" => Ok(EdgeWeightType::Att),
+            other => Err(TspError::ParseError(format!(
+                "Unsupported EDGE_WEIGHT_TYPE: {other}"
+            ))),
+        }
+    }
+}
+
+/// The EDGE_WEIGHT_FORMAT of an explicit TSPLIB problem, i.e. which part of
+/// the distance matrix is listed in which order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeWeightFormat {
+    FullMatrix,
+    UpperRow,
+    LowerRow,
+    UpperDiagRow,
+    LowerDiagRow,
+    UpperCol,
+    LowerCol,
+    UpperDiagCol,
+    LowerDiagCol,
+}
+
+impl EdgeWeightFormat {
+    /// Lists the matrix cells in the order in which this format stores them.
+    ///
+    /// The column-wise formats of a symmetric matrix are the row-wise formats
+    /// of the opposite triangle, e.g. UPPER_COL enumerates the same cells as
+    /// LOWER_ROW with row and column swapped.
+    fn cells(&self, dimension: usize) -> Vec<(usize, usize)> {
+        let n = dimension;
+        let mut cells = Vec::new();
+        match self {
+            EdgeWeightFormat::FullMatrix => {
+                for i in 0..n {
+                    cells.extend((0..n).map(|j| (i, j)));
+                }
+            }
+            EdgeWeightFormat::UpperRow | EdgeWeightFormat::LowerCol => {
+                for i in 0..n {
+                    cells.extend((i + 1..n).map(|j| (i, j)));
+                }
+            }
+            EdgeWeightFormat::LowerRow | EdgeWeightFormat::UpperCol => {
+                for i in 0..n {
+                    cells.extend((0..i).map(|j| (i, j)));
+                }
+            }
+            EdgeWeightFormat::UpperDiagRow | EdgeWeightFormat::LowerDiagCol => {
+                for i in 0..n {
+                    cells.extend((i..n).map(|j| (i, j)));
+                }
+            }
+            EdgeWeightFormat::LowerDiagRow | EdgeWeightFormat::UpperDiagCol => {
+                for i in 0..n {
+                    cells.extend((0..=i).map(|j| (i, j)));
+                }
+            }
+        }
+        cells
+    }
+}
+
+impl FromStr for EdgeWeightFormat {
+    type Err = TspError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "FULL_MATRIX" => Ok(EdgeWeightFormat::FullMatrix),
+            "UPPER_ROW" => Ok(EdgeWeightFormat::UpperRow),
+            "LOWER_ROW" => Ok(EdgeWeightFormat::LowerRow),
+            "UPPER_DIAG_ROW" => Ok(EdgeWeightFormat::UpperDiagRow),
+            "LOWER_DIAG_ROW" => Ok(EdgeWeightFormat::LowerDiagRow),
+            "UPPER_COL" => Ok(EdgeWeightFormat::UpperCol),
+            "LOWER_COL" => Ok(EdgeWeightFormat::LowerCol),
+            "UPPER_DIAG_COL" => Ok(EdgeWeightFormat::UpperDiagCol),
+            "LOWER_DIAG_COL" => Ok(EdgeWeightFormat::LowerDiagCol),
+            other => Err(TspError::ParseError(format!(
+                "Unsupported EDGE_WEIGHT_FORMAT: {other}"
+            ))),
+        }
+    }
+}
+
+/// A TSPLIB problem of any of the supported types.
 ///
-/// Supports EDGE_WEIGHT_TYPE: EUC_2D (coordinates) and EXPLICIT (distance matrix, FULL_MATRIX only).
+/// Nodes are addressed by their zero-based position. Sections that refer to
+/// nodes by their TSPLIB number, i.e. demands, depots, edge data and tours,
+/// are translated by subtracting one.
+#[derive(Debug, Clone)]
+pub struct TspProblem {
+    pub name: String,
+    pub problem_type: ProblemType,
+    pub dimension: usize,
+    pub edge_weight_type: EdgeWeightType,
+    /// Node coordinates, empty for explicit problems
+    pub nodes: Vec<TspNode>,
+    /// Full distance matrix of explicit problems, empty otherwise
+    pub matrix: Vec<Vec<i32>>,
+    /// Vehicle capacity of a CVRP
+    pub capacity: Option<i32>,
+    /// Demand per node of a CVRP
+    pub demands: Vec<i32>,
+    /// Depot nodes of a CVRP
+    pub depots: Vec<usize>,
+    /// Sorted edges `(u, v)` with `u < v` of an HCP
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl TspProblem {
+    /// Returns the distance from node `i` to node `j`.
+    ///
+    /// A Hamiltonian cycle problem is turned into a TSP by charging one for
+    /// each edge of the graph and two for every other pair of nodes, so that
+    /// a tour of length `dimension` is a Hamiltonian cycle.
+    pub fn distance(&self, i: usize, j: usize) -> i32 {
+        if i == j {
+            return 0;
+        }
+        if self.problem_type == ProblemType::Hcp {
+            let edge = (i.min(j), i.max(j));
+            return if self.edges.binary_search(&edge).is_ok() {
+                1
+            } else {
+                2
+            };
+        }
+        match self.edge_weight_type {
+            EdgeWeightType::Explicit => self.matrix[i][j],
+            metric => metric.distance(&self.nodes[i], &self.nodes[j]),
+        }
+    }
+
+    /// Materialises all pairwise distances.
+    pub fn distance_matrix(&self) -> Vec<Vec<i32>> {
+        (0..self.dimension)
+            .map(|i| (0..self.dimension).map(|j| self.distance(i, j)).collect())
+            .collect()
+    }
+
+    /// Length of the closed tour that visits the nodes in the given order.
+    pub fn tour_length(&self, tour: &[usize]) -> i64 {
+        (0..tour.len())
+            .map(|i| self.distance(tour[i], tour[(i + 1) % tour.len()]) as i64)
+            .sum()
+    }
+}
+
+/// A tour as stored in a TSPLIB `.tour` file, with zero-based nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tour {
+    pub name: String,
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Header,
+    NodeCoord,
+    EdgeWeight,
+    Demand,
+    Depot,
+    EdgeData,
+    Tour,
+    /// Sections that carry nothing needed to compute distances, e.g.
+    /// DISPLAY_DATA_SECTION or FIXED_EDGES_SECTION
+    Ignored,
+}
+
+/// TSPLIB rounds to the nearest integer as `(int)(x + 0.5)`.
+fn nint(x: f64) -> i32 {
+    (x + 0.5) as i32
+}
+
+/// Converts a TSPLIB DDD.MM value into radians.
+fn geo_radians(value: f64) -> f64 {
+    // TSPLIB fixes this truncated value, the reference distances depend on it
+    #[allow(clippy::approx_constant)]
+    const PI: f64 = 3.141592;
+    let degrees = value.trunc();
+    let minutes = value - degrees;
+    PI * (degrees + 5.0 * minutes / 3.0) / 180.0
+}
+
+/// Great circle distance of the GEO weight type, truncated after adding one
+/// kilometre exactly as the reference implementation does.
+fn geo_distance(a: &TspNode, b: &TspNode) -> i32 {
+    const RRR: f64 = 6378.388;
+    let (lat_a, lon_a) = (geo_radians(a.x), geo_radians(a.y));
+    let (lat_b, lon_b) = (geo_radians(b.x), geo_radians(b.y));
+    let q1 = (lon_a - lon_b).cos();
+    let q2 = (lat_a - lat_b).cos();
+    let q3 = (lat_a + lat_b).cos();
+    (RRR * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos() + 1.0) as i32
+}
+
+/// Splits a header line of the form `KEY : VALUE` into key and value.
+fn header_entry(line: &str) -> Option<(&str, &str)> {
+    line.split_once(':')
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+/// Maps a section keyword onto the section it opens.
+fn section_start(line: &str) -> Option<Section> {
+    match line.trim_end_matches(':').trim() {
+        "NODE_COORD_SECTION" => Some(Section::NodeCoord),
+        "EDGE_WEIGHT_SECTION" => Some(Section::EdgeWeight),
+        "DEMAND_SECTION" => Some(Section::Demand),
+        "DEPOT_SECTION" => Some(Section::Depot),
+        "EDGE_DATA_SECTION" => Some(Section::EdgeData),
+        "TOUR_SECTION" => Some(Section::Tour),
+        "DISPLAY_DATA_SECTION" | "FIXED_EDGES_SECTION" => Some(Section::Ignored),
+        _ => None,
+    }
+}
+
+/// Translates a one-based TSPLIB node number into a zero-based index.
+fn node_index(token: &str, dimension: Option<usize>) -> Result<usize, TspError> {
+    let id: usize = token
+        .parse()
+        .map_err(|_| TspError::ParseError(format!("Invalid node: {token}")))?;
+    if id == 0 || dimension.is_some_and(|dim| id > dim) {
+        return Err(TspError::ParseError(format!("Node {id} is out of range")));
+    }
+    Ok(id - 1)
+}
+
+fn parse_value<T: FromStr>(token: &str, what: &str) -> Result<T, TspError> {
+    token
+        .parse()
+        .map_err(|_| TspError::ParseError(format!("Invalid {what}: {token}")))
+}
+
+/// Parses a TSPLIB problem file of type TSP, ATSP, HCP or CVRP.
 ///
-/// # Arguments
-/// * `filename` - Path to the TSP file
+/// All edge weight types but the special and display-only ones are supported,
+/// as are all explicit matrix formats. Distances are computed on demand by
+/// [`TspProblem::distance`] with the rounding prescribed by TSPLIB.
 ///
-/// # Returns
-/// A TspInstance containing either coordinates or an explicit distance matrix
-pub fn read_tsp_file(filename: &str) -> Result<TspInstance, TspError> {
-    let mut sites = Vec::new();
-    let mut in_coord_section = false;
-    let mut in_weight_section = false;
+/// # Arguments
+/// * `filename` - Path to the problem file
+pub fn read_problem(filename: &str) -> Result<TspProblem, TspError> {
+    let mut name = String::new();
+    let mut problem_type = ProblemType::Tsp;
     let mut dimension: Option<usize> = None;
-    let mut edge_weight_type: Option<String> = None;
-    let mut edge_weight_format: Option<String> = None;
-    let mut matrix_data: Vec<i32> = Vec::new();
+    let mut edge_weight_type: Option<EdgeWeightType> = None;
+    let mut edge_weight_format: Option<EdgeWeightFormat> = None;
+    let mut three_dimensional = false;
+    let mut adjacency_list = false;
+    let mut capacity = None;
+
+    let mut section = Section::Header;
+    let mut nodes = Vec::new();
+    let mut weights: Vec<i32> = Vec::new();
+    let mut demand_entries = Vec::new();
+    let mut depots = Vec::new();
+    let mut edges = Vec::new();
+    // the TSPLIB lists of depots and edges end with -1
+    let mut terminated = false;
+    let mut edge_source: Option<usize> = None;
 
     for line in read_lines(filename)? {
         let line = line?;
         let line = line.trim();
 
-        if line.is_empty() || line == "EOF" {
+        if line.is_empty() {
             continue;
         }
-
-        if line == "NODE_COORD_SECTION" {
-            in_coord_section = true;
-            in_weight_section = false;
-            continue;
+        if line == "EOF" {
+            break;
         }
-        if line == "EDGE_WEIGHT_SECTION" {
-            in_weight_section = true;
-            in_coord_section = false;
+        if let Some(next) = section_start(line) {
+            section = next;
+            terminated = false;
+            edge_source = None;
             continue;
         }
 
-        if !in_coord_section && !in_weight_section {
-            // Parse header section
-            if line.starts_with("DIMENSION") {
-                if let Some(dim_str) = line.split(':').nth(1) {
-                    dimension = Some(dim_str.trim().parse().map_err(|_| {
-                        TspError::ParseError("Invalid DIMENSION value".to_string())
-                    })?);
-                }
-            } else if line.starts_with("EDGE_WEIGHT_TYPE") {
-                if let Some(t) = line.split(':').nth(1) {
-                    edge_weight_type = Some(t.trim().to_string());
+        match section {
+            Section::Header => {
+                let Some((key, value)) = header_entry(line) else {
+                    continue;
+                };
+                match key {
+                    "NAME" => name = value.to_string(),
+                    "TYPE" => {
+                        let value = value.split_whitespace().next().unwrap_or_default();
+                        problem_type = value.parse()?;
+                    }
+                    "DIMENSION" => {
+                        dimension = Some(value.parse().map_err(|_| {
+                            TspError::ParseError("Invalid DIMENSION value".to_string())
+                        })?);
+                    }
+                    "CAPACITY" => capacity = Some(parse_value(value, "CAPACITY")?),
+                    "EDGE_WEIGHT_TYPE" => edge_weight_type = Some(value.parse()?),
+                    // FUNCTION only restates that the weight type is a metric
+                    "EDGE_WEIGHT_FORMAT" if value != "FUNCTION" => {
+                        edge_weight_format = Some(value.parse()?);
+                    }
+                    "NODE_COORD_TYPE" => three_dimensional = value == "THREED_COORDS",
+                    "EDGE_DATA_FORMAT" => adjacency_list = value == "ADJ_LIST",
+                    _ => {}
                 }
-            } else if line.starts_with("EDGE_WEIGHT_FORMAT")
-                && let Some(f) = line.split(':').nth(1)
-            {
-                edge_weight_format = Some(f.trim().to_string());
             }
-            continue;
-        }
+            Section::NodeCoord => {
+                let dims = if three_dimensional {
+                    3
+                } else {
+                    edge_weight_type.map_or(2, |t| t.dimensions())
+                };
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() != dims + 1 {
+                    return Err(TspError::ParseError(format!(
+                        "Invalid coordinate line: {line}"
+                    )));
+                }
 
-        if in_coord_section {
-            // Parse coordinate section
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() != 3 {
-                return Err(TspError::ParseError(format!(
-                    "Invalid coordinate line: {line}"
-                )));
-            }
+                let id = parts[0]
+                    .parse()
+                    .map_err(|_| TspError::ParseError(format!("Invalid id: {}", parts[0])))?;
+                let x = parse_value(parts[1], "x coordinate")?;
+                let y = parse_value(parts[2], "y coordinate")?;
+                let z = match parts.get(3) {
+                    Some(z) => parse_value(z, "z coordinate")?,
+                    None => 0.,
+                };
 
-            let id = parts[0]
-                .parse()
-                .map_err(|_| TspError::ParseError(format!("Invalid id: {}", parts[0])))?;
-            let x = f64::from_str(parts[1])
-                .map_err(|_| TspError::ParseError(format!("Invalid x coordinate: {}", parts[1])))?
-                as i32;
-            let y = f64::from_str(parts[2])
-                .map_err(|_| TspError::ParseError(format!("Invalid y coordinate: {}", parts[2])))?
-                as i32;
-
-            debug!("Parsed site: id={id}, x={x}, y={y}");
-
-            sites.push(TspSite {
-                id,
-                coordinate: IPoint2D::new(x, y),
-            });
-        } else if in_weight_section {
-            // Parse explicit edge weights (FULL_MATRIX only)
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            for p in parts {
-                let val = i32::from_str(p)
-                    .map_err(|_| TspError::ParseError(format!("Invalid matrix value: {p}")))?;
-                matrix_data.push(val);
+                debug!("Parsed node: id={id}, x={x}, y={y}, z={z}");
+                nodes.push(TspNode { id, x, y, z });
             }
-        }
-    }
-
-    let edge_type = edge_weight_type.as_deref().unwrap_or("EUC_2D");
-    match edge_type {
-        "EUC_2D" => {
-            // Verify we read the expected number of sites
-            if let Some(dim) = dimension
-                && sites.len() != dim
-            {
-                return Err(TspError::ParseError(format!(
-                    "Expected {} sites but found {}",
-                    dim,
-                    sites.len()
-                )));
+            Section::EdgeWeight => {
+                for p in line.split_whitespace() {
+                    weights.push(parse_value(p, "matrix value")?);
+                }
             }
-            Ok(TspInstance::Coordinates(sites))
-        }
-        "EXPLICIT" => {
-            let dim = dimension.ok_or_else(|| {
-                TspError::ParseError("Missing DIMENSION for EXPLICIT".to_string())
-            })?;
-            let format = edge_weight_format.as_deref().unwrap_or("FULL_MATRIX");
-            match format {
-                "FULL_MATRIX" => {
-                    if matrix_data.len() != dim * dim {
-                        return Err(TspError::ParseError(format!(
-                            "Expected {} matrix entries but found {}",
-                            dim * dim,
-                            matrix_data.len()
-                        )));
-                    }
-                    let mut matrix = Vec::with_capacity(dim);
-                    for row in 0..dim {
-                        let start = row * dim;
-                        let end = start + dim;
-                        matrix.push(matrix_data[start..end].to_vec());
+            Section::Demand => {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() != 2 {
+                    return Err(TspError::ParseError(format!("Invalid demand line: {line}")));
+                }
+                let node = node_index(parts[0], dimension)?;
+                demand_entries.push((node, parse_value(parts[1], "demand")?));
+            }
+            Section::Depot => {
+                for token in line.split_whitespace() {
+                    if terminated || token == "-1" {
+                        terminated = true;
+                        continue;
                     }
-                    Ok(TspInstance::ExplicitMatrix(matrix))
+                    depots.push(node_index(token, dimension)?);
                 }
-                "LOWER_DIAG_ROW" => {
-                    // The lower diagonal row format: each row i has i+1 entries (0-based)
-                    let expected = (dim * (dim + 1)) / 2;
-                    if matrix_data.len() != expected {
-                        return Err(TspError::ParseError(format!(
-                            "Expected {} matrix entries for LOWER_DIAG_ROW but found {}",
-                            expected,
-                            matrix_data.len()
-                        )));
+            }
+            Section::EdgeData => {
+                for token in line.split_whitespace() {
+                    if terminated {
+                        continue;
                     }
-                    // Fill lower triangle and diagonal from input, upper triangle by symmetry
-                    let mut matrix = vec![vec![0; dim]; dim];
-                    let mut idx = 0;
-                    for (i, row) in matrix.iter_mut().enumerate() {
-                        for cell in row.iter_mut().take(i + 1) {
-                            *cell = matrix_data[idx];
-                            idx += 1;
+                    if token == "-1" {
+                        // an adjacency list closes each node's list with -1
+                        // and the whole section with one more
+                        if adjacency_list && edge_source.is_some() {
+                            edge_source = None;
+                        } else {
+                            terminated = true;
                         }
+                        continue;
                     }
-
-                    for i in 0..dim {
-                        let (upper_rows, current_and_below) = matrix.split_at_mut(i);
-                        let current_row = &current_and_below[0];
-                        for (j, upper_row) in upper_rows.iter_mut().enumerate() {
-                            upper_row[i] = current_row[j];
+                    let node = node_index(token, dimension)?;
+                    match edge_source {
+                        None => edge_source = Some(node),
+                        Some(source) => {
+                            if source != node {
+                                edges.push((source.min(node), source.max(node)));
+                            }
+                            if !adjacency_list {
+                                edge_source = None;
+                            }
                         }
                     }
-                    Ok(TspInstance::ExplicitMatrix(matrix))
                 }
-                other => Err(TspError::ParseError(format!(
-                    "Only FULL_MATRIX and LOWER_DIAG_ROW formats are supported, got {other}"
-                ))),
+            }
+            Section::Tour | Section::Ignored => {}
+        }
+    }
+
+    let default_type = if problem_type == ProblemType::Hcp {
+        EdgeWeightType::Explicit
+    } else {
+        EdgeWeightType::Euc2D
+    };
+    let edge_weight_type = edge_weight_type.unwrap_or(default_type);
+
+    let mut matrix = Vec::new();
+    let dimension = if problem_type == ProblemType::Hcp {
+        dimension.ok_or_else(|| TspError::ParseError("Missing DIMENSION for HCP".to_string()))?
+    } else if edge_weight_type == EdgeWeightType::Explicit {
+        let dim = dimension
+            .ok_or_else(|| TspError::ParseError("Missing DIMENSION for EXPLICIT".to_string()))?;
+        let format = edge_weight_format.unwrap_or(EdgeWeightFormat::FullMatrix);
+        let cells = format.cells(dim);
+        if weights.len() != cells.len() {
+            return Err(TspError::ParseError(format!(
+                "Expected {} matrix entries for {:?} but found {}",
+                cells.len(),
+                format,
+                weights.len()
+            )));
+        }
+        matrix = vec![vec![0; dim]; dim];
+        for ((i, j), weight) in cells.into_iter().zip(weights) {
+            matrix[i][j] = weight;
+            if format != EdgeWeightFormat::FullMatrix {
+                matrix[j][i] = weight;
             }
         }
-        other => Err(TspError::ParseError(format!(
-            "Unsupported EDGE_WEIGHT_TYPE: {other}"
-        ))),
+        dim
+    } else {
+        // Verify we read the expected number of sites
+        if let Some(dim) = dimension
+            && nodes.len() != dim
+        {
+            return Err(TspError::ParseError(format!(
+                "Expected {} sites but found {}",
+                dim,
+                nodes.len()
+            )));
+        }
+        nodes.len()
+    };
+
+    let mut demands = Vec::new();
+    if !demand_entries.is_empty() {
+        demands = vec![0; dimension];
+        for (node, demand) in demand_entries {
+            demands[node] = demand;
+        }
+    }
+    edges.sort_unstable();
+    edges.dedup();
+
+    Ok(TspProblem {
+        name,
+        problem_type,
+        dimension,
+        edge_weight_type,
+        nodes,
+        matrix,
+        capacity,
+        demands,
+        depots,
+        edges,
+    })
+}
+
+/// Parse a TSP file containing site coordinates or explicit edge weights.
+///
+/// EUC_2D problems are returned as their sites with coordinates truncated to
+/// integers. Every other problem [`read_problem`] understands is returned as
+/// its full distance matrix.
+///
+/// # Arguments
+/// * `filename` - Path to the TSP file
+///
+/// # Returns
+/// A TspInstance containing either coordinates or an explicit distance matrix
+pub fn read_tsp_file(filename: &str) -> Result<TspInstance, TspError> {
+    let problem = read_problem(filename)?;
+    if problem.problem_type != ProblemType::Hcp && problem.edge_weight_type == EdgeWeightType::Euc2D
+    {
+        let sites = problem
+            .nodes
+            .iter()
+            .map(|node| TspSite {
+                id: node.id,
+                coordinate: IPoint2D::new(node.x as i32, node.y as i32),
+            })
+            .collect();
+        return Ok(TspInstance::Coordinates(sites));
     }
+    Ok(TspInstance::ExplicitMatrix(problem.distance_matrix()))
 }
 
 /// Calculate the Euclidean distance between two TSP sites
@@ -204,6 +640,99 @@ pub fn euclidean_distance(a: &TspSite, b: &TspSite) -> i32 {
     a.coordinate.distance_to(&b.coordinate)
 }
 
+/// Reads a TSPLIB `.tour` file.
+///
+/// The tour must visit every node exactly once. If the file states a
+/// DIMENSION, the tour must have exactly that many nodes.
+pub fn read_tour_file(filename: &str) -> Result<Tour, TspError> {
+    let mut name = String::new();
+    let mut dimension: Option<usize> = None;
+    let mut section = Section::Header;
+    let mut terminated = false;
+    let mut nodes = Vec::new();
+
+    for line in read_lines(filename)? {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+        if line == "EOF" {
+            break;
+        }
+        if let Some(next) = section_start(line) {
+            section = next;
+            continue;
+        }
+
+        match section {
+            Section::Header => match header_entry(line) {
+                Some(("NAME", value)) => name = value.to_string(),
+                Some(("DIMENSION", value)) => {
+                    dimension = Some(value.parse().map_err(|_| {
+                        TspError::ParseError("Invalid DIMENSION value".to_string())
+                    })?);
+                }
+                Some(("TYPE", value)) if value != "TOUR" => {
+                    return Err(TspError::ParseError(format!(
+                        "Expected TYPE TOUR but found {value}"
+                    )));
+                }
+                _ => {}
+            },
+            Section::Tour => {
+                for token in line.split_whitespace() {
+                    if terminated || token == "-1" {
+                        terminated = true;
+                        continue;
+                    }
+                    nodes.push(node_index(token, dimension)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(dim) = dimension
+        && nodes.len() != dim
+    {
+        return Err(TspError::ParseError(format!(
+            "Expected {} tour nodes but found {}",
+            dim,
+            nodes.len()
+        )));
+    }
+    let mut seen = vec![false; nodes.len()];
+    for &node in &nodes {
+        if node >= seen.len() || seen[node] {
+            return Err(TspError::ParseError(format!(
+                "Tour is not a permutation, node {} is out of place",
+                node + 1
+            )));
+        }
+        seen[node] = true;
+    }
+
+    Ok(Tour { name, nodes })
+}
+
+/// Writes a tour in the TSPLIB `.tour` format.
+pub fn write_tour_file(filename: &str, tour: &Tour) -> Result<(), TspError> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    writeln!(file, "NAME : {}", tour.name)?;
+    writeln!(file, "TYPE : TOUR")?;
+    writeln!(file, "DIMENSION : {}", tour.nodes.len())?;
+    writeln!(file, "TOUR_SECTION")?;
+    for node in &tour.nodes {
+        writeln!(file, "{}", node + 1)?;
+    }
+    writeln!(file, "-1")?;
+    writeln!(file, "EOF")?;
+    file.flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -502,4 +1031,222 @@ EOF"
             Err(e) => panic!("Expected Ok result, got error: {:?}", e),
         }
     }
+
+    fn write_problem(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{content}").unwrap();
+        file
+    }
+
+    fn node(x: f64, y: f64, z: f64) -> TspNode {
+        TspNode { id: 0, x, y, z }
+    }
+
+    #[test]
+    fn metrics_round_as_tsplib_prescribes() {
+        let origin = node(0., 0., 0.);
+        assert_eq!(
+            EdgeWeightType::Euc2D.distance(&origin, &node(0.6, 0., 0.)),
+            1
+        );
+        assert_eq!(
+            EdgeWeightType::Euc3D.distance(&origin, &node(1., 2., 2.)),
+            3
+        );
+        assert_eq!(
+            EdgeWeightType::Man2D.distance(&origin, &node(3., 4., 0.)),
+            7
+        );
+        assert_eq!(
+            EdgeWeightType::Man3D.distance(&origin, &node(3., 4., 5.)),
+            12
+        );
+        assert_eq!(
+            EdgeWeightType::Max2D.distance(&origin, &node(3., 4.4, 0.)),
+            4
+        );
+        assert_eq!(
+            EdgeWeightType::Max3D.distance(&origin, &node(3., 4., 5.)),
+            5
+        );
+        assert_eq!(
+            EdgeWeightType::Ceil2D.distance(&origin, &node(1., 1., 0.)),
+            2
+        );
+        // sqrt(100 / 10) = 3.16 rounds to 3, which is less and thus bumped up
+        assert_eq!(EdgeWeightType::Att.distance(&origin, &node(10., 0., 0.)), 4);
+        assert_eq!(
+            EdgeWeightType::Att.distance(&origin, &node(30., 10., 0.)),
+            10
+        );
+        // one degree of longitude on the equator is 111.3km, plus one
+        assert_eq!(
+            EdgeWeightType::Geo.distance(&origin, &node(0., 1., 0.)),
+            112
+        );
+        // DDD.MM notation: 0.30 is half a degree
+        assert_eq!(
+            EdgeWeightType::Geo.distance(&origin, &node(0., 0.3, 0.)),
+            56
+        );
+    }
+
+    #[test]
+    fn geo_problem_from_file() {
+        let file = write_problem(
+            "NAME : geo\nTYPE : TSP\nDIMENSION : 2\nEDGE_WEIGHT_TYPE : GEO\nNODE_COORD_SECTION\n1 0.0 0.0\n2 0.0 1.0\nEOF\n",
+        );
+        let problem = read_problem(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(problem.name, "geo");
+        assert_eq!(problem.dimension, 2);
+        assert_eq!(problem.distance(0, 1), 112);
+        assert_eq!(problem.tour_length(&[0, 1]), 224);
+
+        let instance = read_tsp_file(file.path().to_str().unwrap()).unwrap();
+        assert!(matches!(instance, TspInstance::ExplicitMatrix(m) if m[1][0] == 112));
+    }
+
+    #[test]
+    fn euc_3d_needs_three_coordinates() {
+        let file = write_problem(
+            "DIMENSION : 2\nEDGE_WEIGHT_TYPE : EUC_3D\nNODE_COORD_SECTION\n1 0 0 0\n2 1 2 2\nEOF\n",
+        );
+        let problem = read_problem(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(problem.distance(1, 0), 3);
+
+        let file = write_problem(
+            "DIMENSION : 1\nEDGE_WEIGHT_TYPE : EUC_3D\nNODE_COORD_SECTION\n1 0 0\nEOF\n",
+        );
+        let result = read_problem(file.path().to_str().unwrap());
+        assert!(
+            matches!(result, Err(TspError::ParseError(msg)) if msg.contains("Invalid coordinate line"))
+        );
+    }
+
+    #[test]
+    fn all_explicit_formats() {
+        let cases = [
+            ("UPPER_ROW", "1 2\n3"),
+            ("LOWER_ROW", "1\n2 3"),
+            ("UPPER_DIAG_ROW", "0 1 2\n0 3\n0"),
+            ("LOWER_DIAG_ROW", "0\n1 0\n2 3 0"),
+            ("UPPER_COL", "1\n2 3"),
+            ("LOWER_COL", "1 2\n3"),
+            ("UPPER_DIAG_COL", "0\n1 0\n2 3 0"),
+            ("LOWER_DIAG_COL", "0 1 2\n0 3\n0"),
+            ("FULL_MATRIX", "0 1 2 1 0 3 2 3 0"),
+        ];
+        for (format, weights) in cases {
+            let file = write_problem(&format!(
+                "DIMENSION : 3\nEDGE_WEIGHT_TYPE : EXPLICIT\nEDGE_WEIGHT_FORMAT : {format}\nEDGE_WEIGHT_SECTION\n{weights}\nDISPLAY_DATA_SECTION\n1 0.5 0.5\n2 1.5 0.5\n3 1.0 2.5\nEOF\n"
+            ));
+            let problem = read_problem(file.path().to_str().unwrap()).unwrap();
+            assert_eq!(
+                problem.distance_matrix(),
+                vec![vec![0, 1, 2], vec![1, 0, 3], vec![2, 3, 0]],
+                "{format}"
+            );
+        }
+
+        let file = write_problem(
+            "DIMENSION : 3\nEDGE_WEIGHT_TYPE : EXPLICIT\nEDGE_WEIGHT_FORMAT : UPPER_ROW\nEDGE_WEIGHT_SECTION\n1 2\nEOF\n",
+        );
+        let result = read_problem(file.path().to_str().unwrap());
+        assert!(
+            matches!(result, Err(TspError::ParseError(msg)) if msg.contains("Expected 3 matrix entries"))
+        );
+    }
+
+    #[test]
+    fn asymmetric_problem_keeps_both_directions() {
+        let file = write_problem(
+            "NAME : small\nTYPE : ATSP\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EXPLICIT\nEDGE_WEIGHT_FORMAT : FULL_MATRIX\nEDGE_WEIGHT_SECTION\n9999 1 5\n7 9999 1\n1 8 9999\nEOF\n",
+        );
+        let problem = read_problem(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(problem.problem_type, ProblemType::Atsp);
+        assert_eq!(problem.distance(0, 1), 1);
+        assert_eq!(problem.distance(1, 0), 7);
+        assert_eq!(problem.distance(0, 0), 0);
+        assert_eq!(problem.tour_length(&[0, 1, 2]), 3);
+        assert_eq!(problem.tour_length(&[0, 2, 1]), 20);
+    }
+
+    #[test]
+    fn hamiltonian_cycle_problem() {
+        // a square with one diagonal, once as edge list and once as adjacency list
+        let edge_list =
+            "EDGE_DATA_FORMAT : EDGE_LIST\nEDGE_DATA_SECTION\n1 2\n2 3\n3 4\n4 1\n1 3\n-1\n";
+        let adjacency_list =
+            "EDGE_DATA_FORMAT : ADJ_LIST\nEDGE_DATA_SECTION\n1 2 4 3 -1\n3 2 4 -1\n-1\n";
+        for data in [edge_list, adjacency_list] {
+            let file = write_problem(&format!(
+                "NAME : square\nTYPE : HCP\nDIMENSION : 4\n{data}EOF\n"
+            ));
+            let problem = read_problem(file.path().to_str().unwrap()).unwrap();
+            assert_eq!(problem.problem_type, ProblemType::Hcp);
+            assert_eq!(problem.edges, vec![(0, 1), (0, 2), (0, 3), (1, 2), (2, 3)]);
+            assert_eq!(problem.distance(1, 0), 1);
+            assert_eq!(problem.distance(1, 3), 2);
+            assert_eq!(problem.tour_length(&[0, 1, 2, 3]), 4);
+        }
+    }
+
+    #[test]
+    fn capacitated_vehicle_routing_problem() {
+        let file = write_problem(
+            "NAME : vrp\nTYPE : CVRP\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EUC_2D\nCAPACITY : 10\nNODE_COORD_SECTION\n1 0 0\n2 3 4\n3 6 8\nDEMAND_SECTION\n1 0\n2 4\n3 7\nDEPOT_SECTION\n 1\n -1\nEOF\n",
+        );
+        let problem = read_problem(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(problem.problem_type, ProblemType::Cvrp);
+        assert_eq!(problem.capacity, Some(10));
+        assert_eq!(problem.demands, vec![0, 4, 7]);
+        assert_eq!(problem.depots, vec![0]);
+        assert_eq!(problem.distance(0, 2), 10);
+    }
+
+    #[test]
+    fn unsupported_types_are_rejected() {
+        let file = write_problem("TYPE : SOP\nDIMENSION : 1\nEOF\n");
+        let result = read_problem(file.path().to_str().unwrap());
+        assert!(matches!(result, Err(TspError::ParseError(msg)) if msg.contains("SOP")));
+
+        let file = write_problem("DIMENSION : 1\nEDGE_WEIGHT_TYPE : XRAY1\nEOF\n");
+        let result = read_problem(file.path().to_str().unwrap());
+        assert!(matches!(result, Err(TspError::ParseError(msg)) if msg.contains("XRAY1")));
+    }
+
+    #[test]
+    fn tour_round_trip() {
+        let file = NamedTempFile::new().unwrap();
+        let filename = file.path().to_str().unwrap();
+        let tour = Tour {
+            name: "square.tour".to_string(),
+            nodes: vec![0, 2, 3, 1],
+        };
+        write_tour_file(filename, &tour).unwrap();
+        assert_eq!(read_tour_file(filename).unwrap(), tour);
+    }
+
+    #[test]
+    fn tour_with_several_nodes_per_line() {
+        let file = write_problem(
+            "NAME : a.opt.tour\nCOMMENT : Optimal tour\nTYPE : TOUR\nDIMENSION : 4\nTOUR_SECTION\n1 3\n4 2 -1\nEOF\n",
+        );
+        let tour = read_tour_file(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(tour.name, "a.opt.tour");
+        assert_eq!(tour.nodes, vec![0, 2, 3, 1]);
+    }
+
+    #[test]
+    fn tour_must_be_a_permutation() {
+        let file = write_problem("TYPE : TOUR\nDIMENSION : 3\nTOUR_SECTION\n1\n2\n2\n-1\nEOF\n");
+        let result = read_tour_file(file.path().to_str().unwrap());
+        assert!(matches!(result, Err(TspError::ParseError(msg)) if msg.contains("node 2")));
+
+        let file = write_problem("TYPE : TOUR\nDIMENSION : 3\nTOUR_SECTION\n1\n2\n-1\nEOF\n");
+        let result = read_tour_file(file.path().to_str().unwrap());
+        assert!(
+            matches!(result, Err(TspError::ParseError(msg)) if msg.contains("Expected 3 tour nodes"))
+        );
+    }
 }
@@ -0,0 +1,633 @@
+//! Decompression of zlib streams, the DEFLATE format of RFC 1951 in the
+//! wrapper of RFC 1950.
+//!
+//! DEFLATE compresses a stream as a sequence of blocks. A block is either
+//! stored as it is, or a sequence of literal bytes and back references of a
+//! length and a distance into what was decoded before, each of them written as
+//! a Huffman code. The codes are either the fixed ones of the format or given
+//! at the start of the block by the lengths of their codewords, themselves
+//! Huffman coded. The zlib wrapper adds a two byte header in front and the
+//! Adler-32 checksum of the decompressed data at the end.
+//!
+//! A codeword is looked up in a table by the next [`FAST_BITS`] bits of the
+//! stream, which takes in all the short codewords that make up most of the
+//! data. The few longer ones are decoded as Mark Adler's `puff` does, by
+//! walking the canonical code one bit at a time.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use toolbox_rs::zlib::decompress;
+//!
+//! // "hello, hello, hello world" as compressed by zlib
+//! let compressed = [
+//!     120, 218, 203, 72, 205, 201, 201, 215, 81, 200, 64, 162, 20, 202, 243, 139, 114, 82, 0,
+//!     116, 135, 9, 29,
+//! ];
+//! assert_eq!(decompress(&compressed).unwrap(), b"hello, hello, hello world");
+//! ```
+use thiserror::Error;
+
+/// The longest codeword DEFLATE allows.
+const MAX_BITS: usize = 15;
+
+/// How many bits the lookup table of a code is indexed by. Nine bits hold
+/// every literal of the fixed code, and the table of a code takes a kilobyte.
+const FAST_BITS: u32 = 9;
+
+/// The first length of each length symbol from 257 on, and the number of
+/// extra bits added to it.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The first distance of each distance symbol, and the number of extra bits
+/// added to it.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order in which a dynamic block gives the lengths of the code lengths.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Why a stream could not be decompressed.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InflateError {
+    #[error("the stream ends early")]
+    UnexpectedEnd,
+    #[error("not a zlib stream: {0}")]
+    Header(&'static str),
+    #[error("block type 3 does not exist")]
+    BlockType,
+    #[error("the length of a stored block does not match its complement")]
+    StoredLength,
+    #[error("a Huffman code is invalid: {0}")]
+    Code(&'static str),
+    #[error("distance {distance} reaches before the start of the data")]
+    Distance { distance: usize },
+    #[error("the checksum is {found:#010x} instead of {expected:#010x}")]
+    Checksum { expected: u32, found: u32 },
+}
+
+/// Reads the bits of a stream, the least significant bit of each byte first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Fills the buffer with as many whole bytes as fit, fewer at the end of
+    /// the data.
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            let Some(&byte) = self.data.get(self.position) else {
+                return;
+            };
+            self.position += 1;
+            self.buffer |= u64::from(byte) << self.count;
+            self.count += 8;
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, InflateError> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or(InflateError::UnexpectedEnd)?;
+            self.position += 1;
+            self.buffer |= u64::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = (self.buffer & ((1 << count) - 1)) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Drops what is left of the current byte.
+    fn align(&mut self) {
+        let rest = self.count % 8;
+        self.buffer >>= rest;
+        self.count -= rest;
+    }
+}
+
+/// A canonical Huffman code, given by the number of codewords of each length
+/// and the symbols in the order of their codewords, and a table that decodes
+/// the codewords of up to [`FAST_BITS`] bits at once.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+    /// The symbol and the length of the codeword that the next bits of the
+    /// stream begin with, as `symbol << 4 | length`, or zero if it is longer.
+    /// The stream holds a codeword first bit first, so the table is indexed
+    /// by the codewords reversed and every entry is repeated for the bits
+    /// that follow it.
+    fast: [u16; 1 << FAST_BITS],
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0_u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        // a code may be incomplete, but no length may have more codewords
+        // than are left for it
+        let mut left = 1_i32;
+        for &count in &counts[1..] {
+            left = 2 * left - i32::from(count);
+            if left < 0 {
+                return Err(InflateError::Code("over-subscribed"));
+            }
+        }
+
+        let mut offsets = [0_u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; usize::from(offsets[MAX_BITS + 1])];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        // the first codeword of each length, as RFC 1951 assigns them
+        let mut next = [0_u32; MAX_BITS + 1];
+        for length in 2..=MAX_BITS {
+            next[length] = (next[length - 1] + u32::from(counts[length - 1])) << 1;
+        }
+        let mut fast = [0_u16; 1 << FAST_BITS];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            let length = u32::from(length);
+            if length == 0 {
+                continue;
+            }
+            let code = next[length as usize];
+            next[length as usize] += 1;
+            if length > FAST_BITS {
+                continue;
+            }
+            let reversed = code.reverse_bits() >> (32 - length);
+            let entry = (symbol as u16) << 4 | length as u16;
+            for rest in 0..1 << (FAST_BITS - length) {
+                fast[(reversed | rest << length) as usize] = entry;
+            }
+        }
+        Ok(Self {
+            counts,
+            symbols,
+            fast,
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        reader.refill();
+        let entry = self.fast[(reader.buffer & ((1 << FAST_BITS) - 1)) as usize];
+        let length = u32::from(entry & 0xf);
+        if length != 0 && length <= reader.count {
+            reader.buffer >>= length;
+            reader.count -= length;
+            return Ok(entry >> 4);
+        }
+        self.decode_bitwise(reader)
+    }
+
+    /// Decodes a codeword longer than the table reaches, or one that the
+    /// stream ends within.
+    fn decode_bitwise(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        // the codewords of each length follow those of the one before
+        let (mut code, mut first, mut index) = (0_i32, 0_i32, 0_i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError::Code("a codeword that is not part of it"))
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, returning the data and the number of
+/// bytes the stream took.
+///
+/// # Errors
+///
+/// Fails if the stream is cut short or is not valid DEFLATE.
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), InflateError> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::with_capacity(4 * data.len());
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                codes(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                codes(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(InflateError::BlockType),
+        }
+        if last {
+            break;
+        }
+    }
+    reader.align();
+    let consumed = reader.position - (reader.count / 8) as usize;
+    Ok((output, consumed))
+}
+
+/// Decompresses a zlib stream and checks its checksum.
+///
+/// # Errors
+///
+/// Fails if the header is not that of a zlib stream without a preset
+/// dictionary, if the stream is cut short or not valid DEFLATE, or if the
+/// checksum does not match the data.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+    let [method, flags, ..] = *data else {
+        return Err(InflateError::UnexpectedEnd);
+    };
+    if method & 0x0f != 8 || method >> 4 > 7 {
+        return Err(InflateError::Header("the method is not DEFLATE"));
+    }
+    if (u16::from(method) << 8 | u16::from(flags)) % 31 != 0 {
+        return Err(InflateError::Header("the header check fails"));
+    }
+    if flags & 0x20 != 0 {
+        return Err(InflateError::Header("a preset dictionary is needed"));
+    }
+
+    let (output, consumed) = inflate(&data[2..])?;
+    let trailer = data
+        .get(2 + consumed..2 + consumed + 4)
+        .ok_or(InflateError::UnexpectedEnd)?;
+    let expected = u32::from_be_bytes(trailer.try_into().expect("four bytes"));
+    let found = adler32(&output);
+    if expected != found {
+        return Err(InflateError::Checksum { expected, found });
+    }
+    Ok(output)
+}
+
+/// The Adler-32 checksum of the zlib format.
+pub fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    // the sums stay below 2³² for this many bytes before they are reduced
+    const CHUNK: usize = 5552;
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for chunk in data.chunks(CHUNK) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    b << 16 | a
+}
+
+fn stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), InflateError> {
+    reader.align();
+    let length = reader.bits(16)?;
+    if length != !reader.bits(16)? & 0xffff {
+        return Err(InflateError::StoredLength);
+    }
+    for _ in 0..length {
+        output.push(reader.bits(8)? as u8);
+    }
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), InflateError> {
+    let mut lengths = [0_u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(InflateError::Code("too many lengths"));
+    }
+
+    let mut code_lengths = [0_u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or(InflateError::Code("a repeat with nothing to repeat"))?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return Err(InflateError::Code("more lengths than symbols"));
+        }
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        return Err(InflateError::Code("no end of block"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+fn codes(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), InflateError> {
+    loop {
+        let symbol = usize::from(literals.decode(reader)?);
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err(InflateError::Code("a length symbol that does not exist"));
+        }
+        let length = usize::from(LENGTH_BASE[symbol])
+            + reader.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+
+        let symbol = usize::from(distances.decode(reader)?);
+        if symbol >= DISTANCE_BASE.len() {
+            return Err(InflateError::Code("a distance symbol that does not exist"));
+        }
+        let distance = usize::from(DISTANCE_BASE[symbol])
+            + reader.bits(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+        if distance > output.len() {
+            return Err(InflateError::Distance { distance });
+        }
+        // the copy may overlap what it appends
+        let start = output.len() - distance;
+        for i in 0..length {
+            output.push(output[start + i]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{RngExt, SeedableRng, prelude::StdRng};
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fixed_codes_and_back_references() {
+        let compressed = [
+            120, 218, 203, 72, 205, 201, 201, 215, 81, 200, 64, 162, 20, 202, 243, 139, 114, 82, 0,
+            116, 135, 9, 29,
+        ];
+        assert_eq!(
+            decompress(&compressed).unwrap(),
+            b"hello, hello, hello world"
+        );
+    }
+
+    #[test]
+    fn dynamic_codes() {
+        // 200 random letters of a, b, c and d, compressed by zlib at level 9
+        let compressed = from_hex(
+            "78da458e8b0d0031084267e5b3ff0ca7507335d5e4a1220002e2164fee3b44ce0f50f1c4099b43b68e\
+             4c6f8bd2bad891ba0b70c645caba451162109f0ebe23d865a9d6431ea25afabf34a77ef05a4c41",
+        );
+        let data = decompress(&compressed).unwrap();
+        assert_eq!(data.len(), 200);
+        assert!(data.starts_with(b"aaabaacbaabadbaaaa"));
+        assert!(data.iter().all(|byte| (b'a'..=b'd').contains(byte)));
+    }
+
+    /// Writes bits as a stream holds them, the least significant bit of each
+    /// byte first.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        buffer: u64,
+        count: u32,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u32, count: u32) {
+            self.buffer |= u64::from(value) << self.count;
+            self.count += count;
+            while self.count >= 8 {
+                self.bytes.push(self.buffer as u8);
+                self.buffer >>= 8;
+                self.count -= 8;
+            }
+        }
+
+        /// A codeword, which the stream holds first bit first.
+        fn codeword(&mut self, code: u32, length: u8) {
+            let length = u32::from(length);
+            self.bits(code.reverse_bits() >> (32 - length), length);
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            if self.count > 0 {
+                self.bytes.push(self.buffer as u8);
+            }
+            self.bytes
+        }
+    }
+
+    /// The canonical codeword of each symbol of a code of the given lengths.
+    fn codewords(lengths: &[u8]) -> Vec<u32> {
+        let mut counts = [0_u32; MAX_BITS + 1];
+        for &length in lengths.iter().filter(|&&length| length != 0) {
+            counts[usize::from(length)] += 1;
+        }
+        let mut next = [0_u32; MAX_BITS + 1];
+        for length in 2..=MAX_BITS {
+            next[length] = (next[length - 1] + counts[length - 1]) << 1;
+        }
+        lengths
+            .iter()
+            .map(|&length| {
+                let code = next[usize::from(length)];
+                next[usize::from(length)] += 1;
+                code
+            })
+            .collect()
+    }
+
+    /// A zlib stream of one dynamic block that holds `data` as literals of a
+    /// code of the given lengths, the end of the block among them.
+    fn literal_stream(lengths: &[u8; 257], data: &[u8]) -> Vec<u8> {
+        let mut out = BitWriter::default();
+        out.bits(0x78, 8);
+        out.bits(0x01, 8);
+        // the last block, a dynamic one, of 257 literal and one distance code
+        out.bits(1, 1);
+        out.bits(2, 2);
+        out.bits(0, 5);
+        out.bits(0, 5);
+        // the code length codes of the lengths 0 to 15, four bits each, and
+        // none for the repeats, which come first in the order they are given
+        out.bits(15, 4);
+        for symbol in CODE_LENGTH_ORDER {
+            out.bits(if symbol < 16 { 4 } else { 0 }, 3);
+        }
+        let length_codewords = codewords(&[4; 16]);
+        // the distance code takes a single bit
+        for &length in lengths.iter().chain(&[1]) {
+            out.codeword(length_codewords[usize::from(length)], 4);
+        }
+        let literal_codewords = codewords(lengths);
+        for symbol in data.iter().map(|&byte| usize::from(byte)).chain([256]) {
+            out.codeword(literal_codewords[symbol], lengths[symbol]);
+        }
+        let mut compressed = out.finish();
+        compressed.extend(adler32(data).to_be_bytes());
+        compressed
+    }
+
+    #[test]
+    fn long_codewords_in_a_large_block() {
+        // the example of RFC 1951, section 3.2.2
+        assert_eq!(
+            codewords(&[3, 3, 3, 3, 3, 2, 4, 4]),
+            vec![0b010, 0b011, 0b100, 0b101, 0b110, 0b00, 0b1110, 0b1111]
+        );
+
+        // codewords of every length, most of them beyond the lookup table,
+        // with each letter half as frequent as the one before
+        let mut lengths = [0_u8; 257];
+        for (length, symbol) in (1..=15).zip(b'a'..) {
+            lengths[usize::from(symbol)] = length;
+        }
+        lengths[256] = 15;
+        let mut rng = StdRng::seed_from_u64(0xDEF1A7E);
+        let data: Vec<u8> = (0..100_000)
+            .map(|_| b'a' + (rng.random::<u32>() | 1 << 14).trailing_zeros() as u8)
+            .collect();
+        assert!(data.contains(&b'o'));
+        let compressed = literal_stream(&lengths, &data);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+
+        // cut within the codeword of the end of the block
+        assert_eq!(
+            decompress(&compressed[..compressed.len() - 5]),
+            Err(InflateError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn stored_blocks() {
+        // two stored blocks, the first of them not the last
+        let mut compressed = vec![0x78, 0x01, 0x00, 3, 0, !3, !0, b'r', b'o', b'a'];
+        compressed.extend([0x01, 1, 0, !1, !0, b'd']);
+        compressed.extend(adler32(b"road").to_be_bytes());
+        assert_eq!(decompress(&compressed).unwrap(), b"road");
+
+        let empty = [120, 218, 3, 0, 0, 0, 0, 1];
+        assert_eq!(decompress(&empty).unwrap(), b"");
+    }
+
+    #[test]
+    fn broken_streams_are_reported() {
+        let compressed = [
+            120, 218, 203, 72, 205, 201, 201, 215, 81, 200, 64, 162, 20, 202, 243, 139, 114, 82, 0,
+            116, 135, 9, 29,
+        ];
+        assert_eq!(
+            decompress(&compressed[..10]),
+            Err(InflateError::UnexpectedEnd)
+        );
+        let mut wrong_checksum = compressed;
+        wrong_checksum[22] ^= 1;
+        assert!(matches!(
+            decompress(&wrong_checksum),
+            Err(InflateError::Checksum { .. })
+        ));
+        assert!(matches!(
+            decompress(&[0x78, 0x00]),
+            Err(InflateError::Header(_))
+        ));
+        assert_eq!(
+            decompress(&[0x78, 0x01, 0x07]),
+            Err(InflateError::BlockType)
+        );
+        assert_eq!(
+            decompress(&[0x78, 0x01, 0x01, 3, 0, 3, 0]),
+            Err(InflateError::StoredLength)
+        );
+    }
+
+    #[test]
+    fn adler32_of_known_strings() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // long enough to need reducing along the way
+        assert_eq!(adler32(&[0xff; 100_000]), {
+            let (mut a, mut b) = (1_u64, 0_u64);
+            for _ in 0..100_000 {
+                a = (a + 0xff) % 65521;
+                b = (b + a) % 65521;
+            }
+            (b << 16 | a) as u32
+        });
+    }
+}